@@ -6,7 +6,7 @@ use azul_css::{
     StyleTextAlignmentHorz, Overflow,
     LayoutAlignItems, LayoutAlignContent, LayoutJustifyContent, Shape,
     LayoutWrap, LayoutDirection, LayoutPosition, CssProperty, LayoutOverflow,
-    StyleFontFamily, StyleFontSize, StyleLineHeight, LayoutFlexShrink, LayoutFlexGrow,
+    StyleFontFamily, StyleFontSize, StyleLineHeight, StyleFontVariationSettings, FontVariation, LayoutFlexShrink, LayoutFlexGrow,
     LayoutLeft, LayoutRight, LayoutTop, LayoutBottom, StyleCursor, StyleWordSpacing, StyleTabWidth,
     LayoutMaxHeight, LayoutMinHeight, LayoutHeight, LayoutMaxWidth, LayoutMinWidth, LayoutWidth,
     StyleBorderRadius, PixelValue, PercentageValue, FloatValue,
@@ -118,6 +118,7 @@ pub fn parse_key_value_pair<'a>(key: CssPropertyType, value: &'a str) -> Result<
         BorderRadius     => Ok(parse_style_border_radius(value)?.into()),
         FontSize         => Ok(parse_style_font_size(value)?.into()),
         FontFamily       => Ok(parse_style_font_family(value)?.into()),
+        FontVariationSettings => Ok(parse_style_font_variation_settings(value)?.into()),
         LetterSpacing    => Ok(parse_style_letter_spacing(value)?.into()),
         WordSpacing      => Ok(parse_style_word_spacing(value)?.into()),
         TabWidth         => Ok(parse_style_tab_width(value)?.into()),
@@ -207,6 +208,7 @@ pub enum CssParsingError<'a> {
     PercentageParseError(PercentageParseError),
     CssImageParseError(CssImageParseError<'a>),
     CssStyleFontFamilyParseError(CssStyleFontFamilyParseError<'a>),
+    CssStyleFontVariationSettingsParseError(CssStyleFontVariationSettingsParseError<'a>),
     CssBackgroundParseError(CssBackgroundParseError<'a>),
     CssColorParseError(CssColorParseError<'a>),
     CssStyleBorderRadiusParseError(CssStyleBorderRadiusParseError<'a>),
@@ -226,6 +228,7 @@ impl_display!{ CssParsingError<'a>, {
     PercentageParseError(e) => format!("{}", e),
     CssImageParseError(e) => format!("{}", e),
     CssStyleFontFamilyParseError(e) => format!("{}", e),
+    CssStyleFontVariationSettingsParseError(e) => format!("{}", e),
     CssBackgroundParseError(e) => format!("{}", e),
     CssColorParseError(e) => format!("{}", e),
     PaddingParseError(e) => format!("{}", e),
@@ -241,6 +244,7 @@ impl_from!(InvalidValueErr<'a>, CssParsingError::InvalidValueErr);
 impl_from!(PixelParseError<'a>, CssParsingError::PixelParseError);
 impl_from!(CssImageParseError<'a>, CssParsingError::CssImageParseError);
 impl_from!(CssStyleFontFamilyParseError<'a>, CssParsingError::CssStyleFontFamilyParseError);
+impl_from!(CssStyleFontVariationSettingsParseError<'a>, CssParsingError::CssStyleFontVariationSettingsParseError);
 impl_from!(CssBackgroundParseError<'a>, CssParsingError::CssBackgroundParseError);
 impl_from!(CssStyleBorderRadiusParseError<'a>, CssParsingError::CssStyleBorderRadiusParseError);
 impl_from!(LayoutPaddingParseError<'a>, CssParsingError::PaddingParseError);
@@ -2002,6 +2006,74 @@ pub fn parse_style_font_family<'a>(input: &'a str) -> Result<StyleFontFamily, Cs
     })
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum CssStyleFontVariationSettingsParseError<'a> {
+    /// The axis tag isn't a quoted string of four ASCII characters, i.e. `"wght"`
+    InvalidTag(&'a str),
+    /// The value of the axis isn't a number
+    InvalidValue(&'a str, ParseFloatError),
+}
+
+impl_display!{CssStyleFontVariationSettingsParseError<'a>, {
+    InvalidTag(val) => format!("Invalid font-variation-settings axis tag: \"{}\"", val),
+    InvalidValue(val, e) => format!("Invalid font-variation-settings axis value: \"{}\": {}", val, e),
+}}
+
+/// Parses a `StyleFontVariationSettings` declaration from a `&str`
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate azul_css;
+/// # extern crate azul_css_parser;
+/// # use azul_css_parser::parse_style_font_variation_settings;
+/// # use azul_css::{StyleFontVariationSettings, FontVariation, FloatValue};
+/// let input = "\"wght\" 700, 'wdth' 85.5";
+/// let variations = vec![
+///     FontVariation { tag: *b"wght", value: FloatValue::new(700.0) },
+///     FontVariation { tag: *b"wdth", value: FloatValue::new(85.5) },
+/// ];
+///
+/// assert_eq!(parse_style_font_variation_settings(input), Ok(StyleFontVariationSettings { variations }));
+/// ```
+pub fn parse_style_font_variation_settings<'a>(input: &'a str)
+-> Result<StyleFontVariationSettings, CssStyleFontVariationSettingsParseError<'a>>
+{
+    use self::CssStyleFontVariationSettingsParseError::*;
+
+    let input = input.trim();
+
+    if input == "normal" {
+        return Ok(StyleFontVariationSettings { variations: Vec::new() });
+    }
+
+    let mut variations = Vec::new();
+
+    for axis in input.split(',') {
+        let axis = axis.trim();
+        let mut tag_and_value = axis.splitn(2, char::is_whitespace);
+        let tag = tag_and_value.next().unwrap_or("");
+        let value = tag_and_value.next().unwrap_or("").trim();
+
+        let is_quoted = tag.len() == 6 && (
+            (tag.starts_with('"') && tag.ends_with('"')) ||
+            (tag.starts_with('\'') && tag.ends_with('\''))
+        );
+        let tag_bytes = tag.as_bytes();
+        if !is_quoted || !tag_bytes[1..5].iter().all(|c| c.is_ascii_graphic() || *c == b' ') {
+            return Err(InvalidTag(tag));
+        }
+
+        let value = parse_float_value(value).map_err(|e| InvalidValue(value, e))?;
+        variations.push(FontVariation {
+            tag: [tag_bytes[1], tag_bytes[2], tag_bytes[3], tag_bytes[4]],
+            value,
+        });
+    }
+
+    Ok(StyleFontVariationSettings { variations })
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Ord, PartialOrd)]
 pub enum ParenthesisParseError<'a> {
     UnclosedBraces,
@@ -2830,6 +2902,21 @@ mod css_tests {
         }));
     }
 
+    #[test]
+    fn test_parse_style_font_variation_settings() {
+        assert_eq!(parse_style_font_variation_settings("normal"), Ok(StyleFontVariationSettings {
+            variations: Vec::new(),
+        }));
+        assert_eq!(parse_style_font_variation_settings("\"wght\" 700, 'opsz' 12"), Ok(StyleFontVariationSettings {
+            variations: vec![
+                FontVariation { tag: *b"wght", value: FloatValue::new(700.0) },
+                FontVariation { tag: *b"opsz", value: FloatValue::new(12.0) },
+            ]
+        }));
+        assert_eq!(parse_style_font_variation_settings("wght 700"), Err(CssStyleFontVariationSettingsParseError::InvalidTag("wght")));
+        assert!(parse_style_font_variation_settings("\"wght\" bold").is_err());
+    }
+
     #[test]
     fn test_parse_background_image() {
         assert_eq!(parse_style_background("image(\"Cat 01\")"), Ok(StyleBackground::Image(
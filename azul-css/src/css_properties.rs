@@ -220,7 +220,7 @@ macro_rules! impl_float_value{($struct:ident) => (
 )}
 
 /// Map between CSS keys and a statically typed enum
-const CSS_PROPERTY_KEY_MAP: [(CssPropertyType, &'static str);56] = [
+const CSS_PROPERTY_KEY_MAP: [(CssPropertyType, &'static str);57] = [
     (CssPropertyType::Background,       "background"),
     (CssPropertyType::BackgroundSize,   "background-size"),
     (CssPropertyType::BackgroundRepeat, "background-repeat"),
@@ -231,6 +231,7 @@ const CSS_PROPERTY_KEY_MAP: [(CssPropertyType, &'static str);56] = [
     (CssPropertyType::TextColor,        "color"),
     (CssPropertyType::FontSize,         "font-size"),
     (CssPropertyType::FontFamily,       "font-family"),
+    (CssPropertyType::FontVariationSettings, "font-variation-settings"),
     (CssPropertyType::TextAlign,        "text-align"),
     (CssPropertyType::LetterSpacing,    "letter-spacing"),
     (CssPropertyType::LineHeight,       "line-height"),
@@ -299,6 +300,7 @@ pub enum CssPropertyType {
     TextColor,
     FontSize,
     FontFamily,
+    FontVariationSettings,
     TextAlign,
     LetterSpacing,
     WordSpacing,
@@ -383,6 +385,7 @@ impl CssPropertyType {
             | TextColor
             | FontFamily
             | FontSize
+            | FontVariationSettings
             | LineHeight
             | TextAlign => true,
             _ => false,
@@ -397,7 +400,7 @@ impl CssPropertyType {
         // Since the border can be larger than the content,
         // in which case the content needs to be re-layouted, assume true for Border
 
-        // FontFamily, FontSize, LetterSpacing and LineHeight can affect
+        // FontFamily, FontSize, FontVariationSettings, LetterSpacing and LineHeight can affect
         // the text layout and therefore the screen layout
 
         match self {
@@ -437,6 +440,7 @@ pub enum CssProperty {
     Background(StyleBackground),
     FontSize(StyleFontSize),
     FontFamily(StyleFontFamily),
+    FontVariationSettings(StyleFontVariationSettings),
     TextAlign(StyleTextAlignmentHorz),
     LetterSpacing(StyleLetterSpacing),
     BoxShadow(StyleBoxShadow),
@@ -480,6 +484,7 @@ impl CssProperty {
             CssProperty::Background(_) => CssPropertyType::Background,
             CssProperty::FontSize(_) => CssPropertyType::FontSize,
             CssProperty::FontFamily(_) => CssPropertyType::FontFamily,
+            CssProperty::FontVariationSettings(_) => CssPropertyType::FontVariationSettings,
             CssProperty::TextAlign(_) => CssPropertyType::TextAlign,
             CssProperty::LetterSpacing(_) => CssPropertyType::LetterSpacing,
             CssProperty::WordSpacing(_) => CssPropertyType::WordSpacing,
@@ -518,6 +523,7 @@ impl_from!(StyleBoxShadow, CssProperty::BoxShadow);
 impl_from!(StyleBorder, CssProperty::Border);
 impl_from!(StyleFontSize, CssProperty::FontSize);
 impl_from!(StyleFontFamily, CssProperty::FontFamily);
+impl_from!(StyleFontVariationSettings, CssProperty::FontVariationSettings);
 impl_from!(StyleTextAlignmentHorz, CssProperty::TextAlign);
 impl_from!(StyleLineHeight, CssProperty::LineHeight);
 impl_from!(StyleTabWidth, CssProperty::TabWidth);
@@ -1492,6 +1498,8 @@ pub struct RectStyle {
     pub font_size: Option<StyleFontSize>,
     /// Font name / family
     pub font_family: Option<StyleFontFamily>,
+    /// Values of the variation axes of variable fonts
+    pub font_variation_settings: Option<StyleFontVariationSettings>,
     /// Text color
     pub font_color: Option<StyleTextColor>,
     /// Text alignment
@@ -1673,6 +1681,28 @@ pub struct StyleFontFamily {
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct FontId(pub String);
 
+/// Represents a `font-variation-settings` attribute, i.e. `"wght" 700, "wdth" 85`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StyleFontVariationSettings {
+    // empty for `font-variation-settings: normal`
+    pub variations: Vec<FontVariation>,
+}
+
+/// Value of a single variation axis of a variable font
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FontVariation {
+    /// OpenType tag of the axis, i.e. `*b"wght"`
+    pub tag: [u8; 4],
+    pub value: FloatValue,
+}
+
+impl FontVariation {
+    /// Returns the tag as a big-endian `u32`, the way it is stored in the font
+    pub fn tag_u32(&self) -> u32 {
+        (u32::from(self.tag[0]) << 24) | (u32::from(self.tag[1]) << 16) | (u32::from(self.tag[2]) << 8) | u32::from(self.tag[3])
+    }
+}
+
 impl FontId {
     pub fn get_str(&self) -> &str {
         &self.0
@@ -36,11 +36,12 @@ use {
         WindowCreateError, WindowCreateOptions, RendererType,
     },
     window_state::{WindowSize, DebugState},
-    app_resources::TextId,
+    app_resources::{TextId, TextMeta},
     dom::{Dom, ScrollTagId},
     app_resources::{
         ImageId, FontSource, FontId, ImageReloadError,
-        FontReloadError, CssImageId,
+        FontReloadError, CssImageId, ImageIdExists, ResourceCapacities,
+        FontRenderMode, FontPlatformOptions, GcPolicy,
     },
     traits::Layout,
     ui_state::UiState,
@@ -104,6 +105,86 @@ pub struct AppConfig {
     pub debug_state: DebugState,
     /// Background color for all windows
     pub background_color: ColorU,
+    /// If set, CSS background images are only decoded and uploaded once they are
+    /// within this many pixels of the visible window area, instead of as soon as
+    /// they appear in the DOM. Images of `NodeType::Image` nodes are always loaded,
+    /// since the layout needs to know their size.
+    ///
+    /// Default: `None` (all images are loaded immediately)
+    pub lazy_decode_margin_px: Option<f32>,
+    /// If enabled, decoded images are premultiplied in linear light instead of in sRGB space,
+    /// which avoids dark fringes at semi-transparent edges and matches the rendering of browsers.
+    /// Decoding is slightly slower, since every pixel has to be converted to linear light and back.
+    /// Each `App` has its own setting, `RawImage::from_dynamic_image` always premultiplies in sRGB space.
+    ///
+    /// Default: `false`
+    pub premultiply_in_linear_light: bool,
+    /// If enabled, decoded RGBA images keep their native channel order (`RawImageFormat::RGBA8`)
+    /// instead of being swizzled to BGRA8, which saves one pass over every pixel when loading
+    /// a lot of large images. The renderer is not queried for RGBA8 support, so only enable
+    /// this if the WebRender backend of your target platforms accepts RGBA8 textures.
+    /// Each `App` has its own setting, images decoded outside of the `AppResources`
+    /// (i.e. `RawImage::from_dynamic_image`) are always converted to BGRA8.
+    ///
+    /// Default: `false` (all color images are converted to BGRA8)
+    pub keep_rgba8_images: bool,
+    /// If set, at most this many new images are uploaded to the GPU per frame, the remaining
+    /// images are uploaded in the following frames (and not drawn until then). This avoids
+    /// a long stall when an application shows hundreds of images in the first frame.
+    ///
+    /// Default: `None` (all images are uploaded in the frame they first appear in)
+    pub max_image_uploads_per_frame: Option<usize>,
+    /// If set, encoded images (PNG, JPEG, ...) with more than `width * height` pixels are
+    /// rejected with `ImageReloadError::TooLarge` before they are decoded. Guards against
+    /// "decompression bombs", small files that claim huge dimensions, when loading
+    /// untrusted images (downloads, user uploads). The limit also applies to SVG images,
+    /// which are checked before they are rasterized. Each `App` has its own limit.
+    ///
+    /// Default: `None` (no limit)
+    pub max_image_pixels: Option<usize>,
+    /// If enabled, the CPU copy of each font is freed at the end of every frame (WebRender keeps
+    /// its own copy), which saves memory for large (i.e. CJK) fonts while the app is idle. The
+    /// tradeoff is that the fonts of a frame that is laid out again are re-read once from their
+    /// `FontSource` (from disk, or the system font is searched again), the bytes are then kept
+    /// until the end of that frame. Fonts whose bytes were dropped also don't share their
+    /// `FontKey` with identical fonts that are added later.
+    ///
+    /// Default: `false`
+    pub drop_font_bytes_after_upload: bool,
+    /// Anti-aliasing of text: `FontRenderMode::Alpha` (grayscale) avoids the color fringes
+    /// of subpixel anti-aliasing on displays without a RGB subpixel layout and in screenshots.
+    /// Can be overridden per font with `AppResources::set_font_render_mode`.
+    /// Color fonts (emoji) are never rendered with subpixel anti-aliasing.
+    ///
+    /// Default: `FontRenderMode::Subpixel`
+    pub font_render_mode: FontRenderMode,
+    /// Gamma / contrast (Windows) and LCD filter / hinting (Linux) of the glyph rasterizer
+    ///
+    /// Default: gamma 300%, contrast 100%, default LCD filter, LCD hinting
+    pub font_platform_options: FontPlatformOptions,
+    /// If set, decoded images are kept in an LRU cache of (at most) this many bytes, so that images
+    /// which are shown again after they were garbage-collected don't have to be read from disk and
+    /// decoded again. See `AppResources::cache_stats` for the hit rate of the cache.
+    ///
+    /// Default: `None` (no caching, images are decoded every time they are uploaded)
+    pub decoded_image_cache_bytes: Option<usize>,
+    /// If set, new images (PNG, JPEG, SVG, ...) are decoded on this many background threads
+    /// instead of on the main thread, so that the UI doesn't stall when a large image appears.
+    /// Images are not drawn until they are decoded. Use `AppResources::set_image_decoded_synchronously`
+    /// for images that have to be displayed immediately.
+    ///
+    /// Default: `None` (all images are decoded on the main thread)
+    pub image_decode_threads: Option<usize>,
+    /// When fonts and images that are no longer displayed are deleted from the renderer.
+    /// `GcPolicy::FrameTtl` avoids re-uploading resources that are only hidden for a few frames.
+    ///
+    /// Default: `GcPolicy::Immediate` (deleted after the first frame they weren't used in)
+    pub gc_policy: GcPolicy,
+    /// Preallocates the internal maps of the `AppResources` for the expected number of images,
+    /// fonts and texts, to avoid rehashing while loading a lot of resources at startup.
+    ///
+    /// Default: no preallocation
+    pub resource_capacities: ResourceCapacities,
 }
 
 impl Default for AppConfig {
@@ -121,6 +202,18 @@ impl Default for AppConfig {
             renderer_type: RendererType::default(),
             debug_state: DebugState::default(),
             background_color: COLOR_WHITE,
+            lazy_decode_margin_px: None,
+            premultiply_in_linear_light: false,
+            keep_rgba8_images: false,
+            max_image_uploads_per_frame: None,
+            max_image_pixels: None,
+            drop_font_bytes_after_upload: false,
+            font_render_mode: FontRenderMode::default(),
+            font_platform_options: FontPlatformOptions::default(),
+            decoded_image_cache_bytes: None,
+            image_decode_threads: None,
+            gc_policy: GcPolicy::default(),
+            resource_capacities: ResourceCapacities::default(),
         }
     }
 }
@@ -473,9 +566,18 @@ impl<T> App<T> {
             let should_redraw_timers = self.app_state.run_all_timers();
             let should_redraw_tasks = self.app_state.clean_up_finished_tasks();
             let should_redraw_timers_or_tasks = [should_redraw_timers, should_redraw_tasks].into_iter().any(|e| *e == Redraw);
+            // Images that were postponed by `max_image_uploads_per_frame` need another frame
+            let should_upload_deferred_images = self.app_state.resources.has_deferred_image_uploads();
+            // Images whose TTL elapsed are deleted, the UI has to be laid out again without them
+            let should_remove_expired_images = !self.app_state.resources.expire_resources(time_start).is_empty();
+            // Images that finished decoding on a background thread get uploaded in the next layout
+            let should_upload_decoded_images = self.app_state.resources.poll_pending_images();
+            // Images and fonts whose files changed on disk are loaded again (see `enable_file_watching`)
+            let should_reload_changed_files = self.app_state.resources.reload_changed_files(time_start);
+            let should_update_images = should_upload_deferred_images || should_remove_expired_images || should_upload_decoded_images || should_reload_changed_files;
 
             // If there is a relayout necessary, re-layout *all* windows!
-            if should_relayout_all_windows || should_redraw_timers_or_tasks{
+            if should_relayout_all_windows || should_redraw_timers_or_tasks || should_update_images {
                 for (current_window_id, mut window) in self.windows.iter_mut() {
                     relayout_single_window(
                         self.layout_callback,
@@ -491,7 +593,7 @@ impl<T> App<T> {
             }
 
             // If there is a re-render necessary, re-render *all* windows
-            if should_rerender_all_windows || should_redraw_timers_or_tasks {
+            if should_rerender_all_windows || should_redraw_timers_or_tasks || should_update_images {
                 for window in self.windows.values_mut() {
                     // TODO: For some reason this function has to be called twice in order
                     // to actually update the screen. For some reason the first swap_buffers() has
@@ -510,6 +612,7 @@ impl<T> App<T> {
                 // Automatically remove unused fonts and images from webrender
                 // Tell the font + image GC to start a new frame
                 self.app_state.resources.garbage_collect_fonts_and_images();
+                self.app_state.resources.begin_frame();
             }
 
             if !frame_was_resize {
@@ -632,6 +735,7 @@ struct SingleWindowContentResult {
     should_scroll_render: bool,
     needs_relayout_tasks: bool,
     needs_relayout_refresh: bool,
+    needs_relayout_scroll: bool,
     callbacks_update_screen: UpdateScreen,
     hit_test_results: Option<HitTestResult>,
     new_focus_target: Option<FocusTarget>,
@@ -644,6 +748,7 @@ impl SingleWindowContentResult {
         self.needs_relayout_resize ||
         self.needs_relayout_tasks ||
         self.needs_relayout_refresh ||
+        self.needs_relayout_scroll ||
         self.callbacks_update_screen == Redraw
     }
 
@@ -676,6 +781,7 @@ fn hit_test_single_window<T>(
         should_scroll_render: false,
         needs_relayout_tasks: *(awakened_tasks.get(window_id).ok_or(WindowIndexError)?),
         needs_relayout_refresh: *(force_redraw_cache.get(window_id).ok_or(WindowIndexError)?) > 0,
+        needs_relayout_scroll: false,
         callbacks_update_screen: DontRedraw,
         hit_test_results: None,
         new_focus_target: None,
@@ -733,6 +839,9 @@ fn hit_test_single_window<T>(
 
     ret.should_scroll_render = should_scroll_render;
 
+    // Images that are only added once they are near the viewport are added during the layout
+    ret.needs_relayout_scroll = should_scroll_render && app_state.resources.has_viewport_dependent_images();
+
     if frame_event_info.is_resize_event {
         // This is a hack because during a resize event, winit eats the "awakened"
         // event. So what we do is that we call the layout-and-render again, to
@@ -1231,6 +1340,9 @@ fn render_inner<T>(
     scroll_all_nodes(&mut window.scroll_states, &mut txn);
     txn.generate_frame();
 
+    // Make sure that all fonts and images of this frame are known before the frame is generated
+    app_resources.flush_pending_resource_updates();
+
     app_resources.fake_display.render_api.send_transaction(window.internal.document_id, txn);
 
     // Update WR texture cache
@@ -1,25 +1,36 @@
 use std::{
     fmt,
+    borrow::Cow,
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
     path::PathBuf,
     io::Error as IoError,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::{Arc, Mutex, mpsc::{self, Sender, Receiver}},
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 use webrender::api::{
     FontKey, FontInstanceKey, ImageKey, AddImage,
-    ResourceUpdate, AddFont, AddFontInstance, RenderApi,
+    ResourceUpdate, AddFont, AddFontInstance, RenderApi, AlphaType,
+    LayoutRect, LayoutPoint, LayoutSize, LayoutVector2D, UpdateImage, DeviceUintRect, DeviceUintPoint, DeviceUintSize,
 };
 use app_units::Au;
+use azul_css::ColorU;
 use clipboard2::{Clipboard, ClipboardError, SystemClipboard};
 use {
     FastHashMap, FastHashSet,
     window::{FakeDisplay, WindowCreateError},
     app::AppConfig,
     display_list::DisplayList,
-    text_layout::Words,
+    text_layout::{Words, ScaledWords},
+    id_tree::{NodeId, NodeDataContainer},
+    ui_solver::PositionedRectangle,
 };
 pub use webrender::api::{ImageFormat as RawImageFormat, ImageData, ImageDescriptor};
 #[cfg(feature = "image_loading")]
 pub use image::{ImageError, DynamicImage, GenericImageView};
+#[cfg(feature = "image_loading")]
+use clipboard_image::{ImageClipboard, encode_png, decode_png};
 
 pub type CssImageId = String;
 pub type CssFontId = String;
@@ -43,10 +54,43 @@ pub struct AppResources {
     css_ids_to_font_ids: FastHashMap<CssFontId, FontId>,
     /// Stores where the images were loaded from
     image_sources: FastHashMap<ImageId, ImageSource>,
+    /// Point in time after which an image is deleted, see `set_image_ttl`
+    image_expiry: FastHashMap<ImageId, Instant>,
     /// Stores where the fonts were loaded from
     font_sources: FastHashMap<FontId, FontSource>,
+    /// Fonts created by `derive_synthetic_font`, these share the `FontKey` of their base font
+    synthetic_fonts: FastHashMap<FontId, SyntheticFont>,
+    /// Parsed metrics of the fonts, see `get_font_metrics` - filled via a `RefCell`, because the
+    /// metrics are queried with read access only
+    font_metrics_cache: RefCell<FastHashMap<FontId, FontMetrics>>,
     /// All image keys currently active in the RenderApi
     currently_registered_images: FastHashMap<ImageId, ImageInfo>,
+    /// Content hash of the pixels of each registered image, see `shared_image_keys`
+    image_content_hashes: FastHashMap<ImageId, ImageContentHash>,
+    /// Images with identical pixels share one `ImageKey`: content hash -> shared key.
+    /// The key is deleted when the last image using it is deleted.
+    shared_image_keys: FastHashMap<ImageContentHash, SharedImageKey>,
+    /// Content hash of the bytes of each registered font, see `shared_font_keys`
+    font_content_hashes: FastHashMap<ImmediateFontId, u64>,
+    /// Fonts with identical bytes (i.e. one file registered under two CSS names) share one `FontKey`:
+    /// content hash -> (key, number of registered fonts using the key). The key is deleted when the
+    /// last font is deleted.
+    shared_font_keys: FastHashMap<u64, (FontKey, usize)>,
+    /// Border insets `(top, right, bottom, left)` of the images added via `add_nine_patch`
+    nine_patch_insets: FastHashMap<ImageId, (u32, u32, u32, u32)>,
+    /// Images added via `add_tiled_image`, these have no `ImageSource`
+    tiled_images: FastHashMap<ImageId, TiledImage>,
+    /// Glyphs of the fonts added via `add_bitmap_font`, these have no `ImageSource`,
+    /// they are drawn as a cut-out of the atlas image they were packed into
+    atlas_glyphs: FastHashMap<ImageId, AtlasGlyph>,
+    /// Size of the SVG images that were rasterized again at their displayed size,
+    /// the layout keeps using this size instead of the size of the uploaded image
+    svg_intrinsic_sizes: FastHashMap<ImageId, (usize, usize)>,
+    /// Displayed size at which rasterizing an SVG image again failed, it is only
+    /// retried once the displayed size changes, see `rasterize_svg_images_at_displayed_size`
+    failed_svg_rasterizations: FastHashMap<ImageId, (u32, u32)>,
+    /// Frames of the images added via `add_animated_image` / `add_animated_image_raw`
+    animated_images: FastHashMap<ImageId, AnimatedImage>,
     /// All font keys currently active in the RenderApi
     currently_registered_fonts: FastHashMap<ImmediateFontId, LoadedFont>,
     /// If an image isn't displayed, it is deleted from memory, only
@@ -60,56 +104,408 @@ pub struct AppResources {
     /// The only thing remaining in memory permanently is the FontSource (which is only
     /// the string of the file path where the font was loaded from, so no huge memory pressure).
     /// The reason for this agressive strategy is that the
-    last_frame_font_keys: FastHashMap<ImmediateFontId, FastHashSet<Au>>,
+    last_frame_font_keys: FastHashMap<ImmediateFontId, FastHashSet<FontInstanceSpec>>,
+    /// Frame counter, advanced by `begin_frame`. All "used N frames ago" decisions of
+    /// the font / image GC are based on this counter instead of the wall clock.
+    current_frame: usize,
+    /// The frame in which each registered image was last used
+    image_last_used_frame: FastHashMap<ImageId, usize>,
+    /// The frame in which each registered font was last used
+    font_last_used_frame: FastHashMap<ImmediateFontId, usize>,
+    /// The frame in which each font instance of a registered font was last used
+    font_instance_last_used_frame: FastHashMap<ImmediateFontId, FastHashMap<FontInstanceSpec, usize>>,
+    /// See `AppConfig::gc_policy`
+    gc_policy: GcPolicy,
+    /// Decoded images, `None` if disabled, see `AppConfig::decoded_image_cache_bytes`
+    decoded_image_cache: Option<DecodedImageCache>,
+    /// How images are decoded, set from the `AppConfig`
+    image_decode_options: ImageDecodeOptions,
+    /// Threads that decode images in the background, `None` if disabled, see `AppConfig::image_decode_threads`
+    image_decode_pool: Option<ImageDecodePool>,
+    /// Images that are always decoded on the main thread, see `set_image_decoded_synchronously`
+    synchronous_image_decodes: FastHashSet<ImageId>,
+    /// Images that are scaled down when they are decoded, see `set_image_max_dimensions`
+    image_max_dimensions: FastHashMap<ImageId, (u32, u32)>,
+    /// Images that are uploaded without mipmaps, see `set_image_mipmaps`
+    images_without_mipmaps: FastHashSet<ImageId>,
+    /// Pin counts of fonts that the GC must not delete, see `pin_font`
+    pinned_fonts: FastHashMap<FontId, usize>,
+    /// Pin counts of images that the GC must not delete, see `pin_image`
+    pinned_images: FastHashMap<ImageId, usize>,
+    /// Whether glyphs of newly created font instances are positioned at subpixel offsets
+    /// (smooth for animated text) or snapped to the pixel grid (sharper for static text)
+    subpixel_positioning: bool,
+    /// Per-font overrides of `subpixel_positioning`
+    font_subpixel_positioning: FastHashMap<FontId, bool>,
+    /// See `AppConfig::font_render_mode`
+    font_render_mode: FontRenderMode,
+    /// Per-font overrides of `font_render_mode`
+    font_render_modes: FastHashMap<FontId, FontRenderMode>,
+    /// See `AppConfig::font_platform_options`
+    font_platform_options: FontPlatformOptions,
+    /// See `AppConfig::lazy_decode_margin_px`
+    lazy_decode_margin_px: Option<f32>,
+    /// See `AppConfig::max_image_uploads_per_frame`
+    max_image_uploads_per_frame: Option<usize>,
+    /// See `AppConfig::drop_font_bytes_after_upload`
+    drop_font_bytes_after_upload: bool,
+    /// The frame in which the callback of each `ImageSource::Callback` image was last
+    /// invoked, so that images shared by multiple windows are only generated once per frame
+    callback_image_frames: FastHashMap<ImageId, usize>,
+    /// Caches the images generated by `add_gradient_image`, so that identical gradients aren't re-rendered
+    gradient_images: FastHashMap<GradientImage, ImageId>,
+    /// Caches the last mask (and the threshold it was created with) returned by `get_image_alpha_mask`
+    image_alpha_masks: FastHashMap<ImageId, (u8, Vec<bool>)>,
+    /// Whether resource updates were submitted since the last `flush_scene_builder()`.
+    /// All updates of a frame are flushed together in `flush_pending_resource_updates`
+    scene_builder_flush_pending: bool,
+    /// The added / updated / deleted fonts and images since the last `flush_pending_resource_updates`,
+    /// so that they are submitted with a single `update_resources` call per frame
+    pending_resource_updates: Vec<ResourceUpdate>,
+    /// Font and image loading failures, drained by `take_resource_warnings`. Identical warnings
+    /// are only stored once and at most `MAX_RESOURCE_WARNINGS` are kept (the oldest are dropped),
+    /// so that warnings that are never drained don't accumulate
+    resource_warnings: VecDeque<ResourceWarning>,
+    /// Statistics of the frame that is currently being built
+    current_frame_stats: FrameStats,
+    /// Statistics of the last completed frame
+    last_frame_stats: FrameStats,
+    /// Hit / miss counters of the gradient and alpha mask caches
+    cache_stats: CacheStats,
     /// Stores long texts across frames
     text_cache: TextCache,
     /// Keyboard clipboard storage and retrieval functionality
     clipboard: SystemClipboard,
+    /// Native clipboard formats for images, created on the first `set_clipboard_image` call
+    /// and kept alive, since on X11 the copied image is served by this handle
+    #[cfg(feature = "image_loading")]
+    image_clipboard: Option<ImageClipboard>,
+    /// Modification times of the image / font files, `None` if disabled, see `enable_file_watching`
+    file_watcher: Option<FileWatcher>,
+    /// The `ImageId` / `FontId` that is allocated next, so that the IDs are scoped to these
+    /// `AppResources` and reproducible given the same sequence of `add_*` calls
+    next_image_id: usize,
+    next_font_id: usize,
+}
+
+/// Statistics about the resource submission of a single frame, see `AppResources::get_frame_stats`
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FrameStats {
+    /// How many times the scene builder was flushed
+    pub scene_builder_flushes: usize,
+    /// How many times the resource updates were submitted to the RenderApi
+    pub resource_update_submissions: usize,
+    /// How many flushes were skipped, because their resource updates were
+    /// coalesced into the single flush of the frame
+    pub elided_scene_builder_flushes: usize,
+    /// How many images were uploaded to the RenderApi
+    pub image_uploads: usize,
+    /// How many images were postponed to the next frame, see `AppConfig::max_image_uploads_per_frame`
+    pub deferred_image_uploads: usize,
+}
+
+/// Number of resources to preallocate memory for, see `AppConfig::resource_capacities`.
+/// Registering more resources than that is still possible.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ResourceCapacities {
+    /// Number of images (`ImageSource`s)
+    pub images: usize,
+    /// Number of fonts (`FontSource`s)
+    pub fonts: usize,
+    /// Number of texts in the text cache
+    pub texts: usize,
+}
+
+/// When the fonts and images that are no longer displayed are deleted from the RenderApi,
+/// see `AppConfig::gc_policy`. Pinned resources (see `AppResources::pin_font`) are never deleted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GcPolicy {
+    /// Resources (and font instances) are deleted at the end of the first frame they weren't used in
+    Immediate,
+    /// Resources are deleted once they weren't used for this many frames, which avoids
+    /// re-uploading resources that only disappear for a few frames
+    FrameTtl(u32),
+    /// Resources are never deleted by the GC, only by the `delete_*` functions
+    Never,
+}
+
+impl Default for GcPolicy {
+    fn default() -> Self {
+        GcPolicy::Immediate
+    }
+}
+
+/// How the glyphs of newly created font instances are anti-aliased, see `AppConfig::font_render_mode`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FontRenderMode {
+    /// No anti-aliasing, glyphs are black-and-white
+    Mono,
+    /// Grayscale anti-aliasing, looks the same on every display (and in screenshots)
+    Alpha,
+    /// Anti-aliasing on the red, green and blue subpixels of LCD displays (sharpest,
+    /// but causes color fringes on displays without a RGB subpixel layout)
+    Subpixel,
+}
+
+impl Default for FontRenderMode {
+    fn default() -> Self {
+        FontRenderMode::Subpixel
+    }
+}
+
+/// FreeType LCD filter used for subpixel-rendered glyphs (only used on Linux)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FontLcdFilter {
+    None,
+    Default,
+    Light,
+    Legacy,
+}
+
+/// FreeType hinting mode (only used on Linux)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FontHinting {
+    None,
+    Mono,
+    Light,
+    Normal,
+    Lcd,
+}
+
+/// Platform-specific glyph rasterization settings, see `AppConfig::font_platform_options`.
+/// Settings of other platforms are ignored.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FontPlatformOptions {
+    /// Gamma of the glyph rasterizer in percent (Windows only, default: 300)
+    pub gamma: u16,
+    /// Contrast of the glyph rasterizer in percent (Windows only, default: 100)
+    pub contrast: u16,
+    /// LCD filter (Linux only, default: `FontLcdFilter::Default`)
+    pub lcd_filter: FontLcdFilter,
+    /// Hinting mode (Linux only, default: `FontHinting::Lcd`)
+    pub hinting: FontHinting,
+}
+
+impl Default for FontPlatformOptions {
+    fn default() -> Self {
+        // For some reason the gamma is way to low on Windows
+        Self {
+            gamma: 300,
+            contrast: 100,
+            lcd_filter: FontLcdFilter::Default,
+            hinting: FontHinting::Lcd,
+        }
+    }
+}
+
+/// Number of resources currently held by the `AppResources`, see `AppResources::resource_counts`
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ResourceCounts {
+    /// Number of registered `ImageSource`s
+    pub image_sources: usize,
+    /// Number of images currently uploaded to the RenderApi
+    pub resident_images: usize,
+    /// Number of registered `FontSource`s
+    pub font_sources: usize,
+    /// Number of fonts currently uploaded to the RenderApi
+    pub resident_fonts: usize,
+    /// Number of font instances (font + size combinations) of all resident fonts
+    pub font_instances: usize,
+    /// Number of texts in the text cache
+    pub cached_texts: usize,
+}
+
+/// Approximate memory used by the fonts and images that are currently uploaded to
+/// the RenderApi, see `AppResources::memory_report`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ResourceMemoryReport {
+    /// Number of uploaded fonts, fonts that share a `FontKey` are counted once
+    pub font_keys: usize,
+    /// Number of font instances (font + size combinations) of all uploaded fonts
+    pub font_instances: usize,
+    /// Size of the bytes of all uploaded fonts, fonts that share a `FontKey` are counted once
+    pub font_bytes: usize,
+    /// Number of uploaded images, including the frames of animated images and the tiles
+    /// of tiled images. Images that share an `ImageKey` are counted once.
+    pub image_keys: usize,
+    /// Size of the pixels of all uploaded images (`width * height * bytes_per_pixel`)
+    pub image_bytes: usize,
+    /// Instance count and size of each uploaded font, largest font first
+    pub fonts: Vec<FontMemoryUsage>,
+}
+
+/// Memory used by a single uploaded font, see `ResourceMemoryReport`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontMemoryUsage {
+    /// `None` for system fonts that are only referenced by their CSS name
+    pub font_id: Option<FontId>,
+    pub font_key: FontKey,
+    /// Number of font instances (sizes, variations) of the font
+    pub font_instances: usize,
+    /// Size of the font bytes, 0 if the bytes were dropped after the upload
+    /// (see `AppConfig::drop_font_bytes_after_upload`)
+    pub font_bytes: usize,
+}
+
+/// Style of an installed system font family, see `AppResources::query_system_font_variants`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FontVariant {
+    pub bold: bool,
+    pub italic: bool,
+}
+
+/// Hit / miss counters of the internal caches since the `AppResources` were created,
+/// see `AppResources::cache_stats`
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CacheStats {
+    /// How often `add_gradient_image` returned an already rendered gradient
+    pub gradient_image_hits: usize,
+    /// How often `add_gradient_image` had to render the gradient
+    pub gradient_image_misses: usize,
+    /// How often `get_image_alpha_mask` returned a cached mask
+    pub alpha_mask_hits: usize,
+    /// How often `get_image_alpha_mask` had to create the mask
+    pub alpha_mask_misses: usize,
+    /// How often an image that is uploaded again was found in the decoded image cache,
+    /// see `AppConfig::decoded_image_cache_bytes`
+    pub decoded_image_hits: usize,
+    /// How often an image had to be decoded while the decoded image cache was enabled
+    pub decoded_image_misses: usize,
 }
 
-static TEXT_ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// MIME type of plain text on the clipboard, see `AppResources::clipboard_formats`
+pub const CLIPBOARD_FORMAT_TEXT: &str = "text/plain";
+/// MIME type of a list of files on the clipboard, see `AppResources::get_clipboard_files`
+pub const CLIPBOARD_FORMAT_FILE_LIST: &str = "text/uri-list";
+/// MIME type of a PNG image on the clipboard, see `AppResources::get_clipboard_image`
+pub const CLIPBOARD_FORMAT_PNG: &str = "image/png";
 
 impl TextId {
-    fn new() -> Self {
-        Self { inner: TEXT_ID_COUNTER.fetch_add(1, Ordering::SeqCst) }
+    /// Allocates the next ID of a `TextCache`, see `TextCache::next_text_id`
+    fn new(next_text_id: &mut usize) -> Self {
+        let inner = *next_text_id;
+        *next_text_id += 1;
+        Self { inner }
+    }
+
+    /// Returns the raw integer value of this ID, i.e. for passing it across an FFI boundary
+    pub fn as_usize(&self) -> usize {
+        self.inner
+    }
+
+    /// Re-creates a `TextId` from a value previously returned by [`TextId::as_usize`].
+    ///
+    /// # Safety
+    ///
+    /// The value must have been originally produced by this crate, otherwise the
+    /// resulting ID may alias an unrelated (or no longer existing) text.
+    ///
+    /// [`TextId::as_usize`]: #method.as_usize
+    pub unsafe fn from_usize(inner: usize) -> Self {
+        Self { inner }
     }
 }
 
-/// A unique ID by which a large block of text can be uniquely identified
+/// A unique ID by which a large block of text can be uniquely identified within one `TextCache`
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub struct TextId {
     inner: usize,
 }
 
-static IMAGE_ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
-
-/// A unique ID by which an image can be uniquely identified
+/// A unique ID by which an image can be uniquely identified within one `AppResources`
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ImageId { id: usize }
 
 impl ImageId {
-    pub(crate) fn new() -> Self {
-        let unique_id = IMAGE_ID_COUNTER.fetch_add(1, Ordering::SeqCst);
-        Self {
-            id: unique_id,
-        }
+    /// Allocates the next ID of an `AppResources`, see `AppResources::new_image_id`
+    fn new(next_image_id: &mut usize) -> Self {
+        let id = *next_image_id;
+        *next_image_id += 1;
+        Self { id }
+    }
+
+    /// Returns the raw integer value of this ID, i.e. for passing it across an FFI boundary
+    pub fn as_usize(&self) -> usize {
+        self.id
+    }
+
+    /// Re-creates an `ImageId` from a value previously returned by [`ImageId::as_usize`].
+    ///
+    /// # Safety
+    ///
+    /// The value must have been originally produced by this crate, otherwise the
+    /// resulting ID may alias an unrelated (or no longer existing) image.
+    ///
+    /// [`ImageId::as_usize`]: #method.as_usize
+    pub unsafe fn from_usize(id: usize) -> Self {
+        Self { id }
     }
 }
 
-static FONT_ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// Makes sure that the allocator never hands out `id` (again), i.e. because an ID that was
+/// restored from a save file was registered
+fn reserve_id(next_id: &mut usize, id: usize) {
+    *next_id = (*next_id).max(id + 1);
+}
+
+/// Serializes the `TextId`, `ImageId` and `FontId` as their raw integer value.
+///
+/// IDs are only valid within the `AppResources` that created them: After restoring an ID from
+/// a save file, the app has to register the matching image / font source again under that ID
+/// (i.e. via `AppResources::add_image`), which reserves the ID, so that newly created IDs don't
+/// collide with restored ones.
+#[cfg(feature = "serde_serialization")]
+macro_rules! impl_serde_id {($struct_name:ident::$struct_field:ident) => (
+    impl ::serde::Serialize for $struct_name {
+        fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            ::serde::Serialize::serialize(&self.$struct_field, serializer)
+        }
+    }
+
+    impl<'de> ::serde::Deserialize<'de> for $struct_name {
+        fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let $struct_field = <usize as ::serde::Deserialize>::deserialize(deserializer)?;
+            Ok(Self { $struct_field })
+        }
+    }
+)}
+
+#[cfg(feature = "serde_serialization")]
+impl_serde_id!(TextId::inner);
+#[cfg(feature = "serde_serialization")]
+impl_serde_id!(ImageId::id);
+#[cfg(feature = "serde_serialization")]
+impl_serde_id!(FontId::id);
 
-/// A unique ID by which a font can be uniquely identified
+/// A unique ID by which a font can be uniquely identified within one `AppResources`
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct FontId {
     id: usize,
 }
 
 impl FontId {
-    pub(crate) fn new() -> Self {
-        let unique_id = FONT_ID_COUNTER.fetch_add(1, Ordering::SeqCst);
-        Self {
-            id: unique_id,
-        }
+    /// Allocates the next ID of an `AppResources`, see `AppResources::new_font_id`
+    fn new(next_font_id: &mut usize) -> Self {
+        let id = *next_font_id;
+        *next_font_id += 1;
+        Self { id }
+    }
+
+    /// Returns the raw integer value of this ID, i.e. for passing it across an FFI boundary
+    pub fn as_usize(&self) -> usize {
+        self.id
+    }
+
+    /// Re-creates a `FontId` from a value previously returned by [`FontId::as_usize`].
+    ///
+    /// # Safety
+    ///
+    /// The value must have been originally produced by this crate, otherwise the
+    /// resulting ID may alias an unrelated (or no longer existing) font.
+    ///
+    /// [`FontId::as_usize`]: #method.as_usize
+    pub unsafe fn from_usize(id: usize) -> Self {
+        Self { id }
     }
 }
 
@@ -117,20 +513,140 @@ impl FontId {
 pub enum ImageSource {
     /// The image is embedded inside the binary file
     Embedded(&'static [u8]),
+    /// The encoded image (PNG, JPEG, ...) is loaded from a set of bytes, i.e. from an archive or a network stream
+    Bytes(Vec<u8>),
     /// The image is already decoded and loaded from a set of bytes
     Raw(RawImage),
     /// The image is loaded from a file
     File(PathBuf),
+    /// A single page of a multi-page image file (i.e. a TIFF file), the page index starts at 0
+    #[cfg(feature = "tiff")]
+    Page(Box<ImageSource>, usize),
+    /// The image is generated by a callback, which is invoked again in every frame that the
+    /// image is used in. If the callback returns `None`, the currently uploaded image is kept.
+    Callback(ImageCallback),
+    /// The source code of an SVG image. The image is rasterized at its own size first and
+    /// rasterized again at the displayed size once it is laid out in a `NodeType::Image` node,
+    /// so it stays sharp at any size. Only filled paths are drawn (strokes and transforms
+    /// are ignored, gradients are drawn in black).
+    #[cfg(feature = "svg_parsing")]
+    Svg(String),
+}
+
+/// Generates the pixels of an `ImageSource::Callback` image. Returns `None`
+/// if the output didn't change since the last call, so the upload can be skipped.
+#[derive(Clone)]
+pub struct ImageCallback(pub Arc<dyn Fn() -> Option<RawImage> + Send + Sync>);
+
+impl fmt::Debug for ImageCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ImageCallback @ 0x{:x}", &*self.0 as *const _ as *const () as usize)
+    }
+}
+
+impl PartialEq for ImageCallback {
+    fn eq(&self, rhs: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &rhs.0)
+    }
+}
+
+impl Eq for ImageCallback { }
+
+/// Column / row of a tile of a tiled image, see `AppResources::add_tiled_image`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TileCoord {
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Decodes / generates the pixels of a single tile of a tiled image. Tiles at the right and
+/// bottom edge of the image only have to cover the remaining part of the image. Has to be
+/// `Send + Sync`, like the `ImageCallback`.
+pub type TileProvider = Box<dyn Fn(TileCoord) -> RawImage + Send + Sync>;
+
+/// 128-bit digest of the pixels, size and format of an image (a 64-bit FNV-1a and a 64-bit
+/// SipHash of the same bytes), see `image_content_hash`. Images with the same digest are
+/// treated as identical, so that no CPU copy of the pixels has to be kept for comparisons.
+type ImageContentHash = (u64, u64);
+
+/// `ImageKey` of an uploaded image that is shared by all registered images with identical pixels
+struct SharedImageKey {
+    key: ImageKey,
+    /// Descriptor of the uploaded image, compared before the key is shared
+    descriptor: ImageDescriptor,
+    /// Number of registered images using the key
+    image_count: usize,
+}
+
+/// Decoded frames of an animated image. All frames are displayed under the same `ImageId`,
+/// only the `ImageKey` of the registered image is swapped when the frame changes.
+struct AnimatedImage {
+    /// Pixels and display duration of each frame
+    frames: Vec<(ImageData, ImageDescriptor, Duration)>,
+    /// Index of the frame that is currently displayed
+    current_frame: usize,
+    /// How long the current frame has been displayed, see `AppResources::advance_animated_image`
+    current_frame_elapsed: Duration,
+    /// Keys of the frames that were uploaded since the image was registered,
+    /// frames are only uploaded once they are displayed
+    frame_keys: Vec<Option<ImageKey>>,
+}
+
+/// Image that is too large to be held in memory as a whole, only the visible tiles are
+/// requested from the `TileProvider` and uploaded, see `AppResources::add_tiled_image`
+pub(crate) struct TiledImage {
+    tile_provider: TileProvider,
+    /// Size of the entire image in pixels
+    pub(crate) full_size: (u32, u32),
+    /// Size of a single tile in pixels
+    pub(crate) tile_size: (u32, u32),
+    /// Currently uploaded tiles and the frame they were last visible in
+    pub(crate) resident_tiles: FastHashMap<TileCoord, (ImageInfo, usize)>,
+}
+
+impl TiledImage {
+
+    /// Returns the `(x, y, width, height)` of a tile in image pixels
+    pub(crate) fn get_tile_rect(&self, tile: TileCoord) -> (u32, u32, u32, u32) {
+        let x = tile.x * self.tile_size.0;
+        let y = tile.y * self.tile_size.1;
+        (x, y, self.tile_size.0.min(self.full_size.0 - x), self.tile_size.1.min(self.full_size.1 - y))
+    }
+
+    /// Returns all tiles that intersect the rectangle `(x0, y0) - (x1, y1)` (in image pixels)
+    fn get_tiles_in_rect(&self, (x0, y0): (f32, f32), (x1, y1): (f32, f32)) -> Vec<TileCoord> {
+
+        let (tile_width, tile_height) = (self.tile_size.0 as f32, self.tile_size.1 as f32);
+        let columns = (self.full_size.0 + self.tile_size.0 - 1) / self.tile_size.0;
+        let rows = (self.full_size.1 + self.tile_size.1 - 1) / self.tile_size.1;
+
+        let first_column = (x0.max(0.0) / tile_width).floor() as u32;
+        let first_row = (y0.max(0.0) / tile_height).floor() as u32;
+        let last_column = ((x1.max(0.0) / tile_width).ceil() as u32).min(columns);
+        let last_row = ((y1.max(0.0) / tile_height).ceil() as u32).min(rows);
+
+        (first_row..last_row).flat_map(|y| (first_column..last_column).map(move |x| TileCoord { x, y })).collect()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum FontSource {
     /// The font is embedded inside the binary file
     Embedded(&'static [u8]),
+    /// The font is loaded from a set of bytes (i.e. a font downloaded at runtime),
+    /// together with the index of the font in case the bytes are a font collection
+    Bytes(Vec<u8>, i32),
     /// The font is loaded from a file
     File(PathBuf),
     /// The font is a system built-in font
     System(String),
+    /// A list of system fonts in order of precedence, i.e. `["Helvetica", "Arial"]`.
+    /// If none of the fonts can be found, the generic "sans-serif" font is loaded.
+    SystemStack(Vec<String>),
+    /// The font is resolved by matching a fontconfig pattern, i.e. "Noto Sans:weight=bold:lang=ja".
+    /// Falls back to loading the family of the pattern as a system font if fontconfig isn't available.
+    #[cfg(all(target_os = "linux", feature = "fontconfig"))]
+    FontconfigPattern(String),
 }
 
 #[derive(Debug)]
@@ -140,6 +656,15 @@ pub enum ImageReloadError {
     DecodingError(ImageError),
     #[cfg(not(feature = "image_loading"))]
     DecodingModuleNotActive,
+    /// The requested page of a multi-page image doesn't exist
+    PageOutOfRange { requested: usize, available: usize },
+    /// An `ImageSource::Callback` returned `None`, but there is no previous image to reuse
+    NoCallbackImage,
+    /// The image has more pixels than `AppConfig::max_image_pixels` allows, it wasn't decoded
+    TooLarge { width: u32, height: u32, limit: usize },
+    /// The source of an `ImageSource::Svg` could not be parsed
+    #[cfg(feature = "svg_parsing")]
+    InvalidSvg(String),
 }
 
 impl Clone for ImageReloadError {
@@ -151,6 +676,11 @@ impl Clone for ImageReloadError {
             DecodingError(e) => DecodingError(e.clone()),
             #[cfg(not(feature = "image_loading"))]
             DecodingModuleNotActive => DecodingModuleNotActive,
+            PageOutOfRange { requested, available } => PageOutOfRange { requested: *requested, available: *available },
+            NoCallbackImage => NoCallbackImage,
+            TooLarge { width, height, limit } => TooLarge { width: *width, height: *height, limit: *limit },
+            #[cfg(feature = "svg_parsing")]
+            InvalidSvg(e) => InvalidSvg(e.clone()),
         }
     }
 }
@@ -164,6 +694,11 @@ impl fmt::Display for ImageReloadError {
             DecodingError(err) => write!(f, "Image decoding error: \"{}\"", err),
             #[cfg(not(feature = "image_loading"))]
             DecodingModuleNotActive => write!(f, "Found decoded image, but crate was not compiled with --features=\"image_loading\""),
+            PageOutOfRange { requested, available } => write!(f, "Could not load page {} of image, image only has {} page(s)", requested, available),
+            NoCallbackImage => write!(f, "Image callback returned no image, but no previous image exists"),
+            TooLarge { width, height, limit } => write!(f, "Image is too large to decode: {}x{} pixels, limit is {} pixels", width, height, limit),
+            #[cfg(feature = "svg_parsing")]
+            InvalidSvg(e) => write!(f, "Could not parse SVG image: {}", e),
         }
     }
 }
@@ -184,6 +719,160 @@ impl Clone for FontReloadError {
     }
 }
 
+/// Error returned by `AppResources::update_image_region`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageUpdateError {
+    /// No image with this `ImageId` exists
+    InvalidImageId(ImageId),
+    /// Only images loaded from an `ImageSource::Raw` can be partially updated
+    NotARawImage(ImageId),
+    /// The region `(x, y, width, height)` is not contained in the image
+    RegionOutOfBounds { region: (u32, u32, u32, u32), image_dimensions: (u32, u32) },
+    /// The length of the pixel buffer doesn't match the size of the region
+    InvalidPixelBufferLength { expected: usize, actual: usize },
+    /// The pixels of the image itself don't match its dimensions (i.e. a `RawImage` whose
+    /// fields were modified after it was added), so the region can't be located
+    InvalidImage(ImageId, RawImageError),
+}
+
+impl_display!(ImageUpdateError, {
+    InvalidImageId(id) => format!("Could not update image: {:?} does not exist", id),
+    NotARawImage(id) => format!("Could not update image: {:?} is not a raw image", id),
+    RegionOutOfBounds { region, image_dimensions } => format!("Could not update image: region {:?} is out of bounds of the image with the size {:?}", region, image_dimensions),
+    InvalidPixelBufferLength { expected, actual } => format!("Could not update image: expected {} bytes of pixels, got {} bytes", expected, actual),
+    InvalidImage(id, e) => format!("Could not update image {:?}: {}", id, e),
+});
+
+/// Error returned by `RawImage::new` and `AppResources::add_image_raw`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RawImageError {
+    /// The length of the pixel buffer doesn't match `width * height * bytes_per_pixel`
+    InvalidPixelBufferLength { expected: usize, actual: usize },
+    /// `width * height * bytes_per_pixel` doesn't fit into memory
+    DimensionsTooLarge { width: u32, height: u32 },
+    /// An animated image was added without any frames
+    NoFrames,
+}
+
+impl_display!(RawImageError, {
+    InvalidPixelBufferLength { expected, actual } => format!("Invalid raw image: expected {} bytes of pixels, got {} bytes", expected, actual),
+    DimensionsTooLarge { width, height } => format!("Invalid raw image: an image of {}x{} pixels is too large", width, height),
+    NoFrames => "Invalid animated image: the image has no frames",
+});
+
+/// Error correction level of a QR code, see `RawImage::qr_code`. Higher levels can
+/// still be read if more of the code is damaged, but need more modules for the same data.
+#[cfg(feature = "qrcode")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum QrEcLevel {
+    /// Recovers 7% of the data
+    Low,
+    /// Recovers 15% of the data
+    Medium,
+    /// Recovers 25% of the data
+    Quartile,
+    /// Recovers 30% of the data
+    High,
+}
+
+/// Error returned by `RawImage::qr_code`
+#[cfg(feature = "qrcode")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QrError {
+    /// The data is too long to fit into a QR code with the given error correction level
+    Encoding(::qrcode::types::QrError),
+    /// The module size is 0 or the resulting image would be too large
+    InvalidModuleSize(u32),
+}
+
+#[cfg(feature = "qrcode")]
+impl_display!(QrError, {
+    Encoding(e) => format!("Could not encode QR code: {}", e),
+    InvalidModuleSize(size) => format!("Could not create QR code: invalid module size {}", size),
+});
+
+#[cfg(feature = "qrcode")]
+impl From<::qrcode::types::QrError> for QrError {
+    fn from(e: ::qrcode::types::QrError) -> Self {
+        QrError::Encoding(e)
+    }
+}
+
+/// Returned by `try_add_css_image_id` if the CSS ID is already mapped to an image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageIdExists {
+    /// The CSS ID that is already in use
+    pub css_id: CssImageId,
+    /// The `ImageId` that the CSS ID is currently mapped to
+    pub image_id: ImageId,
+}
+
+impl fmt::Display for ImageIdExists {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CSS image ID \"{}\" is already in use by {:?}", self.css_id, self.image_id)
+    }
+}
+
+/// Error returned by `AppResources::rasterize_text_to_image`
+#[cfg(feature = "svg")]
+#[derive(Debug, Clone)]
+pub enum TextRasterizationError {
+    /// No font with this `FontId` exists
+    InvalidFontId(FontId),
+    /// The font could not be loaded from its `FontSource`
+    FontReload(FontReloadError),
+    /// The font was loaded, but could not be parsed
+    InvalidFont(FontId),
+}
+
+#[cfg(feature = "svg")]
+impl_display!(TextRasterizationError, {
+    InvalidFontId(id) => format!("Could not rasterize text: {:?} does not exist", id),
+    FontReload(e) => format!("Could not rasterize text: {}", e),
+    InvalidFont(id) => format!("Could not rasterize text: {:?} is not a valid font", id),
+});
+
+#[cfg(feature = "svg")]
+impl_from!(FontReloadError, TextRasterizationError::FontReload);
+
+/// A font or image that couldn't be loaded, see `AppResources::take_resource_warnings`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceWarning {
+    /// The font or image that caused the warning
+    pub id: ResourceWarningId,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ResourceWarningId {
+    Font(FontId),
+    /// System font that was requested via its CSS font family name
+    SystemFont(CssFontId),
+    Image(ImageId),
+}
+
+/// Why a font or image couldn't be loaded, returned together with the `ResourceWarningId`
+/// of the resource by `AppResources::add_fonts_and_images`
+#[derive(Debug, Clone)]
+pub enum ResourceLoadError {
+    Font(FontReloadError),
+    Image(ImageReloadError),
+}
+
+impl_display!(ResourceLoadError, {
+    Font(e) => format!("Could not load font: {}", e),
+    Image(e) => format!("Could not load image: {}", e),
+});
+
+impl_from!(FontReloadError, ResourceLoadError::Font);
+impl_from!(ImageReloadError, ResourceLoadError::Image);
+
+impl fmt::Display for ResourceWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}: {}", self.id, self.message)
+    }
+}
+
 impl_display!(FontReloadError, {
     Io(err, path_buf) => format!("Could not load \"{}\" - IO error: {}", path_buf.as_path().to_string_lossy(), err),
     FontNotFound(id) => format!("Could not locate system font: \"{}\" found", id),
@@ -191,45 +880,123 @@ impl_display!(FontReloadError, {
 
 impl ImageSource {
 
+    /// Returns the file that the image is loaded from, if any
+    fn get_file_path(&self) -> Option<&PathBuf> {
+        match self {
+            ImageSource::File(file_path) => Some(file_path),
+            #[cfg(feature = "tiff")]
+            ImageSource::Page(image_source, _) => image_source.get_file_path(),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the bytes returned by `get_bytes` are premultiplied or straight alpha:
+    /// Decoded images get premultiplied while decoding, raw images are passed through as-is.
+    /// The alpha type of callback images depends on each produced image (see
+    /// `RawImage::alpha_type`), `Alpha` is only returned as a placeholder.
+    pub(crate) fn get_alpha_type(&self) -> AlphaType {
+        use self::ImageSource::*;
+        match self {
+            Embedded(_) | Bytes(_) | File(_) => AlphaType::PremultipliedAlpha,
+            Raw(raw_image) => raw_image.alpha_type(),
+            Callback(_) => AlphaType::Alpha,
+            #[cfg(feature = "svg_parsing")]
+            Svg(_) => AlphaType::Alpha,
+            #[cfg(feature = "tiff")]
+            Page(image_source, _) => image_source.get_alpha_type(),
+        }
+    }
+
     /// Returns the **decoded** bytes of the image + the descriptor (contains width / height).
     /// Returns an error if the data is encoded, but the crate wasn't built with `--features="image_loading"`
-    #[allow(unused_variables)]
     pub fn get_bytes(&self) -> Result<(ImageData, ImageDescriptor), ImageReloadError> {
+        self.get_bytes_with_max_dimensions(None)
+    }
+
+    /// Same as `get_bytes`, but encoded images (PNG, JPEG, ...) that are larger than `max_dimensions`
+    /// are scaled down to fit into `max_dimensions`, preserving the aspect ratio. Raw, callback and
+    /// SVG images are returned at their own size.
+    pub fn get_bytes_with_max_dimensions(&self, max_dimensions: Option<(u32, u32)>) -> Result<(ImageData, ImageDescriptor), ImageReloadError> {
+        self.get_bytes_with_options(max_dimensions, ImageDecodeOptions::default())
+    }
+
+    /// Same as `get_bytes_with_max_dimensions`, but decodes the image with the `ImageDecodeOptions`
+    /// of the `AppResources` instead of the defaults
+    #[allow(unused_variables)]
+    pub(crate) fn get_bytes_with_options(&self, max_dimensions: Option<(u32, u32)>, options: ImageDecodeOptions) -> Result<(ImageData, ImageDescriptor), ImageReloadError> {
 
         use self::ImageSource::*;
 
         match self {
             Embedded(bytes) => {
                 #[cfg(feature = "image_loading")] {
-                    decode_image_data(bytes.to_vec()).map_err(|e| ImageReloadError::DecodingError(e))
+                    decode_image_data(bytes.to_vec(), max_dimensions, options)
+                }
+                #[cfg(not(feature = "image_loading"))] {
+                    Err(ImageReloadError::DecodingModuleNotActive)
+                }
+            },
+            Bytes(bytes) => {
+                #[cfg(feature = "image_loading")] {
+                    decode_image_data(bytes.clone(), max_dimensions, options)
                 }
                 #[cfg(not(feature = "image_loading"))] {
                     Err(ImageReloadError::DecodingModuleNotActive)
                 }
             },
             Raw(raw_image) => {
-                let opaque = is_image_opaque(raw_image.data_format, &raw_image.pixels[..]);
-                let allow_mipmaps = true;
+                // 16-bit textures aren't supported on all GL ES devices, so R16 images
+                // are down-converted to R8 when uploading (the RawImage keeps the 16 bits)
+                let (data_format, pixels) = match raw_image.data_format {
+                    RawImageFormat::R16 => (RawImageFormat::R8, r16_to_r8(&raw_image.pixels)),
+                    data_format => (data_format, raw_image.pixels.clone()),
+                };
+                let opaque = is_image_opaque(data_format, &pixels[..]);
+                let allow_mipmaps = raw_image.allow_mipmaps;
                 let descriptor = ImageDescriptor::new(
                     raw_image.image_dimensions.0 as i32,
                     raw_image.image_dimensions.1 as i32,
-                    raw_image.data_format,
+                    data_format,
                     opaque,
                     allow_mipmaps
                 );
-                let data = ImageData::new(raw_image.pixels.clone());
+                let data = ImageData::new(pixels);
                 Ok((data, descriptor))
             },
             File(file_path) => {
                 #[cfg(feature = "image_loading")] {
                     use std::fs;
                     let bytes = fs::read(file_path).map_err(|e| ImageReloadError::Io(e, file_path.clone()))?;
-                    decode_image_data(bytes).map_err(|e| ImageReloadError::DecodingError(e))
+                    decode_image_data(bytes, max_dimensions, options)
                 }
                 #[cfg(not(feature = "image_loading"))] {
                     Err(ImageReloadError::DecodingModuleNotActive)
                 }
             },
+            #[cfg(feature = "tiff")]
+            Page(image_source, page) => {
+                use std::fs;
+                let bytes = match &**image_source {
+                    Embedded(bytes) => bytes.to_vec(),
+                    Bytes(bytes) => bytes.clone(),
+                    File(file_path) => fs::read(file_path).map_err(|e| ImageReloadError::Io(e, file_path.clone()))?,
+                    Raw(_) | Page(_, _) | Callback(_) if *page == 0 => return image_source.get_bytes_with_options(max_dimensions, options),
+                    Raw(_) | Page(_, _) | Callback(_) => return Err(ImageReloadError::PageOutOfRange { requested: *page, available: 1 }),
+                    #[cfg(feature = "svg_parsing")]
+                    Svg(_) if *page == 0 => return image_source.get_bytes_with_options(max_dimensions, options),
+                    #[cfg(feature = "svg_parsing")]
+                    Svg(_) => return Err(ImageReloadError::PageOutOfRange { requested: *page, available: 1 }),
+                };
+                decode_image_page(bytes, *page, max_dimensions, options)
+            },
+            Callback(callback) => {
+                match (callback.0)() {
+                    Some(raw_image) => Raw(raw_image).get_bytes(),
+                    None => Err(ImageReloadError::NoCallbackImage),
+                }
+            },
+            #[cfg(feature = "svg_parsing")]
+            Svg(svg) => Raw(rasterize_svg(svg, None, options)?).get_bytes(),
         }
     }
 }
@@ -239,932 +1006,8513 @@ impl FontSource {
     /// Returns the bytes of the font (loads the font from the system in case it is a `FontSource::System` font).
     /// Also returns the index into the font (in case the font is a font collection).
     pub fn get_bytes(&self) -> Result<(Vec<u8>, i32), FontReloadError> {
+        self.get_bytes_and_family().map(|(bytes, _)| bytes)
+    }
+
+    /// Same as `get_bytes`, but for `System` and `SystemStack` fonts, also returns the
+    /// font family that was actually loaded, i.e. to log that a font was substituted.
+    pub fn get_bytes_and_family(&self) -> Result<((Vec<u8>, i32), Option<String>), FontReloadError> {
         use std::fs;
         use self::FontSource::*;
         match self {
-            Embedded(bytes) => Ok((bytes.to_vec(), 0)),
+            Embedded(bytes) => Ok(((bytes.to_vec(), 0), None)),
+            Bytes(bytes, font_index) => Ok(((bytes.clone(), *font_index), None)),
             File(file_path) => {
                 fs::read(file_path)
                 .map_err(|e| FontReloadError::Io(e, file_path.clone()))
-                .map(|f| (f, 0))
+                .map(|f| ((f, 0), None))
+            },
+            System(id) => {
+                load_system_font(id)
+                .map(|f| (f, Some(id.clone())))
+                .ok_or(FontReloadError::FontNotFound(id.clone()))
+            },
+            SystemStack(ids) => {
+                load_system_font_stack(ids)
+                .map(|(f, family)| (f, Some(family)))
+                .ok_or(FontReloadError::FontNotFound(ids.join(", ")))
+            },
+            #[cfg(all(target_os = "linux", feature = "fontconfig"))]
+            FontconfigPattern(pattern) => {
+                load_fontconfig_font(pattern)
+                .map(|f| (f, None))
+                .ok_or(FontReloadError::FontNotFound(pattern.clone()))
             },
-            System(id) => load_system_font(id).ok_or(FontReloadError::FontNotFound(id.clone())),
         }
     }
 }
 
 /// Raw image made up of raw pixels (either BGRA8 or A8)
+///
+/// Prefer creating the image with [`RawImage::new`], which checks that the length of the
+/// pixels matches the dimensions - a mismatch can cause out-of-bounds reads later on.
+///
+/// [`RawImage::new`]: ../app_resources/struct.RawImage.html#method.new
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RawImage {
     pub pixels: Vec<u8>,
     pub image_dimensions: (u32, u32),
     pub data_format: RawImageFormat,
+    /// Whether the color channels are already multiplied with the alpha channel (i.e. for
+    /// buffers that were composited elsewhere). Defaults to straight alpha, premultiplied
+    /// pixels are uploaded as-is instead of being premultiplied a second time when rendering.
+    pub alpha_premultiplied: bool,
+    /// Whether the renderer may generate mipmaps for the image. Defaults to `true`, disabling
+    /// mipmaps saves memory for pixel art and images that are always displayed at their own size.
+    pub allow_mipmaps: bool,
 }
 
-#[derive(Debug, Clone)]
-pub struct LoadedFont {
-    pub font_key: FontKey,
-    pub font_bytes: Vec<u8>,
-    /// Index of the font in case the bytes indicate a font collection
-    pub font_index: i32,
-    pub font_instances: FastHashMap<Au, FontInstanceKey>,
-}
+impl RawImage {
+
+    /// Creates a new image, returns an error if `pixels.len()` doesn't match
+    /// `width * height * bytes_per_pixel(format)`
+    pub fn new(width: u32, height: u32, data_format: RawImageFormat, pixels: Vec<u8>) -> Result<Self, RawImageError> {
+        let raw_image = Self {
+            pixels,
+            image_dimensions: (width, height),
+            data_format,
+            alpha_premultiplied: false,
+            allow_mipmaps: true,
+        };
+        raw_image.validate()?;
+        Ok(raw_image)
+    }
 
-impl LoadedFont {
+    /// Marks the pixels as already premultiplied, see `RawImage::alpha_premultiplied`
+    pub fn with_premultiplied_alpha(self) -> Self {
+        Self { alpha_premultiplied: true, .. self }
+    }
 
-    /// Creates a new loaded font with 0 font instances
-    pub fn new(font_key: FontKey, font_bytes: Vec<u8>, font_index: i32) -> Self {
-        Self {
-            font_key,
-            font_bytes,
-            font_index,
-            font_instances: FastHashMap::default(),
-        }
+    /// Disables mipmaps for this image, see `RawImage::allow_mipmaps`
+    pub fn without_mipmaps(self) -> Self {
+        Self { allow_mipmaps: false, .. self }
     }
 
-    fn delete_font_instance(&mut self, size: &Au) {
-        self.font_instances.remove(size);
+    /// Creates a 16-bit greyscale (R16) image, i.e. for scientific or medical imagery.
+    /// The full precision is kept in the `RawImage` (as little-endian bytes), but the image
+    /// is down-converted to R8 when it is uploaded to the renderer.
+    pub fn from_luma16(width: u32, height: u32, pixels: &[u16]) -> Result<Self, RawImageError> {
+        let pixels = pixels.iter().flat_map(|value| vec![*value as u8, (*value >> 8) as u8]).collect();
+        Self::new(width, height, RawImageFormat::R16, pixels)
     }
-}
 
-/// Cache for accessing large amounts of text
-#[derive(Debug, Default, Clone)]
-pub struct TextCache {
-    /// Mapping from the TextID to the actual, UTF-8 String
-    ///
-    /// This is stored outside of the actual glyph calculation, because usually you don't
-    /// need the string, except for rebuilding a cached string (for example, when the font is changed)
-    pub(crate) string_cache: FastHashMap<TextId, Words>,
+    /// Creates a straight-alpha BGRA8 image from 16-bit RGBA pixels. The renderer has no
+    /// 16-bit color format, so each channel is rounded to 8 bits.
+    pub fn from_rgba16(width: u32, height: u32, pixels: &[u16]) -> Result<Self, RawImageError> {
+        let pixels = pixels.iter().map(|value| u16_to_u8(*value)).collect();
+        let mut raw_image = Self::new(width, height, RawImageFormat::BGRA8, pixels)?;
+        for bgra in raw_image.pixels.chunks_exact_mut(4) {
+            bgra.swap(0, 2);
+        }
+        Ok(raw_image)
+    }
 
-    // -- for now, don't cache ScaledWords, it's too complicated...
+    /// Checks that `pixels.len()` matches `width * height * bytes_per_pixel(format)`,
+    /// i.e. for images that were constructed directly instead of via `RawImage::new`
+    pub fn validate(&self) -> Result<(), RawImageError> {
 
-    // /// Caches the layout of the strings / words.
-    // ///
-    // /// TextId -> FontId (to look up by font)
-    // /// FontId -> PixelValue (to categorize by size within a font)
-    // /// PixelValue -> layouted words (to cache the glyph widths on a per-font-size basis)
-    // pub(crate) layouted_strings_cache: FastHashMap<TextId, FastHashMap<FontInstanceKey, ScaledWords>>,
-}
+        let (width, height) = self.image_dimensions;
 
-impl TextCache {
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixel_count| pixel_count.checked_mul(self.data_format.bytes_per_pixel() as usize))
+            .ok_or(RawImageError::DimensionsTooLarge { width, height })?;
 
-    /// Add a new, large text to the resources
-    pub fn add_text(&mut self, text: &str) -> TextId {
-        use text_layout::split_text_into_words;
-        let id = TextId::new();
-        self.string_cache.insert(id, split_text_into_words(text));
-        id
+        if self.pixels.len() != expected {
+            return Err(RawImageError::InvalidPixelBufferLength { expected, actual: self.pixels.len() });
+        }
+
+        Ok(())
     }
 
-    pub fn get_text(&self, text_id: &TextId) -> Option<&Words> {
-        self.string_cache.get(text_id)
+    /// Renders a linear gradient, see [`GradientImage::Linear`]
+    ///
+    /// [`GradientImage::Linear`]: ../app_resources/enum.GradientImage.html#variant.Linear
+    pub fn linear_gradient(size: (u32, u32), stops: Vec<GradientImageStop>, angle: f32) -> Self {
+        GradientImage::Linear { size, stops, angle }.render()
     }
 
-    /// Removes a string from the string cache, but not the layouted text cache
-    pub fn delete_text(&mut self, id: TextId) {
-        self.string_cache.remove(&id);
+    /// Renders a radial gradient, see [`GradientImage::Radial`]
+    ///
+    /// [`GradientImage::Radial`]: ../app_resources/enum.GradientImage.html#variant.Radial
+    pub fn radial_gradient(size: (u32, u32), stops: Vec<GradientImageStop>, center: (f32, f32), radius: f32) -> Self {
+        GradientImage::Radial { size, stops, center, radius }.render()
     }
 
-    pub fn clear_all_texts(&mut self) {
-        self.string_cache.clear();
+    /// Renders a conic gradient, see [`GradientImage::Conic`]
+    ///
+    /// [`GradientImage::Conic`]: ../app_resources/enum.GradientImage.html#variant.Conic
+    pub fn conic_gradient(size: (u32, u32), stops: Vec<GradientImageStop>, center: (f32, f32), start_angle: f32) -> Self {
+        GradientImage::Conic { size, stops, center, start_angle }.render()
     }
-}
 
-/// Used only for debugging, so that the AppResource garbage
-/// collection tests can run without a real RenderApi
-#[cfg(test)]
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-struct FakeRenderApi { }
+    /// Returns the average color of the image as `(r, g, b, a)`. The color channels are
+    /// weighted by the alpha of each pixel, so that transparent pixels don't darken the result.
+    pub fn average_color(&self) -> (u8, u8, u8, u8) {
+        average_color(&self.pixels, self.data_format, self.alpha_premultiplied)
+    }
 
-#[cfg(test)]
-impl FakeRenderApi { fn new() -> Self { Self { } } }
+    /// Returns the most common color of the image as `(r, g, b)`, ignoring fully transparent pixels.
+    ///
+    /// The pixels are bucketed into a 16x16x16 color histogram, the returned color is the
+    /// average color of the fullest bucket.
+    pub fn dominant_color(&self) -> (u8, u8, u8) {
+        dominant_color(&self.pixels, self.data_format)
+    }
 
-pub(crate) trait FontImageApi {
-    fn new_image_key(&self) -> ImageKey;
-    fn new_font_key(&self) -> FontKey;
-    fn new_font_instance_key(&self) -> FontInstanceKey;
-    fn update_resources(&self, Vec<ResourceUpdate>);
-    fn flush_scene_builder(&self);
-}
+    /// Returns the alpha type under which the pixels are uploaded, see `alpha_premultiplied`
+    pub(crate) fn alpha_type(&self) -> AlphaType {
+        if self.alpha_premultiplied { AlphaType::PremultipliedAlpha } else { AlphaType::Alpha }
+    }
 
-impl FontImageApi for RenderApi {
-    fn new_image_key(&self) -> ImageKey { self.generate_image_key() }
-    fn new_font_key(&self) -> FontKey { self.generate_font_key() }
-    fn new_font_instance_key(&self) -> FontInstanceKey { self.generate_font_instance_key() }
-    fn update_resources(&self, updates: Vec<ResourceUpdate>) { self.update_resources(updates); }
-    fn flush_scene_builder(&self) { self.flush_scene_builder(); }
-}
+    /// Converts the image to an RGBA8 image of the `image` crate, i.e. to apply filters from the
+    /// image ecosystem. Like `to_straight_alpha_rgba8`, premultiplied pixels are un-premultiplied.
+    /// Returns `None` if the image is neither BGRA8, RGBA8 nor R8 or the pixels don't match the dimensions.
+    #[cfg(feature = "image_loading")]
+    pub fn to_dynamic_image(&self) -> Option<DynamicImage> {
+        use image::ImageBuffer;
+        let (width, height) = self.image_dimensions;
+        ImageBuffer::from_raw(width, height, self.to_straight_alpha_rgba8()).map(DynamicImage::ImageRgba8)
+    }
 
-#[cfg(test)]
-use webrender::api::IdNamespace;
+    /// Renders `data` as a black-on-white QR code (BGRA8), including the quiet zone of
+    /// four modules around the code. Each module is `module_size` x `module_size` pixels.
+    #[cfg(feature = "qrcode")]
+    pub fn qr_code(data: &str, module_size: u32, ec_level: QrEcLevel) -> Result<Self, QrError> {
 
-// Fake RenderApi for unit testing
-#[cfg(test)]
-impl FontImageApi for FakeRenderApi {
-    fn new_image_key(&self) -> ImageKey { ImageKey::DUMMY }
-    fn new_font_key(&self) -> FontKey { FontKey::new(IdNamespace(0), 0) }
-    fn new_font_instance_key(&self) -> FontInstanceKey { FontInstanceKey::new(IdNamespace(0), 0) }
-    fn update_resources(&self, _: Vec<ResourceUpdate>) { }
-    fn flush_scene_builder(&self) { }
-}
+        use qrcode::{QrCode, EcLevel, Color};
 
-impl AppResources {
+        const QUIET_ZONE_MODULES: usize = 4;
 
-    /// Creates a new renderer (the renderer manages the resources and is therefore tied to the resources).
-    #[must_use] pub(crate) fn new(app_config: &AppConfig) -> Result<Self, WindowCreateError> {
-        Ok(Self {
-            #[cfg(not(test))]
-            fake_display: FakeDisplay::new(app_config.renderer_type)?,
-            #[cfg(test)]
-            fake_render_api: FakeRenderApi::new(),
-            css_ids_to_font_ids: FastHashMap::default(),
-            css_ids_to_image_ids: FastHashMap::default(),
-            font_sources: FastHashMap::default(),
-            image_sources: FastHashMap::default(),
-            currently_registered_fonts: FastHashMap::default(),
-            currently_registered_images: FastHashMap::default(),
-            last_frame_font_keys: FastHashMap::default(),
-            last_frame_image_keys: FastHashSet::default(),
-            text_cache: TextCache::default(),
-            clipboard: SystemClipboard::new().unwrap(),
-        })
-    }
+        let ec_level = match ec_level {
+            QrEcLevel::Low => EcLevel::L,
+            QrEcLevel::Medium => EcLevel::M,
+            QrEcLevel::Quartile => EcLevel::Q,
+            QrEcLevel::High => EcLevel::H,
+        };
 
-    pub(crate) fn get_render_api(&self) -> &impl FontImageApi {
-        #[cfg(not(test))] {
-            &self.fake_display.render_api
+        if module_size == 0 {
+            return Err(QrError::InvalidModuleSize(module_size));
         }
-        #[cfg(test)] {
-            &self.fake_render_api
+
+        let code = QrCode::with_error_correction_level(data.as_bytes(), ec_level)?;
+        let modules = code.width();
+        let colors = code.to_colors();
+
+        let image_size = (modules + 2 * QUIET_ZONE_MODULES).checked_mul(module_size as usize)
+            .filter(|size| size.checked_mul(*size).and_then(|s| s.checked_mul(4)).is_some() && *size <= u32::max_value() as usize)
+            .ok_or(QrError::InvalidModuleSize(module_size))?;
+        let module_size = module_size as usize;
+
+        let mut pixels = vec![255; image_size * image_size * 4];
+
+        for (module_idx, color) in colors.iter().enumerate() {
+            if *color != Color::Dark {
+                continue;
+            }
+            let module_x = (module_idx % modules + QUIET_ZONE_MODULES) * module_size;
+            let module_y = (module_idx / modules + QUIET_ZONE_MODULES) * module_size;
+            for y in module_y..module_y + module_size {
+                let row_start = (y * image_size + module_x) * 4;
+                for bgra in pixels[row_start..row_start + module_size * 4].chunks_mut(4) {
+                    bgra.copy_from_slice(&[0, 0, 0, 255]);
+                }
+            }
         }
-    }
 
-    /// Returns the IDs of all currently loaded fonts in `self.font_data`
-    pub fn get_loaded_font_ids(&self) -> Vec<FontId> {
-        self.font_sources.keys().cloned().collect()
+        Ok(RawImage {
+            pixels,
+            image_dimensions: (image_size as u32, image_size as u32),
+            data_format: RawImageFormat::BGRA8,
+            alpha_premultiplied: false,
+            allow_mipmaps: true,
+        })
     }
 
-    pub fn get_loaded_image_ids(&self) -> Vec<ImageId> {
-        self.image_sources.keys().cloned().collect()
+    /// Converts an image of the `image` crate, the same way as images are decoded with the
+    /// default `AppConfig`: Color images are converted to premultiplied BGRA8, greyscale images to R8.
+    /// This is the inverse of `to_dynamic_image`.
+    #[cfg(feature = "image_loading")]
+    pub fn from_dynamic_image(image: DynamicImage) -> Result<Self, ImageError> {
+        let (data, descriptor) = prepare_image(image, ImageDecodeOptions::default())?;
+        let pixels = match data {
+            ImageData::Raw(pixels) => (*pixels).clone(),
+            _ => Vec::new(),
+        };
+        Ok(Self {
+            pixels,
+            image_dimensions: (descriptor.size.width as u32, descriptor.size.height as u32),
+            data_format: descriptor.format,
+            alpha_premultiplied: true,
+            allow_mipmaps: descriptor.allow_mipmaps,
+        })
     }
 
-    pub fn get_loaded_css_image_ids(&self) -> Vec<CssImageId> {
-        self.css_ids_to_image_ids.keys().cloned().collect()
+    /// Converts the image to straight-alpha RGBA8 pixels, i.e. for exporting the image to
+    /// other libraries. The pixels are un-premultiplied if `alpha_premultiplied` is set
+    /// (R8 images are expanded to opaque grey pixels).
+    pub fn to_straight_alpha_rgba8(&self) -> Vec<u8> {
+        match self.data_format {
+            RawImageFormat::BGRA8 => {
+                let mut pixels = Vec::with_capacity(self.pixels.len());
+                for bgra in self.pixels.chunks_exact(4) {
+                    let mut rgba = [bgra[2], bgra[1], bgra[0], bgra[3]];
+                    if self.alpha_premultiplied {
+                        unpremultiply(&mut rgba);
+                    }
+                    pixels.extend_from_slice(&rgba);
+                }
+                pixels
+            },
+            RawImageFormat::RGBA8 => {
+                let mut pixels = self.pixels.clone();
+                if self.alpha_premultiplied {
+                    unpremultiply(&mut pixels);
+                }
+                pixels
+            },
+            RawImageFormat::R8 => {
+                let mut pixels = Vec::with_capacity(self.pixels.len() * 4);
+                for grey in self.pixels.iter() {
+                    pixels.extend_from_slice(&[*grey, *grey, *grey, 0xff]);
+                }
+                pixels
+            },
+            RawImageFormat::R16 => {
+                let mut pixels = Vec::with_capacity(self.pixels.len() * 2);
+                for grey in r16_to_r8(&self.pixels) {
+                    pixels.extend_from_slice(&[grey, grey, grey, 0xff]);
+                }
+                pixels
+            },
+            _ => Vec::new(),
+        }
     }
 
-    pub fn get_loaded_css_font_ids(&self) -> Vec<CssFontId> {
-        self.css_ids_to_font_ids.keys().cloned().collect()
-    }
+    /// Counts the values of the blue, green, red and alpha channels in a single pass.
+    /// For `R8` images, the grey value is counted in all color channels and the alpha is always 255.
+    pub fn histogram(&self) -> ChannelHistograms {
 
-    pub fn get_loaded_text_ids(&self) -> Vec<TextId> {
-        self.text_cache.string_cache.keys().cloned().collect()
-    }
+        let mut histograms = ChannelHistograms {
+            blue: [0; 256],
+            green: [0; 256],
+            red: [0; 256],
+            alpha: [0; 256],
+        };
 
-    // -- ImageId cache
+        match self.data_format {
+            RawImageFormat::BGRA8 => {
+                for bgra in self.pixels.chunks_exact(4) {
+                    histograms.blue[bgra[0] as usize] += 1;
+                    histograms.green[bgra[1] as usize] += 1;
+                    histograms.red[bgra[2] as usize] += 1;
+                    histograms.alpha[bgra[3] as usize] += 1;
+                }
+            },
+            RawImageFormat::RGBA8 => {
+                for rgba in self.pixels.chunks_exact(4) {
+                    histograms.red[rgba[0] as usize] += 1;
+                    histograms.green[rgba[1] as usize] += 1;
+                    histograms.blue[rgba[2] as usize] += 1;
+                    histograms.alpha[rgba[3] as usize] += 1;
+                }
+            },
+            RawImageFormat::R8 => {
+                for grey in self.pixels.iter() {
+                    histograms.blue[*grey as usize] += 1;
+                    histograms.green[*grey as usize] += 1;
+                    histograms.red[*grey as usize] += 1;
+                    histograms.alpha[255] += 1;
+                }
+            },
+            _ => { },
+        }
 
-    /// Add an image from a PNG, JPEG or other - note that for specialized image formats,
-    /// you have to enable them as features in the Cargo.toml file.
-    #[cfg(feature = "image_loading")]
-    pub fn add_image(&mut self, image_id: ImageId, image_source: ImageSource) {
-        self.image_sources.insert(image_id, image_source);
+        histograms
     }
 
-    /// Returns whether the AppResources has currently a certain image ID registered
-    pub fn has_image(&self, image_id: &ImageId) -> bool {
-        self.image_sources.get(image_id).is_some()
-    }
+    /// Counts the luminance values of the image (Rec. 709 weights), i.e. for auto-contrast adjustments
+    pub fn luminance_histogram(&self) -> [u32; 256] {
 
-    /// Given an `ImageId`, returns the decoded bytes of that image or `None`, if the `ImageId` is invalid.
-    /// Returns an error on IO failure / image decoding failure or image
-    pub fn get_image_bytes(&self, image_id: &ImageId) -> Option<Result<(ImageData, ImageDescriptor), ImageReloadError>> {
-        self.image_sources.get(image_id).map(|image_source| image_source.get_bytes())
-    }
+        let mut histogram = [0; 256];
 
-    pub fn delete_image(&mut self, image_id: &ImageId) {
-        self.image_sources.remove(image_id);
-    }
+        match self.data_format {
+            RawImageFormat::BGRA8 => {
+                for bgra in self.pixels.chunks_exact(4) {
+                    histogram[luminance(bgra[2], bgra[1], bgra[0]) as usize] += 1;
+                }
+            },
+            RawImageFormat::RGBA8 => {
+                for rgba in self.pixels.chunks_exact(4) {
+                    histogram[luminance(rgba[0], rgba[1], rgba[2]) as usize] += 1;
+                }
+            },
+            RawImageFormat::R8 => {
+                for grey in self.pixels.iter() {
+                    histogram[*grey as usize] += 1;
+                }
+            },
+            _ => { },
+        }
 
-    pub fn add_css_image_id<S: Into<String>>(&mut self, css_id: S) -> ImageId {
-        *self.css_ids_to_image_ids.entry(css_id.into()).or_insert_with(|| ImageId::new())
+        histogram
     }
 
-    pub fn has_css_image_id(&self, css_id: &str) -> bool {
-        self.get_css_image_id(css_id).is_some()
+    /// Returns one entry per pixel (row by row), which is `true` if the alpha of the pixel
+    /// is greater or equal to the `threshold`, i.e. for hit-testing against the opaque parts
+    /// of an image. Images without an alpha channel are fully opaque.
+    pub fn alpha_mask(&self, threshold: u8) -> Vec<bool> {
+        match self.data_format {
+            RawImageFormat::BGRA8 | RawImageFormat::RGBA8 => self.pixels.chunks_exact(4).map(|pixel| pixel[3] >= threshold).collect(),
+            _ => vec![true; self.image_dimensions.0 as usize * self.image_dimensions.1 as usize],
+        }
     }
+}
 
-    pub fn get_css_image_id(&self, css_id: &str) -> Option<&ImageId> {
-        self.css_ids_to_image_ids.get(css_id)
-    }
+/// Outline of a single glyph, see [`AppResources::get_glyph_outline`]
+///
+/// [`AppResources::get_glyph_outline`]: ../app_resources/struct.AppResources.html#method.get_glyph_outline
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphOutline {
+    /// Contour commands in font units, the Y axis points upwards. Every contour
+    /// starts with a `MoveTo` and ends at the point it started at.
+    pub operations: Vec<GlyphOutlineOperation>,
+}
 
-    pub fn delete_css_image_id(&mut self, css_id: &str) -> Option<ImageId> {
-        self.css_ids_to_image_ids.remove(css_id)
-    }
+/// Vertical metrics of a font in font units, see [`AppResources::get_font_metrics`]. To convert the
+/// metrics to pixels, multiply them with `font_size_px / units_per_em`.
+///
+/// [`AppResources::get_font_metrics`]: ../app_resources/struct.AppResources.html#method.get_font_metrics
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FontMetrics {
+    pub units_per_em: u16,
+    /// Distance from the baseline to the top of a line, positive
+    pub ascent: i16,
+    /// Distance from the baseline to the bottom of a line, negative
+    pub descent: i16,
+    /// Additional space between two lines
+    pub line_gap: i16,
+    /// Height of capital letters, `None` if the font doesn't specify it
+    pub cap_height: Option<i16>,
+    /// Height of lowercase letters, `None` if the font doesn't specify it
+    pub x_height: Option<i16>,
+}
 
-    pub fn get_image_info(&self, key: &ImageId) -> Option<&ImageInfo> {
-        self.currently_registered_images.get(key)
-    }
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GlyphOutlineOperation {
+    /// Starts a new contour at the given point
+    MoveTo((f32, f32)),
+    /// Straight line from the current point to the given point
+    LineTo((f32, f32)),
+    /// Quadratic bezier curve from the current point to the end point: `(control, end)`.
+    /// TrueType outlines (`glyf` table) only consist of quadratic curves.
+    QuadraticCurveTo((f32, f32), (f32, f32)),
+    /// Cubic bezier curve from the current point to the end point: `(control 1, control 2, end)`.
+    /// PostScript outlines (`CFF ` table) only consist of cubic curves.
+    CubicCurveTo((f32, f32), (f32, f32), (f32, f32)),
+}
 
-    // -- FontId cache
+impl GlyphOutlineOperation {
 
-    pub fn add_css_font_id<S: Into<String>>(&mut self, css_id: S) -> FontId {
-        *self.css_ids_to_font_ids.entry(css_id.into()).or_insert_with(|| FontId::new())
+    /// Applies `f` to every point of the operation, i.e. to transform the components of a composite glyph
+    fn map_points<F: Fn((f32, f32)) -> (f32, f32)>(self, f: F) -> Self {
+        use self::GlyphOutlineOperation::*;
+        match self {
+            MoveTo(point) => MoveTo(f(point)),
+            LineTo(point) => LineTo(f(point)),
+            QuadraticCurveTo(control, end) => QuadraticCurveTo(f(control), f(end)),
+            CubicCurveTo(control_1, control_2, end) => CubicCurveTo(f(control_1), f(control_2), f(end)),
+        }
     }
+}
 
-    pub fn has_css_font_id(&self, css_id: &str) -> bool {
-        self.get_css_font_id(css_id).is_some()
-    }
+/// Font made up of one image per character (i.e. a legacy bitmap font stored as sprites),
+/// created by [`AppResources::add_bitmap_font`]. The glyphs are packed into shared atlas
+/// images, but every glyph has its own `ImageId`, so it can be drawn like any other image.
+/// The atlas images are garbage-collected if none of their glyphs are used.
+///
+/// [`AppResources::add_bitmap_font`]: ../app_resources/struct.AppResources.html#method.add_bitmap_font
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BitmapFont {
+    pub glyphs: BTreeMap<char, BitmapGlyph>,
+}
 
-    pub fn get_css_font_id(&self, css_id: &str) -> Option<&FontId> {
-        self.css_ids_to_font_ids.get(css_id)
-    }
+/// Image and metrics of a single character of a `BitmapFont`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BitmapGlyph {
+    pub image_id: ImageId,
+    /// Atlas image that the glyph was packed into, shared with other glyphs of the font
+    pub atlas_id: ImageId,
+    /// Top left corner of the glyph inside of the atlas image in pixels
+    pub atlas_offset: (u32, u32),
+    /// Size of the glyph image in pixels
+    pub dimensions: (u32, u32),
+    /// Horizontal distance to the next glyph in pixels (the width of the glyph image)
+    pub advance: u32,
+}
 
-    pub fn delete_css_font_id(&mut self, css_id: &str) -> Option<FontId> {
-        self.css_ids_to_font_ids.remove(css_id)
-    }
+impl BitmapFont {
 
-    pub fn add_font(&mut self, font_id: FontId, font_source: FontSource) {
-        self.font_sources.insert(font_id, font_source);
+    /// Returns the glyph of a character or `None`, if the font has no image for it
+    pub fn get_glyph(&self, c: char) -> Option<&BitmapGlyph> {
+        self.glyphs.get(&c)
     }
 
-    /// Given a `FontId`, returns the bytes for that font or `None`, if the `FontId` is invalid.
-    pub fn get_font_bytes(&self, font_id: &FontId) -> Option<Result<(Vec<u8>, i32), FontReloadError>> {
-        let font_source = self.font_sources.get(font_id)?;
-        Some(font_source.get_bytes())
+    /// Returns the `(width, height)` of a single line of text in pixels.
+    /// Characters that have no glyph are skipped.
+    pub fn get_text_size(&self, text: &str) -> (u32, u32) {
+        text.chars()
+            .filter_map(|c| self.get_glyph(c))
+            .fold((0, 0), |(width, height), glyph| (width + glyph.advance, height.max(glyph.dimensions.1)))
     }
+}
 
-    /// Checks if a `FontId` is valid, i.e. if a font is currently ready-to-use
-    pub fn has_font(&self, id: &FontId) -> bool {
-        self.font_sources.get(id).is_some()
-    }
+/// Width and height of an atlas image of a `BitmapFont`, glyphs that are larger get their own atlas
+const BITMAP_FONT_ATLAS_SIZE: u32 = 1024;
 
-    pub fn delete_font(&mut self, id: &FontId) {
-        self.font_sources.remove(id);
-    }
+/// Transparent pixels between the glyphs of an atlas, so that filtering doesn't bleed into the neighbours
+const BITMAP_FONT_ATLAS_PADDING: u32 = 1;
 
-    // -- TextId cache
+/// Glyph of a `BitmapFont`, see `AppResources::add_bitmap_font`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct AtlasGlyph {
+    /// Atlas image that contains the glyph
+    pub(crate) atlas_id: ImageId,
+    /// `(x, y, width, height)` of the glyph inside of the atlas image in pixels
+    pub(crate) rect: (u32, u32, u32, u32),
+}
 
-    /// Adds a string to the internal text cache, but only store it as a string,
-    /// without caching the layout of the string.
-    pub fn add_text(&mut self, text: &str) -> TextId {
-        self.text_cache.add_text(text)
-    }
+/// Shelf allocator for the atlas images of bitmap fonts: rectangles are placed next to each other
+/// on horizontal shelves, a new shelf is opened below the last one if no shelf has room left.
+#[derive(Debug, Clone)]
+struct ShelfPacker {
+    size: (u32, u32),
+    /// `(y, height, used width)` of each shelf
+    shelves: Vec<(u32, u32, u32)>,
+}
 
-    pub fn get_text(&self, id: &TextId) -> Option<&Words> {
-        self.text_cache.get_text(id)
-    }
+impl ShelfPacker {
 
-    /// Removes a string from both the string cache and the layouted text cache
-    pub fn delete_text(&mut self, id: TextId) {
-        self.text_cache.delete_text(id);
+    fn new(size: (u32, u32)) -> Self {
+        Self { size, shelves: Vec::new() }
     }
 
-    /// Empties the entire internal text cache, invalidating all `TextId`s. Use with care.
-    pub fn clear_all_texts(&mut self) {
-        self.text_cache.clear_all_texts();
-    }
+    /// Returns the top left corner of a free `(width, height)` rectangle or `None`, if the atlas is full
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
 
-    // -- Clipboard
+        if width > self.size.0 || height > self.size.1 {
+            return None;
+        }
 
-    /// Returns the contents of the system clipboard
-    pub fn get_clipboard_string(&self) -> Result<String, ClipboardError> {
-        self.clipboard.get_string_contents()
-    }
+        // The lowest shelf that the rectangle fits on wastes the least space
+        let atlas_width = self.size.0;
+        let best_shelf = self.shelves.iter().enumerate()
+            .filter(|(_, (_, shelf_height, used_width))| *shelf_height >= height && atlas_width - *used_width >= width)
+            .min_by_key(|(_, (_, shelf_height, _))| *shelf_height)
+            .map(|(index, _)| index);
+
+        if let Some(index) = best_shelf {
+            let shelf = &mut self.shelves[index];
+            let position = (shelf.2, shelf.0);
+            shelf.2 += width;
+            return Some(position);
+        }
 
-    /// Sets the contents of the system clipboard - currently only strings are supported
-    pub fn set_clipboard_string<S: Into<String>>(&mut self, contents: S) -> Result<(), ClipboardError> {
-        self.clipboard.set_string_contents(contents.into())
+        let next_y = self.used_height();
+        if self.size.1 - next_y < height {
+            return None;
+        }
+        self.shelves.push((next_y, height, width));
+        Some((0, next_y))
     }
 
-    pub(crate) fn get_loaded_font(&self, font_id: &ImmediateFontId) -> Option<&LoadedFont> {
-        self.currently_registered_fonts.get(font_id)
+    /// Returns the height up to the bottom of the last shelf
+    fn used_height(&self) -> u32 {
+        self.shelves.last().map(|(y, height, _)| y + height).unwrap_or(0)
     }
+}
 
-    /// Scans the DisplayList for new images and fonts. After this call, the RenderApi is
-    /// guaranteed to know about all FontKeys and FontInstanceKey
-    pub(crate) fn add_fonts_and_images<T>(&mut self, display_list: &DisplayList<T>) {
-        let font_keys = scan_ui_description_for_font_keys(&self, display_list);
-        let image_keys = scan_ui_description_for_image_keys(&self, display_list);
-
-        self.last_frame_font_keys.extend(font_keys.clone().into_iter());
-        self.last_frame_image_keys.extend(image_keys.clone().into_iter());
-
-        let add_font_resource_updates = build_add_font_resource_updates(self, &font_keys);
-        let add_image_resource_updates = build_add_image_resource_updates(self, &image_keys);
+/// Copies the pixels of a glyph into a BGRA8 atlas image that is `atlas_width` pixels wide.
+/// Glyphs whose pixels can't be converted (see `RawImage::to_straight_alpha_rgba8`) stay transparent.
+fn copy_glyph_into_atlas(atlas: &mut [u8], atlas_width: u32, glyph: &RawImage, (x, y): (u32, u32)) {
 
-        add_resources(self, add_font_resource_updates, add_image_resource_updates);
+    let (width, height) = (glyph.image_dimensions.0 as usize, glyph.image_dimensions.1 as usize);
+    let rgba = glyph.to_straight_alpha_rgba8();
+    if width == 0 || rgba.len() != width * height * 4 {
+        return;
     }
 
-    /// To be called at the end of a frame (after the UI has rendered):
-    /// Deletes all FontKeys and FontImageKeys that weren't used in
-    /// the last frame, to save on memory. If the font needs to be recreated, it
-    /// needs to be reloaded from the `FontSource`.
-    pub(crate) fn garbage_collect_fonts_and_images(&mut self) {
+    for (row, glyph_row) in rgba.chunks(width * 4).enumerate() {
+        let start = ((y as usize + row) * atlas_width as usize + x as usize) * 4;
+        let atlas_row = &mut atlas[start..start + width * 4];
+        for (bgra, rgba) in atlas_row.chunks_mut(4).zip(glyph_row.chunks(4)) {
+            bgra.copy_from_slice(&[rgba[2], rgba[1], rgba[0], rgba[3]]);
+        }
+    }
+}
 
-        let delete_font_resource_updates = build_delete_font_resource_updates(self);
-        let delete_image_resource_updates = build_delete_image_resource_updates(self);
+/// Color stop of a `GradientImage`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GradientImageStop {
+    /// Position of the stop on the gradient line, from 0.0 to 1.0
+    pub offset: f32,
+    pub color: ColorU,
+}
 
-        delete_resources(self, delete_font_resource_updates, delete_image_resource_updates);
+/// Gradient that can be rendered into a straight-alpha BGRA8 `RawImage`.
+/// The stops have to be sorted by their offset.
+#[derive(Debug, Clone)]
+pub enum GradientImage {
+    /// Gradient along a line through the center of the image. The angle is in degrees
+    /// and follows the CSS convention: 0 degrees points upwards, 90 degrees to the right.
+    Linear { size: (u32, u32), stops: Vec<GradientImageStop>, angle: f32 },
+    /// Circular gradient around `center` (in pixels), reaching the last stop at `radius` pixels
+    Radial { size: (u32, u32), stops: Vec<GradientImageStop>, center: (f32, f32), radius: f32 },
+    /// Gradient that sweeps clockwise around `center` (in pixels), starting at
+    /// `start_angle` degrees (0 degrees points upwards)
+    Conic { size: (u32, u32), stops: Vec<GradientImageStop>, center: (f32, f32), start_angle: f32 },
+}
 
-        self.last_frame_font_keys.clear();
-        self.last_frame_image_keys.clear();
+// The gradient is only used as a cache key - two gradients are equal if all of their floats are bitwise equal
+impl PartialEq for GradientImage {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.cache_key() == rhs.cache_key()
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub(crate) enum ImmediateFontId {
-    Resolved(FontId),
-    Unresolved(CssFontId),
+impl Eq for GradientImage { }
+
+impl ::std::hash::Hash for GradientImage {
+    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+        self.cache_key().hash(state);
+    }
 }
 
-/// Scans the display list for all font IDs + their font size
-fn scan_ui_description_for_font_keys<'a, T>(
-    app_resources: &AppResources,
-    display_list: &DisplayList<'a, T>
-) -> FastHashMap<ImmediateFontId, FastHashSet<Au>> {
+impl GradientImage {
 
-    use dom::NodeType::*;
-    use ui_solver;
+    /// Variant, size, stops and parameters of the gradient, with all floats as their bits
+    fn cache_key(&self) -> (u8, (u32, u32), Vec<(u32, ColorU)>, [u32; 3]) {
+        use self::GradientImage::*;
 
-    let mut font_keys = FastHashMap::default();
+        let stop_bits = |stops: &[GradientImageStop]| stops.iter().map(|stop| (stop.offset.to_bits(), stop.color)).collect();
 
-    for node_id in display_list.rectangles.linear_iter() {
+        match self {
+            Linear { size, stops, angle } => (0, *size, stop_bits(stops), [angle.to_bits(), 0, 0]),
+            Radial { size, stops, center, radius } => (1, *size, stop_bits(stops), [center.0.to_bits(), center.1.to_bits(), radius.to_bits()]),
+            Conic { size, stops, center, start_angle } => (2, *size, stop_bits(stops), [center.0.to_bits(), center.1.to_bits(), start_angle.to_bits()]),
+        }
+    }
 
-        let node_data = &display_list.ui_descr.ui_descr_arena.node_data[node_id];
-        let display_rect = &display_list.rectangles[node_id];
+    /// Renders the gradient into a straight-alpha BGRA8 image
+    pub fn render(&self) -> RawImage {
 
-        match node_data.node_type {
-            Text(_) | Label(_) => {
-                let css_font_id = ui_solver::get_font_id(&display_rect.style);
-                let font_id = match app_resources.css_ids_to_font_ids.get(css_font_id) {
-                    Some(s) => ImmediateFontId::Resolved(*s),
-                    None => ImmediateFontId::Unresolved(css_font_id.to_string()),
-                };
-                let font_size = ui_solver::get_font_size(&display_rect.style);
-                font_keys
-                    .entry(font_id)
-                    .or_insert_with(|| FastHashSet::default())
-                    .insert(ui_solver::font_size_to_au(font_size));
+        use std::f32::consts::PI;
+        use self::GradientImage::*;
+
+        match self {
+            Linear { size, stops, angle } => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let (width, height) = (size.0 as f32, size.1 as f32);
+                // Length of the gradient line, so that the corners get the first / last stop
+                let gradient_length = (width * sin).abs() + (height * cos).abs();
+                render_gradient(*size, stops, |x, y| {
+                    if gradient_length <= 0.0 {
+                        return 0.0;
+                    }
+                    ((x - width / 2.0) * sin - (y - height / 2.0) * cos) / gradient_length + 0.5
+                })
+            },
+            Radial { size, stops, center, radius } => {
+                render_gradient(*size, stops, |x, y| {
+                    if *radius <= 0.0 {
+                        return 1.0;
+                    }
+                    (x - center.0).hypot(y - center.1) / radius
+                })
+            },
+            Conic { size, stops, center, start_angle } => {
+                render_gradient(*size, stops, |x, y| {
+                    // 0 = upwards, clockwise
+                    let angle = (x - center.0).atan2(center.1 - y) - start_angle.to_radians();
+                    let t = (angle / (2.0 * PI)) % 1.0;
+                    if t < 0.0 { t + 1.0 } else { t }
+                })
             },
-            _ => { }
         }
     }
-
-    font_keys
 }
 
-/// Scans the display list for all image keys
-fn scan_ui_description_for_image_keys<'a, T>(
-    app_resources: &AppResources,
-    display_list: &DisplayList<'a, T>
-) -> FastHashSet<ImageId> {
+/// Fills a BGRA8 buffer by evaluating the position on the gradient line (0.0 to 1.0) for each pixel center
+fn render_gradient<F: Fn(f32, f32) -> f32>(size: (u32, u32), stops: &[GradientImageStop], gradient_position: F) -> RawImage {
 
-    use dom::NodeType::*;
+    let mut pixels = Vec::with_capacity(size.0 as usize * size.1 as usize * 4);
 
-    display_list.rectangles
-    .iter()
-    .zip(display_list.ui_descr.ui_descr_arena.node_data.iter())
-    .filter_map(|(display_rect, node_data)| {
-        match node_data.node_type {
-            Image(id) => Some(id),
-            _ => {
-                let background = display_rect.style.background.as_ref()?;
-                let css_image_id = background.get_css_image_id()?;
-                let image_id = app_resources.get_css_image_id(&css_image_id.0)?;
-                Some(*image_id)
-            }
+    for y in 0..size.1 {
+        for x in 0..size.0 {
+            let color = sample_gradient(stops, gradient_position(x as f32 + 0.5, y as f32 + 0.5));
+            pixels.extend_from_slice(&[color.b, color.g, color.r, color.a]);
         }
-    }).collect()
-}
+    }
 
-// Debug, PartialEq, Eq, PartialOrd, Ord
-#[derive(Clone)]
-enum AddFontMsg {
-    Font(LoadedFont),
-    Instance(AddFontInstance, Au),
+    RawImage {
+        pixels,
+        image_dimensions: size,
+        data_format: RawImageFormat::BGRA8,
+        alpha_premultiplied: false,
+        allow_mipmaps: true,
+    }
 }
 
-// Debug, PartialEq, Eq, PartialOrd, Ord
-#[derive(Clone)]
-enum DeleteFontMsg {
-    Font(FontKey),
-    Instance(FontInstanceKey, Au),
-}
-// Debug, PartialEq, Eq, PartialOrd, Ord
-#[derive(Clone)]
-struct AddImageMsg(AddImage, ImageInfo);
+/// Returns the color at the position `t` (0.0 to 1.0) by interpolating between the two nearest stops
+fn sample_gradient(stops: &[GradientImageStop], t: f32) -> ColorU {
 
-// Debug, PartialEq, Eq, PartialOrd, Ord
-#[derive(Clone)]
-struct DeleteImageMsg(ImageKey, ImageInfo);
+    let t = t.max(0.0).min(1.0);
 
-impl AddFontMsg {
-    fn into_resource_update(&self) -> ResourceUpdate {
-        use self::AddFontMsg::*;
-        match self {
-            Font(f) => ResourceUpdate::AddFont(AddFont::Raw(f.font_key, f.font_bytes.clone(), f.font_index as u32)),
-            Instance(fi, _) => ResourceUpdate::AddFontInstance(fi.clone()),
-        }
+    let first = match stops.first() {
+        Some(s) => s,
+        None => return ColorU { r: 0, g: 0, b: 0, a: 0 },
+    };
+
+    if t <= first.offset {
+        return first.color;
     }
-}
 
-impl DeleteFontMsg {
-    fn into_resource_update(&self) -> ResourceUpdate {
-        use self::DeleteFontMsg::*;
-        match self {
-            Font(f) => ResourceUpdate::DeleteFont(*f),
-            Instance(fi, _) => ResourceUpdate::DeleteFontInstance(*fi),
+    for stop_pair in stops.windows(2) {
+        let (start, end) = (&stop_pair[0], &stop_pair[1]);
+        if t <= end.offset {
+            let range = end.offset - start.offset;
+            let factor = if range <= 0.0 { 1.0 } else { (t - start.offset) / range };
+            let lerp = |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * factor).round() as u8;
+            return ColorU {
+                r: lerp(start.color.r, end.color.r),
+                g: lerp(start.color.g, end.color.g),
+                b: lerp(start.color.b, end.color.b),
+                a: lerp(start.color.a, end.color.a),
+            };
         }
     }
-}
 
-impl AddImageMsg {
-    fn into_resource_update(&self) -> ResourceUpdate {
-        ResourceUpdate::AddImage(self.0.clone())
-    }
+    stops[stops.len() - 1].color
 }
 
-impl DeleteImageMsg {
-    fn into_resource_update(&self) -> ResourceUpdate {
-        ResourceUpdate::DeleteImage(self.0.clone())
+/// Per-channel value counts of an image, see [`RawImage::histogram`]
+///
+/// [`RawImage::histogram`]: ../app_resources/struct.RawImage.html#method.histogram
+#[derive(Copy, Clone)]
+pub struct ChannelHistograms {
+    pub blue: [u32; 256],
+    pub green: [u32; 256],
+    pub red: [u32; 256],
+    pub alpha: [u32; 256],
+}
 
-    }
+#[derive(Debug, Clone)]
+pub struct LoadedFont {
+    pub font_key: FontKey,
+    pub font_bytes: Vec<u8>,
+    /// Index of the font in case the bytes indicate a font collection
+    pub font_index: i32,
+    pub font_instances: FastHashMap<FontInstanceSpec, FontInstanceKey>,
+    /// Hash of the font bytes and the font index, computed when the font is loaded,
+    /// so that it survives `AppConfig::drop_font_bytes_after_upload`, see `content_id`
+    content_hash: u64,
 }
 
-/// Given the fonts of the current frame, returns `AddFont` and `AddFontInstance`s of
-/// which fonts / instances are currently not in the `current_registered_fonts` and
-/// need to be added.
-///
-/// Deleting fonts can only be done after the entire frame has finished drawing,
-/// otherwise (if removing fonts would happen after every DOM) we'd constantly
-/// add-and-remove fonts after every IFrameCallback, which would cause a lot of
-/// I/O waiting.
-fn build_add_font_resource_updates(
-    app_resources: &AppResources,
-    fonts_in_dom: &FastHashMap<ImmediateFontId, FastHashSet<Au>>,
-) -> Vec<(ImmediateFontId, AddFontMsg)> {
+impl LoadedFont {
 
-    use webrender::api::{FontInstancePlatformOptions, FontInstanceOptions, FontRenderMode, FontInstanceFlags};
+    /// Creates a new loaded font with 0 font instances
+    pub fn new(font_key: FontKey, font_bytes: Vec<u8>, font_index: i32) -> Self {
+        let index_bytes = [(font_index >> 24) as u8, (font_index >> 16) as u8, (font_index >> 8) as u8, font_index as u8];
+        let content_hash = fnv1a_hash(font_bytes.iter().chain(index_bytes.iter()));
+        Self {
+            font_key,
+            font_bytes,
+            font_index,
+            font_instances: FastHashMap::default(),
+            content_hash,
+        }
+    }
 
-    let mut resource_updates = Vec::new();
+    fn delete_font_instance(&mut self, instance_spec: &FontInstanceSpec) {
+        self.font_instances.remove(instance_spec);
+    }
 
-    for (im_font_id, font_sizes) in fonts_in_dom {
+    /// Returns a fingerprint of the font bytes, the font index and the variation coordinates
+    /// (i.e. `FontInstanceSpec::variations`, the order doesn't matter), which (unlike the
+    /// `FontKey`) is the same across application runs, i.e. to key a persistent glyph cache on.
+    ///
+    /// The hash (64-bit FNV-1a) of the bytes is computed once when the font is loaded, so the
+    /// id stays the same if the bytes are dropped (see `AppConfig::drop_font_bytes_after_upload`).
+    /// Without variations, the id is the hash of the bytes and the index.
+    pub fn content_id(&self, variations: &[(u32, f32)]) -> u64 {
 
-        macro_rules! insert_font_instances {($font_id:expr, $font_key:expr, $font_index:expr, $font_size:expr) => ({
+        if variations.is_empty() {
+            return self.content_hash;
+        }
 
-            let font_instance_key_exists = app_resources.currently_registered_fonts
-                .get(&$font_id)
-                .and_then(|loaded_font| loaded_font.font_instances.get(&$font_size))
-                .is_some();
+        let mut variations = variations.to_vec();
+        variations.sort_by_key(|(tag, _)| *tag);
 
-            if !font_instance_key_exists {
+        let mut bytes = Vec::with_capacity(8 + variations.len() * 8);
+        bytes.extend((0..8).rev().map(|shift| (self.content_hash >> (shift * 8)) as u8));
+        for (tag, value) in variations {
+            let value = value.to_bits();
+            bytes.extend_from_slice(&[(tag >> 24) as u8, (tag >> 16) as u8, (tag >> 8) as u8, tag as u8]);
+            bytes.extend_from_slice(&[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]);
+        }
 
-                let font_instance_key = app_resources.get_render_api().new_font_instance_key();
+        fnv1a_hash(bytes.iter())
+    }
+}
 
-                // For some reason the gamma is way to low on Windows
-                #[cfg(target_os = "windows")]
-                let platform_options = FontInstancePlatformOptions {
-                    gamma: 300,
-                    contrast: 100,
-                };
+/// Font that is rendered with the bytes of another font, but with synthetic styles
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct SyntheticFont {
+    base: FontId,
+    synthetic_bold: bool,
+    synthetic_italic: bool,
+}
 
-                #[cfg(target_os = "linux")]
-                use webrender::api::{FontLCDFilter, FontHinting};
+/// Identifies a font instance (`FontInstanceKey`) of a `LoadedFont`. Instances of the same
+/// font and size, but with different synthetic styles or variations are separate instances.
+#[derive(Debug, Clone)]
+pub struct FontInstanceSpec {
+    pub size: Au,
+    /// Whether the glyphs are emboldened by the rasterizer
+    pub synthetic_bold: bool,
+    /// Whether the glyphs are slanted by the rasterizer. WebRender only supports a
+    /// fixed slant angle for synthetic italics, so this can't be an angle
+    pub synthetic_italic: bool,
+    /// Values of OpenType variation axes, as `(tag, value)`, i.e. `(wght, 700.0)`
+    pub variations: Vec<(u32, f32)>,
+}
 
-                #[cfg(target_os = "linux")]
-                let platform_options = FontInstancePlatformOptions {
-                    lcd_filter: FontLCDFilter::Default,
-                    hinting: FontHinting::LCD,
-                };
+impl FontInstanceSpec {
+    /// Creates a plain instance of the given size, without any synthetic styles or variations
+    pub fn new(size: Au) -> Self {
+        Self {
+            size,
+            synthetic_bold: false,
+            synthetic_italic: false,
+            variations: Vec::new(),
+        }
+    }
+}
 
-                #[cfg(target_os = "macos")]
-                let platform_options = FontInstancePlatformOptions::default();
+impl From<Au> for FontInstanceSpec {
+    fn from(size: Au) -> Self {
+        Self::new(size)
+    }
+}
 
-                let mut font_instance_flags = FontInstanceFlags::empty();
+// The variation values are compared by their bits, consistent with the `Hash` implementation
+impl PartialEq for FontInstanceSpec {
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size &&
+        self.synthetic_bold == other.synthetic_bold &&
+        self.synthetic_italic == other.synthetic_italic &&
+        self.variations.len() == other.variations.len() &&
+        self.variations.iter().zip(other.variations.iter()).all(|((tag, value), (other_tag, other_value))| {
+            tag == other_tag && value.to_bits() == other_value.to_bits()
+        })
+    }
+}
 
-                font_instance_flags.set(FontInstanceFlags::SUBPIXEL_BGR, false);
-                font_instance_flags.set(FontInstanceFlags::NO_AUTOHINT, true);
-                font_instance_flags.set(FontInstanceFlags::LCD_VERTICAL, false);
+impl Eq for FontInstanceSpec { }
 
-                let options = FontInstanceOptions {
-                    render_mode: FontRenderMode::Subpixel,
-                    flags: font_instance_flags,
-                    .. Default::default()
-                };
+impl ::std::hash::Hash for FontInstanceSpec {
+    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+        self.size.hash(state);
+        self.synthetic_bold.hash(state);
+        self.synthetic_italic.hash(state);
+        for (tag, value) in self.variations.iter() {
+            tag.hash(state);
+            value.to_bits().hash(state);
+        }
+    }
+}
 
-                resource_updates.push(($font_id, AddFontMsg::Instance(AddFontInstance {
-                    key: font_instance_key,
-                    font_key: $font_key,
-                    glyph_size: $font_size,
-                    options: Some(options),
-                    platform_options: Some(platform_options),
-                    variations: Vec::new(),
-                }, $font_size)));
-            }
-        })}
+/// Splits a text into words, see `TextCache::set_word_splitter`
+pub type WordSplitter = Arc<dyn Fn(&str) -> Words + Send + Sync>;
 
-        match app_resources.currently_registered_fonts.get(im_font_id) {
-            Some(loaded_font) => {
-                for font_size in font_sizes.iter() {
-                    insert_font_instances!(im_font_id.clone(), loaded_font.font_key, loaded_font.font_index, *font_size);
-                }
-            },
-            None => {
-                use self::ImmediateFontId::*;
+/// ISO 15924 tag of a writing system, i.e. `Script(*b"Latn")` or `Script(*b"Cyrl")`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Script(pub [u8;4]);
+
+/// Additional information about a cached text, which text shaping can use
+/// to select language-specific glyphs, see `TextCache::add_text_with_meta`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct TextMeta {
+    /// BCP 47 language tag of the text, i.e. "en-US" or "sr"
+    pub lang: Option<String>,
+    /// Writing system of the text, if it can't be derived from the language
+    pub script: Option<Script>,
+}
 
-                // If there is no font key, that means there's also no font instances
-                let font_source = match im_font_id {
-                    Resolved(font_id) => {
-                        match app_resources.font_sources.get(font_id) {
-                            Some(s) => s.clone(),
-                            None => continue,
-                        }
-                    },
-                    Unresolved(css_font_id) => FontSource::System(css_font_id.clone()),
-                };
+/// Cache for accessing large amounts of text
+#[derive(Default, Clone)]
+pub struct TextCache {
+    /// Mapping from the TextID to the actual, UTF-8 String
+    ///
+    /// This is stored outside of the actual glyph calculation, because usually you don't
+    /// need the string, except for rebuilding a cached string (for example, when the font is changed)
+    pub(crate) string_cache: FastHashMap<TextId, Words>,
 
-                let (font_bytes, font_index) = match font_source.get_bytes() {
-                    Ok(o) => o,
-                    Err(e) => {
-                        #[cfg(feature = "logging")] {
-                            warn!("Could not load font with ID: {:?} - error: {}", im_font_id, e);
-                        }
-                        continue;
-                    }
-                };
+    /// The original strings of the texts - the `Words` only contain the NFC-normalized string,
+    /// so the original can't be reconstructed from the `string_cache`
+    original_strings: FastHashMap<TextId, String>,
 
-                if !font_sizes.is_empty() {
-                    let font_key = app_resources.get_render_api().new_font_key();
+    /// Caches the layout of the strings / words.
+    ///
+    /// TextId -> FontInstanceKey (font + size, to cache the glyph widths on a per-font-size basis)
+    ///
+    /// The layout only has read access to the `AppResources`, so the cache is filled via a `RefCell`.
+    /// Entries are evicted when the text is deleted or the font instance is deleted from the renderer.
+    pub(crate) layouted_strings_cache: RefCell<FastHashMap<TextId, FastHashMap<FontInstanceKey, ScaledWords>>>,
 
-                    resource_updates.push((im_font_id.clone(), AddFontMsg::Font(LoadedFont::new(font_key, font_bytes, font_index))));
+    /// Language / script of the texts added via `add_text_with_meta`
+    pub(crate) text_meta: FastHashMap<TextId, TextMeta>,
 
-                    for font_size in font_sizes {
-                        insert_font_instances!(im_font_id.clone(), font_key, font_index, *font_size);
-                    }
-                }
-            }
-        }
-    }
+    /// Custom function to split texts into words, `None` = `text_layout::split_text_into_words`
+    word_splitter: Option<WordSplitter>,
 
-    resource_updates
+    /// The `TextId` that `add_text` returns next, the IDs are scoped to this cache
+    next_text_id: usize,
 }
 
-/// Given the images of the current frame, returns `AddImage`s of
-/// which image keys are currently not in the `current_registered_fonts` and
-/// need to be added. Modifies `last_frame_image_keys` to contain the added image keys
-///
-/// Deleting images can only be done after the entire frame has finished drawing,
-/// otherwise (if removing images would happen after every DOM) we'd constantly
-/// add-and-remove images after every IFrameCallback, which would cause a lot of
-/// I/O waiting.
-#[allow(unused_variables)]
-fn build_add_image_resource_updates(
-    app_resources: &AppResources,
-    images_in_dom: &FastHashSet<ImageId>,
-) -> Vec<(ImageId, AddImageMsg)> {
-
-    images_in_dom.iter()
-    .filter(|image_id| !app_resources.currently_registered_images.contains_key(*image_id))
-    .filter_map(|image_id| {
-        let (data, descriptor) = match app_resources.image_sources.get(image_id)?.get_bytes() {
-            Ok(o) => o,
-            Err(e) => {
-                #[cfg(feature = "logging")] {
-                    warn!("Could not load image with ID: {:?} - error: {}", image_id, e);
-                }
-                return None;
-            }
-        };
-
-        let key = app_resources.get_render_api().new_image_key();
-        let add_image = AddImage { key, data, descriptor, tiling: None };
-        Some((*image_id, AddImageMsg(add_image, ImageInfo { key, descriptor })))
-
-    }).collect()
+impl fmt::Debug for TextCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TextCache")
+            .field("string_cache", &self.string_cache)
+            .field("original_strings", &self.original_strings)
+            .field("layouted_strings_cache", &self.layouted_strings_cache)
+            .field("text_meta", &self.text_meta)
+            .field("word_splitter", &self.word_splitter.as_ref().map(|_| "custom"))
+            .finish()
+    }
 }
 
-/// Submits the `AddFont`, `AddFontInstance` and `AddImage` resources to the RenderApi.
-/// Extends `currently_registered_images` and `currently_registered_fonts` by the
-/// `last_frame_image_keys` and `last_frame_font_keys`, so that we don't lose track of
-/// what font and image keys are currently in the API.
-fn add_resources(
-    app_resources: &mut AppResources,
-    add_font_resources: Vec<(ImmediateFontId, AddFontMsg)>,
-    add_image_resources: Vec<(ImageId, AddImageMsg)>,
-) {
-    let mut merged_resource_updates = Vec::new();
-
-    merged_resource_updates.extend(add_font_resources.iter().map(|(_, f)| f.into_resource_update()));
-    merged_resource_updates.extend(add_image_resources.iter().map(|(_, i)| i.into_resource_update()));
+impl TextCache {
 
-    if !merged_resource_updates.is_empty() {
-        app_resources.get_render_api().update_resources(merged_resource_updates);
-        // Assure that the AddFont / AddImage updates get processed immediately
-        app_resources.get_render_api().flush_scene_builder();
+    /// Add a new, large text to the resources
+    pub fn add_text(&mut self, text: &str) -> TextId {
+        let id = TextId::new(&mut self.next_text_id);
+        let words = self.split_text(text);
+        self.string_cache.insert(id, words);
+        self.original_strings.insert(id, text.to_string());
+        id
     }
 
-    for (image_id, add_image_msg) in add_image_resources.iter() {
-        app_resources.currently_registered_images.insert(*image_id, add_image_msg.1);
+    /// Replaces the string of an existing text, keeping its `TextId` (and metadata), so that
+    /// the DOM doesn't have to change. Only the layouts of this text are evicted from the cache.
+    /// Returns `false` (and does nothing) if the text doesn't exist.
+    pub fn update_text(&mut self, id: TextId, new_text: &str) -> bool {
+        if !self.string_cache.contains_key(&id) {
+            return false;
+        }
+        let words = self.split_text(new_text);
+        self.string_cache.insert(id, words);
+        self.original_strings.insert(id, new_text.to_string());
+        self.layouted_strings_cache.get_mut().remove(&id);
+        true
     }
 
-    for (font_id, add_font_msg) in add_font_resources {
-        use self::AddFontMsg::*;
-        match add_font_msg {
-            Font(f) => { app_resources.currently_registered_fonts.insert(font_id, LoadedFont::new(f.font_key, f.font_bytes, f.font_index)); },
-            Instance(fi, size) => { app_resources.currently_registered_fonts.get_mut(&font_id).unwrap().font_instances.insert(size, fi.key); },
+    fn split_text(&self, text: &str) -> Words {
+        use text_layout::split_text_into_words;
+        match &self.word_splitter {
+            Some(word_splitter) => word_splitter(text),
+            None => split_text_into_words(text),
         }
     }
-}
 
-fn build_delete_font_resource_updates(
-    app_resources: &AppResources
-) -> Vec<(ImmediateFontId, DeleteFontMsg)> {
+    /// Same as `add_text`, but also stores the language / script of the text
+    pub fn add_text_with_meta(&mut self, text: &str, meta: TextMeta) -> TextId {
+        let id = self.add_text(text);
+        self.text_meta.insert(id, meta);
+        id
+    }
 
-    let mut resource_updates = Vec::new();
+    /// Returns the metadata of a text, `None` if the text was added without metadata
+    pub fn get_text_meta(&self, text_id: &TextId) -> Option<&TextMeta> {
+        self.text_meta.get(text_id)
+    }
 
-    // Delete fonts that were not used in the last frame or have zero font instances
-    for (font_id, loaded_font) in app_resources.currently_registered_fonts.iter() {
-        resource_updates.extend(
-            loaded_font.font_instances.iter()
-            .filter(|(au, _)| app_resources.last_frame_font_keys[font_id].contains(au))
-            .map(|(au, font_instance_key)| (font_id.clone(), DeleteFontMsg::Instance(*font_instance_key, *au)))
-        );
-        if !app_resources.last_frame_font_keys.contains_key(font_id) || loaded_font.font_instances.is_empty() {
-            // Delete the font and all instances if there are no more instances of the font
-            resource_updates.push((font_id.clone(), DeleteFontMsg::Font(loaded_font.font_key)));
-        }
+    /// Sets a custom function to split texts into words (i.e. for languages that don't use
+    /// whitespace to separate words), `None` resets to the default whitespace-based splitting.
+    /// Only affects texts that are added after this call.
+    pub fn set_word_splitter(&mut self, word_splitter: Option<WordSplitter>) {
+        self.word_splitter = word_splitter;
     }
 
-    resource_updates
-}
+    pub fn get_text(&self, text_id: &TextId) -> Option<&Words> {
+        self.string_cache.get(text_id)
+    }
 
-/// At the end of the frame, all images that are registered, but weren't used in the last frame
-fn build_delete_image_resource_updates(
-    app_resources: &AppResources
-) -> Vec<(ImageId, DeleteImageMsg)> {
-    app_resources.currently_registered_images.iter()
-    .filter(|(id, _info)| !app_resources.last_frame_image_keys.contains(id))
-    .map(|(id, info)| (*id, DeleteImageMsg(info.key, *info)))
-    .collect()
-}
+    /// Returns the text exactly as it was added (i.e. for copying it to the clipboard),
+    /// without the normalization that is applied to the `Words`
+    pub fn get_string(&self, text_id: &TextId) -> Option<&str> {
+        self.original_strings.get(text_id).map(|s| s.as_str())
+    }
 
-fn delete_resources(
-    app_resources: &mut AppResources,
-    delete_font_resources: Vec<(ImmediateFontId, DeleteFontMsg)>,
-    delete_image_resources: Vec<(ImageId, DeleteImageMsg)>,
-) {
-    let mut merged_resource_updates = Vec::new();
+    /// Returns the words of the text scaled to the font instance, `layout` is only
+    /// invoked if the text wasn't laid out with this font instance before.
+    /// Returns `None` if the text doesn't exist or `layout` returns `None`.
+    pub(crate) fn get_or_layout<F: FnOnce(&Words) -> Option<ScaledWords>>(&self, text_id: &TextId, font_instance_key: FontInstanceKey, layout: F) -> Option<ScaledWords> {
 
-    merged_resource_updates.extend(delete_font_resources.iter().map(|(_, f)| f.into_resource_update()));
-    merged_resource_updates.extend(delete_image_resources.iter().map(|(_, i)| i.into_resource_update()));
+        if let Some(scaled_words) = self.layouted_strings_cache.borrow().get(text_id).and_then(|fonts| fonts.get(&font_instance_key)) {
+            return Some(scaled_words.clone());
+        }
 
-    if !merged_resource_updates.is_empty() {
-        app_resources.get_render_api().update_resources(merged_resource_updates);
-    }
+        let scaled_words = layout(self.string_cache.get(text_id)?)?;
+        self.layouted_strings_cache.borrow_mut()
+            .entry(*text_id)
+            .or_insert_with(FastHashMap::default)
+            .insert(font_instance_key, scaled_words.clone());
 
-    for (removed_id, _removed_info) in delete_image_resources {
-        app_resources.currently_registered_images.remove(&removed_id);
+        Some(scaled_words)
     }
 
-    for (font_id, delete_font_msg) in delete_font_resources {
-        use self::DeleteFontMsg::*;
-        match delete_font_msg {
-            Font(_) => { app_resources.currently_registered_fonts.remove(&font_id); },
-            Instance(_, size) => { app_resources.currently_registered_fonts.get_mut(&font_id).unwrap().delete_font_instance(&size); },
+    /// Evicts the layouts of all texts that were scaled to a (deleted) font instance
+    pub(crate) fn evict_font_instance(&mut self, font_instance_key: &FontInstanceKey) {
+        let layouted_strings_cache = self.layouted_strings_cache.get_mut();
+        for fonts in layouted_strings_cache.values_mut() {
+            fonts.remove(font_instance_key);
         }
+        layouted_strings_cache.retain(|_, fonts| !fonts.is_empty());
     }
-}
 
-#[cfg(feature = "image_loading")]
-fn decode_image_data(image_data: Vec<u8>) -> Result<(ImageData, ImageDescriptor), ImageError> {
-    use image; // the crate
-
-    let image_format = image::guess_format(&image_data)?;
-    let decoded = image::load_from_memory_with_format(&image_data, image_format)?;
-    Ok(prepare_image(decoded)?)
-}
+    /// Removes a string from both the string cache and the layouted text cache
+    pub fn delete_text(&mut self, id: TextId) {
+        self.string_cache.remove(&id);
+        self.original_strings.remove(&id);
+        self.layouted_strings_cache.get_mut().remove(&id);
+        self.text_meta.remove(&id);
+    }
+
+    pub fn clear_all_texts(&mut self) {
+        self.string_cache.clear();
+        self.original_strings.clear();
+        self.layouted_strings_cache.get_mut().clear();
+        self.text_meta.clear();
+    }
+
+    /// Releases the excess capacity of the string cache
+    pub fn shrink_to_fit(&mut self) {
+        self.string_cache.shrink_to_fit();
+        self.original_strings.shrink_to_fit();
+        self.layouted_strings_cache.get_mut().shrink_to_fit();
+        self.text_meta.shrink_to_fit();
+    }
+}
+
+/// Used only for debugging, so that the AppResource garbage
+/// collection tests can run without a real RenderApi
+///
+/// Hands out unique keys and records the resource updates it receives,
+/// so that tests can assert which fonts / images got added or deleted
+#[cfg(test)]
+#[derive(Debug, Default)]
+struct FakeRenderApi {
+    last_key: Cell<u32>,
+    resource_updates: RefCell<Vec<FakeResourceUpdate>>,
+}
+
+/// Key of a `ResourceUpdate` received by the `FakeRenderApi`
+/// (`ResourceUpdate` itself can't be compared)
+#[cfg(test)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum FakeResourceUpdate {
+    AddImage(ImageKey),
+    UpdateImage(ImageKey),
+    DeleteImage(ImageKey),
+    AddFont(FontKey),
+    DeleteFont(FontKey),
+    AddFontInstance(FontInstanceKey),
+    DeleteFontInstance(FontInstanceKey),
+}
+
+#[cfg(test)]
+impl FakeRenderApi {
+
+    fn new() -> Self { Self::default() }
+
+    fn next_key(&self) -> u32 {
+        let key = self.last_key.get() + 1;
+        self.last_key.set(key);
+        key
+    }
+
+    /// Returns how many image, font and font instance keys were generated so far
+    fn generated_keys(&self) -> u32 {
+        self.last_key.get()
+    }
+
+    /// Returns and clears the resource updates received since the last call
+    fn take_resource_updates(&self) -> Vec<FakeResourceUpdate> {
+        self.resource_updates.replace(Vec::new())
+    }
+}
+
+#[cfg(test)]
+impl AppResources {
+    /// Submits the queued resource updates to the fake RenderApi, then returns and clears them
+    fn take_resource_updates(&mut self) -> Vec<FakeResourceUpdate> {
+        self.flush_pending_resource_updates();
+        self.fake_render_api.take_resource_updates()
+    }
+}
+
+pub(crate) trait FontImageApi {
+    fn new_image_key(&self) -> ImageKey;
+    fn new_font_key(&self) -> FontKey;
+    fn new_font_instance_key(&self) -> FontInstanceKey;
+    fn update_resources(&self, Vec<ResourceUpdate>);
+    fn flush_scene_builder(&self);
+}
+
+impl FontImageApi for RenderApi {
+    fn new_image_key(&self) -> ImageKey { self.generate_image_key() }
+    fn new_font_key(&self) -> FontKey { self.generate_font_key() }
+    fn new_font_instance_key(&self) -> FontInstanceKey { self.generate_font_instance_key() }
+    fn update_resources(&self, updates: Vec<ResourceUpdate>) { self.update_resources(updates); }
+    fn flush_scene_builder(&self) { self.flush_scene_builder(); }
+}
+
+#[cfg(test)]
+use webrender::api::IdNamespace;
+#[cfg(test)]
+use std::cell::Cell;
+
+// Fake RenderApi for unit testing
+#[cfg(test)]
+impl FontImageApi for FakeRenderApi {
+    fn new_image_key(&self) -> ImageKey { ImageKey::new(IdNamespace(0), self.next_key()) }
+    fn new_font_key(&self) -> FontKey { FontKey::new(IdNamespace(0), self.next_key()) }
+    fn new_font_instance_key(&self) -> FontInstanceKey { FontInstanceKey::new(IdNamespace(0), self.next_key()) }
+    fn update_resources(&self, updates: Vec<ResourceUpdate>) {
+        self.resource_updates.borrow_mut().extend(updates.iter().map(|update| match update {
+            ResourceUpdate::AddImage(add_image) => FakeResourceUpdate::AddImage(add_image.key),
+            ResourceUpdate::UpdateImage(update_image) => FakeResourceUpdate::UpdateImage(update_image.key),
+            ResourceUpdate::DeleteImage(key) => FakeResourceUpdate::DeleteImage(*key),
+            ResourceUpdate::AddFont(AddFont::Raw(key, _, _)) |
+            ResourceUpdate::AddFont(AddFont::Native(key, _)) => FakeResourceUpdate::AddFont(*key),
+            ResourceUpdate::DeleteFont(key) => FakeResourceUpdate::DeleteFont(*key),
+            ResourceUpdate::AddFontInstance(add_font_instance) => FakeResourceUpdate::AddFontInstance(add_font_instance.key),
+            ResourceUpdate::DeleteFontInstance(key) => FakeResourceUpdate::DeleteFontInstance(*key),
+        }));
+    }
+    fn flush_scene_builder(&self) { }
+}
+
+impl AppResources {
+
+    /// Creates a new renderer (the renderer manages the resources and is therefore tied to the resources).
+    #[must_use] pub(crate) fn new(app_config: &AppConfig) -> Result<Self, WindowCreateError> {
+        Self::with_capacity(app_config, app_config.resource_capacities)
+    }
+
+    /// Same as `new`, but preallocates the internal maps for the given number of images,
+    /// fonts and texts, so that registering many resources at startup doesn't rehash the maps.
+    #[must_use] pub(crate) fn with_capacity(app_config: &AppConfig, capacities: ResourceCapacities) -> Result<Self, WindowCreateError> {
+
+        fn map<K: ::std::hash::Hash + Eq, V>(capacity: usize) -> FastHashMap<K, V> {
+            FastHashMap::with_capacity_and_hasher(capacity, Default::default())
+        }
+
+        let ResourceCapacities { images, fonts, texts } = capacities;
+
+        let image_decode_options = ImageDecodeOptions::new(app_config);
+        Ok(Self {
+            #[cfg(not(test))]
+            fake_display: FakeDisplay::new(app_config.renderer_type)?,
+            #[cfg(test)]
+            fake_render_api: FakeRenderApi::new(),
+            css_ids_to_font_ids: FastHashMap::default(),
+            css_ids_to_image_ids: FastHashMap::default(),
+            font_sources: map(fonts),
+            synthetic_fonts: FastHashMap::default(),
+            font_metrics_cache: RefCell::new(FastHashMap::default()),
+            image_sources: map(images),
+            image_expiry: FastHashMap::default(),
+            currently_registered_fonts: map(fonts),
+            currently_registered_images: map(images),
+            image_content_hashes: map(images),
+            shared_image_keys: map(images),
+            font_content_hashes: map(fonts),
+            shared_font_keys: map(fonts),
+            nine_patch_insets: FastHashMap::default(),
+            tiled_images: FastHashMap::default(),
+            atlas_glyphs: FastHashMap::default(),
+            svg_intrinsic_sizes: FastHashMap::default(),
+            failed_svg_rasterizations: FastHashMap::default(),
+            animated_images: FastHashMap::default(),
+            last_frame_font_keys: map(fonts),
+            last_frame_image_keys: FastHashSet::with_capacity_and_hasher(images, Default::default()),
+            current_frame: 0,
+            image_last_used_frame: map(images),
+            font_last_used_frame: map(fonts),
+            font_instance_last_used_frame: map(fonts),
+            gc_policy: app_config.gc_policy,
+            decoded_image_cache: app_config.decoded_image_cache_bytes.map(DecodedImageCache::new),
+            image_decode_options,
+            image_decode_pool: app_config.image_decode_threads.map(|thread_count| ImageDecodePool::new(thread_count, image_decode_options)),
+            synchronous_image_decodes: FastHashSet::default(),
+            image_max_dimensions: FastHashMap::default(),
+            images_without_mipmaps: FastHashSet::default(),
+            pinned_fonts: FastHashMap::default(),
+            pinned_images: FastHashMap::default(),
+            subpixel_positioning: false,
+            font_subpixel_positioning: FastHashMap::default(),
+            font_render_mode: app_config.font_render_mode,
+            font_render_modes: FastHashMap::default(),
+            font_platform_options: app_config.font_platform_options,
+            lazy_decode_margin_px: app_config.lazy_decode_margin_px,
+            max_image_uploads_per_frame: app_config.max_image_uploads_per_frame,
+            drop_font_bytes_after_upload: app_config.drop_font_bytes_after_upload,
+            callback_image_frames: FastHashMap::default(),
+            gradient_images: FastHashMap::default(),
+            image_alpha_masks: FastHashMap::default(),
+            scene_builder_flush_pending: false,
+            pending_resource_updates: Vec::new(),
+            resource_warnings: VecDeque::new(),
+            current_frame_stats: FrameStats::default(),
+            last_frame_stats: FrameStats::default(),
+            cache_stats: CacheStats::default(),
+            text_cache: TextCache { string_cache: map(texts), .. TextCache::default() },
+            clipboard: SystemClipboard::new().unwrap(),
+            #[cfg(feature = "image_loading")]
+            image_clipboard: None,
+            file_watcher: None,
+            next_image_id: 0,
+            next_font_id: 0,
+        })
+    }
+
+    pub(crate) fn get_render_api(&self) -> &impl FontImageApi {
+        #[cfg(not(test))] {
+            &self.fake_display.render_api
+        }
+        #[cfg(test)] {
+            &self.fake_render_api
+        }
+    }
+
+    // NOTE: The get_loaded_* functions return the IDs sorted, so that the order
+    // is stable between calls (the internal hash maps have no defined order)
+
+    /// Returns the IDs of all currently loaded fonts in `self.font_data`
+    pub fn get_loaded_font_ids(&self) -> Vec<FontId> {
+        sorted_keys(&self.font_sources)
+    }
+
+    /// Returns all fonts in `self.font_data` together with their source and whether
+    /// the font is currently loaded into the RenderApi (i.e. it was used in the last frame)
+    pub fn get_loaded_fonts(&self) -> Vec<(FontId, FontSource, bool)> {
+        let mut fonts = self.font_sources.iter().map(|(font_id, font_source)| {
+            let is_resident = self.currently_registered_fonts.contains_key(&ImmediateFontId::Resolved(*font_id));
+            (*font_id, font_source.clone(), is_resident)
+        }).collect::<Vec<_>>();
+        fonts.sort_by_key(|(font_id, _, _)| *font_id);
+        fonts
+    }
+
+    pub fn get_loaded_image_ids(&self) -> Vec<ImageId> {
+        sorted_keys(&self.image_sources)
+    }
+
+    pub fn get_loaded_css_image_ids(&self) -> Vec<CssImageId> {
+        sorted_keys(&self.css_ids_to_image_ids)
+    }
+
+    pub fn get_loaded_css_font_ids(&self) -> Vec<CssFontId> {
+        sorted_keys(&self.css_ids_to_font_ids)
+    }
+
+    pub fn get_loaded_text_ids(&self) -> Vec<TextId> {
+        sorted_keys(&self.text_cache.string_cache)
+    }
+
+    // -- ImageId cache
+
+    /// Add an image from a PNG, JPEG or other - note that for specialized image formats,
+    /// you have to enable them as features in the Cargo.toml file.
+    #[cfg(feature = "image_loading")]
+    pub fn add_image(&mut self, image_id: ImageId, image_source: ImageSource) {
+        self.set_image_source(image_id, image_source, None);
+    }
+
+    /// Adds several images at once (i.e. an icon set), same as calling `add_image` for each image,
+    /// but the space for the images is reserved up front.
+    #[cfg(feature = "image_loading")]
+    pub fn add_images<I: IntoIterator<Item=(ImageId, ImageSource)>>(&mut self, images: I) {
+        let images = images.into_iter();
+        self.image_sources.reserve(images.size_hint().0);
+        for (image_id, image_source) in images {
+            self.set_image_source(image_id, image_source, None);
+        }
+    }
+
+    /// Same as `add_images`, but the images are decoded and uploaded to the RenderApi immediately
+    /// instead of in the first frame they are displayed in, so that the first frame doesn't stutter.
+    ///
+    /// The uploaded images are garbage-collected according to the `GcPolicy` like any other image,
+    /// use `pin_image` to keep images uploaded that aren't displayed in the next frames. Images that
+    /// exceed `AppConfig::max_image_uploads_per_frame` or that are decoded in the background are
+    /// uploaded when they are first displayed. Images that fail to decode are reported by
+    /// `take_resource_warnings`.
+    #[cfg(feature = "image_loading")]
+    pub fn add_images_prewarm<I: IntoIterator<Item=(ImageId, ImageSource)>>(&mut self, images: I) {
+
+        let images = images.into_iter().collect::<Vec<_>>();
+        let image_keys = images.iter().map(|(image_id, _)| *image_id).collect::<FastHashSet<ImageId>>();
+        self.add_images(images);
+
+        // Prevents the GC of the current frame from deleting the images again right away
+        self.last_frame_image_keys.extend(image_keys.iter().cloned());
+        let current_frame = self.current_frame;
+        self.image_last_used_frame.extend(image_keys.iter().map(|image_id| (*image_id, current_frame)));
+
+        let mut load_errors = Vec::new();
+        let add_image_resource_updates = build_add_image_resource_updates(self, &image_keys, &mut load_errors);
+        self.push_resource_warnings(&load_errors);
+        self.current_frame_stats.image_uploads += add_image_resource_updates.len();
+
+        add_resources(self, Vec::new(), add_image_resource_updates);
+        self.flush_pending_resource_updates();
+    }
+
+    /// Adds an image from already decoded pixels (i.e. from a camera or a video frame),
+    /// works without the `image_loading` feature. Returns an error if the length of the
+    /// pixel buffer doesn't match the dimensions and format of the image.
+    pub fn add_image_raw(&mut self, image_id: ImageId, raw: RawImage) -> Result<(), RawImageError> {
+        raw.validate()?;
+        self.set_image_source(image_id, ImageSource::Raw(raw), None);
+        Ok(())
+    }
+
+    /// Adds an image that is drawn as a nine-patch (9-slice) image: The `insets`
+    /// `(top, right, bottom, left)` (in image pixels) cut the image into four fixed-size
+    /// corners, four edges that are stretched in one direction and a center that
+    /// is stretched in both directions to fill the size of the node.
+    pub fn add_nine_patch(&mut self, image_id: ImageId, image_source: ImageSource, insets: (u32, u32, u32, u32)) {
+        self.set_image_source(image_id, image_source, Some(insets));
+    }
+
+    fn set_image_source(&mut self, image_id: ImageId, image_source: ImageSource, nine_patch_insets: Option<(u32, u32, u32, u32)>) {
+        if self.tiled_images.contains_key(&image_id) || self.animated_images.contains_key(&image_id) || self.atlas_glyphs.contains_key(&image_id) {
+            self.delete_image(&image_id);
+        }
+        self.image_alpha_masks.remove(&image_id);
+        self.invalidate_decoded_image(&image_id);
+        self.svg_intrinsic_sizes.remove(&image_id);
+        self.failed_svg_rasterizations.remove(&image_id);
+        self.set_nine_patch_insets(image_id, nine_patch_insets);
+        reserve_id(&mut self.next_image_id, image_id.id);
+
+        #[cfg(feature = "svg_parsing")] {
+            if let ImageSource::Svg(svg) = &image_source {
+                let unsupported_features = unsupported_svg_features(svg);
+                if !unsupported_features.is_empty() {
+                    let message = format!("SVG features that are not supported are ignored: {}", unsupported_features.join(", "));
+                    self.push_resource_warning(ResourceWarning { id: ResourceWarningId::Image(image_id), message });
+                }
+            }
+        }
+
+        self.image_sources.insert(image_id, image_source);
+    }
+
+    /// Adds an image that is too large to be decoded at once (i.e. for a map viewer). The image
+    /// of the size `full_size` is split into tiles of the size `tile_size`. Only the tiles that
+    /// are visible in the window are requested from the `tile_provider` and uploaded, tiles that
+    /// are scrolled out of view are deleted again.
+    ///
+    /// Tiled images can only be used in `NodeType::Image` nodes, not as CSS background images.
+    pub fn add_tiled_image(&mut self, image_id: ImageId, tile_provider: TileProvider, full_size: (u32, u32), tile_size: (u32, u32)) {
+        self.delete_image(&image_id);
+        reserve_id(&mut self.next_image_id, image_id.id);
+        self.tiled_images.insert(image_id, TiledImage {
+            tile_provider,
+            full_size,
+            tile_size: (tile_size.0.max(1), tile_size.1.max(1)),
+            resident_tiles: FastHashMap::default(),
+        });
+    }
+
+    pub(crate) fn get_tiled_image(&self, image_id: &ImageId) -> Option<&TiledImage> {
+        self.tiled_images.get(image_id)
+    }
+
+    /// Decodes all frames of an animated GIF, other image formats are added as a single frame.
+    /// Returns how long each frame should be displayed. The DOM references the animation
+    /// via the `image_id`, call `advance_animated_image` to move the animation forward.
+    #[cfg(feature = "image_loading")]
+    pub fn add_animated_image(&mut self, image_id: ImageId, image_data: Vec<u8>) -> Result<Vec<Duration>, ImageReloadError> {
+        let frames = decode_animated_image(image_data.clone(), self.image_decode_options)?;
+        let frame_durations = frames.iter().map(|(_, _, duration)| *duration).collect();
+        self.set_image_source(image_id, ImageSource::Bytes(image_data), None);
+        self.insert_animated_image(image_id, frames);
+        Ok(frame_durations)
+    }
+
+    /// Adds an animated image from already decoded frames and the duration of each frame
+    /// (i.e. from an APNG or a video decoder), works without the `image_loading` feature.
+    pub fn add_animated_image_raw(&mut self, image_id: ImageId, frames: Vec<(RawImage, Duration)>) -> Result<(), RawImageError> {
+
+        for (raw, _) in frames.iter() {
+            raw.validate()?;
+        }
+
+        let first_frame = match frames.first() {
+            Some((raw, _)) => raw.clone(),
+            None => return Err(RawImageError::NoFrames),
+        };
+
+        let frames = frames.into_iter().filter_map(|(raw, duration)| {
+            ImageSource::Raw(raw).get_bytes().ok().map(|(data, descriptor)| (data, descriptor, duration))
+        }).collect();
+
+        self.set_image_source(image_id, ImageSource::Raw(first_frame), None);
+        self.insert_animated_image(image_id, frames);
+        Ok(())
+    }
+
+    fn insert_animated_image(&mut self, image_id: ImageId, frames: Vec<(ImageData, ImageDescriptor, Duration)>) {
+        let frame_count = frames.len();
+        self.animated_images.insert(image_id, AnimatedImage {
+            frames,
+            current_frame: 0,
+            current_frame_elapsed: Duration::from_secs(0),
+            frame_keys: vec![None; frame_count],
+        });
+    }
+
+    /// Returns the number of frames and the currently displayed frame of an animated image
+    pub fn get_animated_image_frame(&self, image_id: &ImageId) -> Option<(usize, usize)> {
+        self.animated_images.get(image_id).map(|animation| (animation.frames.len(), animation.current_frame))
+    }
+
+    /// Moves the animation forward by the `elapsed` time, looping back to the first frame after
+    /// the last frame. Returns whether the displayed frame changed, in which case the UI has to
+    /// be redrawn. Frames without a duration (i.e. a still image) are never advanced.
+    pub fn advance_animated_image(&mut self, image_id: &ImageId, elapsed: Duration) -> bool {
+
+        fn as_nanos(duration: Duration) -> u64 {
+            duration.as_secs().saturating_mul(1_000_000_000).saturating_add(u64::from(duration.subsec_nanos()))
+        }
+
+        let (next_frame, next_frame_elapsed) = {
+
+            let animation = match self.animated_images.get(image_id) {
+                Some(s) => s,
+                None => return false,
+            };
+
+            let loop_nanos = animation.frames.iter().map(|(_, _, duration)| as_nanos(*duration)).fold(0, u64::saturating_add);
+            if animation.frames.len() < 2 || loop_nanos == 0 {
+                return false;
+            }
+
+            // Skip all complete loops, they end at the current frame again
+            let mut remaining = as_nanos(animation.current_frame_elapsed).saturating_add(as_nanos(elapsed)) % loop_nanos;
+            let mut frame = animation.current_frame;
+            while remaining >= as_nanos(animation.frames[frame].2) {
+                remaining -= as_nanos(animation.frames[frame].2);
+                frame = (frame + 1) % animation.frames.len();
+            }
+
+            (frame, Duration::from_nanos(remaining))
+        };
+
+        let frame_changed = self.set_animated_image_frame(image_id, next_frame);
+        if let Some(animation) = self.animated_images.get_mut(image_id) {
+            animation.current_frame_elapsed = next_frame_elapsed;
+        }
+        frame_changed
+    }
+
+    /// Displays a certain frame of an animated image. If the image is currently uploaded, the
+    /// `ImageKey` of the image is swapped to the key of the frame (uploading the frame if it
+    /// wasn't displayed before). Returns whether the displayed frame changed.
+    pub fn set_animated_image_frame(&mut self, image_id: &ImageId, frame: usize) -> bool {
+
+        let frame_key = match self.animated_images.get_mut(image_id) {
+            Some(animation) if frame < animation.frames.len() => {
+                animation.current_frame_elapsed = Duration::from_secs(0);
+                if animation.current_frame == frame {
+                    return false;
+                }
+                animation.current_frame = frame;
+                animation.frame_keys[frame]
+            },
+            _ => return false,
+        };
+
+        self.image_alpha_masks.remove(image_id);
+
+        // Images that aren't uploaded yet are uploaded with the current frame
+        if !self.currently_registered_images.contains_key(image_id) {
+            return true;
+        }
+
+        let (data, mut descriptor, _) = self.animated_images[image_id].frames[frame].clone();
+        descriptor.allow_mipmaps &= self.get_image_mipmaps(image_id);
+
+        let key = match frame_key {
+            Some(key) => key,
+            None => {
+                let key = self.get_render_api().new_image_key();
+                self.queue_resource_updates(vec![ResourceUpdate::AddImage(AddImage { key, descriptor, data, tiling: None })]);
+                self.current_frame_stats.image_uploads += 1;
+                self.scene_builder_flush_pending = true;
+                self.animated_images.get_mut(image_id).unwrap().frame_keys[frame] = Some(key);
+                key
+            },
+        };
+
+        let image_info = self.currently_registered_images.get_mut(image_id).unwrap();
+        image_info.key = key;
+        image_info.descriptor = descriptor;
+        true
+    }
+
+    /// Returns the border insets `(top, right, bottom, left)` if the image was added via `add_nine_patch`
+    pub fn get_nine_patch_insets(&self, image_id: &ImageId) -> Option<(u32, u32, u32, u32)> {
+        self.nine_patch_insets.get(image_id).cloned()
+    }
+
+    fn set_nine_patch_insets(&mut self, image_id: ImageId, insets: Option<(u32, u32, u32, u32)>) {
+        match insets {
+            Some(insets) => { self.nine_patch_insets.insert(image_id, insets); },
+            None => { self.nine_patch_insets.remove(&image_id); },
+        }
+        // Images that are already uploaded keep their ImageKey, only the display list changes
+        if let Some(image_info) = self.currently_registered_images.get_mut(&image_id) {
+            image_info.nine_patch_insets = insets;
+        }
+    }
+
+    /// Adds a single page of a multi-page image (i.e. a multi-page TIFF file). The image is
+    /// decoded once to check that the page exists, returns `ImageReloadError::PageOutOfRange`
+    /// if the image has fewer pages. For single-page images, only page 0 is valid.
+    #[cfg(feature = "tiff")]
+    pub fn add_image_page(&mut self, image_id: ImageId, image_source: ImageSource, page: usize) -> Result<(), ImageReloadError> {
+        let page_source = ImageSource::Page(Box::new(image_source), page);
+        page_source.get_bytes_with_options(None, self.image_decode_options)?;
+        self.image_alpha_masks.remove(&image_id);
+        reserve_id(&mut self.next_image_id, image_id.id);
+        self.image_sources.insert(image_id, page_source);
+        Ok(())
+    }
+
+    /// Returns whether the AppResources has currently a certain image ID registered
+    pub fn has_image(&self, image_id: &ImageId) -> bool {
+        self.image_sources.get(image_id).is_some()
+    }
+
+    /// Returns the source the image was registered with, i.e. to show which file an image was loaded from
+    pub fn get_image_source(&self, image_id: &ImageId) -> Option<&ImageSource> {
+        self.image_sources.get(image_id)
+    }
+
+    /// Same as `get_image_bytes`, but uses the decoded image cache (if enabled), so that images
+    /// which are uploaded again after they were garbage-collected aren't decoded again
+    fn get_cached_image_bytes(&mut self, image_id: &ImageId) -> Option<Result<(ImageData, ImageDescriptor), ImageReloadError>> {
+
+        // Animated images are uploaded with the frame that is currently displayed
+        if let Some(frame) = self.get_displayed_animation_frame(image_id) {
+            return Some(Ok(frame));
+        }
+
+        if let Some(cached) = self.decoded_image_cache.as_mut().and_then(|cache| cache.get(image_id)) {
+            self.cache_stats.decoded_image_hits += 1;
+            return Some(Ok(cached));
+        }
+
+        let image_source = self.image_sources.get(image_id)?;
+        // Callback images generate new pixels every frame, caching them is pointless
+        let is_cacheable = match image_source {
+            ImageSource::Callback(_) => false,
+            _ => true,
+        };
+        let decoded = image_source.get_bytes_with_options(self.get_image_max_dimensions(image_id), self.image_decode_options);
+
+        if is_cacheable {
+            if let (Some(cache), Ok((data, descriptor))) = (self.decoded_image_cache.as_mut(), &decoded) {
+                cache.insert(*image_id, data, *descriptor);
+                self.cache_stats.decoded_image_misses += 1;
+            }
+        }
+
+        Some(decoded)
+    }
+
+    fn invalidate_decoded_image(&mut self, image_id: &ImageId) {
+        if let Some(cache) = self.decoded_image_cache.as_mut() {
+            cache.remove(image_id);
+        }
+        if let Some(pool) = self.image_decode_pool.as_mut() {
+            pool.cancel(image_id);
+        }
+    }
+
+    /// If background decoding is enabled (see `AppConfig::image_decode_threads`), the image
+    /// is still decoded on the main thread, so that it is displayed in the first frame it
+    /// appears in, i.e. for small icons or images that are critical for the UI.
+    pub fn set_image_decoded_synchronously(&mut self, image_id: ImageId, synchronous: bool) {
+        if synchronous {
+            self.synchronous_image_decodes.insert(image_id);
+        } else {
+            self.synchronous_image_decodes.remove(&image_id);
+        }
+    }
+
+    /// Scales the image down to fit into `(max_width, max_height)` pixels (preserving the aspect
+    /// ratio) when it is decoded, i.e. to display a large photo as a thumbnail without uploading it
+    /// at full resolution. `None` uploads the image at its full size again. Only affects encoded
+    /// images (PNG, JPEG, ...) and only takes effect once the image is uploaded again.
+    pub fn set_image_max_dimensions(&mut self, image_id: ImageId, max_dimensions: Option<(u32, u32)>) {
+        match max_dimensions {
+            Some(max_dimensions) => { self.image_max_dimensions.insert(image_id, max_dimensions); },
+            None => { self.image_max_dimensions.remove(&image_id); },
+        }
+        self.invalidate_decoded_image(&image_id);
+    }
+
+    /// Returns the maximum size of the image set via `set_image_max_dimensions`
+    pub fn get_image_max_dimensions(&self, image_id: &ImageId) -> Option<(u32, u32)> {
+        self.image_max_dimensions.get(image_id).cloned()
+    }
+
+    /// Sets whether the renderer may generate mipmaps for the image (the default), i.e. to disable
+    /// them for pixel art or images that are always displayed at their own size. Raw images can also
+    /// disable mipmaps via `RawImage::without_mipmaps`. Only takes effect once the image is uploaded again.
+    pub fn set_image_mipmaps(&mut self, image_id: ImageId, allow_mipmaps: bool) {
+        if allow_mipmaps {
+            self.images_without_mipmaps.remove(&image_id);
+        } else {
+            self.images_without_mipmaps.insert(image_id);
+        }
+    }
+
+    /// Returns whether mipmaps are allowed for the image, see `set_image_mipmaps`
+    pub fn get_image_mipmaps(&self, image_id: &ImageId) -> bool {
+        !self.images_without_mipmaps.contains(image_id)
+    }
+
+    /// Returns whether the image has to be decoded by the `image_decode_pool`. Raw and callback
+    /// images have no decoding cost and images in the decoded image cache are already decoded.
+    fn decodes_in_background(&self, image_id: &ImageId) -> bool {
+
+        if self.image_decode_pool.is_none() || self.synchronous_image_decodes.contains(image_id) || self.animated_images.contains_key(image_id) {
+            return false;
+        }
+
+        if self.decoded_image_cache.as_ref().map(|cache| cache.contains(image_id)).unwrap_or(false) {
+            return false;
+        }
+
+        match self.image_sources.get(image_id) {
+            Some(ImageSource::Raw(_)) | Some(ImageSource::Callback(_)) | None => false,
+            Some(_) => true,
+        }
+    }
+
+    /// Returns the image if it was decoded in the background, otherwise starts decoding
+    /// it (if it isn't being decoded already) and returns `None`
+    fn take_background_decoded_image(&mut self, image_id: &ImageId) -> Option<Result<(ImageData, ImageDescriptor), ImageReloadError>> {
+
+        let pool = self.image_decode_pool.as_mut()?;
+
+        if let Some(decoded) = pool.decoded.remove(image_id) {
+            if let (Some(cache), Ok((data, descriptor))) = (self.decoded_image_cache.as_mut(), &decoded) {
+                cache.insert(*image_id, data, *descriptor);
+                self.cache_stats.decoded_image_misses += 1;
+            }
+            return Some(decoded);
+        }
+
+        if !pool.pending.contains_key(image_id) {
+            pool.submit(*image_id, self.image_sources.get(image_id)?.clone(), self.image_max_dimensions.get(image_id).cloned());
+        }
+
+        None
+    }
+
+    /// Collects the images that finished decoding in the background, to be called once per frame.
+    /// Returns whether any image finished, in which case the UI has to be laid out again to upload them.
+    pub(crate) fn poll_pending_images(&mut self) -> bool {
+        self.image_decode_pool.as_mut().map(|pool| pool.poll()).unwrap_or(false)
+    }
+
+    /// Returns how many images are currently being decoded in the background, see `AppConfig::image_decode_threads`
+    pub fn pending_image_decodes(&self) -> usize {
+        self.image_decode_pool.as_ref().map(|pool| pool.pending.len()).unwrap_or(0)
+    }
+
+    /// Given an `ImageId`, returns the decoded bytes of that image or `None`, if the `ImageId` is invalid.
+    /// Returns an error on IO failure / image decoding failure or image
+    pub fn get_image_bytes(&self, image_id: &ImageId) -> Option<Result<(ImageData, ImageDescriptor), ImageReloadError>> {
+        if let Some(frame) = self.get_displayed_animation_frame(image_id) {
+            return Some(Ok(frame));
+        }
+        self.image_sources.get(image_id).map(|image_source| image_source.get_bytes_with_options(self.get_image_max_dimensions(image_id), self.image_decode_options))
+    }
+
+    fn get_displayed_animation_frame(&self, image_id: &ImageId) -> Option<(ImageData, ImageDescriptor)> {
+        let animation = self.animated_images.get(image_id)?;
+        let (data, descriptor, _) = &animation.frames[animation.current_frame];
+        Some((data.clone(), *descriptor))
+    }
+
+    pub fn delete_image(&mut self, image_id: &ImageId) {
+        self.atlas_glyphs.remove(image_id);
+        self.image_alpha_masks.remove(image_id);
+        self.image_expiry.remove(image_id);
+        self.pinned_images.remove(image_id);
+        self.synchronous_image_decodes.remove(image_id);
+        self.image_max_dimensions.remove(image_id);
+        self.images_without_mipmaps.remove(image_id);
+        self.invalidate_decoded_image(image_id);
+        self.svg_intrinsic_sizes.remove(image_id);
+        self.failed_svg_rasterizations.remove(image_id);
+        self.nine_patch_insets.remove(image_id);
+        self.gradient_images.retain(|_, gradient_image_id| gradient_image_id != image_id);
+        if let Some(animation) = self.animated_images.remove(image_id) {
+            // The key of the displayed frame is deleted by the GC, together with the registered image
+            let registered_key = self.currently_registered_images.get(image_id).map(|image_info| image_info.key);
+            let delete_frames = animation.frame_keys.iter().filter_map(|key| *key).filter(|key| Some(*key) != registered_key).map(ResourceUpdate::DeleteImage).collect::<Vec<_>>();
+            if !delete_frames.is_empty() {
+                self.queue_resource_updates(delete_frames);
+            }
+        }
+        if let Some(tiled_image) = self.tiled_images.remove(image_id) {
+            let delete_tiles = tiled_image.resident_tiles.values().map(|(image_info, _)| ResourceUpdate::DeleteImage(image_info.key)).collect::<Vec<_>>();
+            if !delete_tiles.is_empty() {
+                self.queue_resource_updates(delete_tiles);
+            }
+        }
+        self.image_sources.remove(image_id);
+    }
+
+    /// Keeps the image uploaded to the RenderApi while it isn't displayed, i.e. for an image that
+    /// is only shown in a modal dialog and is expensive to decode again. Pins are reference-counted,
+    /// the image is only garbage-collected again once `unpin_image` was called as often as `pin_image`.
+    /// Deleting the image with `delete_image` removes all pins.
+    pub fn pin_image(&mut self, image_id: ImageId) {
+        *self.pinned_images.entry(image_id).or_insert(0) += 1;
+    }
+
+    /// Removes one pin of the image, see `pin_image`. Does nothing if the image isn't pinned.
+    pub fn unpin_image(&mut self, image_id: ImageId) {
+        unpin(&mut self.pinned_images, image_id);
+    }
+
+    /// Returns whether the image is currently pinned, see `pin_image`
+    pub fn is_image_pinned(&self, image_id: &ImageId) -> bool {
+        self.pinned_images.contains_key(image_id)
+    }
+
+    /// Deletes the image once `ttl` has passed (from now on), regardless of whether the image
+    /// is still displayed, i.e. for cached thumbnails of remote images. After that, the `ImageId`
+    /// is invalid and the image has to be added again. Setting a new TTL replaces the old one.
+    pub fn set_image_ttl(&mut self, image_id: ImageId, ttl: Duration) {
+        self.image_expiry.insert(image_id, Instant::now() + ttl);
+    }
+
+    /// Deletes all images whose TTL (see `set_image_ttl`) has elapsed at `now`, including their
+    /// keys in the RenderApi. Called once per frame by the `App`, returns the deleted images.
+    pub fn expire_resources(&mut self, now: Instant) -> Vec<ImageId> {
+
+        let mut expired_images = self.image_expiry.iter()
+            .filter(|(_, expiry)| **expiry <= now)
+            .map(|(image_id, _)| *image_id)
+            .collect::<Vec<_>>();
+
+        if expired_images.is_empty() {
+            return expired_images;
+        }
+
+        expired_images.sort();
+
+        let mut resource_updates = Vec::new();
+
+        for image_id in expired_images.iter() {
+            self.delete_image(image_id);
+            if let Some(image_info) = self.currently_registered_images.remove(image_id) {
+                if self.release_shared_image_key(image_id) {
+                    resource_updates.push(ResourceUpdate::DeleteImage(image_info.key));
+                }
+            }
+            self.last_frame_image_keys.remove(image_id);
+            self.image_last_used_frame.remove(image_id);
+            self.callback_image_frames.remove(image_id);
+        }
+
+        if !resource_updates.is_empty() {
+            self.queue_resource_updates(resource_updates);
+        }
+
+        expired_images
+    }
+
+    /// Overwrites the pixels of a region `(x, y, width, height)` of an `ImageSource::Raw` image.
+    /// The pixels have to be in the same format as the image. If the image is currently
+    /// registered in the RenderApi, only the region gets re-uploaded, keeping the `ImageKey`.
+    pub fn update_image_region(&mut self, image_id: ImageId, region: (u32, u32, u32, u32), pixels: &[u8]) -> Result<(), ImageUpdateError> {
+
+        let (x, y, width, height) = region;
+
+        let raw_image = match self.image_sources.get_mut(&image_id) {
+            Some(ImageSource::Raw(raw_image)) => raw_image,
+            Some(_) => return Err(ImageUpdateError::NotARawImage(image_id)),
+            None => return Err(ImageUpdateError::InvalidImageId(image_id)),
+        };
+
+        // The rows of the region are located via the dimensions of the image
+        raw_image.validate().map_err(|e| ImageUpdateError::InvalidImage(image_id, e))?;
+
+        let image_dimensions = raw_image.image_dimensions;
+        let is_in_bounds =
+            x.checked_add(width).map(|right| right <= image_dimensions.0).unwrap_or(false) &&
+            y.checked_add(height).map(|bottom| bottom <= image_dimensions.1).unwrap_or(false);
+
+        if !is_in_bounds {
+            return Err(ImageUpdateError::RegionOutOfBounds { region, image_dimensions });
+        }
+
+        let bytes_per_pixel = raw_image.data_format.bytes_per_pixel() as usize;
+        let row_length = width as usize * bytes_per_pixel;
+        let expected_length = row_length * height as usize;
+
+        if pixels.len() != expected_length {
+            return Err(ImageUpdateError::InvalidPixelBufferLength { expected: expected_length, actual: pixels.len() });
+        }
+
+        let image_row_length = image_dimensions.0 as usize * bytes_per_pixel;
+        for (row_idx, row) in pixels.chunks(row_length.max(1)).enumerate() {
+            let row_start = (y as usize + row_idx) * image_row_length + x as usize * bytes_per_pixel;
+            raw_image.pixels[row_start..row_start + row_length].copy_from_slice(row);
+        }
+
+        let is_opaque = is_image_opaque(raw_image.data_format, &raw_image.pixels[..]);
+        let data = ImageData::new(raw_image.pixels.clone());
+
+        self.image_alpha_masks.remove(&image_id);
+        self.invalidate_decoded_image(&image_id);
+
+        if self.currently_registered_images.contains_key(&image_id) {
+            // The pixels changed, so the image can't share its ImageKey with other images anymore
+            let new_key = if self.release_shared_image_key(&image_id) { None } else { Some(self.get_render_api().new_image_key()) };
+            let image_info = self.currently_registered_images.get_mut(&image_id).unwrap();
+            image_info.descriptor.is_opaque = is_opaque;
+            let resource_update = match new_key {
+                None => ResourceUpdate::UpdateImage(UpdateImage {
+                    key: image_info.key,
+                    descriptor: image_info.descriptor,
+                    data,
+                    dirty_rect: Some(DeviceUintRect::new(DeviceUintPoint::new(x, y), DeviceUintSize::new(width, height))),
+                }),
+                Some(key) => {
+                    image_info.key = key;
+                    ResourceUpdate::AddImage(AddImage { key, descriptor: image_info.descriptor, data, tiling: None })
+                },
+            };
+            self.queue_resource_updates(vec![resource_update]);
+        }
+
+        Ok(())
+    }
+
+    /// Adds a QR code of the `data` as an image, see [`RawImage::qr_code`]. The image isn't updated
+    /// automatically - to display different data, add a new QR code with a new `ImageId`.
+    ///
+    /// [`RawImage::qr_code`]: ../app_resources/struct.RawImage.html#method.qr_code
+    #[cfg(feature = "qrcode")]
+    pub fn add_qr_code(&mut self, image_id: ImageId, data: &str, module_size: u32, ec_level: QrEcLevel) -> Result<(), QrError> {
+        let raw_image = RawImage::qr_code(data, module_size, ec_level)?;
+        self.set_image_source(image_id, ImageSource::Raw(raw_image), None);
+        Ok(())
+    }
+
+    /// Renders the gradient into a `RawImage` and adds it as a new image. If the same
+    /// gradient has already been added before (and wasn't deleted), returns the existing
+    /// `ImageId` instead of rendering the gradient again.
+    pub fn add_gradient_image(&mut self, gradient: GradientImage) -> ImageId {
+
+        if let Some(image_id) = self.gradient_images.get(&gradient) {
+            if self.has_image(image_id) {
+                self.cache_stats.gradient_image_hits += 1;
+                return *image_id;
+            }
+        }
+
+        self.cache_stats.gradient_image_misses += 1;
+        let image_id = self.new_image_id();
+        self.image_sources.insert(image_id, ImageSource::Raw(gradient.render()));
+        self.gradient_images.insert(gradient, image_id);
+        image_id
+    }
+
+    /// Copies an image under a new `ImageId`, i.e. to snapshot an image before modifying it.
+    /// Images loaded from files are decoded into an `ImageSource::Raw`, so that the copy doesn't
+    /// depend on the file anymore. Returns `None` if the `ImageId` is invalid or the image can't be decoded.
+    pub fn duplicate_image(&mut self, image_id: &ImageId) -> Option<ImageId> {
+
+        let image_source = self.image_sources.get(image_id)?;
+        let image_source = match image_source {
+            ImageSource::Embedded(_) | ImageSource::Bytes(_) | ImageSource::Raw(_) | ImageSource::Callback(_) => image_source.clone(),
+            #[cfg(feature = "svg_parsing")]
+            ImageSource::Svg(_) => image_source.clone(),
+            _ => {
+                let (data, descriptor) = image_source.get_bytes_with_options(None, self.image_decode_options).ok()?;
+                ImageSource::Raw(decoded_image_to_raw_image(data, descriptor, self.image_decode_options)?)
+            },
+        };
+
+        let new_image_id = self.new_image_id();
+        self.image_sources.insert(new_image_id, image_source);
+        if let Some(insets) = self.get_nine_patch_insets(image_id) {
+            self.nine_patch_insets.insert(new_image_id, insets);
+        }
+        if let Some(max_dimensions) = self.get_image_max_dimensions(image_id) {
+            self.image_max_dimensions.insert(new_image_id, max_dimensions);
+        }
+        if !self.get_image_mipmaps(image_id) {
+            self.images_without_mipmaps.insert(new_image_id);
+        }
+        Some(new_image_id)
+    }
+
+    /// Packs the glyph images of a bitmap font into shared BGRA8 atlas images (instead of
+    /// uploading one image per glyph) and returns the `ImageId` and metrics of each character,
+    /// see [`BitmapFont`]. Atlases are 1024x1024 pixels, larger glyphs get an atlas of their own.
+    ///
+    /// Like tiled images, the glyphs are meant for `NodeType::Image` nodes, a glyph that is
+    /// used as a CSS background image isn't repeated.
+    ///
+    /// [`BitmapFont`]: ../app_resources/struct.BitmapFont.html
+    pub fn add_bitmap_font(&mut self, glyphs: BTreeMap<char, RawImage>) -> BitmapFont {
+
+        // Tallest glyphs first, so that the shelves don't waste the space above short glyphs
+        let mut sorted_glyphs = glyphs.iter().collect::<Vec<_>>();
+        sorted_glyphs.sort_by_key(|(_, raw_image)| ::std::cmp::Reverse(raw_image.image_dimensions.1));
+
+        let mut packers: Vec<ShelfPacker> = Vec::new();
+        let mut placements = Vec::with_capacity(sorted_glyphs.len());
+
+        for (c, raw_image) in sorted_glyphs {
+            let (width, height) = raw_image.image_dimensions;
+            let (padded_width, padded_height) = (width + BITMAP_FONT_ATLAS_PADDING, height + BITMAP_FONT_ATLAS_PADDING);
+            let allocation = packers.iter_mut().enumerate()
+                .find_map(|(atlas, packer)| packer.allocate(padded_width, padded_height).map(|position| (atlas, position)));
+            let (atlas, position) = match allocation {
+                Some(s) => s,
+                None => {
+                    let mut packer = ShelfPacker::new((padded_width.max(BITMAP_FONT_ATLAS_SIZE), padded_height.max(BITMAP_FONT_ATLAS_SIZE)));
+                    let position = packer.allocate(padded_width, padded_height).unwrap_or((0, 0));
+                    packers.push(packer);
+                    (packers.len() - 1, position)
+                },
+            };
+            placements.push((*c, raw_image, atlas, position));
+        }
+
+        // Atlases are only as high as their shelves
+        let mut atlas_pixels = packers.iter()
+            .map(|packer| vec![0; packer.size.0 as usize * packer.used_height() as usize * 4])
+            .collect::<Vec<Vec<u8>>>();
+
+        for (_, raw_image, atlas, position) in placements.iter() {
+            copy_glyph_into_atlas(&mut atlas_pixels[*atlas], packers[*atlas].size.0, raw_image, *position);
+        }
+
+        let atlas_ids = packers.iter().zip(atlas_pixels).map(|(packer, pixels)| {
+            let atlas_id = self.new_image_id();
+            self.image_sources.insert(atlas_id, ImageSource::Raw(RawImage {
+                pixels,
+                image_dimensions: (packer.size.0, packer.used_height()),
+                data_format: RawImageFormat::BGRA8,
+                alpha_premultiplied: false,
+                // Mipmaps would blend neighbouring glyphs
+                allow_mipmaps: false,
+            }));
+            atlas_id
+        }).collect::<Vec<_>>();
+
+        let glyphs = placements.into_iter().map(|(c, raw_image, atlas, (x, y))| {
+            let image_id = self.new_image_id();
+            let atlas_id = atlas_ids[atlas];
+            let dimensions = raw_image.image_dimensions;
+            self.atlas_glyphs.insert(image_id, AtlasGlyph { atlas_id, rect: (x, y, dimensions.0, dimensions.1) });
+            (c, BitmapGlyph { image_id, atlas_id, atlas_offset: (x, y), dimensions, advance: dimensions.0 })
+        }).collect();
+
+        BitmapFont { glyphs }
+    }
+
+    /// Deletes the glyphs and atlas images of a `BitmapFont`
+    pub fn delete_bitmap_font(&mut self, bitmap_font: &BitmapFont) {
+        let atlas_ids = bitmap_font.glyphs.values().map(|glyph| glyph.atlas_id).collect::<FastHashSet<_>>();
+        for glyph in bitmap_font.glyphs.values() {
+            self.delete_image(&glyph.image_id);
+        }
+        for atlas_id in atlas_ids.iter() {
+            self.delete_image(atlas_id);
+        }
+    }
+
+    /// Returns the atlas image and the position of a glyph of a `BitmapFont` or `None`,
+    /// if the `ImageId` isn't a glyph, see `add_bitmap_font`
+    pub(crate) fn get_atlas_glyph(&self, image_id: &ImageId) -> Option<&AtlasGlyph> {
+        self.atlas_glyphs.get(image_id)
+    }
+
+    /// Returns the average color of an image (see [`RawImage::average_color`]) or `None`,
+    /// if the `ImageId` is invalid. Decodes the image on demand, without registering it in the RenderApi.
+    ///
+    /// [`RawImage::average_color`]: ../app_resources/struct.RawImage.html#method.average_color
+    pub fn get_image_average_color(&self, image_id: &ImageId) -> Option<Result<(u8, u8, u8, u8), ImageReloadError>> {
+        match self.image_sources.get(image_id)? {
+            ImageSource::Raw(raw_image) => Some(Ok(raw_image.average_color())),
+            image_source => {
+                // Decoded images are always premultiplied, see `prepare_image`
+                let premultiplied = image_source.get_alpha_type() == AlphaType::PremultipliedAlpha;
+                Some(image_source.get_bytes_with_options(None, self.image_decode_options).map(|(data, descriptor)| match data {
+                    ImageData::Raw(pixels) => average_color(&pixels[..], descriptor.format, premultiplied),
+                    _ => (0, 0, 0, 0),
+                }))
+            },
+        }
+    }
+
+    /// Returns the alpha mask of an image (see [`RawImage::alpha_mask`]) or `None`, if the `ImageId`
+    /// is invalid. Decodes the image on demand, the mask is cached until the image changes
+    /// or a different `threshold` is requested.
+    ///
+    /// [`RawImage::alpha_mask`]: ../app_resources/struct.RawImage.html#method.alpha_mask
+    pub fn get_image_alpha_mask(&mut self, image_id: &ImageId, threshold: u8) -> Option<Result<&[bool], ImageReloadError>> {
+
+        let is_cached = self.image_alpha_masks.get(image_id).map(|(t, _)| *t == threshold).unwrap_or(false);
+
+        if is_cached {
+            self.cache_stats.alpha_mask_hits += 1;
+        } else {
+            self.cache_stats.alpha_mask_misses += 1;
+            let mask = match self.image_sources.get(image_id)? {
+                ImageSource::Raw(raw_image) => raw_image.alpha_mask(threshold),
+                image_source => match image_source.get_bytes_with_options(None, self.image_decode_options) {
+                    // Premultiplying doesn't change the alpha channel, so the decoded pixels can be used directly
+                    Ok((ImageData::Raw(pixels), descriptor)) => RawImage {
+                        pixels: (*pixels).clone(),
+                        image_dimensions: (descriptor.size.width as u32, descriptor.size.height as u32),
+                        data_format: descriptor.format,
+                        alpha_premultiplied: true,
+                        allow_mipmaps: descriptor.allow_mipmaps,
+                    }.alpha_mask(threshold),
+                    Ok(_) => Vec::new(),
+                    Err(e) => return Some(Err(e)),
+                },
+            };
+            self.image_alpha_masks.insert(*image_id, (threshold, mask));
+        }
+
+        self.image_alpha_masks.get(image_id).map(|(_, mask)| Ok(&mask[..]))
+    }
+
+    /// Allocates a new `ImageId` that isn't mapped to a CSS ID
+    pub(crate) fn new_image_id(&mut self) -> ImageId {
+        ImageId::new(&mut self.next_image_id)
+    }
+
+    pub fn add_css_image_id<S: Into<String>>(&mut self, css_id: S) -> ImageId {
+        let next_image_id = &mut self.next_image_id;
+        *self.css_ids_to_image_ids.entry(css_id.into()).or_insert_with(|| ImageId::new(next_image_id))
+    }
+
+    /// Same as `add_css_image_id`, but returns an error instead of the existing `ImageId`
+    /// if the CSS ID is already in use, i.e. to catch accidentally reused CSS IDs.
+    pub fn try_add_css_image_id<S: Into<String>>(&mut self, css_id: S) -> Result<ImageId, ImageIdExists> {
+        use std::collections::hash_map::Entry::*;
+        match self.css_ids_to_image_ids.entry(css_id.into()) {
+            Occupied(o) => Err(ImageIdExists { css_id: o.key().clone(), image_id: *o.get() }),
+            Vacant(v) => Ok(*v.insert(ImageId::new(&mut self.next_image_id))),
+        }
+    }
+
+    pub fn has_css_image_id(&self, css_id: &str) -> bool {
+        self.get_css_image_id(css_id).is_some()
+    }
+
+    pub fn get_css_image_id(&self, css_id: &str) -> Option<&ImageId> {
+        self.css_ids_to_image_ids.get(css_id)
+    }
+
+    pub fn delete_css_image_id(&mut self, css_id: &str) -> Option<ImageId> {
+        self.css_ids_to_image_ids.remove(css_id)
+    }
+
+    pub fn get_image_info(&self, key: &ImageId) -> Option<&ImageInfo> {
+        self.currently_registered_images.get(key)
+    }
+
+    /// Returns the (width, height) of an uploaded image, of a tiled image or of a bitmap font glyph
+    pub(crate) fn get_image_dimensions(&self, image_id: &ImageId) -> Option<(usize, usize)> {
+        if let Some(tiled_image) = self.tiled_images.get(image_id) {
+            return Some((tiled_image.full_size.0 as usize, tiled_image.full_size.1 as usize));
+        }
+        if let Some(atlas_glyph) = self.atlas_glyphs.get(image_id) {
+            return Some((atlas_glyph.rect.2 as usize, atlas_glyph.rect.3 as usize));
+        }
+        let image_info = self.get_image_info(image_id)?;
+        Some(self.svg_intrinsic_sizes.get(image_id).cloned().unwrap_or_else(|| image_info.get_dimensions()))
+    }
+
+    // -- FontId cache
+
+    /// Allocates a new `FontId` that isn't mapped to a CSS ID
+    pub(crate) fn new_font_id(&mut self) -> FontId {
+        FontId::new(&mut self.next_font_id)
+    }
+
+    pub fn add_css_font_id<S: Into<String>>(&mut self, css_id: S) -> FontId {
+        let next_font_id = &mut self.next_font_id;
+        *self.css_ids_to_font_ids.entry(css_id.into()).or_insert_with(|| FontId::new(next_font_id))
+    }
+
+    pub fn has_css_font_id(&self, css_id: &str) -> bool {
+        self.get_css_font_id(css_id).is_some()
+    }
+
+    pub fn get_css_font_id(&self, css_id: &str) -> Option<&FontId> {
+        self.css_ids_to_font_ids.get(css_id)
+    }
+
+    /// Maps a CSS font ID to an existing `FontId`, i.e. to make a font created by
+    /// `derive_synthetic_font` selectable in the CSS. Returns the previously mapped `FontId`.
+    pub fn set_css_font_id<S: Into<String>>(&mut self, css_id: S, font_id: FontId) -> Option<FontId> {
+        self.css_ids_to_font_ids.insert(css_id.into(), font_id)
+    }
+
+    pub fn delete_css_font_id(&mut self, css_id: &str) -> Option<FontId> {
+        self.css_ids_to_font_ids.remove(css_id)
+    }
+
+    pub fn add_font(&mut self, font_id: FontId, font_source: FontSource) {
+        reserve_id(&mut self.next_font_id, font_id.id);
+        self.font_metrics_cache.get_mut().remove(&font_id);
+        self.font_sources.insert(font_id, font_source);
+    }
+
+    /// Adds several fonts at once, same as calling `add_font` for each font,
+    /// but the space for the fonts is reserved up front.
+    pub fn add_fonts<I: IntoIterator<Item=(FontId, FontSource)>>(&mut self, fonts: I) {
+        let fonts = fonts.into_iter();
+        self.font_sources.reserve(fonts.size_hint().0);
+        for (font_id, font_source) in fonts {
+            self.add_font(font_id, font_source);
+        }
+    }
+
+    /// Given a `FontId`, returns the bytes for that font or `None`, if the `FontId` is invalid.
+    pub fn get_font_bytes(&self, font_id: &FontId) -> Option<Result<(Vec<u8>, i32), FontReloadError>> {
+        let font_source = self.font_sources.get(font_id)?;
+        Some(font_source.get_bytes())
+    }
+
+    /// Returns the ascent, descent, line gap, cap height and x-height of a font, i.e. to size a container
+    /// before the text is laid out. Uses the already-loaded font bytes if the font is currently registered,
+    /// otherwise the font is loaded from its `FontSource`. The metrics are cached until the font is replaced
+    /// or deleted. Returns `None` if the `FontId` is invalid or the font can't be parsed.
+    pub fn get_font_metrics(&self, font_id: &FontId) -> Option<FontMetrics> {
+
+        // Synthetic fonts share the font file of their base font
+        let font_id = self.synthetic_fonts.get(font_id).map(|synthetic_font| synthetic_font.base).unwrap_or(*font_id);
+
+        if let Some(font_metrics) = self.font_metrics_cache.borrow().get(&font_id) {
+            return Some(*font_metrics);
+        }
+
+        let font_metrics = match self.currently_registered_fonts.get(&ImmediateFontId::Resolved(font_id)) {
+            Some(loaded_font) if !loaded_font.font_bytes.is_empty() => parse_font_metrics(&loaded_font.font_bytes, loaded_font.font_index)?,
+            _ => {
+                let (font_bytes, font_index) = self.get_font_bytes(&font_id)?.ok()?;
+                parse_font_metrics(&font_bytes, font_index)?
+            },
+        };
+
+        self.font_metrics_cache.borrow_mut().insert(font_id, font_metrics);
+        Some(font_metrics)
+    }
+
+    /// Returns the outline of a glyph (by its glyph index in the font, not the character)
+    /// in font units, i.e. for rendering text along a path. Uses the already-loaded font
+    /// bytes if the font is currently registered, otherwise the font is loaded from its `FontSource`.
+    ///
+    /// The outline is read from the font tables like the `FontMetrics`, so it doesn't need the
+    /// `svg` feature. TrueType fonts have quadratic curves, OpenType fonts with PostScript outlines
+    /// (`CFF ` table) have cubic curves. Returns `None` for variable fonts with `CFF2` outlines.
+    pub fn get_glyph_outline(&self, font_id: &FontId, glyph_index: u32) -> Option<GlyphOutline> {
+        match self.currently_registered_fonts.get(&ImmediateFontId::Resolved(*font_id)) {
+            Some(loaded_font) if !loaded_font.font_bytes.is_empty() => parse_glyph_outline(&loaded_font.font_bytes, loaded_font.font_index, glyph_index),
+            _ => {
+                let (font_bytes, font_index) = self.get_font_bytes(font_id)?.ok()?;
+                parse_glyph_outline(&font_bytes, font_index, glyph_index)
+            },
+        }
+    }
+
+    /// Shapes a single line of text and rasterizes it into a new BGRA8 image, i.e. to draw a static
+    /// label that appears many times with a single image instead of many glyphs. Returns the
+    /// `ImageId` and the size of the image in pixels. The image is as high as the ascent + descent of the font.
+    #[cfg(feature = "svg")]
+    pub fn rasterize_text_to_image(&mut self, text: &str, font_id: FontId, size: Au, color: (u8, u8, u8, u8))
+    -> Result<(ImageId, (u32, u32)), TextRasterizationError>
+    {
+        use stb_truetype::{FontInfo, VertexType, get_font_offset_for_index};
+        use text_shaping::{self, HbBuffer, HbFont, HbScaledFont};
+
+        const QUADRATIC_CURVE_SEGMENTS: usize = 8;
+
+        let (font_bytes, font_index) = match self.currently_registered_fonts.get(&ImmediateFontId::Resolved(font_id)) {
+            Some(loaded_font) if !loaded_font.font_bytes.is_empty() => (loaded_font.font_bytes.clone(), loaded_font.font_index),
+            _ => self.get_font_bytes(&font_id).ok_or(TextRasterizationError::InvalidFontId(font_id))??,
+        };
+
+        let font_size_px = size.to_f32_px();
+
+        let (glyph_instances, text_width) = {
+            let hb_font = HbFont::from_bytes(&font_bytes, font_index as u32);
+            let hb_scaled_font = HbScaledFont::from_font(&hb_font, font_size_px);
+            let hb_buffer = HbBuffer::from_str(text);
+            let shaped_text = text_shaping::shape_word_hb(&hb_buffer, &hb_scaled_font);
+            (
+                text_shaping::get_glyph_instances_hb(&shaped_text.glyph_infos, &shaped_text.glyph_positions),
+                text_shaping::get_word_visual_width_hb(&shaped_text.glyph_positions),
+            )
+        };
+
+        let font_offset = get_font_offset_for_index(&font_bytes, font_index).ok_or(TextRasterizationError::InvalidFont(font_id))?;
+        let font_info = FontInfo::new(font_bytes, font_offset as usize).ok_or(TextRasterizationError::InvalidFont(font_id))?;
+
+        let scale = font_info.scale_for_mapping_em_to_pixels(font_size_px);
+        let v_metrics = font_info.get_v_metrics();
+        let ascent = v_metrics.ascent as f32 * scale;
+        let descent = v_metrics.descent as f32 * scale;
+
+        let width = text_width.ceil().max(1.0) as u32;
+        let height = (ascent - descent).ceil().max(1.0) as u32;
+
+        // Flatten the glyph outlines into line segments, in pixels, Y axis pointing down
+        let mut edges = Vec::new();
+
+        for glyph in glyph_instances.iter() {
+
+            // Whitespace glyphs have no outline
+            let shape = match font_info.get_glyph_shape(glyph.index) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let to_px = |x: f32, y: f32| (glyph.point.x + x * scale, ascent + glyph.point.y - y * scale);
+
+            let mut contour_start = None;
+            let mut current = (0.0, 0.0);
+
+            for vertex in shape {
+                let point = to_px(vertex.x as f32, vertex.y as f32);
+                match vertex.vertex_type() {
+                    VertexType::MoveTo => {
+                        if let Some(start) = contour_start {
+                            edges.push((current, start));
+                        }
+                        contour_start = Some(point);
+                    },
+                    VertexType::LineTo => edges.push((current, point)),
+                    VertexType::CurveTo => {
+                        let control = to_px(vertex.cx as f32, vertex.cy as f32);
+                        let mut previous = current;
+                        for segment in 1..=QUADRATIC_CURVE_SEGMENTS {
+                            let t = segment as f32 / QUADRATIC_CURVE_SEGMENTS as f32;
+                            let mt = 1.0 - t;
+                            let next = (
+                                mt * mt * current.0 + 2.0 * mt * t * control.0 + t * t * point.0,
+                                mt * mt * current.1 + 2.0 * mt * t * control.1 + t * t * point.1,
+                            );
+                            edges.push((previous, next));
+                            previous = next;
+                        }
+                    },
+                }
+                current = point;
+            }
+
+            if let Some(start) = contour_start {
+                edges.push((current, start));
+            }
+        }
+
+        let (r, g, b, a) = color;
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for coverage in rasterize_polygon_coverage(&edges, width, height) {
+            pixels.extend_from_slice(&[b, g, r, (f32::from(a) * coverage).round() as u8]);
+        }
+
+        let image_id = self.new_image_id();
+        self.image_sources.insert(image_id, ImageSource::Raw(RawImage {
+            pixels,
+            image_dimensions: (width, height),
+            data_format: RawImageFormat::BGRA8,
+            alpha_premultiplied: false,
+            allow_mipmaps: true,
+        }));
+
+        Ok((image_id, (width, height)))
+    }
+
+    /// Returns whether the font supports an OpenType feature, i.e. `"smcp"` for small caps,
+    /// by looking up the tag in the GSUB / GPOS feature lists. Returns `false` if the
+    /// font can't be loaded or the tag isn't exactly four bytes long.
+    pub fn font_supports_feature(&self, font_id: &FontId, feature_tag: &str) -> bool {
+        let feature_tag = feature_tag.as_bytes();
+        match self.currently_registered_fonts.get(&ImmediateFontId::Resolved(*font_id)) {
+            Some(loaded_font) if !loaded_font.font_bytes.is_empty() => font_has_opentype_feature(&loaded_font.font_bytes, loaded_font.font_index, feature_tag),
+            _ => match self.get_font_bytes(font_id) {
+                Some(Ok((font_bytes, font_index))) => font_has_opentype_feature(&font_bytes, font_index, feature_tag),
+                _ => false,
+            },
+        }
+    }
+
+    /// Checks if a `FontId` is valid, i.e. if a font is currently ready-to-use
+    pub fn has_font(&self, id: &FontId) -> bool {
+        self.font_sources.get(id).is_some()
+    }
+
+    /// Returns the source the font was registered with, i.e. to show which file or system font a font was loaded from
+    pub fn get_font_source(&self, id: &FontId) -> Option<&FontSource> {
+        self.font_sources.get(id)
+    }
+
+    pub fn delete_font(&mut self, id: &FontId) {
+        self.font_sources.remove(id);
+        self.font_metrics_cache.get_mut().remove(id);
+        self.synthetic_fonts.remove(id);
+        self.pinned_fonts.remove(id);
+    }
+
+    /// Keeps the font and its font instances uploaded to the RenderApi while no text uses
+    /// the font, see `pin_image`. Deleting the font with `delete_font` removes all pins.
+    pub fn pin_font(&mut self, font_id: FontId) {
+        *self.pinned_fonts.entry(font_id).or_insert(0) += 1;
+    }
+
+    /// Removes one pin of the font, see `pin_font`. Does nothing if the font isn't pinned.
+    pub fn unpin_font(&mut self, font_id: FontId) {
+        unpin(&mut self.pinned_fonts, font_id);
+    }
+
+    /// Returns whether the font is currently pinned, see `pin_font`
+    pub fn is_font_pinned(&self, font_id: &FontId) -> bool {
+        self.pinned_fonts.contains_key(font_id)
+    }
+
+    /// Creates a new font (i.e. "Roboto Bold") which renders the glyphs of the `base` font with
+    /// synthetic emboldening and / or synthetic italics (the glyphs are slanted by a fixed angle).
+    /// WebRender only supports switching the synthetic styles on or off, the strength of the
+    /// emboldening and the slant angle can't be configured.
+    /// The derived font shares the font bytes and the `FontKey` of the base font, only the font
+    /// instances are separate. Use `set_css_font_id` to make the font selectable in the CSS.
+    ///
+    /// Note that the derived font stops rendering if the base font is deleted.
+    /// Returns `None` if the base font doesn't exist.
+    pub fn derive_synthetic_font(&mut self, base: FontId, synthetic_bold: bool, synthetic_italic: bool) -> Option<FontId> {
+
+        let font_source = self.font_sources.get(&base)?.clone();
+
+        // Deriving from a derived font combines the styles
+        let base_style = self.synthetic_fonts.get(&base).cloned().unwrap_or(SyntheticFont {
+            base,
+            synthetic_bold: false,
+            synthetic_italic: false,
+        });
+
+        let font_id = self.new_font_id();
+        self.font_sources.insert(font_id, font_source);
+        self.synthetic_fonts.insert(font_id, SyntheticFont {
+            base: base_style.base,
+            synthetic_bold: base_style.synthetic_bold || synthetic_bold,
+            synthetic_italic: base_style.synthetic_italic || synthetic_italic,
+        });
+
+        Some(font_id)
+    }
+
+    /// Returns the font and the font instance that text in the font `font_id` at the given
+    /// size and variation axis values (`font-variation-settings`) is rendered with. Fonts created
+    /// by `derive_synthetic_font` are rendered with a synthetic instance of their base font.
+    pub(crate) fn resolve_font_instance(&self, font_id: ImmediateFontId, size: Au, variations: Vec<(u32, f32)>) -> (ImmediateFontId, FontInstanceSpec) {
+        let synthetic_font = match &font_id {
+            ImmediateFontId::Resolved(id) => self.synthetic_fonts.get(id),
+            ImmediateFontId::Unresolved(_) => None,
+        };
+
+        match synthetic_font {
+            Some(synthetic_font) => (ImmediateFontId::Resolved(synthetic_font.base), FontInstanceSpec {
+                synthetic_bold: synthetic_font.synthetic_bold,
+                synthetic_italic: synthetic_font.synthetic_italic,
+                variations,
+                .. FontInstanceSpec::new(size)
+            }),
+            None => (font_id, FontInstanceSpec { variations, .. FontInstanceSpec::new(size) }),
+        }
+    }
+
+    /// Resolves and caches the native "sans-serif", "serif" and "monospace" font families
+    /// up front. On Linux, this avoids querying fontconfig / gsettings (which spawns a subprocess)
+    /// when the first frame renders text, so it's best called at application startup.
+    pub fn preload_system_font_defaults(&mut self) {
+        preload_system_font_defaults();
+    }
+
+    /// Returns the family names of all installed system fonts (sorted, without duplicates),
+    /// i.e. for a font picker. Each family can be loaded via `FontSource::System`.
+    pub fn query_system_fonts(&self) -> Vec<String> {
+        use font_loader::system_fonts;
+        let mut families = system_fonts::query_all();
+        families.sort();
+        families.dedup();
+        families
+    }
+
+    /// Returns which styles (regular, bold, italic, bold italic) of a system font family are
+    /// installed. Returns an empty `Vec` if the family isn't installed at all.
+    pub fn query_system_font_variants(&self, family: &str) -> Vec<FontVariant> {
+
+        use font_loader::system_fonts::{self, FontPropertyBuilder};
+
+        let variants = [
+            FontVariant { bold: false, italic: false },
+            FontVariant { bold: true, italic: false },
+            FontVariant { bold: false, italic: true },
+            FontVariant { bold: true, italic: true },
+        ];
+
+        variants.iter().cloned().filter(|variant| {
+            let mut font_builder = FontPropertyBuilder::new().family(family);
+            if variant.bold {
+                font_builder = font_builder.bold();
+            }
+            if variant.italic {
+                font_builder = font_builder.italic();
+            }
+            system_fonts::query_specific(&mut font_builder.build()).iter().any(|f| f.eq_ignore_ascii_case(family))
+        }).collect()
+    }
+
+    /// Returns the sizes of all font instances of the font that are currently registered in the
+    /// RenderApi (sorted, without duplicates), or `None` if the font isn't registered at all.
+    /// Includes the instances of the font that were registered under one of its CSS font IDs.
+    pub fn get_registered_font_sizes(&self, font_id: &FontId) -> Option<Vec<Au>> {
+
+        let css_font_ids = self.css_ids_to_font_ids.iter()
+            .filter(|(_, id)| *id == font_id)
+            .map(|(css_font_id, _)| ImmediateFontId::Unresolved(css_font_id.clone()));
+
+        let mut sizes = None;
+
+        for im_font_id in ::std::iter::once(ImmediateFontId::Resolved(*font_id)).chain(css_font_ids) {
+            if let Some(loaded_font) = self.currently_registered_fonts.get(&im_font_id) {
+                sizes.get_or_insert_with(Vec::new).extend(loaded_font.font_instances.keys().map(|instance_spec| instance_spec.size));
+            }
+        }
+
+        sizes.map(|mut sizes: Vec<Au>| {
+            sizes.sort();
+            sizes.dedup();
+            sizes
+        })
+    }
+
+    /// Watches the files of all `ImageSource::File` and `FontSource::File` resources: once a file
+    /// changes on disk, the image / font is deleted from the renderer and loaded again from the
+    /// file in the next frame. Meant for development, i.e. to see changes to icons or fonts without
+    /// restarting the application. The modification times of the files are checked every 500 ms.
+    pub fn enable_file_watching(&mut self) {
+        if self.file_watcher.is_none() {
+            self.file_watcher = Some(FileWatcher::default());
+        }
+    }
+
+    /// Stops watching the image and font files, see `enable_file_watching`
+    pub fn disable_file_watching(&mut self) {
+        self.file_watcher = None;
+    }
+
+    /// Deletes the images and fonts whose files changed from the renderer, so that they are
+    /// loaded again in the next frame, see `enable_file_watching`. Returns whether any
+    /// resource was deleted, in which case the UI has to be laid out again.
+    pub(crate) fn reload_changed_files(&mut self, now: Instant) -> bool {
+
+        use std::fs;
+
+        let changed_files = {
+
+            let file_watcher = match self.file_watcher.as_mut() {
+                Some(s) => s,
+                None => return false,
+            };
+
+            if file_watcher.last_check.map(|last_check| now - last_check < Duration::from_millis(FILE_WATCH_INTERVAL_MS)).unwrap_or(false) {
+                return false;
+            }
+            file_watcher.last_check = Some(now);
+
+            let image_files = self.image_sources.values().filter_map(|image_source| image_source.get_file_path());
+            let font_files = self.font_sources.values().filter_map(|font_source| match font_source {
+                FontSource::File(file_path) => Some(file_path),
+                _ => None,
+            });
+
+            let modification_times = image_files.chain(font_files)
+                .map(|file_path| (file_path.clone(), fs::metadata(file_path).and_then(|metadata| metadata.modified()).ok()))
+                .collect();
+
+            file_watcher.update(modification_times)
+        };
+
+        if changed_files.is_empty() {
+            return false;
+        }
+
+        let image_ids = self.image_sources.iter()
+            .filter(|(_, image_source)| image_source.get_file_path().map(|file_path| changed_files.contains(file_path)).unwrap_or(false))
+            .map(|(image_id, _)| *image_id)
+            .collect::<Vec<_>>();
+
+        let font_ids = self.font_sources.iter()
+            .filter(|(_, font_source)| match font_source {
+                FontSource::File(file_path) => changed_files.contains(file_path),
+                _ => false,
+            })
+            .map(|(font_id, _)| ImmediateFontId::Resolved(*font_id))
+            .collect::<Vec<_>>();
+
+        for image_id in image_ids.iter() {
+            self.image_alpha_masks.remove(image_id);
+            self.invalidate_decoded_image(image_id);
+            self.svg_intrinsic_sizes.remove(image_id);
+            self.failed_svg_rasterizations.remove(image_id);
+        }
+
+        // The metrics are also cached for fonts that aren't registered in the renderer
+        for font_id in font_ids.iter() {
+            if let ImmediateFontId::Resolved(font_id) = font_id {
+                self.font_metrics_cache.get_mut().remove(font_id);
+            }
+        }
+
+        let delete_image_resource_updates = image_ids.iter()
+            .filter_map(|image_id| self.currently_registered_images.get(image_id).map(|info| (*image_id, DeleteImageMsg(info.key, *info))))
+            .collect::<Vec<_>>();
+
+        let mut delete_font_resource_updates = Vec::new();
+        for font_id in font_ids.iter() {
+            if let Some(loaded_font) = self.currently_registered_fonts.get(font_id) {
+                // The font instances have to be deleted before the font
+                delete_font_resource_updates.extend(loaded_font.font_instances.iter().map(|(instance_spec, font_instance_key)| {
+                    (font_id.clone(), DeleteFontMsg::Instance(*font_instance_key, instance_spec.clone()))
+                }));
+                delete_font_resource_updates.push((font_id.clone(), DeleteFontMsg::Font(loaded_font.font_key)));
+            }
+        }
+
+        let any_reloaded = !delete_image_resource_updates.is_empty() || !delete_font_resource_updates.is_empty();
+        delete_resources(self, delete_font_resource_updates, delete_image_resource_updates);
+        any_reloaded
+    }
+
+    /// Immediately deletes all instances (font sizes) of a font that weren't used in the current frame,
+    /// instead of waiting for the frame-by-frame GC. The font itself is only deleted if no instance remains.
+    pub fn compact_font_instances(&mut self, font_id: &FontId) {
+
+        let im_font_id = ImmediateFontId::Resolved(*font_id);
+
+        let delete_font_resource_updates = {
+
+            let loaded_font = match self.currently_registered_fonts.get(&im_font_id) {
+                Some(s) => s,
+                None => return,
+            };
+
+            let used_font_sizes = self.last_frame_font_keys.get(&im_font_id);
+
+            let mut resource_updates: Vec<(ImmediateFontId, DeleteFontMsg)> = loaded_font.font_instances.iter()
+                .filter(|(instance_spec, _)| !used_font_sizes.map(|sizes| sizes.contains(instance_spec)).unwrap_or(false))
+                .map(|(instance_spec, font_instance_key)| (im_font_id.clone(), DeleteFontMsg::Instance(*font_instance_key, instance_spec.clone())))
+                .collect();
+
+            if resource_updates.len() == loaded_font.font_instances.len() {
+                resource_updates.push((im_font_id.clone(), DeleteFontMsg::Font(loaded_font.font_key)));
+            }
+
+            resource_updates
+        };
+
+        delete_resources(self, delete_font_resource_updates, Vec::new());
+    }
+
+    /// Sets whether glyphs should be positioned at subpixel offsets (smooth motion for animated text)
+    /// or snapped to the pixel grid (sharper static text, the default) for all fonts without
+    /// a per-font setting. Only affects font instances that are created after this call.
+    pub fn set_subpixel_positioning(&mut self, subpixel_positioning: bool) {
+        self.subpixel_positioning = subpixel_positioning;
+    }
+
+    /// Overrides the global subpixel positioning setting for a single font, `None` resets
+    /// the font to the global setting. Only affects font instances that are created after this call.
+    pub fn set_font_subpixel_positioning(&mut self, font_id: &FontId, subpixel_positioning: Option<bool>) {
+        match subpixel_positioning {
+            Some(s) => { self.font_subpixel_positioning.insert(*font_id, s); },
+            None => { self.font_subpixel_positioning.remove(font_id); },
+        }
+    }
+
+    /// Returns whether new instances of the given font use subpixel glyph positioning
+    pub fn get_font_subpixel_positioning(&self, font_id: &FontId) -> bool {
+        self.font_subpixel_positioning.get(font_id).cloned().unwrap_or(self.subpixel_positioning)
+    }
+
+    /// Overrides `AppConfig::font_render_mode` for a single font (i.e. `FontRenderMode::Alpha` for
+    /// a font that is only used on a transparent overlay), `None` resets the font to the global
+    /// setting. Only affects font instances that are created after this call.
+    pub fn set_font_render_mode(&mut self, font_id: &FontId, render_mode: Option<FontRenderMode>) {
+        match render_mode {
+            Some(r) => { self.font_render_modes.insert(*font_id, r); },
+            None => { self.font_render_modes.remove(font_id); },
+        }
+    }
+
+    /// Returns the anti-aliasing mode that new instances of the given font are created with
+    pub fn get_font_render_mode(&self, font_id: &FontId) -> FontRenderMode {
+        self.font_render_modes.get(font_id).cloned().unwrap_or(self.font_render_mode)
+    }
+
+    // -- TextId cache
+
+    /// Adds a string to the internal text cache, but only store it as a string,
+    /// without caching the layout of the string.
+    pub fn add_text(&mut self, text: &str) -> TextId {
+        self.text_cache.add_text(text)
+    }
+
+    pub fn get_text(&self, id: &TextId) -> Option<&Words> {
+        self.text_cache.get_text(id)
+    }
+
+    /// Returns the original string of a cached text, see [`TextCache::get_string`]
+    ///
+    /// [`TextCache::get_string`]: ../app_resources/struct.TextCache.html#method.get_string
+    pub fn get_text_string(&self, id: &TextId) -> Option<&str> {
+        self.text_cache.get_string(id)
+    }
+
+    /// Replaces the string of a cached text, keeping its `TextId`, see [`TextCache::update_text`]
+    ///
+    /// [`TextCache::update_text`]: ../app_resources/struct.TextCache.html#method.update_text
+    pub fn update_text(&mut self, id: TextId, new_text: &str) -> bool {
+        self.text_cache.update_text(id, new_text)
+    }
+
+    pub(crate) fn get_text_cache(&self) -> &TextCache {
+        &self.text_cache
+    }
+
+    /// Same as `add_text`, but also stores the language / script of the text, see [`TextMeta`]
+    ///
+    /// [`TextMeta`]: ../app_resources/struct.TextMeta.html
+    pub fn add_text_with_meta(&mut self, text: &str, meta: TextMeta) -> TextId {
+        self.text_cache.add_text_with_meta(text, meta)
+    }
+
+    /// Returns the language / script of a text, `None` if the text was added without metadata
+    pub fn get_text_meta(&self, id: &TextId) -> Option<&TextMeta> {
+        self.text_cache.get_text_meta(id)
+    }
+
+    /// Estimates how many lines a cached text occupies if it's wrapped at `max_width` pixels,
+    /// without a full layout - i.e. to size the scroll area of a virtualized text view.
+    /// The text is shaped and broken into lines with the default `TextLayoutOptions`, so the
+    /// result is the same for the same inputs. Returns `None` if the text or font doesn't exist.
+    ///
+    /// If the font isn't in use in the current frame, it is loaded from its `FontSource`,
+    /// which is slow - the result should be cached by the caller.
+    pub fn estimate_line_count(&self, text_id: &TextId, font_id: &FontId, size: Au, max_width: f32) -> Option<usize> {
+
+        use text_layout::{self, TextLayoutOptions};
+
+        let words = self.get_text(text_id)?;
+
+        let loaded_font_bytes;
+        let (font_bytes, font_index) = match self.get_loaded_font(&ImmediateFontId::Resolved(*font_id)) {
+            Some(loaded_font) if !loaded_font.font_bytes.is_empty() => (&loaded_font.font_bytes[..], loaded_font.font_index),
+            _ => {
+                loaded_font_bytes = self.font_sources.get(font_id)?.get_bytes().ok()?;
+                (&loaded_font_bytes.0[..], loaded_font_bytes.1)
+            },
+        };
+
+        let font_size_px = size.to_f32_px();
+        let scaled_words = text_layout::words_to_scaled_words(words, font_bytes, font_index as u32, font_size_px);
+        let text_layout_options = TextLayoutOptions {
+            max_horizontal_width: Some(max_width),
+            .. Default::default()
+        };
+        let word_positions = text_layout::position_words(words, &scaled_words, &text_layout_options, font_size_px);
+
+        Some(word_positions.number_of_lines)
+    }
+
+    /// Sets a custom function to split texts into words, see [`TextCache::set_word_splitter`]
+    ///
+    /// [`TextCache::set_word_splitter`]: ../app_resources/struct.TextCache.html#method.set_word_splitter
+    pub fn set_word_splitter(&mut self, word_splitter: Option<WordSplitter>) {
+        self.text_cache.set_word_splitter(word_splitter);
+    }
+
+    /// Removes a string from both the string cache and the layouted text cache
+    pub fn delete_text(&mut self, id: TextId) {
+        self.text_cache.delete_text(id);
+    }
+
+    /// Empties the entire internal text cache, invalidating all `TextId`s. Use with care.
+    pub fn clear_all_texts(&mut self) {
+        self.text_cache.clear_all_texts();
+    }
+
+    // -- Clipboard
+
+    /// Returns the contents of the system clipboard
+    pub fn get_clipboard_string(&self) -> Result<String, ClipboardError> {
+        self.clipboard.get_string_contents()
+    }
+
+    /// Sets the contents of the system clipboard - currently only strings are supported
+    pub fn set_clipboard_string<S: Into<String>>(&mut self, contents: S) -> Result<(), ClipboardError> {
+        self.clipboard.set_string_contents(contents.into())
+    }
+
+    /// Returns the MIME types of the data that is currently available on the system clipboard,
+    /// i.e. to only show the applicable options of a "Paste special" menu.
+    ///
+    /// The clipboard backend can't enumerate the formats, so the formats are detected by
+    /// trying to read them - currently only text (`"text/plain"`), the text-based formats
+    /// (see `get_clipboard_files`) and images (see `get_clipboard_image`) can be detected this way.
+    /// If the clipboard is empty or contains no readable format, an empty list is returned.
+    pub fn clipboard_formats(&self) -> Result<Vec<String>, ClipboardError> {
+        let mut formats = Vec::new();
+        let mut has_image = false;
+        if let Ok(text) = self.clipboard.get_string_contents() {
+            if !text.is_empty() {
+                formats.push(CLIPBOARD_FORMAT_TEXT.to_string());
+            }
+            if !parse_clipboard_uri_list(&text).is_empty() {
+                formats.push(CLIPBOARD_FORMAT_FILE_LIST.to_string());
+            }
+            has_image = text.trim_start().starts_with(CLIPBOARD_PNG_DATA_URI_PREFIX);
+        }
+        #[cfg(feature = "image_loading")] {
+            has_image = has_image || self.get_native_clipboard_image().ok().and_then(|image| image).is_some();
+        }
+        if has_image {
+            formats.push(CLIPBOARD_FORMAT_PNG.to_string());
+        }
+        Ok(formats)
+    }
+
+    /// Returns the files that were copied to the clipboard, i.e. by a file manager.
+    ///
+    /// The clipboard backend only supports text, so the files are read from a text/uri-list
+    /// (one `file://` URI per line), which is what most Linux file managers put on the clipboard.
+    /// Native file lists (i.e. `CF_HDROP` on Windows) aren't supported and result in an empty list.
+    pub fn get_clipboard_files(&self) -> Result<Vec<PathBuf>, ClipboardError> {
+        self.clipboard.get_string_contents().map(|text| parse_clipboard_uri_list(&text))
+    }
+
+    /// Puts a list of files on the clipboard as a text/uri-list, see `get_clipboard_files`
+    pub fn set_clipboard_files(&mut self, paths: &[PathBuf]) -> Result<(), ClipboardError> {
+        self.clipboard.set_string_contents(format_clipboard_uri_list(paths))
+    }
+
+    /// Returns the image on the clipboard as a BGRA8 image (straight alpha), or `None` if the
+    /// clipboard doesn't contain an image.
+    ///
+    /// The image is read from the native image formats (the registered `"PNG"` format or
+    /// `CF_DIB` on Windows, the `image/png` target on X11, `public.png` on macOS). If the
+    /// clipboard has no native image, a PNG data URI in the clipboard text
+    /// (`data:image/png;base64,...`, which some editors copy) is decoded instead.
+    #[cfg(feature = "image_loading")]
+    pub fn get_clipboard_image(&self) -> Result<Option<RawImage>, ClipboardError> {
+        match self.get_native_clipboard_image() {
+            Ok(Some(image)) => Ok(Some(image)),
+            _ => self.clipboard.get_string_contents().map(|text| parse_clipboard_image(&text)),
+        }
+    }
+
+    #[cfg(feature = "image_loading")]
+    fn get_native_clipboard_image(&self) -> Result<Option<RawImage>, ClipboardError> {
+        match self.image_clipboard {
+            Some(ref image_clipboard) => image_clipboard.get_image(),
+            None => ImageClipboard::new()?.get_image(),
+        }
+    }
+
+    /// Puts the image on the clipboard in the native image formats, see `get_clipboard_image`.
+    /// Premultiplied images are un-premultiplied first. Only BGRA8, RGBA8 and R8 images can be
+    /// copied, other formats (or pixels that don't match the dimensions) and platforms without
+    /// a native image clipboard return `ClipboardError::Unimplemented`.
+    #[cfg(feature = "image_loading")]
+    pub fn set_clipboard_image(&mut self, image: &RawImage) -> Result<(), ClipboardError> {
+        if self.image_clipboard.is_none() {
+            self.image_clipboard = Some(ImageClipboard::new()?);
+        }
+        match self.image_clipboard {
+            Some(ref mut image_clipboard) => image_clipboard.set_image(image),
+            None => Err(ClipboardError::Unimplemented),
+        }
+    }
+
+    /// Releases the excess capacity of all internal caches, i.e. after a burst of resources
+    /// has been added and deleted again. Meant to be called when the application is idle.
+    pub fn shrink_to_fit(&mut self) {
+        self.css_ids_to_image_ids.shrink_to_fit();
+        self.css_ids_to_font_ids.shrink_to_fit();
+        self.image_sources.shrink_to_fit();
+        self.font_sources.shrink_to_fit();
+        self.currently_registered_images.shrink_to_fit();
+        self.currently_registered_fonts.shrink_to_fit();
+        for loaded_font in self.currently_registered_fonts.values_mut() {
+            loaded_font.font_instances.shrink_to_fit();
+        }
+        self.last_frame_image_keys.shrink_to_fit();
+        self.last_frame_font_keys.shrink_to_fit();
+        self.text_cache.shrink_to_fit();
+    }
+
+    pub(crate) fn get_loaded_font(&self, font_id: &ImmediateFontId) -> Option<&LoadedFont> {
+        self.currently_registered_fonts.get(font_id)
+    }
+
+    /// Returns the bytes + index of a registered font, i.e. for text shaping. The bytes of the fonts
+    /// of the current frame are always available. If the bytes of another font were dropped
+    /// (see `AppConfig::drop_font_bytes_after_upload`), it is re-read from its `FontSource`.
+    pub(crate) fn get_loaded_font_bytes(&self, font_id: &ImmediateFontId) -> Option<(Cow<[u8]>, i32)> {
+
+        let loaded_font = self.currently_registered_fonts.get(font_id)?;
+
+        if !loaded_font.font_bytes.is_empty() {
+            return Some((Cow::Borrowed(&loaded_font.font_bytes[..]), loaded_font.font_index));
+        }
+
+        let font_source = match font_id {
+            ImmediateFontId::Resolved(font_id) => self.font_sources.get(font_id)?.clone(),
+            ImmediateFontId::Unresolved(css_font_id) => FontSource::System(css_font_id.clone()),
+        };
+
+        let (font_bytes, font_index) = font_source.get_bytes().ok()?;
+        Some((Cow::Owned(font_bytes), font_index))
+    }
+
+    /// Scans the DisplayList for new images and fonts. After this call, the RenderApi is
+    /// guaranteed to know about all FontKeys and FontInstanceKey
+    ///
+    /// If lazy image decoding is enabled, background images are skipped, they are only
+    /// added after the layout, see `add_images_in_viewport`.
+    /// Uploads the fonts and images of the display list that aren't uploaded yet. Returns the
+    /// resources that failed to load, which are also reported by `take_resource_warnings`.
+    pub(crate) fn add_fonts_and_images<T>(&mut self, display_list: &DisplayList<T>) -> Vec<(ResourceWarningId, ResourceLoadError)> {
+        let image_scan_filter = match self.lazy_decode_margin_px {
+            Some(_) => ImageScanFilter::LayoutOnly,
+            None => ImageScanFilter::All,
+        };
+        let font_keys = scan_ui_description_for_font_keys(&self, display_list);
+        let image_keys = scan_ui_description_for_image_keys(&self, display_list, image_scan_filter);
+
+        self.last_frame_font_keys.extend(font_keys.clone().into_iter());
+        self.last_frame_image_keys.extend(image_keys.clone().into_iter());
+
+        let current_frame = self.current_frame;
+        self.font_last_used_frame.extend(font_keys.keys().map(|font_id| (font_id.clone(), current_frame)));
+        self.image_last_used_frame.extend(image_keys.iter().map(|image_id| (*image_id, current_frame)));
+        for (font_id, instance_specs) in font_keys.iter() {
+            self.font_instance_last_used_frame.entry(font_id.clone()).or_insert_with(FastHashMap::default)
+                .extend(instance_specs.iter().map(|instance_spec| (instance_spec.clone(), current_frame)));
+        }
+
+        self.update_callback_images(&image_keys);
+
+        // The layout needs the bytes of all fonts of the frame, see `AppConfig::drop_font_bytes_after_upload`
+        if self.drop_font_bytes_after_upload {
+            self.reload_dropped_font_bytes(font_keys.keys());
+        }
+
+        let mut load_errors = Vec::new();
+        let add_font_resource_updates = build_add_font_resource_updates(self, &font_keys, &mut load_errors);
+        let add_image_resource_updates = build_add_image_resource_updates(self, &image_keys, &mut load_errors);
+        self.push_resource_warnings(&load_errors);
+        self.current_frame_stats.image_uploads += add_image_resource_updates.len();
+
+        add_resources(self, add_font_resource_updates, add_image_resource_updates);
+
+        load_errors
+    }
+
+    /// Re-reads the bytes of registered fonts that were dropped at the end of the last frame
+    /// (see `AppConfig::drop_font_bytes_after_upload`), so that each font is only read once
+    /// per frame instead of once per text. The bytes are dropped again by the GC.
+    fn reload_dropped_font_bytes<'a, I: IntoIterator<Item=&'a ImmediateFontId>>(&mut self, font_ids: I) {
+        for font_id in font_ids {
+
+            match self.currently_registered_fonts.get(font_id) {
+                Some(loaded_font) if loaded_font.font_bytes.is_empty() => { },
+                _ => continue,
+            }
+
+            let font_source = match font_id {
+                ImmediateFontId::Resolved(font_id) => match self.font_sources.get(font_id) {
+                    Some(s) => s.clone(),
+                    None => continue,
+                },
+                ImmediateFontId::Unresolved(css_font_id) => FontSource::System(css_font_id.clone()),
+            };
+
+            if let (Ok((font_bytes, _)), Some(loaded_font)) = (font_source.get_bytes(), self.currently_registered_fonts.get_mut(font_id)) {
+                loaded_font.font_bytes = font_bytes;
+            }
+        }
+    }
+
+    /// Registers the pixels of a newly uploaded image. If an image with the same pixels is
+    /// already uploaded, returns its `ImageKey`, so that the pixels are only uploaded once.
+    fn share_image_key(&mut self, image_id: ImageId, add_image: &AddImage) -> Option<ImageKey> {
+
+        use std::collections::hash_map::Entry::*;
+
+        // Callback images are updated in place, so they need their own ImageKey
+        if let Some(ImageSource::Callback(_)) = self.image_sources.get(&image_id) {
+            return None;
+        }
+
+        // The key of an animated image is swapped when the frame changes
+        if self.animated_images.contains_key(&image_id) {
+            return None;
+        }
+
+        let content_hash = image_content_hash(&add_image.data, &add_image.descriptor)?;
+
+        match self.shared_image_keys.entry(content_hash) {
+            Occupied(mut o) => {
+                // Different descriptor: the image gets its own key and isn't registered under the hash
+                if o.get().descriptor != add_image.descriptor {
+                    return None;
+                }
+                self.image_content_hashes.insert(image_id, content_hash);
+                o.get_mut().image_count += 1;
+                Some(o.get().key)
+            },
+            Vacant(v) => {
+                self.image_content_hashes.insert(image_id, content_hash);
+                v.insert(SharedImageKey {
+                    key: add_image.key,
+                    descriptor: add_image.descriptor,
+                    image_count: 1,
+                });
+                None
+            },
+        }
+    }
+
+    /// Unregisters the pixels of an image that is about to be deleted or changed. Returns
+    /// whether the `ImageKey` of the image isn't used by any other image (anymore).
+    fn release_shared_image_key(&mut self, image_id: &ImageId) -> bool {
+
+        let content_hash = match self.image_content_hashes.remove(image_id) {
+            Some(s) => s,
+            None => return true,
+        };
+
+        match self.shared_image_keys.get_mut(&content_hash) {
+            Some(ref mut shared_key) if shared_key.image_count > 1 => {
+                shared_key.image_count -= 1;
+                false
+            },
+            _ => {
+                self.shared_image_keys.remove(&content_hash);
+                true
+            },
+        }
+    }
+
+    /// Returns the `FontKey` of an already uploaded font with the same bytes and font index.
+    /// Different fonts can have the same `content_hash`, so the bytes are compared before the
+    /// key is shared. Fonts whose bytes were dropped after the upload (see
+    /// `AppConfig::drop_font_bytes_after_upload`) can't be compared and aren't shared.
+    fn find_shared_font_key(&self, content_hash: u64, font_bytes: &[u8], font_index: i32) -> Option<FontKey> {
+        let (shared_key, _) = self.shared_font_keys.get(&content_hash)?;
+        let is_identical = self.currently_registered_fonts.values().any(|loaded_font| {
+            loaded_font.font_key == *shared_key &&
+            loaded_font.font_index == font_index &&
+            loaded_font.font_bytes[..] == font_bytes[..]
+        });
+        if is_identical { Some(*shared_key) } else { None }
+    }
+
+    /// Registers a font that is about to be added under the `content_hash` of its bytes. Returns
+    /// whether the `font_key` is already uploaded by another font, so the upload can be skipped.
+    /// Fonts that got a different key than the shared one (because both were loaded in the same
+    /// frame) are uploaded as well.
+    fn share_font_key(&mut self, content_hash: u64, font_key: FontKey) -> bool {
+
+        use std::collections::hash_map::Entry::*;
+
+        match self.shared_font_keys.entry(content_hash) {
+            Occupied(mut o) => {
+                let is_shared = o.get().0 == font_key;
+                if is_shared {
+                    o.get_mut().1 += 1;
+                }
+                is_shared
+            },
+            Vacant(v) => {
+                v.insert((font_key, 1));
+                false
+            },
+        }
+    }
+
+    /// Unregisters a font from its shared `FontKey`, returns whether the key can be deleted
+    fn release_shared_font_key(&mut self, font_id: &ImmediateFontId) -> bool {
+
+        let content_hash = match self.font_content_hashes.remove(font_id) {
+            Some(s) => s,
+            None => return true,
+        };
+
+        match self.shared_font_keys.get_mut(&content_hash) {
+            Some((_, font_count)) if *font_count > 1 => {
+                *font_count -= 1;
+                false
+            },
+            _ => {
+                self.shared_font_keys.remove(&content_hash);
+                true
+            },
+        }
+    }
+
+    fn push_resource_warnings(&mut self, load_errors: &[(ResourceWarningId, ResourceLoadError)]) {
+        for (id, e) in load_errors {
+            self.push_resource_warning(ResourceWarning { id: id.clone(), message: e.to_string() });
+        }
+    }
+
+    fn push_resource_warning(&mut self, warning: ResourceWarning) {
+        if self.resource_warnings.contains(&warning) {
+            return;
+        }
+        if self.resource_warnings.len() >= MAX_RESOURCE_WARNINGS {
+            self.resource_warnings.pop_front();
+        }
+        self.resource_warnings.push_back(warning);
+    }
+
+    /// Invokes the callbacks of all already uploaded `ImageSource::Callback` images of the
+    /// current frame and re-uploads the images that changed. If a callback returns `None`,
+    /// the upload is skipped. New callback images are uploaded by `build_add_image_resource_updates`.
+    fn update_callback_images(&mut self, image_keys: &FastHashSet<ImageId>) {
+
+        let current_frame = self.current_frame;
+        let mut resource_updates = Vec::new();
+
+        for image_id in image_keys.iter() {
+
+            let callback = match self.image_sources.get(image_id) {
+                Some(ImageSource::Callback(callback)) => callback.clone(),
+                _ => continue,
+            };
+
+            let image_info = match self.currently_registered_images.get_mut(image_id) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            if self.callback_image_frames.insert(*image_id, current_frame) == Some(current_frame) {
+                continue;
+            }
+
+            let (data, mut descriptor, alpha_type) = match (callback.0)() {
+                Some(raw_image) => {
+                    let alpha_type = raw_image.alpha_type();
+                    match ImageSource::Raw(raw_image).get_bytes() {
+                        Ok((data, descriptor)) => (data, descriptor, alpha_type),
+                        Err(_) => continue,
+                    }
+                },
+                None => continue,
+            };
+            descriptor.allow_mipmaps &= !self.images_without_mipmaps.contains(image_id);
+
+            image_info.descriptor = descriptor;
+            image_info.alpha_type = alpha_type;
+            self.image_alpha_masks.remove(image_id);
+            resource_updates.push(ResourceUpdate::UpdateImage(UpdateImage {
+                key: image_info.key,
+                descriptor,
+                data,
+                dirty_rect: None,
+            }));
+        }
+
+        if !resource_updates.is_empty() {
+            self.current_frame_stats.image_uploads += resource_updates.len();
+            self.queue_resource_updates(resource_updates);
+            self.scene_builder_flush_pending = true;
+        }
+    }
+
+    /// Whether images are added depending on the visible area of the window (lazy image decoding
+    /// or tiled images), so that the window has to be laid out again when it is scrolled
+    pub(crate) fn has_viewport_dependent_images(&self) -> bool {
+        self.lazy_decode_margin_px.is_some() || !self.tiled_images.is_empty()
+    }
+
+    /// If lazy image decoding is enabled (see `AppConfig::lazy_decode_margin_px`), adds the background
+    /// images of all nodes that are (partially) within the visible area, enlarged by the margin.
+    /// Has to be called after the layout, since the position of each node has to be known.
+    pub(crate) fn add_images_in_viewport<T>(
+        &mut self,
+        display_list: &DisplayList<T>,
+        layouted_rects: &NodeDataContainer<PositionedRectangle>,
+        scroll_offsets: &BTreeMap<NodeId, (f32, f32)>,
+        visible_area: LayoutRect,
+        hidpi_factor: f32,
+    ) {
+        self.add_visible_tiles(display_list, layouted_rects, scroll_offsets, visible_area.inflate(
+            self.lazy_decode_margin_px.unwrap_or(0.0),
+            self.lazy_decode_margin_px.unwrap_or(0.0),
+        ));
+
+        #[cfg(feature = "svg_parsing")]
+        self.rasterize_svg_images_at_displayed_size(display_list, layouted_rects, hidpi_factor);
+        #[cfg(not(feature = "svg_parsing"))]
+        let _ = hidpi_factor;
+
+        let margin = match self.lazy_decode_margin_px {
+            Some(s) => s,
+            None => return,
+        };
+
+        let viewport = visible_area.inflate(margin, margin);
+        let image_keys = scan_ui_description_for_image_keys(&self, display_list, ImageScanFilter::InViewport(layouted_rects, scroll_offsets, viewport));
+
+        self.last_frame_image_keys.extend(image_keys.iter().cloned());
+
+        let current_frame = self.current_frame;
+        self.image_last_used_frame.extend(image_keys.iter().map(|image_id| (*image_id, current_frame)));
+
+        let mut load_errors = Vec::new();
+        let add_image_resource_updates = build_add_image_resource_updates(self, &image_keys, &mut load_errors);
+        self.push_resource_warnings(&load_errors);
+        self.current_frame_stats.image_uploads += add_image_resource_updates.len();
+
+        add_resources(self, Vec::new(), add_image_resource_updates);
+    }
+
+    /// Rasterizes the `ImageSource::Svg` images of `NodeType::Image` nodes again if their displayed
+    /// size (in physical pixels) differs by more than `SVG_RERASTERIZE_THRESHOLD` from the size they
+    /// were last rasterized at. If an image is displayed several times, it is rasterized at the largest size.
+    #[cfg(feature = "svg_parsing")]
+    fn rasterize_svg_images_at_displayed_size<T>(
+        &mut self,
+        display_list: &DisplayList<T>,
+        layouted_rects: &NodeDataContainer<PositionedRectangle>,
+        hidpi_factor: f32,
+    ) {
+        use dom::NodeType::Image;
+
+        let mut displayed_sizes = FastHashMap::<ImageId, (u32, u32)>::default();
+
+        for node_id in display_list.rectangles.linear_iter() {
+
+            let image_id = match display_list.ui_descr.ui_descr_arena.node_data[node_id].node_type {
+                Image(image_id) => image_id,
+                _ => continue,
+            };
+
+            // Nine-patch insets are in pixels of the original image
+            match self.image_sources.get(&image_id) {
+                Some(ImageSource::Svg(_)) if self.get_nine_patch_insets(&image_id).is_none() => { },
+                _ => continue,
+            }
+
+            let bounds = layouted_rects[node_id].bounds;
+            let size = (
+                (bounds.size.width * hidpi_factor).round().max(1.0) as u32,
+                (bounds.size.height * hidpi_factor).round().max(1.0) as u32,
+            );
+            let displayed_size = displayed_sizes.entry(image_id).or_insert(size);
+            *displayed_size = (displayed_size.0.max(size.0), displayed_size.1.max(size.1));
+        }
+
+        let mut resource_updates = Vec::new();
+
+        for (image_id, displayed_size) in displayed_sizes {
+
+            let rasterized_size = match self.currently_registered_images.get(&image_id) {
+                Some(image_info) => (image_info.descriptor.size.width as u32, image_info.descriptor.size.height as u32),
+                None => continue,
+            };
+
+            let exceeds_threshold = |displayed: u32, rasterized: u32| {
+                (displayed as f32 - rasterized as f32).abs() > rasterized as f32 * SVG_RERASTERIZE_THRESHOLD
+            };
+
+            if !exceeds_threshold(displayed_size.0, rasterized_size.0) && !exceeds_threshold(displayed_size.1, rasterized_size.1) {
+                continue;
+            }
+
+            if let Some(failed_size) = self.failed_svg_rasterizations.get(&image_id) {
+                if !exceeds_threshold(displayed_size.0, failed_size.0) && !exceeds_threshold(displayed_size.1, failed_size.1) {
+                    continue;
+                }
+            }
+
+            let rasterized = match self.image_sources.get(&image_id) {
+                Some(ImageSource::Svg(svg)) => rasterize_svg(svg, Some(displayed_size), self.image_decode_options).and_then(|raw_image| ImageSource::Raw(raw_image).get_bytes()),
+                _ => continue,
+            };
+
+            let (data, descriptor) = match rasterized {
+                Ok(o) => o,
+                Err(e) => {
+                    self.push_resource_warning(ResourceWarning { id: ResourceWarningId::Image(image_id), message: e.to_string() });
+                    self.failed_svg_rasterizations.insert(image_id, displayed_size);
+                    continue;
+                },
+            };
+
+            self.failed_svg_rasterizations.remove(&image_id);
+
+            if !self.svg_intrinsic_sizes.contains_key(&image_id) {
+                self.svg_intrinsic_sizes.insert(image_id, (rasterized_size.0 as usize, rasterized_size.1 as usize));
+            }
+
+            // The pixels changed, so the image can't share its ImageKey with other images anymore
+            let new_key = if self.release_shared_image_key(&image_id) { None } else { Some(self.get_render_api().new_image_key()) };
+            let image_info = self.currently_registered_images.get_mut(&image_id).unwrap();
+            image_info.descriptor = descriptor;
+            resource_updates.push(match new_key {
+                None => ResourceUpdate::UpdateImage(UpdateImage { key: image_info.key, descriptor, data, dirty_rect: None }),
+                Some(key) => {
+                    image_info.key = key;
+                    ResourceUpdate::AddImage(AddImage { key, descriptor, data, tiling: None })
+                },
+            });
+        }
+
+        if !resource_updates.is_empty() {
+            self.current_frame_stats.image_uploads += resource_updates.len();
+            self.queue_resource_updates(resource_updates);
+            self.scene_builder_flush_pending = true;
+        }
+    }
+
+    /// Uploads the tiles of all tiled images (see `add_tiled_image`) that are within
+    /// the `viewport` (after scrolling) and marks them as used in the current frame.
+    fn add_visible_tiles<T>(
+        &mut self,
+        display_list: &DisplayList<T>,
+        layouted_rects: &NodeDataContainer<PositionedRectangle>,
+        scroll_offsets: &BTreeMap<NodeId, (f32, f32)>,
+        viewport: LayoutRect,
+    ) {
+        use dom::NodeType::Image;
+
+        if self.tiled_images.is_empty() {
+            return;
+        }
+
+        let mut visible_tiles = Vec::new();
+
+        for node_id in display_list.rectangles.linear_iter() {
+
+            let image_id = match display_list.ui_descr.ui_descr_arena.node_data[node_id].node_type {
+                Image(image_id) => image_id,
+                _ => continue,
+            };
+
+            let tiled_image = match self.tiled_images.get(&image_id) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            let bounds = get_scrolled_bounds(display_list, layouted_rects, scroll_offsets, node_id);
+            let visible_rect = match bounds.intersection(&viewport) {
+                Some(s) if bounds.size.width > 0.0 && bounds.size.height > 0.0 => s,
+                _ => continue,
+            };
+
+            // Node coordinates -> image pixels
+            let scale_x = tiled_image.full_size.0 as f32 / bounds.size.width;
+            let scale_y = tiled_image.full_size.1 as f32 / bounds.size.height;
+            let top_left = ((visible_rect.min_x() - bounds.min_x()) * scale_x, (visible_rect.min_y() - bounds.min_y()) * scale_y);
+            let bottom_right = ((visible_rect.max_x() - bounds.min_x()) * scale_x, (visible_rect.max_y() - bounds.min_y()) * scale_y);
+
+            visible_tiles.extend(tiled_image.get_tiles_in_rect(top_left, bottom_right).into_iter().map(|tile| (image_id, tile)));
+        }
+
+        let current_frame = self.current_frame;
+        let mut resource_updates = Vec::new();
+
+        for (image_id, tile) in visible_tiles {
+
+            let tile_provider_result = match self.tiled_images.get_mut(&image_id) {
+                Some(tiled_image) => match tiled_image.resident_tiles.get_mut(&tile) {
+                    Some((_, last_used_frame)) => { *last_used_frame = current_frame; continue; },
+                    None => (tiled_image.tile_provider)(tile),
+                },
+                None => continue,
+            };
+
+            let image_source = ImageSource::Raw(tile_provider_result);
+            let alpha_type = image_source.get_alpha_type();
+            let (data, descriptor) = match image_source.get_bytes() {
+                Ok(o) => o,
+                Err(e) => {
+                    self.push_resource_warning(ResourceWarning { id: ResourceWarningId::Image(image_id), message: format!("Could not load tile {:?}: {}", tile, e) });
+                    continue;
+                },
+            };
+
+            let key = self.get_render_api().new_image_key();
+            resource_updates.push(ResourceUpdate::AddImage(AddImage { key, data, descriptor, tiling: None }));
+            if let Some(tiled_image) = self.tiled_images.get_mut(&image_id) {
+                let image_info = ImageInfo { key, descriptor, alpha_type, nine_patch_insets: None };
+                tiled_image.resident_tiles.insert(tile, (image_info, current_frame));
+            }
+        }
+
+        if !resource_updates.is_empty() {
+            self.current_frame_stats.image_uploads += resource_updates.len();
+            self.queue_resource_updates(resource_updates);
+            self.scene_builder_flush_pending = true;
+        }
+    }
+
+    /// Deletes all tiles of tiled images that weren't visible in the current frame
+    fn evict_unused_tiles(&mut self) {
+
+        let current_frame = self.current_frame;
+        let mut resource_updates = Vec::new();
+
+        for tiled_image in self.tiled_images.values_mut() {
+            tiled_image.resident_tiles.retain(|_, (image_info, last_used_frame)| {
+                let is_used = *last_used_frame == current_frame;
+                if !is_used {
+                    resource_updates.push(ResourceUpdate::DeleteImage(image_info.key));
+                }
+                is_used
+            });
+        }
+
+        if !resource_updates.is_empty() {
+            self.queue_resource_updates(resource_updates);
+        }
+    }
+
+    /// Immediately deletes the fonts and images that the end-of-frame GC would delete (i.e. to
+    /// free GPU memory when the window is minimized). The `FontSource`s / `ImageSource`s are kept,
+    /// so the resources are loaded again once they are used.
+    pub fn force_evict_unused(&mut self) {
+        let delete_font_resource_updates = build_delete_font_resource_updates(self);
+        let delete_image_resource_updates = build_delete_image_resource_updates(self);
+        delete_resources(self, delete_font_resource_updates, delete_image_resource_updates);
+        self.evict_unused_tiles();
+        self.flush_pending_resource_updates();
+    }
+
+    /// Immediately deletes all fonts and images from the RenderApi, including the ones that
+    /// are used in the current frame and pinned ones (i.e. before a known idle period).
+    /// The `FontSource`s / `ImageSource`s are kept, the resources are loaded again the next
+    /// time the UI is laid out - so the UI should be redrawn before it is shown again.
+    pub fn force_evict_all(&mut self) {
+
+        let mut delete_font_resource_updates = Vec::new();
+        for (font_id, loaded_font) in self.currently_registered_fonts.iter() {
+            // The font instances have to be deleted before the font
+            delete_font_resource_updates.extend(loaded_font.font_instances.iter().map(|(instance_spec, font_instance_key)| {
+                (font_id.clone(), DeleteFontMsg::Instance(*font_instance_key, instance_spec.clone()))
+            }));
+            delete_font_resource_updates.push((font_id.clone(), DeleteFontMsg::Font(loaded_font.font_key)));
+        }
+
+        let delete_image_resource_updates = self.currently_registered_images.iter()
+            .map(|(image_id, image_info)| (*image_id, DeleteImageMsg(image_info.key, *image_info)))
+            .collect();
+
+        delete_resources(self, delete_font_resource_updates, delete_image_resource_updates);
+
+        let delete_tiles = self.tiled_images.values_mut()
+            .flat_map(|tiled_image| tiled_image.resident_tiles.drain().map(|(_, (image_info, _))| ResourceUpdate::DeleteImage(image_info.key)))
+            .collect::<Vec<_>>();
+
+        if !delete_tiles.is_empty() {
+            self.queue_resource_updates(delete_tiles);
+        }
+
+        self.flush_pending_resource_updates();
+    }
+
+    /// To be called at the end of a frame (after the UI has rendered):
+    /// Deletes all FontKeys and FontImageKeys that weren't used in
+    /// the last frame, to save on memory. If the font needs to be recreated, it
+    /// needs to be reloaded from the `FontSource`.
+    pub(crate) fn garbage_collect_fonts_and_images(&mut self) {
+
+        let delete_font_resource_updates = build_delete_font_resource_updates(self);
+        let delete_image_resource_updates = build_delete_image_resource_updates(self);
+
+        delete_resources(self, delete_font_resource_updates, delete_image_resource_updates);
+        self.evict_unused_tiles();
+
+        #[cfg(debug_assertions)]
+        self.debug_assert_resources_consistent();
+
+        // WebRender has its own copy of the fonts, the next frame reloads the bytes it needs
+        if self.drop_font_bytes_after_upload {
+            for loaded_font in self.currently_registered_fonts.values_mut() {
+                loaded_font.font_bytes = Vec::new();
+            }
+        }
+
+        self.last_frame_font_keys.clear();
+        self.last_frame_image_keys.clear();
+    }
+
+    /// Returns whether the GC should delete a resource that was (not) used in the last frame
+    /// and was last used in `last_used_frame`, according to the `GcPolicy`
+    fn gc_should_delete(&self, used_in_last_frame: bool, last_used_frame: Option<usize>) -> bool {
+        if used_in_last_frame {
+            return false;
+        }
+        match self.gc_policy {
+            GcPolicy::Immediate => true,
+            GcPolicy::FrameTtl(ttl) => match last_used_frame {
+                Some(frame) => self.current_frame - frame >= ttl as usize,
+                None => true,
+            },
+            GcPolicy::Never => false,
+        }
+    }
+
+    /// Checks that the GC didn't miss any resources: After the unused resources of a frame were
+    /// deleted, every font, font instance and image that is still registered in the RenderApi
+    /// has to be pinned or kept alive by the `GcPolicy` (with `GcPolicy::Immediate`: has to have
+    /// been used in that frame). Panics with a description of the first violation.
+    #[cfg(debug_assertions)]
+    fn debug_assert_resources_consistent(&self) {
+
+        for (font_id, loaded_font) in self.currently_registered_fonts.iter() {
+            if is_font_pinned(self, font_id) {
+                continue;
+            }
+            let used_instances = self.last_frame_font_keys.get(font_id);
+            let font_last_used_frame = match self.font_last_used_frame.get(font_id) {
+                Some(s) => *s,
+                None => panic!("font {:?} is registered, but has no last-used frame", font_id),
+            };
+            assert!(
+                !self.gc_should_delete(used_instances.is_some(), Some(font_last_used_frame)),
+                "font {:?} is registered, but wasn't used in the last frame(s)", font_id
+            );
+            assert!(!loaded_font.font_instances.is_empty(), "font {:?} is registered without any font instances", font_id);
+            let instance_frames = self.font_instance_last_used_frame.get(font_id);
+            for (instance_spec, font_instance_key) in loaded_font.font_instances.iter() {
+                assert!(
+                    !self.gc_should_delete(
+                        used_instances.map(|used| used.contains(instance_spec)).unwrap_or(false),
+                        instance_frames.and_then(|frames| frames.get(instance_spec).cloned()),
+                    ),
+                    "font instance {:?} ({:?}) of font {:?} is registered, but wasn't used in the last frame(s)",
+                    font_instance_key, instance_spec, font_id
+                );
+            }
+        }
+
+        for image_id in self.currently_registered_images.keys().filter(|image_id| !self.pinned_images.contains_key(image_id)) {
+            let image_last_used_frame = match self.image_last_used_frame.get(image_id) {
+                Some(s) => *s,
+                None => panic!("image {:?} is registered, but has no last-used frame", image_id),
+            };
+            assert!(
+                !self.gc_should_delete(self.last_frame_image_keys.contains(image_id), Some(image_last_used_frame)),
+                "image {:?} is registered, but wasn't used in the last frame(s)", image_id
+            );
+        }
+    }
+
+    /// Advances the frame counter of the font / image GC, to be called once
+    /// after the GC has run. Tests can call this to simulate many frames quickly.
+    ///
+    /// The deletions of the GC are submitted together with the additions of the next frame,
+    /// see `flush_pending_resource_updates`.
+    pub(crate) fn begin_frame(&mut self) {
+        self.current_frame += 1;
+        self.last_frame_stats = self.current_frame_stats;
+        self.current_frame_stats = FrameStats::default();
+    }
+
+    /// Queues resource updates, see `flush_pending_resource_updates`
+    fn queue_resource_updates(&mut self, resource_updates: Vec<ResourceUpdate>) {
+        self.pending_resource_updates.extend(resource_updates);
+    }
+
+    /// Submits all queued resource updates (the deletions of the last GC run and the additions
+    /// of all windows) with a single `update_resources` call, then flushes the scene builder
+    /// once if any fonts or images were added. Has to be called before a frame is generated.
+    pub(crate) fn flush_pending_resource_updates(&mut self) {
+
+        if !self.pending_resource_updates.is_empty() {
+            let resource_updates = ::std::mem::replace(&mut self.pending_resource_updates, Vec::new());
+            self.get_render_api().update_resources(resource_updates);
+            self.current_frame_stats.resource_update_submissions += 1;
+        }
+
+        if self.scene_builder_flush_pending {
+            self.get_render_api().flush_scene_builder();
+            self.scene_builder_flush_pending = false;
+            self.current_frame_stats.scene_builder_flushes += 1;
+        }
+    }
+
+    /// Returns all font and image loading failures since the last call, independent
+    /// of whether the `logging` feature is enabled, i.e. to show them to the user.
+    pub fn take_resource_warnings(&mut self) -> Vec<ResourceWarning> {
+        self.resource_warnings.drain(..).collect()
+    }
+
+    /// Returns whether images were postponed in the last frame because of
+    /// `AppConfig::max_image_uploads_per_frame`, so the next frame has to be laid out again
+    pub(crate) fn has_deferred_image_uploads(&self) -> bool {
+        self.last_frame_stats.deferred_image_uploads > 0
+    }
+
+    /// Returns how many images are waiting to be uploaded: the images that were postponed in
+    /// the last frame (see `AppConfig::max_image_uploads_per_frame`) plus the images that were
+    /// submitted in the current frame, but not flushed to the RenderApi yet. Applications
+    /// that load many images can use this to throttle their loading.
+    pub fn pending_image_uploads(&self) -> usize {
+        let unflushed_uploads = if self.scene_builder_flush_pending {
+            self.current_frame_stats.image_uploads
+        } else {
+            0
+        };
+        self.last_frame_stats.deferred_image_uploads + unflushed_uploads
+    }
+
+    /// Returns the resource statistics of the last completed frame
+    pub fn get_frame_stats(&self) -> FrameStats {
+        self.last_frame_stats
+    }
+
+    /// Returns how many images, fonts and texts are currently registered / uploaded
+    pub fn resource_counts(&self) -> ResourceCounts {
+        ResourceCounts {
+            image_sources: self.image_sources.len(),
+            resident_images: self.currently_registered_images.len(),
+            font_sources: self.font_sources.len(),
+            resident_fonts: self.currently_registered_fonts.len(),
+            font_instances: self.currently_registered_fonts.values().map(|f| f.font_instances.len()).sum(),
+            cached_texts: self.text_cache.string_cache.len(),
+        }
+    }
+
+    /// Returns how many font keys, font instances and image keys are uploaded to the RenderApi
+    /// and approximately how much memory they occupy, i.e. to find resources that the GC
+    /// doesn't delete. Only reads the registered resources, nothing is uploaded or deleted.
+    pub fn memory_report(&self) -> ResourceMemoryReport {
+
+        let mut report = ResourceMemoryReport::default();
+        let mut counted_font_keys = FastHashSet::default();
+        let mut counted_image_keys = FastHashSet::default();
+
+        for (font_id, loaded_font) in self.currently_registered_fonts.iter() {
+            report.font_instances += loaded_font.font_instances.len();
+            if counted_font_keys.insert(loaded_font.font_key) {
+                report.font_keys += 1;
+                report.font_bytes += loaded_font.font_bytes.len();
+            }
+            report.fonts.push(FontMemoryUsage {
+                font_id: match font_id {
+                    ImmediateFontId::Resolved(font_id) => Some(*font_id),
+                    ImmediateFontId::Unresolved(_) => None,
+                },
+                font_key: loaded_font.font_key,
+                font_instances: loaded_font.font_instances.len(),
+                font_bytes: loaded_font.font_bytes.len(),
+            });
+        }
+
+        report.fonts.sort_by(|a, b| b.font_bytes.cmp(&a.font_bytes));
+
+        let registered_images = self.currently_registered_images.values().map(|image_info| (image_info.key, image_info.descriptor));
+        let animation_frames = self.animated_images.values().flat_map(|animation| {
+            animation.frame_keys.iter().zip(animation.frames.iter()).filter_map(|(key, (_, descriptor, _))| key.map(|key| (key, *descriptor)))
+        });
+        let resident_tiles = self.tiled_images.values().flat_map(|tiled_image| {
+            tiled_image.resident_tiles.values().map(|(image_info, _)| (image_info.key, image_info.descriptor))
+        });
+
+        for (image_key, descriptor) in registered_images.chain(animation_frames).chain(resident_tiles) {
+            if counted_image_keys.insert(image_key) {
+                report.image_keys += 1;
+                report.image_bytes += descriptor.size.width as usize * descriptor.size.height as usize * descriptor.format.bytes_per_pixel() as usize;
+            }
+        }
+
+        report
+    }
+
+    /// Returns the hit / miss counters of the gradient image and alpha mask caches
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache_stats
+    }
+
+    /// Returns the current value of the GC frame counter
+    pub(crate) fn get_current_frame(&self) -> usize {
+        self.current_frame
+    }
+
+    /// Returns how many frames ago an image was last used, or `None`
+    /// if the image wasn't used since it was last garbage-collected
+    pub(crate) fn frames_since_image_use(&self, image_id: &ImageId) -> Option<usize> {
+        self.image_last_used_frame.get(image_id).map(|frame| self.current_frame - frame)
+    }
+
+    /// Returns how many frames ago a font was last used, or `None`
+    /// if the font wasn't used since it was last garbage-collected
+    pub(crate) fn frames_since_font_use(&self, font_id: &ImmediateFontId) -> Option<usize> {
+        self.font_last_used_frame.get(font_id).map(|frame| self.current_frame - frame)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum ImmediateFontId {
+    Resolved(FontId),
+    Unresolved(CssFontId),
+}
+
+/// Scans the display list for all font IDs + their font size
+fn scan_ui_description_for_font_keys<'a, T>(
+    app_resources: &AppResources,
+    display_list: &DisplayList<'a, T>
+) -> FastHashMap<ImmediateFontId, FastHashSet<FontInstanceSpec>> {
+
+    use dom::NodeType::*;
+    use ui_solver;
+
+    let mut font_keys = FastHashMap::default();
+
+    for node_id in display_list.rectangles.linear_iter() {
+
+        let node_data = &display_list.ui_descr.ui_descr_arena.node_data[node_id];
+        let display_rect = &display_list.rectangles[node_id];
+
+        match node_data.node_type {
+            Text(_) | Label(_) => {
+                let css_font_id = ui_solver::get_font_id(&display_rect.style);
+                let font_id = match app_resources.css_ids_to_font_ids.get(css_font_id) {
+                    Some(s) => ImmediateFontId::Resolved(*s),
+                    None => ImmediateFontId::Unresolved(css_font_id.to_string()),
+                };
+                let font_size = ui_solver::get_font_size(&display_rect.style);
+                let variations = ui_solver::get_font_variations(&display_rect.style);
+                let (font_id, instance_spec) = app_resources.resolve_font_instance(font_id, ui_solver::font_size_to_au(font_size), variations);
+                font_keys
+                    .entry(font_id)
+                    .or_insert_with(|| FastHashSet::default())
+                    .insert(instance_spec);
+            },
+            _ => { }
+        }
+    }
+
+    font_keys
+}
+
+/// Determines which images `scan_ui_description_for_image_keys` picks up
+#[derive(Copy, Clone)]
+enum ImageScanFilter<'a> {
+    /// All images in the display list
+    All,
+    /// Only images that the layout depends on (`NodeType::Image`), but no background images
+    LayoutOnly,
+    /// Only background images of nodes which intersect the given viewport, after the nodes
+    /// are moved by the scroll offsets of their scroll frames (see `get_scrolled_bounds`)
+    InViewport(&'a NodeDataContainer<PositionedRectangle>, &'a BTreeMap<NodeId, (f32, f32)>, LayoutRect),
+}
+
+/// Returns the bounds of a node as it is displayed: moved by the `scroll_offsets` (node of
+/// the scroll frame -> amount its content is scrolled) of all scroll frames containing the node
+fn get_scrolled_bounds<'a, T>(
+    display_list: &DisplayList<'a, T>,
+    layouted_rects: &NodeDataContainer<PositionedRectangle>,
+    scroll_offsets: &BTreeMap<NodeId, (f32, f32)>,
+    node_id: NodeId,
+) -> LayoutRect {
+    let node_hierarchy = &display_list.ui_descr.ui_descr_arena.node_layout;
+    let (scroll_x, scroll_y) = node_id.ancestors(node_hierarchy).skip(1)
+        .filter_map(|ancestor| scroll_offsets.get(&ancestor))
+        .fold((0.0, 0.0), |(x, y), (scroll_x, scroll_y)| (x + scroll_x, y + scroll_y));
+    layouted_rects[node_id].bounds.translate(&LayoutVector2D::new(-scroll_x, -scroll_y))
+}
+
+/// Scans the display list for all image keys
+fn scan_ui_description_for_image_keys<'a, T>(
+    app_resources: &AppResources,
+    display_list: &DisplayList<'a, T>,
+    filter: ImageScanFilter,
+) -> FastHashSet<ImageId> {
+
+    use dom::NodeType::*;
+
+    display_list.rectangles
+    .linear_iter()
+    .filter_map(|node_id| {
+        let display_rect = &display_list.rectangles[node_id];
+        let node_data = &display_list.ui_descr.ui_descr_arena.node_data[node_id];
+        match node_data.node_type {
+            Image(id) => match filter {
+                ImageScanFilter::InViewport(..) => None,
+                // Glyphs of bitmap fonts are drawn from their atlas image
+                _ => Some(app_resources.get_atlas_glyph(&id).map(|glyph| glyph.atlas_id).unwrap_or(id)),
+            },
+            _ => {
+                match filter {
+                    ImageScanFilter::All => { },
+                    ImageScanFilter::LayoutOnly => return None,
+                    ImageScanFilter::InViewport(layouted_rects, scroll_offsets, viewport) => {
+                        if !get_scrolled_bounds(display_list, layouted_rects, scroll_offsets, node_id).intersects(&viewport) {
+                            return None;
+                        }
+                    },
+                }
+                let background = display_rect.style.background.as_ref()?;
+                let css_image_id = background.get_css_image_id()?;
+                let image_id = app_resources.get_css_image_id(&css_image_id.0)?;
+                Some(*image_id)
+            }
+        }
+    }).collect()
+}
+
+// Debug, PartialEq, Eq, PartialOrd, Ord
+#[derive(Clone)]
+enum AddFontMsg {
+    /// The font and the content hash of its bytes, see `font_content_hash`
+    Font(LoadedFont, u64),
+    Instance(AddFontInstance, FontInstanceSpec),
+}
+
+// Debug, PartialEq, Eq, PartialOrd, Ord
+#[derive(Clone)]
+enum DeleteFontMsg {
+    Font(FontKey),
+    Instance(FontInstanceKey, FontInstanceSpec),
+}
+// Debug, PartialEq, Eq, PartialOrd, Ord
+#[derive(Clone)]
+struct AddImageMsg(AddImage, ImageInfo);
+
+// Debug, PartialEq, Eq, PartialOrd, Ord
+#[derive(Clone)]
+struct DeleteImageMsg(ImageKey, ImageInfo);
+
+impl AddFontMsg {
+    fn into_resource_update(&self) -> ResourceUpdate {
+        use self::AddFontMsg::*;
+        match self {
+            Font(f, _) => ResourceUpdate::AddFont(AddFont::Raw(f.font_key, f.font_bytes.clone(), f.font_index as u32)),
+            Instance(fi, _) => ResourceUpdate::AddFontInstance(fi.clone()),
+        }
+    }
+}
+
+impl DeleteFontMsg {
+    fn into_resource_update(&self) -> ResourceUpdate {
+        use self::DeleteFontMsg::*;
+        match self {
+            Font(f) => ResourceUpdate::DeleteFont(*f),
+            Instance(fi, _) => ResourceUpdate::DeleteFontInstance(*fi),
+        }
+    }
+}
+
+impl AddImageMsg {
+    fn into_resource_update(&self) -> ResourceUpdate {
+        ResourceUpdate::AddImage(self.0.clone())
+    }
+}
+
+impl DeleteImageMsg {
+    fn into_resource_update(&self) -> ResourceUpdate {
+        ResourceUpdate::DeleteImage(self.0.clone())
+
+    }
+}
+
+/// Given the fonts of the current frame, returns `AddFont` and `AddFontInstance`s of
+/// which fonts / instances are currently not in the `current_registered_fonts` and
+/// need to be added.
+///
+/// Deleting fonts can only be done after the entire frame has finished drawing,
+/// otherwise (if removing fonts would happen after every DOM) we'd constantly
+/// add-and-remove fonts after every IFrameCallback, which would cause a lot of
+/// I/O waiting.
+fn build_add_font_resource_updates(
+    app_resources: &AppResources,
+    fonts_in_dom: &FastHashMap<ImmediateFontId, FastHashSet<FontInstanceSpec>>,
+    load_errors: &mut Vec<(ResourceWarningId, ResourceLoadError)>,
+) -> Vec<(ImmediateFontId, AddFontMsg)> {
+
+    use webrender::api::{
+        FontInstancePlatformOptions, FontInstanceOptions, FontRenderMode as WrFontRenderMode,
+        FontInstanceFlags, FontVariation,
+    };
+
+    let mut resource_updates = Vec::new();
+
+    for (im_font_id, instance_specs) in fonts_in_dom {
+
+        macro_rules! insert_font_instances {($font_id:expr, $font_key:expr, $font_index:expr, $instance_spec:expr, $is_color_font:expr) => ({
+
+            let instance_spec: &FontInstanceSpec = $instance_spec;
+
+            let font_instance_key_exists = app_resources.currently_registered_fonts
+                .get(&$font_id)
+                .and_then(|loaded_font| loaded_font.font_instances.get(instance_spec))
+                .is_some();
+
+            if !font_instance_key_exists {
+
+                let font_instance_key = app_resources.get_render_api().new_font_instance_key();
+
+                #[cfg(target_os = "windows")]
+                let platform_options = FontInstancePlatformOptions {
+                    gamma: app_resources.font_platform_options.gamma,
+                    contrast: app_resources.font_platform_options.contrast,
+                };
+
+                #[cfg(target_os = "linux")]
+                let platform_options = FontInstancePlatformOptions {
+                    lcd_filter: wr_translate_font_lcd_filter(app_resources.font_platform_options.lcd_filter),
+                    hinting: wr_translate_font_hinting(app_resources.font_platform_options.hinting),
+                };
+
+                #[cfg(target_os = "macos")]
+                let platform_options = FontInstancePlatformOptions::default();
+
+                let mut font_instance_flags = FontInstanceFlags::empty();
+
+                font_instance_flags.set(FontInstanceFlags::SUBPIXEL_BGR, false);
+                font_instance_flags.set(FontInstanceFlags::NO_AUTOHINT, true);
+                font_instance_flags.set(FontInstanceFlags::LCD_VERTICAL, false);
+
+                let subpixel_positioning = match &$font_id {
+                    ImmediateFontId::Resolved(font_id) => app_resources.get_font_subpixel_positioning(font_id),
+                    ImmediateFontId::Unresolved(_) => app_resources.subpixel_positioning,
+                };
+                font_instance_flags.set(FontInstanceFlags::SUBPIXEL_POSITION, subpixel_positioning);
+
+                let render_mode = match &$font_id {
+                    ImmediateFontId::Resolved(font_id) => app_resources.get_font_render_mode(font_id),
+                    ImmediateFontId::Unresolved(_) => app_resources.font_render_mode,
+                };
+
+                // Color glyphs can't be rendered with subpixel AA. Embedded bitmaps
+                // have to be enabled so that FreeType rasterizes CBDT bitmap strikes.
+                font_instance_flags.set(FontInstanceFlags::EMBEDDED_BITMAPS, $is_color_font);
+                let render_mode = match render_mode {
+                    FontRenderMode::Subpixel if $is_color_font => WrFontRenderMode::Alpha,
+                    FontRenderMode::Subpixel => WrFontRenderMode::Subpixel,
+                    FontRenderMode::Alpha => WrFontRenderMode::Alpha,
+                    FontRenderMode::Mono => WrFontRenderMode::Mono,
+                };
+
+                font_instance_flags.set(FontInstanceFlags::SYNTHETIC_BOLD, instance_spec.synthetic_bold);
+                font_instance_flags.set(FontInstanceFlags::SYNTHETIC_ITALICS, instance_spec.synthetic_italic);
+
+                let options = FontInstanceOptions {
+                    render_mode,
+                    flags: font_instance_flags,
+                    .. Default::default()
+                };
+
+                resource_updates.push(($font_id, AddFontMsg::Instance(AddFontInstance {
+                    key: font_instance_key,
+                    font_key: $font_key,
+                    glyph_size: instance_spec.size,
+                    options: Some(options),
+                    platform_options: Some(platform_options),
+                    variations: instance_spec.variations.iter().map(|(tag, value)| FontVariation { tag: *tag, value: *value }).collect(),
+                }, instance_spec.clone())));
+            }
+        })}
+
+        match app_resources.currently_registered_fonts.get(im_font_id) {
+            Some(loaded_font) => {
+                let is_color_font = app_resources.get_loaded_font_bytes(im_font_id)
+                    .map(|(font_bytes, font_index)| font_has_color_glyphs(&font_bytes, font_index))
+                    .unwrap_or(false);
+                for instance_spec in instance_specs.iter() {
+                    insert_font_instances!(im_font_id.clone(), loaded_font.font_key, loaded_font.font_index, instance_spec, is_color_font);
+                }
+            },
+            None => {
+                use self::ImmediateFontId::*;
+
+                // If there is no font key, that means there's also no font instances
+                let font_source = match im_font_id {
+                    Resolved(font_id) => {
+                        match app_resources.font_sources.get(font_id) {
+                            Some(s) => s.clone(),
+                            None => continue,
+                        }
+                    },
+                    Unresolved(css_font_id) => FontSource::System(css_font_id.clone()),
+                };
+
+                let (font_bytes, font_index) = match font_source.get_bytes() {
+                    Ok(o) => o,
+                    Err(e) => {
+                        #[cfg(feature = "logging")] {
+                            warn!("Could not load font with ID: {:?} - error: {}", im_font_id, e);
+                        }
+                        let id = match im_font_id {
+                            Resolved(font_id) => ResourceWarningId::Font(*font_id),
+                            Unresolved(css_font_id) => ResourceWarningId::SystemFont(css_font_id.clone()),
+                        };
+                        load_errors.push((id, e.into()));
+                        continue;
+                    }
+                };
+
+                if !instance_specs.is_empty() {
+                    // Same bytes as an already uploaded font, reuse its FontKey instead of uploading the font again
+                    let content_hash = font_content_hash(&font_bytes, font_index);
+                    let font_key = match app_resources.find_shared_font_key(content_hash, &font_bytes, font_index) {
+                        Some(shared_key) => shared_key,
+                        None => app_resources.get_render_api().new_font_key(),
+                    };
+                    let is_color_font = font_has_color_glyphs(&font_bytes, font_index);
+
+                    resource_updates.push((im_font_id.clone(), AddFontMsg::Font(LoadedFont::new(font_key, font_bytes, font_index), content_hash)));
+
+                    for instance_spec in instance_specs {
+                        insert_font_instances!(im_font_id.clone(), font_key, font_index, instance_spec, is_color_font);
+                    }
+                }
+            }
+        }
+    }
+
+    resource_updates
+}
+
+#[cfg(target_os = "linux")]
+fn wr_translate_font_lcd_filter(input: FontLcdFilter) -> ::webrender::api::FontLCDFilter {
+    use webrender::api::FontLCDFilter;
+    match input {
+        FontLcdFilter::None => FontLCDFilter::None,
+        FontLcdFilter::Default => FontLCDFilter::Default,
+        FontLcdFilter::Light => FontLCDFilter::Light,
+        FontLcdFilter::Legacy => FontLCDFilter::Legacy,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn wr_translate_font_hinting(input: FontHinting) -> ::webrender::api::FontHinting {
+    use webrender::api::FontHinting as WrFontHinting;
+    match input {
+        FontHinting::None => WrFontHinting::None,
+        FontHinting::Mono => WrFontHinting::Mono,
+        FontHinting::Light => WrFontHinting::Light,
+        FontHinting::Normal => WrFontHinting::Normal,
+        FontHinting::Lcd => WrFontHinting::LCD,
+    }
+}
+
+/// Given the images of the current frame, returns `AddImage`s of
+/// which image keys are currently not in the `current_registered_fonts` and
+/// need to be added. Modifies `last_frame_image_keys` to contain the added image keys
+///
+/// Deleting images can only be done after the entire frame has finished drawing,
+/// otherwise (if removing images would happen after every DOM) we'd constantly
+/// add-and-remove images after every IFrameCallback, which would cause a lot of
+/// I/O waiting.
+///
+/// If `AppConfig::max_image_uploads_per_frame` is set, only the remaining uploads of the
+/// current frame are returned (oldest `ImageId` first), the other images are treated as not
+/// loaded yet and get uploaded in one of the next frames.
+fn build_add_image_resource_updates(
+    app_resources: &mut AppResources,
+    images_in_dom: &FastHashSet<ImageId>,
+    load_errors: &mut Vec<(ResourceWarningId, ResourceLoadError)>,
+) -> Vec<(ImageId, AddImageMsg)> {
+
+    let mut new_images = images_in_dom.iter()
+        .filter(|image_id| !app_resources.currently_registered_images.contains_key(*image_id))
+        .cloned()
+        .collect::<Vec<ImageId>>();
+
+    if let Some(max_uploads) = app_resources.max_image_uploads_per_frame {
+        let remaining_uploads = max_uploads.saturating_sub(app_resources.current_frame_stats.image_uploads);
+        if new_images.len() > remaining_uploads {
+            new_images.sort();
+            app_resources.current_frame_stats.deferred_image_uploads += new_images.len() - remaining_uploads;
+            new_images.truncate(remaining_uploads);
+        }
+    }
+
+    let mut resource_updates = Vec::new();
+
+    for image_id in new_images.iter() {
+        let (mut alpha_type, callback) = match app_resources.image_sources.get(image_id) {
+            Some(ImageSource::Callback(callback)) => (AlphaType::Alpha, Some(callback.clone())),
+            Some(image_source) => (image_source.get_alpha_type(), None),
+            None => continue,
+        };
+        let decoded = if let Some(callback) = callback {
+            // The produced image decides whether the pixels are premultiplied
+            match (callback.0)() {
+                Some(raw_image) => {
+                    alpha_type = raw_image.alpha_type();
+                    Some(ImageSource::Raw(raw_image).get_bytes())
+                },
+                None => Some(Err(ImageReloadError::NoCallbackImage)),
+            }
+        } else if app_resources.decodes_in_background(image_id) {
+            // Not drawn until it is decoded, like the images postponed by `max_image_uploads_per_frame`
+            match app_resources.take_background_decoded_image(image_id) {
+                Some(decoded) => Some(decoded),
+                None => continue,
+            }
+        } else {
+            app_resources.get_cached_image_bytes(image_id)
+        };
+        let (data, mut descriptor) = match decoded {
+            Some(Ok(o)) => o,
+            Some(Err(e)) => {
+                #[cfg(feature = "logging")] {
+                    warn!("Could not load image with ID: {:?} - error: {}", image_id, e);
+                }
+                load_errors.push((ResourceWarningId::Image(*image_id), e.into()));
+                continue;
+            },
+            None => continue,
+        };
+        descriptor.allow_mipmaps &= app_resources.get_image_mipmaps(image_id);
+
+        let key = app_resources.get_render_api().new_image_key();
+        let add_image = AddImage { key, data, descriptor, tiling: None };
+        let nine_patch_insets = app_resources.get_nine_patch_insets(image_id);
+        resource_updates.push((*image_id, AddImageMsg(add_image, ImageInfo { key, descriptor, alpha_type, nine_patch_insets })));
+    }
+
+    resource_updates
+}
+
+/// LRU cache of decoded images, see `AppConfig::decoded_image_cache_bytes`
+#[derive(Debug)]
+struct DecodedImageCache {
+    /// Maximum size of all cached pixels in bytes
+    max_bytes: usize,
+    /// Size of all currently cached pixels in bytes
+    current_bytes: usize,
+    /// Decoded images with their size in bytes and the value of `access_counter` at their last use
+    images: FastHashMap<ImageId, (ImageData, ImageDescriptor, usize, u64)>,
+    /// Incremented on every access, the image with the lowest value is evicted first
+    access_counter: u64,
+}
+
+impl DecodedImageCache {
+
+    fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            current_bytes: 0,
+            images: FastHashMap::default(),
+            access_counter: 0,
+        }
+    }
+
+    fn get(&mut self, image_id: &ImageId) -> Option<(ImageData, ImageDescriptor)> {
+        self.access_counter += 1;
+        let access_counter = self.access_counter;
+        let (data, descriptor, _, last_access) = self.images.get_mut(image_id)?;
+        *last_access = access_counter;
+        Some((data.clone(), *descriptor))
+    }
+
+    /// Caches the image, evicting the least recently used images until it fits into the budget.
+    /// Images that are larger than the whole budget (or aren't raw pixels) aren't cached.
+    fn insert(&mut self, image_id: ImageId, data: &ImageData, descriptor: ImageDescriptor) {
+
+        let size = match data {
+            ImageData::Raw(bytes) => bytes.len(),
+            _ => return,
+        };
+
+        if size > self.max_bytes {
+            return;
+        }
+
+        self.remove(&image_id);
+
+        while self.current_bytes + size > self.max_bytes {
+            let least_recently_used = self.images.iter()
+                .min_by_key(|(_, (_, _, _, last_access))| *last_access)
+                .map(|(image_id, _)| *image_id);
+            match least_recently_used {
+                Some(lru_image_id) => self.remove(&lru_image_id),
+                None => break,
+            }
+        }
+
+        self.access_counter += 1;
+        self.current_bytes += size;
+        self.images.insert(image_id, (data.clone(), descriptor, size, self.access_counter));
+    }
+
+    fn remove(&mut self, image_id: &ImageId) {
+        if let Some((_, _, size, _)) = self.images.remove(image_id) {
+            self.current_bytes -= size;
+        }
+    }
+
+    fn contains(&self, image_id: &ImageId) -> bool {
+        self.images.contains_key(image_id)
+    }
+}
+
+/// How often the modification times of the watched files are checked, see `AppResources::enable_file_watching`
+const FILE_WATCH_INTERVAL_MS: u64 = 500;
+
+/// Maximum number of warnings that are stored until they are drained, see `AppResources::take_resource_warnings`
+const MAX_RESOURCE_WARNINGS: usize = 256;
+
+/// Last known modification times of the image and font files, see `AppResources::enable_file_watching`
+#[derive(Debug, Default)]
+struct FileWatcher {
+    /// When the modification times were last checked
+    last_check: Option<Instant>,
+    /// Modification time of each file at the last check
+    modification_times: FastHashMap<PathBuf, SystemTime>,
+    /// Files that changed at the last check. Editors often write a file in several steps,
+    /// so a file is only reloaded once it stayed the same for one more check.
+    changed_files: FastHashSet<PathBuf>,
+}
+
+impl FileWatcher {
+
+    /// Updates the modification times of the watched files (`None` if the file can't be read),
+    /// returns the files that changed and are done being written to
+    fn update(&mut self, modification_times: Vec<(PathBuf, Option<SystemTime>)>) -> FastHashSet<PathBuf> {
+
+        let mut finished_files = FastHashSet::default();
+        let mut new_modification_times = FastHashMap::default();
+
+        for (file_path, modification_time) in modification_times {
+
+            let previous_time = self.modification_times.get(&file_path).cloned();
+
+            match (previous_time, modification_time) {
+                (Some(previous), Some(current)) if previous != current => {
+                    self.changed_files.insert(file_path.clone());
+                },
+                (Some(_), Some(_)) => {
+                    if self.changed_files.remove(&file_path) {
+                        finished_files.insert(file_path.clone());
+                    }
+                },
+                // Files that are seen for the first time are already loaded
+                _ => { },
+            }
+
+            // A file that is temporarily missing (while it is replaced) keeps its last known time
+            if let Some(time) = modification_time.or(previous_time) {
+                new_modification_times.insert(file_path, time);
+            }
+        }
+
+        // Forget the files of deleted images and fonts
+        self.changed_files.retain(|file_path| new_modification_times.contains_key(file_path));
+        self.modification_times = new_modification_times;
+        finished_files
+    }
+}
+
+/// Settings of the `AppConfig` that change how images are decoded. Stored per `AppResources`
+/// (and copied to the threads of the `ImageDecodePool`), the defaults match the default `AppConfig`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct ImageDecodeOptions {
+    /// See `AppConfig::keep_rgba8_images`
+    pub(crate) keep_rgba8_images: bool,
+    /// See `AppConfig::max_image_pixels`
+    pub(crate) max_image_pixels: Option<usize>,
+    /// See `AppConfig::premultiply_in_linear_light`
+    pub(crate) premultiply_in_linear_light: bool,
+}
+
+impl ImageDecodeOptions {
+    fn new(app_config: &AppConfig) -> Self {
+        Self {
+            keep_rgba8_images: app_config.keep_rgba8_images,
+            max_image_pixels: app_config.max_image_pixels,
+            premultiply_in_linear_light: app_config.premultiply_in_linear_light,
+        }
+    }
+}
+
+/// Result of decoding an image on one of the threads of the `ImageDecodePool`
+type DecodeResult = (ImageId, u64, Result<(ImageData, ImageDescriptor), ImageReloadError>);
+
+/// Threads that decode images in the background, see `AppConfig::image_decode_threads`
+struct ImageDecodePool {
+    /// Sends the image to decode (with the number of the decode job and the maximum size of the image) to the threads
+    job_sender: Sender<(ImageId, u64, ImageSource, Option<(u32, u32)>)>,
+    /// Receives the decoded images from the threads
+    result_receiver: Receiver<DecodeResult>,
+    /// Images that are currently being decoded and the number of their decode job. Results
+    /// of jobs that were cancelled (the image source changed in the meantime) are discarded.
+    pending: FastHashMap<ImageId, u64>,
+    /// Decoded images that are uploaded in the next frame
+    decoded: FastHashMap<ImageId, Result<(ImageData, ImageDescriptor), ImageReloadError>>,
+    /// Number of the last submitted decode job
+    last_job: u64,
+}
+
+impl ImageDecodePool {
+
+    fn new(thread_count: usize, options: ImageDecodeOptions) -> Self {
+
+        let (job_sender, job_receiver) = mpsc::channel::<(ImageId, u64, ImageSource, Option<(u32, u32)>)>();
+        let (result_sender, result_receiver) = mpsc::channel::<DecodeResult>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        for _ in 0..thread_count.max(1) {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            thread::spawn(move || loop {
+                // The lock is only held while waiting for the next job, not while decoding
+                let job = job_receiver.lock().ok().and_then(|receiver| receiver.recv().ok());
+                // The channel is closed when the AppResources are dropped
+                let (image_id, job, image_source, max_dimensions) = match job {
+                    Some(s) => s,
+                    None => return,
+                };
+                if result_sender.send((image_id, job, image_source.get_bytes_with_options(max_dimensions, options))).is_err() {
+                    return;
+                }
+            });
+        }
+
+        Self {
+            job_sender,
+            result_receiver,
+            pending: FastHashMap::default(),
+            decoded: FastHashMap::default(),
+            last_job: 0,
+        }
+    }
+
+    fn submit(&mut self, image_id: ImageId, image_source: ImageSource, max_dimensions: Option<(u32, u32)>) {
+        self.last_job += 1;
+        self.pending.insert(image_id, self.last_job);
+        // Can only fail if all threads panicked, the image then stays pending
+        let _ = self.job_sender.send((image_id, self.last_job, image_source, max_dimensions));
+    }
+
+    fn cancel(&mut self, image_id: &ImageId) {
+        self.pending.remove(image_id);
+        self.decoded.remove(image_id);
+    }
+
+    /// Moves the images that finished decoding into `decoded`, returns whether any image finished
+    fn poll(&mut self) -> bool {
+        let mut any_finished = false;
+        while let Ok((image_id, job, decoded)) = self.result_receiver.try_recv() {
+            if self.pending.get(&image_id) == Some(&job) {
+                self.pending.remove(&image_id);
+                self.decoded.insert(image_id, decoded);
+                any_finished = true;
+            }
+        }
+        any_finished
+    }
+}
+
+/// Submits the `AddFont`, `AddFontInstance` and `AddImage` resources to the RenderApi.
+/// Extends `currently_registered_images` and `currently_registered_fonts` by the
+/// `last_frame_image_keys` and `last_frame_font_keys`, so that we don't lose track of
+/// what font and image keys are currently in the API.
+fn add_resources(
+    app_resources: &mut AppResources,
+    add_font_resources: Vec<(ImmediateFontId, AddFontMsg)>,
+    mut add_image_resources: Vec<(ImageId, AddImageMsg)>,
+) {
+    let mut merged_resource_updates = Vec::new();
+
+    // Font instances can only be added if their font is already registered or added in the same
+    // batch - i.e. if the font failed to load, its instances have to be skipped
+    let added_fonts = add_font_resources.iter().filter_map(|(font_id, add_font_msg)| match add_font_msg {
+        AddFontMsg::Font(..) => Some(font_id.clone()),
+        AddFontMsg::Instance(..) => None,
+    }).collect::<FastHashSet<_>>();
+
+    let add_font_resources = add_font_resources.into_iter().filter(|(font_id, add_font_msg)| match add_font_msg {
+        AddFontMsg::Instance(..) if !added_fonts.contains(font_id) && !is_font_registered(app_resources, font_id) => {
+            #[cfg(feature = "logging")] {
+                warn!("Skipping font instance of font {:?}: the font is not registered", font_id);
+            }
+            false
+        },
+        _ => true,
+    }).collect::<Vec<_>>();
+
+    for (_, add_font_msg) in add_font_resources.iter() {
+        if let AddFontMsg::Font(loaded_font, content_hash) = add_font_msg {
+            if app_resources.share_font_key(*content_hash, loaded_font.font_key) {
+                continue;
+            }
+        }
+        merged_resource_updates.push(add_font_msg.into_resource_update());
+    }
+
+    for (image_id, add_image_msg) in add_image_resources.iter_mut() {
+        match app_resources.share_image_key(*image_id, &add_image_msg.0) {
+            // Same pixels as an already uploaded image, reuse its ImageKey instead of uploading them again
+            Some(shared_key) => add_image_msg.1.key = shared_key,
+            None => merged_resource_updates.push(add_image_msg.into_resource_update()),
+        }
+    }
+
+    if !merged_resource_updates.is_empty() {
+        app_resources.queue_resource_updates(merged_resource_updates);
+        // The AddFont / AddImage updates get flushed once per frame,
+        // see `flush_pending_resource_updates`
+        if app_resources.scene_builder_flush_pending {
+            app_resources.current_frame_stats.elided_scene_builder_flushes += 1;
+        } else {
+            app_resources.scene_builder_flush_pending = true;
+        }
+    }
+
+    for (image_id, add_image_msg) in add_image_resources.iter() {
+        app_resources.currently_registered_images.insert(*image_id, add_image_msg.1);
+        if let Some(ImageSource::Callback(_)) = app_resources.image_sources.get(image_id) {
+            let current_frame = app_resources.current_frame;
+            app_resources.callback_image_frames.insert(*image_id, current_frame);
+        }
+        if let Some(animation) = app_resources.animated_images.get_mut(image_id) {
+            animation.frame_keys[animation.current_frame] = Some(add_image_msg.1.key);
+        }
+    }
+
+    for (font_id, add_font_msg) in add_font_resources {
+        use self::AddFontMsg::*;
+        let font_id = resolve_system_font_id(app_resources, font_id);
+        match add_font_msg {
+            Font(f, content_hash) => {
+                // Identical fonts that were loaded in the same frame got separate keys, only the first one is shared
+                if app_resources.shared_font_keys.get(&content_hash).map(|(shared_key, _)| *shared_key) == Some(f.font_key) {
+                    app_resources.font_content_hashes.insert(font_id.clone(), content_hash);
+                }
+                app_resources.currently_registered_fonts.insert(font_id, LoadedFont::new(f.font_key, f.font_bytes, f.font_index));
+            },
+            Instance(fi, instance_spec) => {
+                if let Some(loaded_font) = app_resources.currently_registered_fonts.get_mut(&font_id) {
+                    loaded_font.font_instances.insert(instance_spec, fi.key);
+                }
+            },
+        }
+    }
+}
+
+/// Returns whether the font is registered in the RenderApi, either under its own ID or
+/// (for system fonts requested by their CSS name) under the `FontId` it was resolved to
+fn is_font_registered(app_resources: &AppResources, font_id: &ImmediateFontId) -> bool {
+    if app_resources.currently_registered_fonts.contains_key(font_id) {
+        return true;
+    }
+    match font_id {
+        ImmediateFontId::Unresolved(css_font_id) => app_resources.css_ids_to_font_ids.get(css_font_id)
+            .map(|font_id| app_resources.currently_registered_fonts.contains_key(&ImmediateFontId::Resolved(*font_id)))
+            .unwrap_or(false),
+        ImmediateFontId::Resolved(_) => false,
+    }
+}
+
+/// Once a system font that was requested by its CSS name (`ImmediateFontId::Unresolved`) is loaded,
+/// it is registered under a new `FontId` (mapped to the CSS name), so that the following
+/// frames find the font via the resolved ID and share the already uploaded `FontKey`.
+fn resolve_system_font_id(app_resources: &mut AppResources, font_id: ImmediateFontId) -> ImmediateFontId {
+
+    use self::ImmediateFontId::*;
+
+    let css_font_id = match font_id {
+        Unresolved(css_font_id) => css_font_id,
+        resolved => return resolved,
+    };
+
+    if let Some(font_id) = app_resources.css_ids_to_font_ids.get(&css_font_id) {
+        return Resolved(*font_id);
+    }
+
+    let font_id = app_resources.new_font_id();
+    app_resources.css_ids_to_font_ids.insert(css_font_id.clone(), font_id);
+    app_resources.font_sources.insert(font_id, FontSource::System(css_font_id.clone()));
+
+    // Carry over the GC information that was recorded under the unresolved ID
+    let unresolved_id = Unresolved(css_font_id);
+    if let Some(instance_specs) = app_resources.last_frame_font_keys.remove(&unresolved_id) {
+        app_resources.last_frame_font_keys.insert(Resolved(font_id), instance_specs);
+    }
+    if let Some(frame) = app_resources.font_last_used_frame.remove(&unresolved_id) {
+        app_resources.font_last_used_frame.insert(Resolved(font_id), frame);
+    }
+    if let Some(instance_frames) = app_resources.font_instance_last_used_frame.remove(&unresolved_id) {
+        app_resources.font_instance_last_used_frame.insert(Resolved(font_id), instance_frames);
+    }
+
+    Resolved(font_id)
+}
+
+fn is_font_pinned(app_resources: &AppResources, font_id: &ImmediateFontId) -> bool {
+    match font_id {
+        ImmediateFontId::Resolved(font_id) => app_resources.pinned_fonts.contains_key(font_id),
+        ImmediateFontId::Unresolved(_) => false,
+    }
+}
+
+fn build_delete_font_resource_updates(
+    app_resources: &AppResources
+) -> Vec<(ImmediateFontId, DeleteFontMsg)> {
+
+    let mut resource_updates = Vec::new();
+
+    // Delete fonts that were not used in the last frame(s) (depending on the GcPolicy) or have zero font instances
+    for (font_id, loaded_font) in app_resources.currently_registered_fonts.iter() {
+        if is_font_pinned(app_resources, font_id) {
+            continue;
+        }
+        let used_instances = app_resources.last_frame_font_keys.get(font_id);
+        let instance_frames = app_resources.font_instance_last_used_frame.get(font_id);
+        let delete_font = app_resources.gc_should_delete(
+            used_instances.is_some(),
+            app_resources.font_last_used_frame.get(font_id).cloned(),
+        );
+        let delete_instances = loaded_font.font_instances.iter()
+            .filter(|(instance_spec, _)| delete_font || app_resources.gc_should_delete(
+                used_instances.map(|used| used.contains(*instance_spec)).unwrap_or(false),
+                instance_frames.and_then(|frames| frames.get(*instance_spec).cloned()),
+            ))
+            .map(|(instance_spec, font_instance_key)| (font_id.clone(), DeleteFontMsg::Instance(*font_instance_key, instance_spec.clone())))
+            .collect::<Vec<_>>();
+        let no_instances_left = delete_instances.len() == loaded_font.font_instances.len();
+        resource_updates.extend(delete_instances);
+        if delete_font || no_instances_left {
+            // Delete the font and all instances if there are no more instances of the font
+            resource_updates.push((font_id.clone(), DeleteFontMsg::Font(loaded_font.font_key)));
+        }
+    }
+
+    resource_updates
+}
+
+/// At the end of the frame, all images that are registered, but weren't used in the last frame
+/// (or the last frames, depending on the `GcPolicy`) and aren't pinned
+fn build_delete_image_resource_updates(
+    app_resources: &AppResources
+) -> Vec<(ImageId, DeleteImageMsg)> {
+    app_resources.currently_registered_images.iter()
+    .filter(|(id, _info)| !app_resources.pinned_images.contains_key(id) && app_resources.gc_should_delete(
+        app_resources.last_frame_image_keys.contains(id),
+        app_resources.image_last_used_frame.get(id).cloned(),
+    ))
+    .map(|(id, info)| (*id, DeleteImageMsg(info.key, *info)))
+    .collect()
+}
+
+fn delete_resources(
+    app_resources: &mut AppResources,
+    delete_font_resources: Vec<(ImmediateFontId, DeleteFontMsg)>,
+    delete_image_resources: Vec<(ImageId, DeleteImageMsg)>,
+) {
+    let mut merged_resource_updates = Vec::new();
+
+    for (font_id, delete_font_msg) in delete_font_resources.iter() {
+        // Fonts with the same bytes share one FontKey, delete it once the last font is gone
+        if let DeleteFontMsg::Font(_) = delete_font_msg {
+            if !app_resources.release_shared_font_key(font_id) {
+                continue;
+            }
+        }
+        merged_resource_updates.push(delete_font_msg.into_resource_update());
+    }
+
+    for (image_id, delete_image_msg) in delete_image_resources.iter() {
+        // Images with the same pixels share one ImageKey, delete it once the last image is gone
+        if app_resources.release_shared_image_key(image_id) {
+            merged_resource_updates.push(delete_image_msg.into_resource_update());
+        }
+        // The other uploaded frames of an animated image are deleted together with the displayed frame
+        if let Some(animation) = app_resources.animated_images.get_mut(image_id) {
+            let other_frame_keys = animation.frame_keys.iter_mut().filter_map(Option::take).filter(|key| *key != delete_image_msg.0);
+            merged_resource_updates.extend(other_frame_keys.map(ResourceUpdate::DeleteImage));
+        }
+    }
+
+    if !merged_resource_updates.is_empty() {
+        app_resources.queue_resource_updates(merged_resource_updates);
+    }
+
+    for (removed_id, _removed_info) in delete_image_resources {
+        app_resources.currently_registered_images.remove(&removed_id);
+        app_resources.image_last_used_frame.remove(&removed_id);
+        app_resources.callback_image_frames.remove(&removed_id);
+        app_resources.svg_intrinsic_sizes.remove(&removed_id);
+        app_resources.failed_svg_rasterizations.remove(&removed_id);
+    }
+
+    for (font_id, delete_font_msg) in delete_font_resources {
+        use self::DeleteFontMsg::*;
+        match delete_font_msg {
+            Font(_) => {
+                app_resources.currently_registered_fonts.remove(&font_id);
+                app_resources.font_last_used_frame.remove(&font_id);
+                app_resources.font_instance_last_used_frame.remove(&font_id);
+            },
+            Instance(font_instance_key, instance_spec) => {
+                app_resources.text_cache.evict_font_instance(&font_instance_key);
+                if let Some(loaded_font) = app_resources.currently_registered_fonts.get_mut(&font_id) {
+                    loaded_font.delete_font_instance(&instance_spec);
+                } else {
+                    // The font was deleted before its instances
+                    #[cfg(feature = "logging")] {
+                        warn!("Deleting font instance of font {:?}, but the font is not registered", font_id);
+                    }
+                }
+                if let Some(instance_frames) = app_resources.font_instance_last_used_frame.get_mut(&font_id) {
+                    instance_frames.remove(&instance_spec);
+                }
+            },
+        }
+    }
+}
+
+/// Decrements a pin count, removing the entry once it reaches zero
+fn unpin<K: ::std::hash::Hash + Eq>(pin_counts: &mut FastHashMap<K, usize>, key: K) {
+    let is_last_pin = match pin_counts.get_mut(&key) {
+        Some(count) => { *count -= 1; *count == 0 },
+        None => false,
+    };
+    if is_last_pin {
+        pin_counts.remove(&key);
+    }
+}
+
+fn sorted_keys<K: Clone + Ord, V>(map: &FastHashMap<K, V>) -> Vec<K> {
+    let mut keys = map.keys().cloned().collect::<Vec<K>>();
+    keys.sort();
+    keys
+}
+
+/// 64-bit FNV-1a hash, stable across application runs (unlike the hasher of the `FastHashMap`)
+fn fnv1a_hash<'a, I: Iterator<Item = &'a u8>>(bytes: I) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+    bytes.fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Hashes the bytes and the index (in case of a font collection) of a font,
+/// used to detect fonts with identical content
+fn font_content_hash(font_bytes: &[u8], font_index: i32) -> u64 {
+    let index_bytes = [(font_index >> 24) as u8, (font_index >> 16) as u8, (font_index >> 8) as u8, font_index as u8];
+    fnv1a_hash(index_bytes.iter().chain(font_bytes.iter()))
+}
+
+/// Hashes the pixels, size and format of an image, used to detect images with identical
+/// content. Returns `None` for external / blob images, which have no pixels to hash.
+fn image_content_hash(data: &ImageData, descriptor: &ImageDescriptor) -> Option<ImageContentHash> {
+
+    use std::hash::Hasher;
+    use std::collections::hash_map::DefaultHasher;
+
+    let pixels = match data {
+        ImageData::Raw(pixels) => pixels,
+        _ => return None,
+    };
+
+    let mut header = Vec::with_capacity(16);
+    for value in [descriptor.size.width, descriptor.size.height, descriptor.format as u32, descriptor.stride.unwrap_or(0) as u32].iter() {
+        header.extend_from_slice(&[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, *value as u8]);
+    }
+
+    let mut sip_hasher = DefaultHasher::new();
+    sip_hasher.write(&header);
+    sip_hasher.write(pixels);
+
+    Some((fnv1a_hash(header.iter().chain(pixels.iter())), sip_hasher.finish()))
+}
+
+#[cfg(feature = "image_loading")]
+fn decode_image_data(image_data: Vec<u8>, max_dimensions: Option<(u32, u32)>, options: ImageDecodeOptions) -> Result<(ImageData, ImageDescriptor), ImageReloadError> {
+    use image; // the crate
+
+    let image_format = image::guess_format(&image_data).map_err(ImageReloadError::DecodingError)?;
+
+    // Reject decompression bombs before the decoder allocates the pixel buffer
+    if let Some((width, height)) = read_image_header_dimensions(&image_data, image_format) {
+        check_image_size(width, height, options)?;
+    }
+
+    let decoded = image::load_from_memory_with_format(&image_data, image_format).map_err(ImageReloadError::DecodingError)?;
+    // Photos store the camera rotation in the EXIF metadata, which only JPEG files carry
+    let decoded = match image_format {
+        image::ImageFormat::JPEG => match read_jpeg_exif_orientation(&image_data) {
+            Some(orientation) => apply_exif_orientation(decoded, orientation),
+            None => decoded,
+        },
+        _ => decoded,
+    };
+    // Headers that couldn't be read (i.e. PAM files) are checked after decoding, before the image is copied again
+    let (width, height) = decoded.dimensions();
+    check_image_size(width, height, options)?;
+    let decoded = downscale_image(decoded, max_dimensions);
+    #[cfg(feature = "icc")]
+    let decoded = apply_embedded_icc_profile(&image_data, image_format, decoded);
+    prepare_image(decoded, options).map_err(ImageReloadError::DecodingError)
+}
+
+/// Scales the image down to fit into `max_dimensions` (preserving the aspect ratio),
+/// images that already fit are returned unchanged, see `AppResources::set_image_max_dimensions`
+#[cfg(feature = "image_loading")]
+fn downscale_image(decoded: DynamicImage, max_dimensions: Option<(u32, u32)>) -> DynamicImage {
+
+    use image::FilterType;
+
+    let (max_width, max_height) = match max_dimensions {
+        Some((max_width, max_height)) => (max_width.max(1), max_height.max(1)),
+        None => return decoded,
+    };
+
+    let (width, height) = decoded.dimensions();
+    if width <= max_width && height <= max_height {
+        return decoded;
+    }
+
+    decoded.resize(max_width, max_height, FilterType::Lanczos3)
+}
+
+/// Decodes all frames of an animated GIF and the display duration of each frame.
+/// Other image formats are decoded as a single frame without a duration.
+#[cfg(feature = "image_loading")]
+fn decode_animated_image(image_data: Vec<u8>, options: ImageDecodeOptions) -> Result<Vec<(ImageData, ImageDescriptor, Duration)>, ImageReloadError> {
+
+    use std::io::Cursor;
+    use image::{self, ImageFormat, AnimationDecoder, gif::Decoder as GifDecoder};
+
+    let image_format = image::guess_format(&image_data).map_err(ImageReloadError::DecodingError)?;
+
+    if image_format != ImageFormat::GIF {
+        return decode_image_data(image_data, None, options).map(|(data, descriptor)| vec![(data, descriptor, Duration::from_secs(0))]);
+    }
+
+    if let Some((width, height)) = read_image_header_dimensions(&image_data, image_format) {
+        check_image_size(width, height, options)?;
+    }
+
+    let decoder = GifDecoder::new(Cursor::new(image_data)).map_err(ImageReloadError::DecodingError)?;
+    let frames = decoder.into_frames().map_err(ImageReloadError::DecodingError)?;
+
+    frames.into_iterator().map(|frame| {
+        // GIF delays are given in 1/100 s. Like web browsers, frames with a delay
+        // of 10 ms or less are shown for 100 ms, many GIFs rely on this.
+        let delay = u64::from(frame.delay().to_integer());
+        let duration = Duration::from_millis(if delay <= 1 { 100 } else { delay * 10 });
+        let (data, descriptor) = prepare_image(DynamicImage::ImageRgba8(frame.into_buffer()), options).map_err(ImageReloadError::DecodingError)?;
+        Ok((data, descriptor, duration))
+    }).collect()
+}
+
+/// Returns `ImageReloadError::TooLarge` if the image exceeds `AppConfig::max_image_pixels`
+fn check_image_size(width: u32, height: u32, options: ImageDecodeOptions) -> Result<(), ImageReloadError> {
+    match options.max_image_pixels {
+        Some(limit) if (width as usize).saturating_mul(height as usize) > limit => Err(ImageReloadError::TooLarge { width, height, limit }),
+        _ => Ok(()),
+    }
+}
+
+/// Reads the width and height from the header of an image file without decoding the image.
+/// Returns `None` if the header is truncated or invalid and for PAM files (the "P7" variant
+/// of PNM), these images are only checked against `AppConfig::max_image_pixels` after decoding.
+#[cfg(feature = "image_loading")]
+fn read_image_header_dimensions(data: &[u8], image_format: ::image::ImageFormat) -> Option<(u32, u32)> {
+
+    use image::ImageFormat;
+
+    fn read_u16_le(bytes: &[u8], offset: usize) -> Option<u16> {
+        let b = bytes.get(offset..offset + 2)?;
+        Some(u16::from(b[0]) | (u16::from(b[1]) << 8))
+    }
+
+    fn read_u24_le(bytes: &[u8], offset: usize) -> Option<u32> {
+        let b = bytes.get(offset..offset + 3)?;
+        Some(u32::from(b[0]) | (u32::from(b[1]) << 8) | (u32::from(b[2]) << 16))
+    }
+
+    fn read_i32_le(bytes: &[u8], offset: usize) -> Option<i32> {
+        let b = bytes.get(offset..offset + 4)?;
+        Some(i32::from(b[0]) | (i32::from(b[1]) << 8) | (i32::from(b[2]) << 16) | (i32::from(b[3]) << 24))
+    }
+
+    match image_format {
+        // IHDR chunk, directly after the 8-byte signature
+        ImageFormat::PNG => Some((read_u32_be(data, 16)?, read_u32_be(data, 20)?)),
+        // Logical screen descriptor
+        ImageFormat::GIF => Some((u32::from(read_u16_le(data, 6)?), u32::from(read_u16_le(data, 8)?))),
+        // BITMAPINFOHEADER, the height is negative for top-down bitmaps
+        ImageFormat::BMP => Some((read_i32_le(data, 18)?.checked_abs()? as u32, read_i32_le(data, 22)?.checked_abs()? as u32)),
+        ImageFormat::JPEG => {
+            let mut pos = 2;
+            while let (Some(&0xFF), Some(&marker)) = (data.get(pos), data.get(pos + 1)) {
+                match marker {
+                    // DHT, JPG and DAC share the marker range of the frame headers
+                    0xC4 | 0xC8 | 0xCC => { },
+                    // Start of frame
+                    0xC0..=0xCF => {
+                        let height = read_u16_be(data, pos + 5)?;
+                        let width = read_u16_be(data, pos + 7)?;
+                        return Some((u32::from(width), u32::from(height)));
+                    },
+                    // Start of scan / end of image without a frame header
+                    0xDA | 0xD9 => return None,
+                    _ => { },
+                }
+                pos += 2 + read_u16_be(data, pos + 2)? as usize;
+            }
+            None
+        },
+        ImageFormat::TIFF => read_tiff_dimensions(data),
+        // The first chunk after the RIFF header: VP8 (lossy), VP8L (lossless) or VP8X (extended)
+        ImageFormat::WEBP => match data.get(12..16)? {
+            b"VP8 " => Some((u32::from(read_u16_le(data, 26)? & 0x3FFF), u32::from(read_u16_le(data, 28)? & 0x3FFF))),
+            b"VP8L" => {
+                let size = read_i32_le(data, 21)? as u32;
+                Some(((size & 0x3FFF) + 1, ((size >> 14) & 0x3FFF) + 1))
+            },
+            b"VP8X" => Some((read_u24_le(data, 24)? + 1, read_u24_le(data, 27)? + 1)),
+            _ => None,
+        },
+        // The decoder picks one of the icons in the directory, so the largest one is checked. The
+        // sizes in the directory can differ from the embedded images, so their headers are read instead.
+        ImageFormat::ICO => {
+            let icon_count = read_u16_le(data, 4)? as usize;
+            (0..icon_count).filter_map(|icon| {
+                let image_offset = read_i32_le(data, 6 + icon * 16 + 12)? as u32 as usize;
+                let image = data.get(image_offset..)?;
+                match image.get(0..8) {
+                    Some(b"\x89PNG\r\n\x1a\n") => read_image_header_dimensions(image, ImageFormat::PNG),
+                    // BITMAPINFOHEADER without the file header, the height includes the AND mask
+                    _ => Some((read_i32_le(image, 4)?.checked_abs()? as u32, read_i32_le(image, 8)?.checked_abs()? as u32 / 2)),
+                }
+            }).max_by_key(|&(width, height)| u64::from(width) * u64::from(height))
+        },
+        // The text header ends with an empty line, followed by the resolution (i.e. "-Y 512 +X 768")
+        ImageFormat::HDR => {
+            let header_end = data.windows(2).position(|window| window == b"\n\n")? + 2;
+            let resolution = data[header_end..].split(|byte| *byte == b'\n').next()?;
+            let resolution = ::std::str::from_utf8(resolution).ok()?.split_whitespace().collect::<Vec<_>>();
+            let first_axis = resolution.get(1)?.parse().ok()?;
+            let second_axis = resolution.get(3)?.parse().ok()?;
+            if resolution[0].ends_with('Y') {
+                Some((second_axis, first_axis))
+            } else {
+                Some((first_axis, second_axis))
+            }
+        },
+        // The magic number ("P1" to "P6") is followed by the width and height,
+        // separated by whitespace and comments
+        ImageFormat::PNM => {
+            let mut dimensions: Vec<u32> = Vec::with_capacity(2);
+            let mut pos = 2;
+            while dimensions.len() < 2 {
+                match *data.get(pos)? {
+                    b'#' => while *data.get(pos)? != b'\n' { pos += 1; },
+                    byte if byte.is_ascii_whitespace() => pos += 1,
+                    _ => {
+                        let start = pos;
+                        while let Some(b'0'..=b'9') = data.get(pos) { pos += 1; }
+                        dimensions.push(::std::str::from_utf8(&data[start..pos]).ok()?.parse().ok()?);
+                    },
+                }
+            }
+            Some((dimensions[0], dimensions[1]))
+        },
+        _ => None,
+    }
+}
+
+/// Reads the orientation tag (1 - 8) from the EXIF metadata in the APP1 segment of a JPEG file.
+/// Returns `None` if the file has no EXIF metadata or no orientation tag.
+#[cfg(feature = "image_loading")]
+fn read_jpeg_exif_orientation(data: &[u8]) -> Option<u16> {
+
+    const EXIF_MARKER: &[u8] = b"Exif\0\0";
+    const ORIENTATION_TAG: u16 = 0x0112;
+
+    if data.get(0..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+
+    let mut pos = 2;
+    let exif = loop {
+        let marker = match (data.get(pos), data.get(pos + 1)) {
+            (Some(&0xFF), Some(&marker)) => marker,
+            _ => return None,
+        };
+        // Start of scan / end of image: no more metadata segments
+        if marker == 0xDA || marker == 0xD9 {
+            return None;
+        }
+        let length = read_u16_be(data, pos + 2)? as usize;
+        let segment = data.get(pos + 4..pos + 2 + length)?;
+        if marker == 0xE1 && segment.starts_with(EXIF_MARKER) {
+            break &segment[EXIF_MARKER.len()..];
+        }
+        pos += 2 + length;
+    };
+
+    // The EXIF data is a TIFF file, which is either little-endian ("II") or big-endian ("MM")
+    let little_endian = match exif.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+
+    let read_u16 = |offset: usize| -> Option<u16> {
+        let b = exif.get(offset..offset + 2)?;
+        Some(if little_endian { u16::from(b[0]) | (u16::from(b[1]) << 8) } else { (u16::from(b[0]) << 8) | u16::from(b[1]) })
+    };
+
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let (high, low) = if little_endian { (read_u16(offset + 2)?, read_u16(offset)?) } else { (read_u16(offset)?, read_u16(offset + 2)?) };
+        Some((u32::from(high) << 16) | u32::from(low))
+    };
+
+    // IFD0: number of entries, followed by 12-byte entries (tag, type, count, value)
+    let ifd_offset = read_u32(4)? as usize;
+    let entry_count = read_u16(ifd_offset)? as usize;
+
+    (0..entry_count)
+        .map(|entry| ifd_offset + 2 + entry * 12)
+        .find(|entry_offset| read_u16(*entry_offset) == Some(ORIENTATION_TAG))
+        .and_then(|entry_offset| read_u16(entry_offset + 8))
+        .filter(|orientation| *orientation >= 1 && *orientation <= 8)
+}
+
+/// Rotates / mirrors a decoded image so that it is displayed upright, according to its EXIF orientation
+#[cfg(feature = "image_loading")]
+fn apply_exif_orientation(decoded: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => decoded.fliph(),
+        3 => decoded.rotate180(),
+        4 => decoded.flipv(),
+        // Mirrored along the top-left to bottom-right diagonal
+        5 => decoded.rotate90().fliph(),
+        6 => decoded.rotate90(),
+        // Mirrored along the top-right to bottom-left diagonal
+        7 => decoded.rotate270().fliph(),
+        8 => decoded.rotate270(),
+        _ => decoded,
+    }
+}
+
+/// Converts the pixels of an RGB / RGBA image with an embedded ICC profile (i.e. Display P3
+/// or Adobe RGB) to sRGB. Images without a profile are assumed to be sRGB already.
+///
+/// Only matrix / TRC profiles (which covers the common RGB working spaces) embedded in JPEG
+/// files are supported. PNG files store the profile zlib-compressed, these are left unchanged.
+#[cfg(feature = "icc")]
+fn apply_embedded_icc_profile(image_data: &[u8], image_format: ::image::ImageFormat, decoded: DynamicImage) -> DynamicImage {
+
+    use image::{ImageFormat, DynamicImage::{ImageRgb8, ImageRgba8}};
+
+    let icc_profile = match image_format {
+        ImageFormat::JPEG => extract_jpeg_icc_profile(image_data),
+        _ => None,
+    };
+
+    let icc_profile = match icc_profile.as_ref().and_then(|p| IccRgbProfile::parse(p)) {
+        Some(s) => s,
+        None => return decoded,
+    };
+
+    match decoded {
+        ImageRgb8(mut buf) => { icc_profile.convert_to_srgb(&mut buf, 3); ImageRgb8(buf) },
+        ImageRgba8(mut buf) => { icc_profile.convert_to_srgb(&mut buf, 4); ImageRgba8(buf) },
+        other => other,
+    }
+}
+
+/// Concatenates the `ICC_PROFILE` APP2 segments of a JPEG file
+#[cfg(feature = "icc")]
+fn extract_jpeg_icc_profile(data: &[u8]) -> Option<Vec<u8>> {
+
+    const ICC_MARKER: &[u8] = b"ICC_PROFILE\0";
+
+    if data.get(0..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+
+    let mut chunks = Vec::new();
+    let mut pos = 2;
+
+    while let (Some(&0xFF), Some(&marker)) = (data.get(pos), data.get(pos + 1)) {
+        // Start of scan / end of image: no more metadata segments
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let length = u16::from(*data.get(pos + 2)?) << 8 | u16::from(*data.get(pos + 3)?);
+        let segment = data.get(pos + 4..pos + 2 + length as usize)?;
+        if marker == 0xE2 && segment.len() > ICC_MARKER.len() + 2 && segment.starts_with(ICC_MARKER) {
+            let sequence_number = segment[ICC_MARKER.len()];
+            chunks.push((sequence_number, &segment[ICC_MARKER.len() + 2..]));
+        }
+        pos += 2 + length as usize;
+    }
+
+    if chunks.is_empty() {
+        return None;
+    }
+
+    chunks.sort_by_key(|(sequence_number, _)| *sequence_number);
+    Some(chunks.into_iter().flat_map(|(_, chunk)| chunk.iter().cloned()).collect())
+}
+
+/// Tone response curve of a single channel of an ICC profile
+#[cfg(feature = "icc")]
+#[derive(Debug, Clone, PartialEq)]
+enum IccCurve {
+    Gamma(f32),
+    Table(Vec<u16>),
+    /// Parametric curve (`para` tag): function type and the parameters `g, a, b, c, d, e, f`
+    Parametric(u16, [f32;7]),
+}
+
+#[cfg(feature = "icc")]
+impl IccCurve {
+
+    /// Converts an encoded value (0.0 - 1.0) to linear light
+    fn to_linear(&self, x: f32) -> f32 {
+        match self {
+            IccCurve::Gamma(g) => x.powf(*g),
+            IccCurve::Table(table) => {
+                if table.len() < 2 {
+                    return x;
+                }
+                let pos = x * (table.len() - 1) as f32;
+                let idx = (pos as usize).min(table.len() - 2);
+                let t = pos - idx as f32;
+                (f32::from(table[idx]) * (1.0 - t) + f32::from(table[idx + 1]) * t) / 65535.0
+            },
+            IccCurve::Parametric(function_type, p) => {
+                let [g, a, b, c, d, e, f] = *p;
+                match *function_type {
+                    0 => x.powf(g),
+                    1 => if x >= -b / a { (a * x + b).powf(g) } else { 0.0 },
+                    2 => if x >= -b / a { (a * x + b).powf(g) + c } else { c },
+                    3 => if x >= d { (a * x + b).powf(g) } else { c * x },
+                    4 => if x >= d { (a * x + b).powf(g) + e } else { c * x + f },
+                    _ => x,
+                }
+            },
+        }
+    }
+}
+
+/// RGB matrix / TRC ICC profile: per-channel tone curves + the matrix from
+/// linear RGB to the (D50) XYZ profile connection space
+#[cfg(feature = "icc")]
+#[derive(Debug, Clone, PartialEq)]
+struct IccRgbProfile {
+    curves: [IccCurve;3],
+    /// Columns are the XYZ values of the red, green and blue primaries
+    rgb_to_xyz: [[f32;3];3],
+}
+
+#[cfg(feature = "icc")]
+impl IccRgbProfile {
+
+    /// Parses the `rXYZ` / `gXYZ` / `bXYZ` and `rTRC` / `gTRC` / `bTRC` tags of an RGB display profile.
+    /// Returns `None` for non-RGB profiles and LUT-based profiles.
+    fn parse(profile: &[u8]) -> Option<Self> {
+
+        fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+            let b = data.get(pos..pos + 4)?;
+            Some(u32::from(b[0]) << 24 | u32::from(b[1]) << 16 | u32::from(b[2]) << 8 | u32::from(b[3]))
+        }
+
+        fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+            let b = data.get(pos..pos + 2)?;
+            Some(u16::from(b[0]) << 8 | u16::from(b[1]))
+        }
+
+        fn read_s15_fixed16(data: &[u8], pos: usize) -> Option<f32> {
+            Some(read_u32(data, pos)? as i32 as f32 / 65536.0)
+        }
+
+        fn find_tag<'a>(profile: &'a [u8], signature: &[u8]) -> Option<&'a [u8]> {
+            let tag_count = read_u32(profile, 128)? as usize;
+            (0..tag_count).find_map(|i| {
+                let entry = 132 + i * 12;
+                if profile.get(entry..entry + 4)? != signature {
+                    return None;
+                }
+                let offset = read_u32(profile, entry + 4)? as usize;
+                let size = read_u32(profile, entry + 8)? as usize;
+                profile.get(offset..offset.checked_add(size)?)
+            })
+        }
+
+        fn read_xyz(profile: &[u8], signature: &[u8]) -> Option<[f32;3]> {
+            let tag = find_tag(profile, signature)?;
+            if tag.get(0..4)? != b"XYZ " {
+                return None;
+            }
+            Some([read_s15_fixed16(tag, 8)?, read_s15_fixed16(tag, 12)?, read_s15_fixed16(tag, 16)?])
+        }
+
+        fn read_curve(profile: &[u8], signature: &[u8]) -> Option<IccCurve> {
+            let tag = find_tag(profile, signature)?;
+            match tag.get(0..4)? {
+                b"curv" => {
+                    let count = read_u32(tag, 8)? as usize;
+                    match count {
+                        0 => Some(IccCurve::Gamma(1.0)),
+                        // u8Fixed8Number
+                        1 => Some(IccCurve::Gamma(f32::from(read_u16(tag, 12)?) / 256.0)),
+                        _ => Some(IccCurve::Table((0..count).map(|i| read_u16(tag, 12 + i * 2)).collect::<Option<Vec<u16>>>()?)),
+                    }
+                },
+                b"para" => {
+                    let function_type = read_u16(tag, 8)?;
+                    let param_count = match function_type { 0 => 1, 1 => 3, 2 => 4, 3 => 5, 4 => 7, _ => return None };
+                    let mut params = [0.0;7];
+                    for (i, param) in params.iter_mut().take(param_count).enumerate() {
+                        *param = read_s15_fixed16(tag, 12 + i * 4)?;
+                    }
+                    Some(IccCurve::Parametric(function_type, params))
+                },
+                _ => None,
+            }
+        }
+
+        if profile.get(16..20)? != b"RGB " {
+            return None;
+        }
+
+        let r = read_xyz(profile, b"rXYZ")?;
+        let g = read_xyz(profile, b"gXYZ")?;
+        let b = read_xyz(profile, b"bXYZ")?;
+
+        Some(Self {
+            curves: [read_curve(profile, b"rTRC")?, read_curve(profile, b"gTRC")?, read_curve(profile, b"bTRC")?],
+            rgb_to_xyz: [
+                [r[0], g[0], b[0]],
+                [r[1], g[1], b[1]],
+                [r[2], g[2], b[2]],
+            ],
+        })
+    }
+
+    /// Converts RGB(A) pixels (`channels` = 3 or 4 bytes per pixel, alpha is left unchanged)
+    /// from this profile to sRGB. Must be called before the pixels are premultiplied.
+    fn convert_to_srgb(&self, pixels: &mut [u8], channels: usize) {
+
+        // Bradford-adapted D50 XYZ -> linear sRGB
+        const XYZ_D50_TO_SRGB: [[f32;3];3] = [
+            [ 3.133_856, -1.616_867, -0.490_615],
+            [-0.978_768,  1.916_142,  0.033_454],
+            [ 0.071_945, -0.228_991,  1.405_243],
+        ];
+
+        let mut matrix = [[0.0_f32;3];3];
+        for (row, matrix_row) in matrix.iter_mut().enumerate() {
+            for (col, value) in matrix_row.iter_mut().enumerate() {
+                *value = (0..3).map(|i| XYZ_D50_TO_SRGB[row][i] * self.rgb_to_xyz[i][col]).sum();
+            }
+        }
+
+        let mut to_linear = [[0.0_f32;256];3];
+        for (curve, lut) in self.curves.iter().zip(to_linear.iter_mut()) {
+            for (i, value) in lut.iter_mut().enumerate() {
+                *value = curve.to_linear(i as f32 / 255.0);
+            }
+        }
+
+        const ENCODE_LUT_SIZE: usize = 4096;
+        let to_srgb = (0..ENCODE_LUT_SIZE).map(|i| {
+            let linear = i as f32 / (ENCODE_LUT_SIZE - 1) as f32;
+            let srgb = if linear <= 0.003_130_8 { linear * 12.92 } else { 1.055 * linear.powf(1.0 / 2.4) - 0.055 };
+            (srgb * 255.0).round() as u8
+        }).collect::<Vec<u8>>();
+
+        for pixel in pixels.chunks_mut(channels) {
+            let rgb = [to_linear[0][pixel[0] as usize], to_linear[1][pixel[1] as usize], to_linear[2][pixel[2] as usize]];
+            for (channel, matrix_row) in pixel.iter_mut().zip(matrix.iter()) {
+                let linear = matrix_row[0] * rgb[0] + matrix_row[1] * rgb[1] + matrix_row[2] * rgb[2];
+                // Colors outside of the sRGB gamut are clipped
+                let idx = (linear * (ENCODE_LUT_SIZE - 1) as f32).round().max(0.0) as usize;
+                *channel = to_srgb[idx.min(ENCODE_LUT_SIZE - 1)];
+            }
+        }
+    }
+}
+
+/// `ImageSource::Svg` images are rasterized again once their displayed size differs
+/// by more than this fraction from the size they were rasterized at
+#[cfg(feature = "svg_parsing")]
+const SVG_RERASTERIZE_THRESHOLD: f32 = 0.1;
+
+/// Rasterizes the filled and stroked paths of an SVG image into a BGRA8 image (straight alpha).
+/// Without a `target_size`, the image has the size of the SVG (its `width` and `height`),
+/// otherwise the viewBox is stretched to the target size.
+///
+/// Only a subset of SVG is supported: paths with solid fill and stroke colors and the transforms
+/// of paths and groups. Strokes always have round joins. Gradients and patterns are painted black,
+/// text, embedded images, clip paths, masks and filters are ignored (see `unsupported_svg_features`).
+#[cfg(feature = "svg_parsing")]
+fn rasterize_svg(svg: &str, target_size: Option<(u32, u32)>, options: ImageDecodeOptions) -> Result<RawImage, ImageReloadError> {
+
+    use usvg::{Tree, Options, NodeKind};
+
+    let tree = Tree::from_str(svg, &Options::default()).map_err(|e| ImageReloadError::InvalidSvg(format!("{:?}", e)))?;
+
+    // The paths are in the coordinate space of the viewBox, which is stretched to the image size
+    let svg_node = tree.svg_node();
+    let view_box = svg_node.view_box.rect;
+    let (origin_x, origin_y) = (view_box.x as f32, view_box.y as f32);
+    let (view_box_width, view_box_height) = ((view_box.width as f32).max(1.0), (view_box.height as f32).max(1.0));
+
+    let (width, height) = target_size.unwrap_or((
+        (svg_node.size.width as f32).ceil().max(1.0) as u32,
+        (svg_node.size.height as f32).ceil().max(1.0) as u32,
+    ));
+    check_image_size(width, height, options)?;
+    let (scale_x, scale_y) = (width as f32 / view_box_width, height as f32 / view_box_height);
+    let view_box_to_px = (scale_x, 0.0, 0.0, scale_y, -origin_x * scale_x, -origin_y * scale_y);
+
+    let mut pixels = vec![0_u8; width as usize * height as usize * 4];
+
+    for node in tree.root().descendants() {
+
+        // The paths of clip paths, masks and patterns aren't drawn directly
+        if node.ancestors().any(|ancestor| match *ancestor.borrow() { NodeKind::Defs => true, _ => false }) {
+            continue;
+        }
+
+        // Transform of the path, its parent groups and the viewBox
+        let transform = concat_svg_transforms(view_box_to_px, node.ancestors().fold(SVG_IDENTITY_TRANSFORM, |transform, ancestor| {
+            let ancestor = ancestor.borrow();
+            let t = match &*ancestor {
+                NodeKind::Path(p) => &p.transform,
+                NodeKind::Group(g) => &g.transform,
+                _ => return transform,
+            };
+            concat_svg_transforms((t.a as f32, t.b as f32, t.c as f32, t.d as f32, t.e as f32, t.f as f32), transform)
+        }));
+
+        let node = node.borrow();
+        let path = match &*node {
+            NodeKind::Path(p) => p,
+            _ => continue,
+        };
+
+        let contours = flatten_svg_path(&path.segments);
+        let to_px = |polygon: &[(f32, f32)]| polygon.iter().map(|point| apply_svg_transform(transform, *point)).collect::<Vec<_>>();
+
+        if let Some(fill) = &path.fill {
+            let edges = contours.iter().flat_map(|(points, _)| polygon_edges(&to_px(points))).collect::<Vec<_>>();
+            composite_svg_polygons(&mut pixels, width, height, &edges, svg_paint_color(&fill.paint), fill.opacity.value() as f32);
+        }
+
+        if let Some(stroke) = &path.stroke {
+            let outlines = stroke_svg_contours(&contours, stroke.width as f32 / 2.0, stroke.linecap);
+            let edges = outlines.iter().flat_map(|outline| polygon_edges(&to_px(outline))).collect::<Vec<_>>();
+            composite_svg_polygons(&mut pixels, width, height, &edges, svg_paint_color(&stroke.paint), stroke.opacity.value() as f32);
+        }
+    }
+
+    Ok(RawImage { pixels, image_dimensions: (width, height), data_format: RawImageFormat::BGRA8, alpha_premultiplied: false, allow_mipmaps: true })
+}
+
+/// Returns the features of an SVG image that `rasterize_svg` doesn't support, these are
+/// reported once when the image is added. Masks and filters aren't detected.
+#[cfg(feature = "svg_parsing")]
+fn unsupported_svg_features(svg: &str) -> Vec<&'static str> {
+
+    use usvg::{Tree, Options, NodeKind, Paint};
+
+    // Parsing errors are reported when the image is rasterized
+    let tree = match Tree::from_str(svg, &Options::default()) {
+        Ok(o) => o,
+        Err(_) => return Vec::new(),
+    };
+
+    let is_solid_color = |paint: &Paint| match paint {
+        Paint::Color(_) => true,
+        _ => false,
+    };
+
+    let mut features = Vec::new();
+
+    for node in tree.root().descendants() {
+        let feature = match &*node.borrow() {
+            NodeKind::Text(_) => "text",
+            NodeKind::Image(_) => "embedded images",
+            NodeKind::ClipPath(_) => "clip paths",
+            NodeKind::Path(p) if !p.fill.as_ref().map(|fill| is_solid_color(&fill.paint)).unwrap_or(true) ||
+                                 !p.stroke.as_ref().map(|stroke| is_solid_color(&stroke.paint)).unwrap_or(true) => "gradients and patterns",
+            _ => continue,
+        };
+        if !features.contains(&feature) {
+            features.push(feature);
+        }
+    }
+
+    features
+}
+
+/// Affine transform `(a, b, c, d, e, f)` of a SVG node, maps `(x, y)` to `(a * x + c * y + e, b * x + d * y + f)`
+#[cfg(feature = "svg_parsing")]
+type SvgTransform = (f32, f32, f32, f32, f32, f32);
+
+#[cfg(feature = "svg_parsing")]
+const SVG_IDENTITY_TRANSFORM: SvgTransform = (1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+
+/// Returns the transform that applies `inner` first and `outer` second
+#[cfg(feature = "svg_parsing")]
+fn concat_svg_transforms(outer: SvgTransform, inner: SvgTransform) -> SvgTransform {
+    let (a1, b1, c1, d1, e1, f1) = outer;
+    let (a2, b2, c2, d2, e2, f2) = inner;
+    (
+        a1 * a2 + c1 * b2,
+        b1 * a2 + d1 * b2,
+        a1 * c2 + c1 * d2,
+        b1 * c2 + d1 * d2,
+        a1 * e2 + c1 * f2 + e1,
+        b1 * e2 + d1 * f2 + f1,
+    )
+}
+
+#[cfg(feature = "svg_parsing")]
+fn apply_svg_transform(transform: SvgTransform, (x, y): (f32, f32)) -> (f32, f32) {
+    (transform.0 * x + transform.2 * y + transform.4, transform.1 * x + transform.3 * y + transform.5)
+}
+
+/// Flattens the segments of a SVG path into contours: the points of the contour
+/// and whether the contour is closed. Contours with less than two points are skipped.
+#[cfg(feature = "svg_parsing")]
+fn flatten_svg_path(segments: &[::usvg::PathSegment]) -> Vec<(Vec<(f32, f32)>, bool)> {
+
+    use usvg::PathSegment;
+
+    const CUBIC_CURVE_SEGMENTS: usize = 16;
+
+    let mut contours = Vec::new();
+    let mut points = Vec::<(f32, f32)>::new();
+
+    for segment in segments.iter() {
+        match *segment {
+            PathSegment::MoveTo { x, y } => {
+                if points.len() > 1 {
+                    contours.push((::std::mem::replace(&mut points, Vec::new()), false));
+                }
+                points.clear();
+                points.push((x as f32, y as f32));
+            },
+            PathSegment::LineTo { x, y } => {
+                points.push((x as f32, y as f32));
+            },
+            PathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                let current = points.last().cloned().unwrap_or((0.0, 0.0));
+                let (c1, c2, end) = ((x1 as f32, y1 as f32), (x2 as f32, y2 as f32), (x as f32, y as f32));
+                for step in 1..=CUBIC_CURVE_SEGMENTS {
+                    let t = step as f32 / CUBIC_CURVE_SEGMENTS as f32;
+                    let mt = 1.0 - t;
+                    points.push((
+                        mt * mt * mt * current.0 + 3.0 * mt * mt * t * c1.0 + 3.0 * mt * t * t * c2.0 + t * t * t * end.0,
+                        mt * mt * mt * current.1 + 3.0 * mt * mt * t * c1.1 + 3.0 * mt * t * t * c2.1 + t * t * t * end.1,
+                    ));
+                }
+            },
+            PathSegment::ClosePath => {
+                // The next contour starts at the start of the closed contour, unless it is moved
+                if let Some(start) = points.first().cloned() {
+                    if points.len() > 1 {
+                        contours.push((::std::mem::replace(&mut points, vec![start]), true));
+                    }
+                }
+            },
+        }
+    }
+
+    if points.len() > 1 {
+        contours.push((points, false));
+    }
+
+    contours
+}
+
+/// Returns the edges of a closed polygon
+#[cfg(feature = "svg_parsing")]
+fn polygon_edges(polygon: &[(f32, f32)]) -> Vec<PolygonEdge> {
+    polygon.iter().cloned().zip(polygon.iter().cloned().cycle().skip(1)).collect()
+}
+
+/// Outlines the stroke of the `contours` as polygons: a quad for each line segment, a circle at
+/// each joint (round joins) and the caps at the ends of open contours. All polygons have the same
+/// orientation, so that `rasterize_polygon_coverage` merges overlapping polygons.
+#[cfg(feature = "svg_parsing")]
+fn stroke_svg_contours(contours: &[(Vec<(f32, f32)>, bool)], half_width: f32, line_cap: ::usvg::LineCap) -> Vec<Vec<(f32, f32)>> {
+
+    use usvg::LineCap;
+    use std::f32::consts::PI;
+
+    const CIRCLE_SEGMENTS: usize = 16;
+
+    if half_width <= 0.0 {
+        return Vec::new();
+    }
+
+    let circle = |(x, y): (f32, f32)| (0..CIRCLE_SEGMENTS).map(|i| {
+        let angle = i as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * PI;
+        (x + half_width * angle.cos(), y + half_width * angle.sin())
+    }).collect::<Vec<_>>();
+
+    let quad = |start: (f32, f32), end: (f32, f32)| {
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length <= 0.0 {
+            return None;
+        }
+        let (nx, ny) = (-dy / length * half_width, dx / length * half_width);
+        Some(vec![(start.0 + nx, start.1 + ny), (end.0 + nx, end.1 + ny), (end.0 - nx, end.1 - ny), (start.0 - nx, start.1 - ny)])
+    };
+
+    // A square cap extends the end segment by half of the stroke width
+    let square_cap = |end: (f32, f32), previous: (f32, f32)| {
+        let (dx, dy) = (end.0 - previous.0, end.1 - previous.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length <= 0.0 {
+            return None;
+        }
+        quad(end, (end.0 + dx / length * half_width, end.1 + dy / length * half_width))
+    };
+
+    let mut polygons = Vec::new();
+
+    for (points, closed) in contours.iter() {
+
+        let segment_count = if *closed { points.len() } else { points.len() - 1 };
+        polygons.extend((0..segment_count).filter_map(|i| quad(points[i], points[(i + 1) % points.len()])));
+
+        let joints = if *closed { &points[..] } else { &points[1..points.len() - 1] };
+        polygons.extend(joints.iter().map(|point| circle(*point)));
+
+        if *closed {
+            continue;
+        }
+
+        let (first, last) = (points[0], points[points.len() - 1]);
+        match line_cap {
+            LineCap::Butt => { },
+            LineCap::Round => {
+                polygons.push(circle(first));
+                polygons.push(circle(last));
+            },
+            LineCap::Square => {
+                polygons.extend(square_cap(first, points[1]));
+                polygons.extend(square_cap(last, points[points.len() - 2]));
+            },
+        }
+    }
+
+    for polygon in polygons.iter_mut() {
+        let doubled_area = polygon_edges(polygon).iter().map(|(a, b)| a.0 * b.1 - b.0 * a.1).sum::<f32>();
+        if doubled_area < 0.0 {
+            polygon.reverse();
+        }
+    }
+
+    polygons
+}
+
+/// Returns the color of a solid SVG paint, gradients and patterns are painted black
+#[cfg(feature = "svg_parsing")]
+fn svg_paint_color(paint: &::usvg::Paint) -> (u8, u8, u8) {
+    match paint {
+        ::usvg::Paint::Color(c) => (c.red, c.green, c.blue),
+        _ => (0, 0, 0),
+    }
+}
+
+/// Composites the polygon (given as its edges, in pixels) with a solid color over
+/// the BGRA8 (straight alpha) `pixels` of an image
+#[cfg(feature = "svg_parsing")]
+fn composite_svg_polygons(pixels: &mut [u8], width: u32, height: u32, edges: &[PolygonEdge], (r, g, b): (u8, u8, u8), opacity: f32) {
+    for (pixel, coverage) in pixels.chunks_mut(4).zip(rasterize_polygon_coverage(edges, width, height)) {
+        let source_alpha = coverage.min(1.0) * opacity;
+        if source_alpha <= 0.0 {
+            continue;
+        }
+        // Source-over compositing of straight-alpha colors
+        let dest_alpha = f32::from(pixel[3]) / 255.0;
+        let out_alpha = source_alpha + dest_alpha * (1.0 - source_alpha);
+        let blend = |source: u8, dest: u8| {
+            ((f32::from(source) * source_alpha + f32::from(dest) * dest_alpha * (1.0 - source_alpha)) / out_alpha).round() as u8
+        };
+        pixel[0] = blend(b, pixel[0]);
+        pixel[1] = blend(g, pixel[1]);
+        pixel[2] = blend(r, pixel[2]);
+        pixel[3] = (out_alpha * 255.0).round() as u8;
+    }
+}
+
+/// Edge of a polygon from its start to its end point, in pixels
+#[cfg(feature = "svg")]
+type PolygonEdge = ((f32, f32), (f32, f32));
+
+/// Rasterizes closed polygons (non-zero winding rule) into one coverage value (0.0 - 1.0) per pixel.
+/// Anti-aliased with several samples per pixel row and the exact horizontal coverage of each span.
+#[cfg(feature = "svg")]
+fn rasterize_polygon_coverage(edges: &[PolygonEdge], width: u32, height: u32) -> Vec<f32> {
+
+    const SAMPLES_PER_ROW: usize = 4;
+
+    let (width, height) = (width as usize, height as usize);
+    let mut coverage = vec![0.0_f32; width * height];
+    let mut crossings = Vec::new();
+
+    for row in 0..height {
+
+        let row_coverage = &mut coverage[row * width..(row + 1) * width];
+
+        for sample in 0..SAMPLES_PER_ROW {
+
+            let y = row as f32 + (sample as f32 + 0.5) / SAMPLES_PER_ROW as f32;
+
+            crossings.clear();
+            for &((x0, y0), (x1, y1)) in edges {
+                if (y0 <= y && y < y1) || (y1 <= y && y < y0) {
+                    let x = x0 + (y - y0) / (y1 - y0) * (x1 - x0);
+                    crossings.push((x, if y1 > y0 { 1 } else { -1 }));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                if winding == 0 {
+                    continue;
+                }
+                let span_start = pair[0].0.max(0.0);
+                let span_end = pair[1].0.min(width as f32);
+                if span_end <= span_start {
+                    continue;
+                }
+                let first_pixel = span_start.floor() as usize;
+                let last_pixel = (span_end.ceil() as usize).min(width);
+                for (x, pixel_coverage) in row_coverage.iter_mut().enumerate().take(last_pixel).skip(first_pixel) {
+                    let covered = ((x + 1) as f32).min(span_end) - (x as f32).max(span_start);
+                    *pixel_coverage += covered.max(0.0) / SAMPLES_PER_ROW as f32;
+                }
+            }
+        }
+    }
+
+    for value in coverage.iter_mut() {
+        *value = value.min(1.0);
+    }
+
+    coverage
+}
+
+/// Parses the `file://` URIs of a text/uri-list into paths, other lines (comments,
+/// non-file URIs, the "copy" / "cut" header of GNOME file managers) are ignored
+fn parse_clipboard_uri_list(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| line.starts_with("file://"))
+        .filter_map(|line| {
+            // Skip the (usually empty or "localhost") host part
+            let uri_path = line.trim_start_matches("file://");
+            let path = &uri_path[uri_path.find('/')?..];
+            let path = percent_decode(path)?;
+            // "file:///C:/dir" is "C:/dir" on Windows
+            if cfg!(target_os = "windows") && path.len() > 2 && path.as_bytes()[2] == b':' {
+                Some(PathBuf::from(&path[1..]))
+            } else {
+                Some(PathBuf::from(path))
+            }
+        })
+        .collect()
+}
+
+/// Formats absolute paths as a text/uri-list with one `file://` URI per line
+fn format_clipboard_uri_list(paths: &[PathBuf]) -> String {
+    paths.iter().map(|path| {
+        let path = path.to_string_lossy().replace('\\', "/");
+        let mut uri = String::from(if path.starts_with('/') { "file://" } else { "file:///" });
+        for byte in path.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' | b':' => uri.push(byte as char),
+                _ => uri.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+        uri
+    }).collect::<Vec<String>>().join("\r\n")
+}
+
+const CLIPBOARD_PNG_DATA_URI_PREFIX: &str = "data:image/png;base64,";
+
+/// Decodes a PNG data URI into a BGRA8 image, returns `None` if the text isn't a valid PNG data URI
+#[cfg(feature = "image_loading")]
+fn parse_clipboard_image(text: &str) -> Option<RawImage> {
+
+    let text = text.trim();
+    if !text.starts_with(CLIPBOARD_PNG_DATA_URI_PREFIX) {
+        return None;
+    }
+
+    decode_png(&base64_decode(&text[CLIPBOARD_PNG_DATA_URI_PREFIX.len()..])?)
+}
+
+#[cfg(feature = "image_loading")]
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes the bytes as standard (padded) base64
+#[cfg(all(test, feature = "image_loading"))]
+fn base64_encode(input: &[u8]) -> String {
+    let mut output = String::with_capacity((input.len() / 3 + 1) * 4);
+    for chunk in input.chunks(3) {
+        let bits = (u32::from(chunk[0]) << 16)
+            | (u32::from(*chunk.get(1).unwrap_or(&0)) << 8)
+            | u32::from(*chunk.get(2).unwrap_or(&0));
+        for sextet in 0..4 {
+            if sextet <= chunk.len() {
+                output.push(BASE64_ALPHABET[(bits >> (18 - 6 * sextet)) as usize & 0x3F] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+/// Decodes standard base64 (whitespace is ignored), returns `None` on invalid characters
+#[cfg(feature = "image_loading")]
+fn base64_decode(input: &str) -> Option<Vec<u8>> {
+    let input = input.trim().trim_end_matches('=');
+    let mut output = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer = 0_u32;
+    let mut buffered_bits = 0;
+    for c in input.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(value);
+        buffered_bits += 6;
+        if buffered_bits >= 8 {
+            buffered_bits -= 8;
+            output.push((buffer >> buffered_bits) as u8);
+            buffer &= (1 << buffered_bits) - 1;
+        }
+    }
+    Some(output)
+}
+
+/// Decodes `%XX` escapes, returns `None` if an escape is invalid or the result isn't UTF-8
+fn percent_decode(input: &str) -> Option<String> {
+    let input = input.as_bytes();
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] == b'%' {
+            let hex = ::std::str::from_utf8(input.get(i + 1..i + 3)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            bytes.push(input[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Resolves the native sans-serif / serif / monospace font families ahead of time and caches
+/// the fonts (see `load_system_font`), so that the first text layout doesn't have to wait
+/// for the system to be queried
+fn preload_system_font_defaults() {
+    for generic_family in ["sans-serif", "serif", "monospace"].iter() {
+        load_system_font(generic_family);
+    }
+}
+
+/// Tries to load each font of the stack in order, then falls back to the generic "sans-serif" font.
+/// Returns the font + the index of the font + the family that was actually loaded
+fn load_system_font_stack(ids: &[String]) -> Option<((Vec<u8>, i32), String)> {
+    let fallback = "sans-serif";
+    let (font, family) = ids.iter()
+        .map(|id| id.as_str())
+        .chain(Some(fallback))
+        .find_map(|id| load_system_font(id).map(|font| (font, id)))?;
+
+    #[cfg(feature = "logging")] {
+        if ids.first().map(|first| first.as_str()) != Some(family) {
+            warn!("Could not load font \"{}\", using font \"{}\" instead", ids.join(", "), family);
+        }
+    }
+
+    Some((font, family.to_string()))
+}
+
+/// Decodes a single page of a multi-page TIFF image. Other image formats only have a single page.
+///
+/// The TIFF decoder only decodes the first page, so the header of the file is changed
+/// to point at the image file directory (IFD) of the requested page instead.
+#[cfg(feature = "tiff")]
+fn decode_image_page(mut image_data: Vec<u8>, page: usize, max_dimensions: Option<(u32, u32)>, options: ImageDecodeOptions) -> Result<(ImageData, ImageDescriptor), ImageReloadError> {
+
+    use image::{self, ImageFormat};
+
+    let image_format = image::guess_format(&image_data).map_err(ImageReloadError::DecodingError)?;
+
+    if image_format != ImageFormat::TIFF {
+        return match page {
+            0 => decode_image_data(image_data, max_dimensions, options),
+            _ => Err(ImageReloadError::PageOutOfRange { requested: page, available: 1 }),
+        };
+    }
+
+    if page != 0 {
+        let ifd_offsets = read_tiff_ifd_offsets(&image_data, page + 1)
+            .ok_or_else(|| ImageReloadError::DecodingError(ImageError::FormatError("Invalid TIFF header".to_string())))?;
+
+        let ifd_offset = match ifd_offsets.get(page) {
+            Some(s) => *s as u32,
+            None => return Err(ImageReloadError::PageOutOfRange { requested: page, available: ifd_offsets.len() }),
+        };
+
+        let ifd_offset_bytes = match &image_data[0..2] {
+            b"II" => [ifd_offset as u8, (ifd_offset >> 8) as u8, (ifd_offset >> 16) as u8, (ifd_offset >> 24) as u8],
+            _ => [(ifd_offset >> 24) as u8, (ifd_offset >> 16) as u8, (ifd_offset >> 8) as u8, ifd_offset as u8],
+        };
+        image_data[4..8].copy_from_slice(&ifd_offset_bytes);
+    }
+
+    if let Some((width, height)) = read_tiff_dimensions(&image_data) {
+        check_image_size(width, height, options)?;
+    }
+
+    let decoded = downscale_image(decode_tiff(image_data)?, max_dimensions);
+    prepare_image(decoded, options).map_err(ImageReloadError::DecodingError)
+}
+
+/// Decodes the first page of a TIFF file with the `tiff` crate, so that loading pages doesn't
+/// depend on the formats that are enabled in the `image` crate. Supports 8 and 16-bit greyscale,
+/// RGB and RGBA images, 16-bit images are rounded to 8 bits.
+#[cfg(feature = "tiff")]
+fn decode_tiff(image_data: Vec<u8>) -> Result<DynamicImage, ImageReloadError> {
+
+    use std::io::Cursor;
+    use image::ImageBuffer;
+    use tiff::{ColorType, TiffError, decoder::{Decoder, DecodingResult}};
+
+    let tiff_error = |e: TiffError| ImageReloadError::DecodingError(ImageError::FormatError(e.to_string()));
+
+    let mut decoder = Decoder::new(Cursor::new(image_data)).map_err(tiff_error)?;
+    let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+    let color_type = decoder.colortype().map_err(tiff_error)?;
+    let pixels = match decoder.read_image().map_err(tiff_error)? {
+        DecodingResult::U8(pixels) => pixels,
+        DecodingResult::U16(pixels) => pixels.into_iter().map(u16_to_u8).collect(),
+    };
+
+    let decoded = match color_type {
+        ColorType::Gray(_) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+        ColorType::GrayA(_) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLumaA8),
+        ColorType::RGB(_) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+        ColorType::RGBA(_) => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8),
+        _ => None,
+    };
+
+    decoded.ok_or_else(|| ImageReloadError::DecodingError(ImageError::FormatError(format!("Unsupported TIFF color type {:?}", color_type))))
+}
+
+/// Returns the offsets of the first `max_pages` image file directories (one per page) of a TIFF
+/// file or `None` if the header is invalid. BigTIFF files (with 64-bit offsets) aren't supported.
+#[cfg(feature = "tiff")]
+fn read_tiff_ifd_offsets(tiff: &[u8], max_pages: usize) -> Option<Vec<usize>> {
+
+    let little_endian = is_tiff_little_endian(tiff)?;
+    let read_u16 = |offset: usize| read_tiff_u16(tiff, little_endian, offset);
+    let read_u32 = |offset: usize| read_tiff_u32(tiff, little_endian, offset);
+
+    // Each IFD: number of entries, 12-byte entries, offset of the next IFD (0 = last page).
+    // Stopping at `max_pages` also stops at IFDs that (in a broken file) link to each other in a cycle.
+    let mut ifd_offsets = Vec::new();
+    let mut ifd_offset = read_u32(4)? as usize;
+
+    while ifd_offset != 0 && ifd_offsets.len() < max_pages {
+        ifd_offsets.push(ifd_offset);
+        let entry_count = read_u16(ifd_offset)? as usize;
+        ifd_offset = read_u32(ifd_offset + 2 + entry_count * 12)? as usize;
+    }
+
+    Some(ifd_offsets)
+}
+
+/// Returns the ImageWidth and ImageLength tags of the first image file directory of a TIFF file
+#[cfg(feature = "image_loading")]
+fn read_tiff_dimensions(tiff: &[u8]) -> Option<(u32, u32)> {
+
+    const IMAGE_WIDTH: u16 = 256;
+    const IMAGE_LENGTH: u16 = 257;
+    const SHORT: u16 = 3;
+
+    let little_endian = is_tiff_little_endian(tiff)?;
+    let read_u16 = |offset: usize| read_tiff_u16(tiff, little_endian, offset);
+    let read_u32 = |offset: usize| read_tiff_u32(tiff, little_endian, offset);
+
+    let ifd_offset = read_u32(4)? as usize;
+    let entry_count = read_u16(ifd_offset)? as usize;
+    let (mut width, mut height) = (None, None);
+
+    for entry in 0..entry_count {
+        let entry_offset = ifd_offset + 2 + entry * 12;
+        // SHORT values are stored in the first two bytes of the 4-byte value field
+        let value = match read_u16(entry_offset + 2)? {
+            SHORT => u32::from(read_u16(entry_offset + 8)?),
+            _ => read_u32(entry_offset + 8)?,
+        };
+        match read_u16(entry_offset)? {
+            IMAGE_WIDTH => width = Some(value),
+            IMAGE_LENGTH => height = Some(value),
+            _ => { },
+        }
+    }
+
+    Some((width?, height?))
+}
+
+/// Returns whether the TIFF file is little-endian ("II") or big-endian ("MM"),
+/// `None` if the file doesn't start with a TIFF header
+#[cfg(feature = "image_loading")]
+fn is_tiff_little_endian(tiff: &[u8]) -> Option<bool> {
+
+    const TIFF_MAGIC: u16 = 42;
+
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+
+    if read_tiff_u16(tiff, little_endian, 2)? != TIFF_MAGIC {
+        return None;
+    }
+
+    Some(little_endian)
+}
+
+#[cfg(feature = "image_loading")]
+fn read_tiff_u16(tiff: &[u8], little_endian: bool, offset: usize) -> Option<u16> {
+    let b = tiff.get(offset..offset + 2)?;
+    Some(if little_endian { u16::from(b[0]) | (u16::from(b[1]) << 8) } else { (u16::from(b[0]) << 8) | u16::from(b[1]) })
+}
+
+#[cfg(feature = "image_loading")]
+fn read_tiff_u32(tiff: &[u8], little_endian: bool, offset: usize) -> Option<u32> {
+    let (high, low) = if little_endian {
+        (read_tiff_u16(tiff, little_endian, offset + 2)?, read_tiff_u16(tiff, little_endian, offset)?)
+    } else {
+        (read_tiff_u16(tiff, little_endian, offset)?, read_tiff_u16(tiff, little_endian, offset + 2)?)
+    };
+    Some((u32::from(high) << 16) | u32::from(low))
+}
+
+#[cfg(feature = "tiff")]
+#[test]
+fn test_read_tiff_ifd_offsets() {
+    let tiff = vec![
+        // Little-endian header, first IFD at offset 8
+        b'I', b'I', 42, 0,  8, 0, 0, 0,
+        // IFD of page 0 (no entries), next IFD at offset 14
+        0, 0,   14, 0, 0, 0,
+        // IFD of page 1 (one entry), last page
+        1, 0,   0, 1, 3, 0, 1, 0, 0, 0, 1, 0, 0, 0,     0, 0, 0, 0,
+    ];
+    assert_eq!(read_tiff_ifd_offsets(&tiff, 10), Some(vec![8, 14]));
+    assert_eq!(read_tiff_ifd_offsets(&tiff, 1), Some(vec![8]));
+    assert_eq!(read_tiff_ifd_offsets(b"MM\0\x2B\0\0\0\x08", 10), None);
+    assert_eq!(read_tiff_ifd_offsets(b"GIF89a", 10), None);
+}
+
+#[cfg(feature = "tiff")]
+#[test]
+fn test_decode_image_page() {
+
+    // Uncompressed 2x1 greyscale page, the pixels are stored at `strip_offset`
+    fn ifd(strip_offset: u32, next_ifd: u32) -> Vec<u8> {
+        let entries: [(u16, u16, u32); 9] = [
+            (256, 3, 2), (257, 3, 1), (258, 3, 8), (259, 3, 1), (262, 3, 1),
+            (273, 4, strip_offset), (277, 3, 1), (278, 3, 1), (279, 4, 2),
+        ];
+        let mut ifd = vec![entries.len() as u8, 0];
+        for &(tag, field_type, value) in entries.iter() {
+            ifd.extend_from_slice(&[tag as u8, (tag >> 8) as u8, field_type as u8, 0, 1, 0, 0, 0]);
+            ifd.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]);
+        }
+        ifd.extend_from_slice(&[next_ifd as u8, (next_ifd >> 8) as u8, 0, 0]);
+        ifd
+    }
+
+    // Little-endian header, the pixels of both pages, IFD of page 0 at offset 12, IFD of page 1 at 126
+    let mut tiff = vec![b'I', b'I', 42, 0, 12, 0, 0, 0, 10, 20, 200, 250];
+    tiff.extend(ifd(8, 126));
+    tiff.extend(ifd(10, 0));
+
+    let options = ImageDecodeOptions::default();
+    for &(page, expected) in [(0, [10, 20]), (1, [200, 250])].iter() {
+        let (data, descriptor) = decode_image_page(tiff.clone(), page, None, options).unwrap();
+        assert_eq!(descriptor.format, RawImageFormat::R8);
+        assert_eq!((descriptor.size.width, descriptor.size.height), (2, 1));
+        match data {
+            ImageData::Raw(pixels) => assert_eq!(&pixels[..], &expected),
+            _ => panic!("expected raw image data"),
+        }
+    }
+
+    match decode_image_page(tiff, 2, None, options) {
+        Err(ImageReloadError::PageOutOfRange { requested: 2, available: 2 }) => { },
+        other => panic!("expected ImageReloadError::PageOutOfRange, got {:?}", other.map(|(_, descriptor)| descriptor)),
+    }
+}
+
+fn read_u16_be(bytes: &[u8], offset: usize) -> Option<u16> {
+    let b = bytes.get(offset..offset + 2)?;
+    Some((u16::from(b[0]) << 8) | u16::from(b[1]))
+}
+
+fn read_i16_be(bytes: &[u8], offset: usize) -> Option<i16> {
+    read_u16_be(bytes, offset).map(|value| value as i16)
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset + 4)?;
+    Some((u32::from(b[0]) << 24) | (u32::from(b[1]) << 16) | (u32::from(b[2]) << 8) | u32::from(b[3]))
+}
+
+/// Returns the bytes of an OpenType table (i.e. `b"GSUB"`) of the font at `font_index`
+/// (in case the font is a collection) or `None` if the font doesn't contain the table.
+fn get_opentype_table<'a>(font_bytes: &'a [u8], font_index: i32, table_tag: &[u8]) -> Option<&'a [u8]> {
+
+    // Font collections start with a "ttcf" header, followed by the offsets of each font
+    let font_offset = if font_bytes.get(0..4)? == b"ttcf" {
+        read_u32_be(font_bytes, 12 + 4 * font_index.max(0) as usize)? as usize
+    } else {
+        0
+    };
+
+    let num_tables = read_u16_be(font_bytes, font_offset + 4)? as usize;
+
+    for table_idx in 0..num_tables {
+        let table_record = font_offset + 12 + table_idx * 16;
+        if font_bytes.get(table_record..table_record + 4)? == table_tag {
+            let table_offset = read_u32_be(font_bytes, table_record + 8)? as usize;
+            let table_length = read_u32_be(font_bytes, table_record + 12)? as usize;
+            return font_bytes.get(table_offset..table_offset + table_length);
+        }
+    }
+
+    None
+}
+
+/// Returns whether the GSUB or GPOS feature list of the font contains the feature tag
+fn font_has_opentype_feature(font_bytes: &[u8], font_index: i32, feature_tag: &[u8]) -> bool {
+
+    fn feature_list_contains(table: &[u8], feature_tag: &[u8]) -> Option<bool> {
+        let feature_list = read_u16_be(table, 6)? as usize;
+        let feature_count = read_u16_be(table, feature_list)? as usize;
+        for feature_idx in 0..feature_count {
+            let feature_record = feature_list + 2 + feature_idx * 6;
+            if table.get(feature_record..feature_record + 4)? == feature_tag {
+                return Some(true);
+            }
+        }
+        Some(false)
+    }
+
+    if feature_tag.len() != 4 {
+        return false;
+    }
+
+    [&b"GSUB"[..], &b"GPOS"[..]].iter().any(|table_tag| {
+        get_opentype_table(font_bytes, font_index, table_tag)
+        .and_then(|table| feature_list_contains(table, feature_tag))
+        .unwrap_or(false)
+    })
+}
+
+/// Returns whether the font contains color glyphs (i.e. an emoji font), either as color layers
+/// (`COLR` / `CPAL`) or as bitmaps (`CBDT` / `sbix`).
+///
+/// Whether the glyphs are actually rendered in color depends on the platform rasterizer
+/// of WebRender: FreeType renders `CBDT` bitmaps, DirectWrite renders `COLR` layers and
+/// Core Text renders `sbix` bitmaps. Unsupported formats fall back to monochrome glyphs.
+fn font_has_color_glyphs(font_bytes: &[u8], font_index: i32) -> bool {
+    [&b"COLR"[..], &b"CBDT"[..], &b"sbix"[..]].iter()
+    .any(|table_tag| get_opentype_table(font_bytes, font_index, table_tag).is_some())
+}
+
+/// Reads the `FontMetrics` from the `head`, `hhea` and `OS/2` tables of the font. Like HarfBuzz,
+/// the ascent / descent / line gap are taken from the `hhea` table, unless the `OS/2` table
+/// sets the `USE_TYPO_METRICS` flag.
+fn parse_font_metrics(font_bytes: &[u8], font_index: i32) -> Option<FontMetrics> {
+
+    const USE_TYPO_METRICS: u16 = 1 << 7;
+
+    let head = get_opentype_table(font_bytes, font_index, b"head")?;
+    let hhea = get_opentype_table(font_bytes, font_index, b"hhea")?;
+    let os2 = get_opentype_table(font_bytes, font_index, b"OS/2");
+
+    let units_per_em = read_u16_be(head, 18)?;
+
+    let use_typo_metrics = os2.and_then(|os2| read_u16_be(os2, 62)).map(|fs_selection| fs_selection & USE_TYPO_METRICS != 0).unwrap_or(false);
+    let (ascent, descent, line_gap) = match os2 {
+        Some(os2) if use_typo_metrics => (read_i16_be(os2, 68)?, read_i16_be(os2, 70)?, read_i16_be(os2, 72)?),
+        _ => (read_i16_be(hhea, 4)?, read_i16_be(hhea, 6)?, read_i16_be(hhea, 8)?),
+    };
+
+    // sxHeight and sCapHeight only exist since version 2 of the OS/2 table, 0 means "not specified"
+    let os2_v2 = os2.filter(|os2| read_u16_be(os2, 0).map(|version| version >= 2).unwrap_or(false));
+    let x_height = os2_v2.and_then(|os2| read_i16_be(os2, 86)).filter(|x_height| *x_height != 0);
+    let cap_height = os2_v2.and_then(|os2| read_i16_be(os2, 88)).filter(|cap_height| *cap_height != 0);
+
+    Some(FontMetrics { units_per_em, ascent, descent, line_gap, cap_height, x_height })
+}
+
+/// Composite glyphs and CFF subroutines that are nested deeper than this are rejected (i.e. cyclic references)
+const MAX_GLYPH_NESTING_DEPTH: usize = 10;
+
+/// Reads the outline of a glyph from the `glyf` table (quadratic curves, TrueType outlines) or from
+/// the `CFF ` table (cubic curves, PostScript outlines). Returns `None` if the glyph index is out of
+/// range, if the tables are malformed or if the font only has `CFF2` outlines (variable fonts).
+fn parse_glyph_outline(font_bytes: &[u8], font_index: i32, glyph_index: u32) -> Option<GlyphOutline> {
+    let operations = match get_opentype_table(font_bytes, font_index, b"glyf") {
+        Some(glyf) => {
+            let head = get_opentype_table(font_bytes, font_index, b"head")?;
+            let loca = get_opentype_table(font_bytes, font_index, b"loca")?;
+            let long_offsets = read_i16_be(head, 50)? != 0;
+            parse_truetype_glyph(glyf, loca, long_offsets, glyph_index, 0)?
+        },
+        None => parse_cff_glyph(get_opentype_table(font_bytes, font_index, b"CFF ")?, glyph_index)?,
+    };
+    Some(GlyphOutline { operations })
+}
+
+/// Returns the operations of a glyph of the `glyf` table, the components of composite glyphs are resolved recursively
+fn parse_truetype_glyph(glyf: &[u8], loca: &[u8], long_offsets: bool, glyph_index: u32, depth: usize) -> Option<Vec<GlyphOutlineOperation>> {
+
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+    if depth > MAX_GLYPH_NESTING_DEPTH {
+        return None;
+    }
+
+    let glyph_index = glyph_index as usize;
+    let (start, end) = if long_offsets {
+        (read_u32_be(loca, glyph_index * 4)? as usize, read_u32_be(loca, glyph_index * 4 + 4)? as usize)
+    } else {
+        (read_u16_be(loca, glyph_index * 2)? as usize * 2, read_u16_be(loca, glyph_index * 2 + 2)? as usize * 2)
+    };
+
+    // Glyphs without an outline (i.e. the space character) have no data
+    if start == end {
+        return Some(Vec::new());
+    }
+
+    let glyph = glyf.get(start..end)?;
+    let number_of_contours = read_i16_be(glyph, 0)?;
+    if number_of_contours >= 0 {
+        return parse_truetype_simple_glyph(glyph, number_of_contours as usize);
+    }
+
+    let mut operations = Vec::new();
+    let mut offset = 10;
+
+    loop {
+        let flags = read_u16_be(glyph, offset)?;
+        let component_index = read_u16_be(glyph, offset + 2)?;
+        offset += 4;
+
+        let (arg1, arg2, args_size) = if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+            (f32::from(read_i16_be(glyph, offset)?), f32::from(read_i16_be(glyph, offset + 2)?), 4)
+        } else {
+            (f32::from(*glyph.get(offset)? as i8), f32::from(*glyph.get(offset + 1)? as i8), 2)
+        };
+        offset += args_size;
+
+        // Aligning a component by matching its points isn't supported, such components aren't moved
+        let (dx, dy) = if flags & ARGS_ARE_XY_VALUES != 0 { (arg1, arg2) } else { (0.0, 0.0) };
+
+        let f2dot14 = |offset: usize| read_i16_be(glyph, offset).map(|value| f32::from(value) / 16384.0);
+        let ((a, b, c, d), transform_size) = if flags & WE_HAVE_A_SCALE != 0 {
+            let scale = f2dot14(offset)?;
+            ((scale, 0.0, 0.0, scale), 2)
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            ((f2dot14(offset)?, 0.0, 0.0, f2dot14(offset + 2)?), 4)
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            ((f2dot14(offset)?, f2dot14(offset + 2)?, f2dot14(offset + 4)?, f2dot14(offset + 6)?), 8)
+        } else {
+            ((1.0, 0.0, 0.0, 1.0), 0)
+        };
+        offset += transform_size;
+
+        let component = parse_truetype_glyph(glyf, loca, long_offsets, u32::from(component_index), depth + 1)?;
+        operations.extend(component.into_iter().map(|operation| operation.map_points(|(x, y)| (a * x + c * y + dx, b * x + d * y + dy))));
+
+        if flags & MORE_COMPONENTS == 0 {
+            return Some(operations);
+        }
+    }
+}
+
+/// Returns the operations of a glyph of the `glyf` table that is made up of contours (not of other glyphs)
+fn parse_truetype_simple_glyph(glyph: &[u8], number_of_contours: usize) -> Option<Vec<GlyphOutlineOperation>> {
+
+    const ON_CURVE_POINT: u8 = 0x01;
+    const X_SHORT_VECTOR: u8 = 0x02;
+    const Y_SHORT_VECTOR: u8 = 0x04;
+    const REPEAT_FLAG: u8 = 0x08;
+    const X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR: u8 = 0x10;
+    const Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR: u8 = 0x20;
+
+    let contour_ends = (0..number_of_contours)
+        .map(|contour| read_u16_be(glyph, 10 + contour * 2).map(usize::from))
+        .collect::<Option<Vec<_>>>()?;
+
+    let point_count = match contour_ends.last() {
+        Some(last_point) => last_point + 1,
+        None => return Some(Vec::new()),
+    };
+
+    let instruction_length = read_u16_be(glyph, 10 + number_of_contours * 2)? as usize;
+    let mut offset = 12 + number_of_contours * 2 + instruction_length;
+
+    let mut flags = Vec::with_capacity(point_count);
+    while flags.len() < point_count {
+        let flag = *glyph.get(offset)?;
+        let repeat = if flag & REPEAT_FLAG != 0 { *glyph.get(offset + 1)? as usize } else { 0 };
+        offset += if flag & REPEAT_FLAG != 0 { 2 } else { 1 };
+        for _ in 0..=repeat {
+            flags.push(flag);
+        }
+    }
+    flags.truncate(point_count);
+
+    // Coordinates are stored as deltas to the previous point, all X coordinates come before the Y coordinates
+    let mut read_coordinates = |short_vector: u8, same_or_positive: u8| -> Option<Vec<f32>> {
+        let mut value = 0_i32;
+        let mut coordinates = Vec::with_capacity(flags.len());
+        for flag in flags.iter() {
+            if flag & short_vector != 0 {
+                let delta = i32::from(*glyph.get(offset)?);
+                value += if flag & same_or_positive != 0 { delta } else { -delta };
+                offset += 1;
+            } else if flag & same_or_positive == 0 {
+                value += i32::from(read_i16_be(glyph, offset)?);
+                offset += 2;
+            }
+            coordinates.push(value as f32);
+        }
+        Some(coordinates)
+    };
+
+    let xs = read_coordinates(X_SHORT_VECTOR, X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR)?;
+    let ys = read_coordinates(Y_SHORT_VECTOR, Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR)?;
+
+    let mut operations = Vec::new();
+    let mut contour_start = 0;
+
+    for contour_end in contour_ends {
+        if contour_end < contour_start || contour_end >= point_count {
+            return None;
+        }
+        let points = (contour_start..=contour_end)
+            .map(|point| ((xs[point], ys[point]), flags[point] & ON_CURVE_POINT != 0))
+            .collect::<Vec<_>>();
+        push_quadratic_contour(&points, &mut operations);
+        contour_start = contour_end + 1;
+    }
+
+    Some(operations)
+}
+
+/// Converts a TrueType contour of `(point, is_on_curve)` into operations. Two consecutive
+/// off-curve points have an implied on-curve point in between. The contour is closed.
+fn push_quadratic_contour(points: &[((f32, f32), bool)], operations: &mut Vec<GlyphOutlineOperation>) {
+
+    use self::GlyphOutlineOperation::*;
+
+    fn midpoint(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+        ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0)
+    }
+
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return,
+    };
+
+    // The contour has to start at an on-curve point
+    let (start, remaining_points) = if first.1 {
+        (first.0, &points[1..])
+    } else if last.1 {
+        (last.0, &points[..points.len() - 1])
+    } else {
+        (midpoint(first.0, last.0), points)
+    };
+
+    operations.push(MoveTo(start));
+    let mut current_point = start;
+    let mut control_point = None;
+
+    for &(point, is_on_curve) in remaining_points {
+        match (is_on_curve, control_point) {
+            (true, Some(control)) => {
+                operations.push(QuadraticCurveTo(control, point));
+                current_point = point;
+                control_point = None;
+            },
+            (true, None) => {
+                operations.push(LineTo(point));
+                current_point = point;
+            },
+            (false, Some(control)) => {
+                current_point = midpoint(control, point);
+                operations.push(QuadraticCurveTo(control, current_point));
+                control_point = Some(point);
+            },
+            (false, None) => {
+                control_point = Some(point);
+            },
+        }
+    }
+
+    match control_point {
+        Some(control) => operations.push(QuadraticCurveTo(control, start)),
+        None => {
+            if current_point != start {
+                operations.push(LineTo(start));
+            }
+        },
+    }
+}
+
+/// Returns the items of a CFF INDEX at `offset` and the offset of the first byte after the INDEX
+fn read_cff_index(cff: &[u8], offset: usize) -> Option<(Vec<&[u8]>, usize)> {
+
+    let count = read_u16_be(cff, offset)? as usize;
+    if count == 0 {
+        return Some((Vec::new(), offset + 2));
+    }
+
+    let offset_size = *cff.get(offset + 2)? as usize;
+    if offset_size == 0 || offset_size > 4 {
+        return None;
+    }
+
+    let read_offset = |item: usize| -> Option<usize> {
+        let start = offset + 3 + item * offset_size;
+        Some(cff.get(start..start + offset_size)?.iter().fold(0, |value, byte| (value << 8) | *byte as usize))
+    };
+
+    // The offsets are relative to the byte in front of the data
+    let data_start = offset + 3 + (count + 1) * offset_size - 1;
+    let items = (0..count)
+        .map(|item| cff.get(data_start + read_offset(item)?..data_start + read_offset(item + 1)?))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some((items, data_start + read_offset(count)?))
+}
+
+/// Reads an integer operand of a CFF DICT or charstring, returns the value and its size in bytes
+fn read_cff_integer(data: &[u8], offset: usize) -> Option<(f32, usize)> {
+    let b0 = i32::from(*data.get(offset)?);
+    match b0 {
+        28 => Some((f32::from(read_i16_be(data, offset + 1)?), 3)),
+        32..=246 => Some(((b0 - 139) as f32, 1)),
+        247..=250 => Some((((b0 - 247) * 256 + i32::from(*data.get(offset + 1)?) + 108) as f32, 2)),
+        251..=254 => Some(((-(b0 - 251) * 256 - i32::from(*data.get(offset + 1)?) - 108) as f32, 2)),
+        _ => None,
+    }
+}
+
+/// Parses a CFF DICT into `(operator, operands)` pairs, two-byte operators are returned as `1200 + second byte`
+fn parse_cff_dict(dict: &[u8]) -> Option<Vec<(u16, Vec<f32>)>> {
+
+    let mut entries = Vec::new();
+    let mut operands = Vec::new();
+    let mut offset = 0;
+
+    while offset < dict.len() {
+        if let Some((value, size)) = read_cff_integer(dict, offset) {
+            operands.push(value);
+            offset += size;
+            continue;
+        }
+        match dict[offset] {
+            12 => {
+                entries.push((1200 + u16::from(*dict.get(offset + 1)?), ::std::mem::replace(&mut operands, Vec::new())));
+                offset += 2;
+            },
+            operator @ 0..=21 => {
+                entries.push((u16::from(operator), ::std::mem::replace(&mut operands, Vec::new())));
+                offset += 1;
+            },
+            29 => {
+                operands.push(read_u32_be(dict, offset + 1)? as i32 as f32);
+                offset += 5;
+            },
+            30 => {
+                // Real numbers are only used for values that the outlines don't depend on (i.e. the font matrix)
+                offset += 1;
+                while dict.get(offset).map(|nibbles| nibbles & 0x0f != 0x0f && nibbles >> 4 != 0x0f)? {
+                    offset += 1;
+                }
+                offset += 1;
+                operands.push(0.0);
+            },
+            _ => return None,
+        }
+    }
+
+    Some(entries)
+}
+
+/// Returns the operands of an operator of a parsed CFF DICT, see `parse_cff_dict`
+fn get_cff_dict_operands(dict: &[(u16, Vec<f32>)], operator: u16) -> Option<&[f32]> {
+    dict.iter().find(|(dict_operator, _)| *dict_operator == operator).map(|(_, operands)| &operands[..])
+}
+
+/// Returns an operand of a CFF DICT as an offset or size, `None` if it is missing or negative
+fn get_cff_dict_offset(operands: &[f32], index: usize) -> Option<usize> {
+    operands.get(index).filter(|value| **value >= 0.0).map(|value| *value as usize)
+}
+
+/// Returns the index of the font DICT of a glyph in the FDSelect table of a CID-keyed CFF font
+fn read_cff_fd_select(cff: &[u8], offset: usize, glyph_index: u32) -> Option<usize> {
+    match *cff.get(offset)? {
+        0 => cff.get(offset + 1 + glyph_index as usize).map(|font_dict| *font_dict as usize),
+        3 => {
+            // Ranges of (first glyph, font DICT), followed by the glyph after the last range
+            let range_count = read_u16_be(cff, offset + 1)? as usize;
+            for range in 0..range_count {
+                let range_record = offset + 3 + range * 3;
+                let first_glyph = u32::from(read_u16_be(cff, range_record)?);
+                let next_first_glyph = u32::from(read_u16_be(cff, range_record + 3)?);
+                if glyph_index >= first_glyph && glyph_index < next_first_glyph {
+                    return cff.get(range_record + 2).map(|font_dict| *font_dict as usize);
+                }
+            }
+            None
+        },
+        _ => None,
+    }
+}
+
+/// Returns the operations of a glyph of a `CFF ` table (CFF version 1 with Type 2 charstrings)
+fn parse_cff_glyph(cff: &[u8], glyph_index: u32) -> Option<Vec<GlyphOutlineOperation>> {
+
+    const CHAR_STRINGS: u16 = 17;
+    const PRIVATE: u16 = 18;
+    const SUBRS: u16 = 19;
+    const FD_ARRAY: u16 = 1236;
+    const FD_SELECT: u16 = 1237;
+
+    // Header, followed by the Name, Top DICT, String and Global Subr INDEX
+    let header_size = *cff.get(2)? as usize;
+    let (_, top_dict_index) = read_cff_index(cff, header_size)?;
+    let (top_dicts, string_index) = read_cff_index(cff, top_dict_index)?;
+    let (_, global_subr_index) = read_cff_index(cff, string_index)?;
+    let (global_subrs, _) = read_cff_index(cff, global_subr_index)?;
+
+    let top_dict = parse_cff_dict(top_dicts.first()?)?;
+    let char_strings_offset = get_cff_dict_offset(get_cff_dict_operands(&top_dict, CHAR_STRINGS)?, 0)?;
+    let (char_strings, _) = read_cff_index(cff, char_strings_offset)?;
+    let char_string = char_strings.get(glyph_index as usize)?;
+
+    // CID-keyed fonts have one Private DICT per font DICT, the FDSelect table maps the glyph to its font DICT
+    let private_dict_location = match (get_cff_dict_operands(&top_dict, FD_ARRAY), get_cff_dict_operands(&top_dict, FD_SELECT)) {
+        (Some(fd_array), Some(fd_select)) => {
+            let (font_dicts, _) = read_cff_index(cff, get_cff_dict_offset(fd_array, 0)?)?;
+            let font_dict_index = read_cff_fd_select(cff, get_cff_dict_offset(fd_select, 0)?, glyph_index)?;
+            let font_dict = parse_cff_dict(font_dicts.get(font_dict_index)?)?;
+            get_cff_dict_operands(&font_dict, PRIVATE).map(|operands| operands.to_vec())
+        },
+        _ => get_cff_dict_operands(&top_dict, PRIVATE).map(|operands| operands.to_vec()),
+    };
+
+    // The offset of the local subroutines is relative to the Private DICT, they are optional
+    let local_subrs = private_dict_location.and_then(|private| {
+        let (size, offset) = (get_cff_dict_offset(&private, 0)?, get_cff_dict_offset(&private, 1)?);
+        let private_dict = parse_cff_dict(cff.get(offset..offset + size)?)?;
+        let subrs_offset = get_cff_dict_offset(get_cff_dict_operands(&private_dict, SUBRS)?, 0)?;
+        read_cff_index(cff, offset + subrs_offset).map(|(subrs, _)| subrs)
+    }).unwrap_or_default();
+
+    let mut interpreter = CffCharStringInterpreter::new(&global_subrs, &local_subrs);
+    interpreter.run(char_string, 0)?;
+    interpreter.close_contour();
+    Some(interpreter.operations)
+}
+
+/// Interprets the Type 2 charstring of a CFF glyph, see `parse_cff_glyph`. Hints are skipped,
+/// only the outline is kept. Contours are closed, like in the `glyf` table.
+struct CffCharStringInterpreter<'a> {
+    global_subrs: &'a [&'a [u8]],
+    local_subrs: &'a [&'a [u8]],
+    stack: Vec<f32>,
+    /// Number of stem hints, determines the size of the `hintmask` / `cntrmask` operands
+    stem_count: usize,
+    /// Whether the optional advance width in front of the first stack-clearing operator was already skipped
+    width_parsed: bool,
+    current_point: (f32, f32),
+    contour_start: Option<(f32, f32)>,
+    operations: Vec<GlyphOutlineOperation>,
+}
+
+impl<'a> CffCharStringInterpreter<'a> {
+
+    fn new(global_subrs: &'a [&'a [u8]], local_subrs: &'a [&'a [u8]]) -> Self {
+        Self {
+            global_subrs,
+            local_subrs,
+            stack: Vec::new(),
+            stem_count: 0,
+            width_parsed: false,
+            current_point: (0.0, 0.0),
+            contour_start: None,
+            operations: Vec::new(),
+        }
+    }
+
+    /// Runs a charstring or subroutine, returns `Some(true)` once `endchar` was reached
+    /// and `None` if the charstring is malformed
+    fn run(&mut self, char_string: &[u8], depth: usize) -> Option<bool> {
+
+        if depth > MAX_GLYPH_NESTING_DEPTH {
+            return None;
+        }
+
+        let mut offset = 0;
+
+        while offset < char_string.len() {
+
+            if let Some((value, size)) = read_cff_integer(char_string, offset) {
+                self.stack.push(value);
+                offset += size;
+                continue;
+            }
+
+            let operator = char_string[offset];
+            offset += 1;
+
+            match operator {
+                // hstem, vstem, hstemhm, vstemhm
+                1 | 3 | 18 | 23 => {
+                    let has_width = self.stack.len() % 2 == 1;
+                    self.stem_count += self.take_operands(has_width).len() / 2;
+                },
+                // hintmask, cntrmask: the operands are the stems of an implicit vstemhm
+                19 | 20 => {
+                    let has_width = self.stack.len() % 2 == 1;
+                    self.stem_count += self.take_operands(has_width).len() / 2;
+                    offset += (self.stem_count + 7) / 8;
+                },
+                // rmoveto
+                21 => {
+                    let has_width = self.stack.len() > 2;
+                    let operands = self.take_operands(has_width);
+                    self.move_to(*operands.first()?, *operands.get(1)?);
+                },
+                // hmoveto
+                22 => {
+                    let has_width = self.stack.len() > 1;
+                    let operands = self.take_operands(has_width);
+                    self.move_to(*operands.first()?, 0.0);
+                },
+                // vmoveto
+                4 => {
+                    let has_width = self.stack.len() > 1;
+                    let operands = self.take_operands(has_width);
+                    self.move_to(0.0, *operands.first()?);
+                },
+                // rlineto
+                5 => {
+                    for delta in self.take_operands(false).chunks(2).filter(|delta| delta.len() == 2) {
+                        self.line_to(delta[0], delta[1]);
+                    }
+                },
+                // hlineto, vlineto: alternating horizontal and vertical lines
+                6 | 7 => {
+                    for (line, delta) in self.take_operands(false).into_iter().enumerate() {
+                        if (line % 2 == 0) == (operator == 6) {
+                            self.line_to(delta, 0.0);
+                        } else {
+                            self.line_to(0.0, delta);
+                        }
+                    }
+                },
+                // rrcurveto
+                8 => {
+                    for d in self.take_operands(false).chunks(6).filter(|d| d.len() == 6) {
+                        self.curve_to(d[0], d[1], d[2], d[3], d[4], d[5]);
+                    }
+                },
+                // rcurveline: curves followed by a line
+                24 => {
+                    let operands = self.take_operands(false);
+                    let (curves, line) = operands.split_at(operands.len().checked_sub(2)?);
+                    for d in curves.chunks(6).filter(|d| d.len() == 6) {
+                        self.curve_to(d[0], d[1], d[2], d[3], d[4], d[5]);
+                    }
+                    self.line_to(line[0], line[1]);
+                },
+                // rlinecurve: lines followed by a curve
+                25 => {
+                    let operands = self.take_operands(false);
+                    let (lines, d) = operands.split_at(operands.len().checked_sub(6)?);
+                    for delta in lines.chunks(2).filter(|delta| delta.len() == 2) {
+                        self.line_to(delta[0], delta[1]);
+                    }
+                    self.curve_to(d[0], d[1], d[2], d[3], d[4], d[5]);
+                },
+                // vvcurveto: vertical curves, the first one may start with a horizontal delta
+                26 => {
+                    let operands = self.take_operands(false);
+                    let (mut dx1, curves) = if operands.len() % 4 == 1 { (operands[0], &operands[1..]) } else { (0.0, &operands[..]) };
+                    for d in curves.chunks(4).filter(|d| d.len() == 4) {
+                        self.curve_to(dx1, d[0], d[1], d[2], 0.0, d[3]);
+                        dx1 = 0.0;
+                    }
+                },
+                // hhcurveto: horizontal curves, the first one may start with a vertical delta
+                27 => {
+                    let operands = self.take_operands(false);
+                    let (mut dy1, curves) = if operands.len() % 4 == 1 { (operands[0], &operands[1..]) } else { (0.0, &operands[..]) };
+                    for d in curves.chunks(4).filter(|d| d.len() == 4) {
+                        self.curve_to(d[0], dy1, d[1], d[2], d[3], 0.0);
+                        dy1 = 0.0;
+                    }
+                },
+                // vhcurveto, hvcurveto: curves that alternate between starting vertically and horizontally,
+                // the last curve may end with an additional delta
+                30 | 31 => {
+                    let operands = self.take_operands(false);
+                    let mut starts_horizontally = operator == 31;
+                    for (curve, d) in operands.chunks(4).enumerate().filter(|(_, d)| d.len() == 4) {
+                        let last_delta = if operands.len() - curve * 4 == 5 { operands[operands.len() - 1] } else { 0.0 };
+                        if starts_horizontally {
+                            self.curve_to(d[0], 0.0, d[1], d[2], last_delta, d[3]);
+                        } else {
+                            self.curve_to(0.0, d[0], d[1], d[2], d[3], last_delta);
+                        }
+                        starts_horizontally = !starts_horizontally;
+                    }
+                },
+                // callsubr, callgsubr
+                10 | 29 => {
+                    let subrs = if operator == 10 { self.local_subrs } else { self.global_subrs };
+                    let bias = match subrs.len() {
+                        0..=1239 => 107,
+                        1240..=33899 => 1131,
+                        _ => 32768,
+                    };
+                    let subr_index = self.stack.pop()? as i32 + bias;
+                    if subr_index < 0 {
+                        return None;
+                    }
+                    if self.run(subrs.get(subr_index as usize)?, depth + 1)? {
+                        return Some(true);
+                    }
+                },
+                // return
+                11 => return Some(false),
+                // endchar, accented characters (the deprecated `seac` operands) aren't supported
+                14 => {
+                    let has_width = self.stack.len() == 1 || self.stack.len() == 5;
+                    self.take_operands(has_width);
+                    return Some(true);
+                },
+                12 => {
+                    let escaped_operator = *char_string.get(offset)?;
+                    offset += 1;
+                    self.run_escaped_operator(escaped_operator)?;
+                },
+                // 16.16 fixed-point number
+                255 => {
+                    self.stack.push(read_u32_be(char_string, offset)? as i32 as f32 / 65536.0);
+                    offset += 4;
+                },
+                _ => return None,
+            }
+        }
+
+        Some(false)
+    }
+
+    /// Runs the flex operators, the other two-byte operators (arithmetic and storage operators) are skipped
+    fn run_escaped_operator(&mut self, operator: u8) -> Option<()> {
+
+        let d = self.take_operands(false);
+
+        match operator {
+            // hflex
+            34 => {
+                if d.len() < 7 { return None; }
+                self.curve_to(d[0], 0.0, d[1], d[2], d[3], 0.0);
+                self.curve_to(d[4], 0.0, d[5], -d[2], d[6], 0.0);
+            },
+            // flex
+            35 => {
+                if d.len() < 12 { return None; }
+                self.curve_to(d[0], d[1], d[2], d[3], d[4], d[5]);
+                self.curve_to(d[6], d[7], d[8], d[9], d[10], d[11]);
+            },
+            // hflex1
+            36 => {
+                if d.len() < 9 { return None; }
+                self.curve_to(d[0], d[1], d[2], d[3], d[4], 0.0);
+                self.curve_to(d[5], 0.0, d[6], d[7], d[8], -(d[1] + d[3] + d[7]));
+            },
+            // flex1: the last delta is either horizontal or vertical, depending on the direction of the flex
+            37 => {
+                if d.len() < 11 { return None; }
+                let dx = d[0] + d[2] + d[4] + d[6] + d[8];
+                let dy = d[1] + d[3] + d[5] + d[7] + d[9];
+                let (dx6, dy6) = if dx.abs() > dy.abs() { (d[10], -dy) } else { (-dx, d[10]) };
+                self.curve_to(d[0], d[1], d[2], d[3], d[4], d[5]);
+                self.curve_to(d[6], d[7], d[8], d[9], dx6, dy6);
+            },
+            _ => { },
+        }
+
+        Some(())
+    }
+
+    /// Clears the stack and returns the operands. The first stack-clearing operator may have the
+    /// advance width of the glyph in front of its operands, which is removed if `has_width` is set.
+    fn take_operands(&mut self, has_width: bool) -> Vec<f32> {
+        let mut operands = ::std::mem::replace(&mut self.stack, Vec::new());
+        if has_width && !self.width_parsed && !operands.is_empty() {
+            operands.remove(0);
+        }
+        self.width_parsed = true;
+        operands
+    }
+
+    fn move_to(&mut self, dx: f32, dy: f32) {
+        self.close_contour();
+        self.current_point = (self.current_point.0 + dx, self.current_point.1 + dy);
+        self.contour_start = Some(self.current_point);
+        self.operations.push(GlyphOutlineOperation::MoveTo(self.current_point));
+    }
+
+    fn line_to(&mut self, dx: f32, dy: f32) {
+        self.current_point = (self.current_point.0 + dx, self.current_point.1 + dy);
+        self.operations.push(GlyphOutlineOperation::LineTo(self.current_point));
+    }
+
+    fn curve_to(&mut self, dx1: f32, dy1: f32, dx2: f32, dy2: f32, dx3: f32, dy3: f32) {
+        let control_1 = (self.current_point.0 + dx1, self.current_point.1 + dy1);
+        let control_2 = (control_1.0 + dx2, control_1.1 + dy2);
+        self.current_point = (control_2.0 + dx3, control_2.1 + dy3);
+        self.operations.push(GlyphOutlineOperation::CubicCurveTo(control_1, control_2, self.current_point));
+    }
+
+    /// Closes the current contour with a line back to its start, the current point stays where it is
+    fn close_contour(&mut self) {
+        if let Some(start) = self.contour_start.take() {
+            if self.current_point != start {
+                self.operations.push(GlyphOutlineOperation::LineTo(start));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_font_has_opentype_feature() {
+    let font_bytes = vec![
+        // Offset table: sfnt version, 1 table, search range / entry selector / range shift
+        0, 1, 0, 0,     0, 1,   0, 0, 0, 0, 0, 0,
+        // Table record: "GSUB", checksum, offset 28, length 18
+        b'G', b'S', b'U', b'B',     0, 0, 0, 0,     0, 0, 0, 28,    0, 0, 0, 18,
+        // GSUB header: version 1.0, script list, feature list at 10, lookup list
+        0, 1, 0, 0,     0, 0,   0, 10,  0, 0,
+        // Feature list: 1 feature, "smcp" at offset 0
+        0, 1,   b's', b'm', b'c', b'p',     0, 0,
+    ];
+    assert!(font_has_opentype_feature(&font_bytes, 0, b"smcp"));
+    assert!(!font_has_opentype_feature(&font_bytes, 0, b"liga"));
+    assert!(!font_has_opentype_feature(&font_bytes, 0, b"smc"));
+    assert!(!font_has_opentype_feature(&[], 0, b"smcp"));
+}
+
+/// Builds the bytes of a font that only consists of the given tables
+#[cfg(test)]
+fn font_with_tables(tables: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+
+    fn u32_be(value: usize) -> [u8; 4] {
+        [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+    }
+
+    let mut font_bytes = vec![0, 1, 0, 0,   0, tables.len() as u8,   0, 0, 0, 0, 0, 0];
+    let mut table_offset = 12 + tables.len() * 16;
+    for (tag, table) in tables.iter() {
+        font_bytes.extend_from_slice(&tag[..]);
+        font_bytes.extend_from_slice(&[0, 0, 0, 0]);
+        font_bytes.extend_from_slice(&u32_be(table_offset));
+        font_bytes.extend_from_slice(&u32_be(table.len()));
+        table_offset += table.len();
+    }
+    for (_, table) in tables.iter() {
+        font_bytes.extend_from_slice(table);
+    }
+    font_bytes
+}
+
+#[test]
+fn test_parse_font_metrics() {
+
+    fn write_i16(table: &mut [u8], offset: usize, value: i16) {
+        table[offset] = (value >> 8) as u8;
+        table[offset + 1] = value as u8;
+    }
+
+    let mut head = vec![0; 54];
+    write_i16(&mut head, 18, 1000);
+    let mut hhea = vec![0; 36];
+    write_i16(&mut hhea, 4, 900);
+    write_i16(&mut hhea, 6, -250);
+    write_i16(&mut hhea, 8, 50);
+    let mut os2 = vec![0; 96];
+    write_i16(&mut os2, 0, 2);
+    write_i16(&mut os2, 68, 800);
+    write_i16(&mut os2, 70, -200);
+    write_i16(&mut os2, 72, 100);
+    write_i16(&mut os2, 86, 500);
+    write_i16(&mut os2, 88, 700);
+
+    let font_bytes = font_with_tables(&[(b"head", head.clone()), (b"hhea", hhea.clone()), (b"OS/2", os2.clone())]);
+    assert_eq!(parse_font_metrics(&font_bytes, 0), Some(FontMetrics {
+        units_per_em: 1000,
+        ascent: 900,
+        descent: -250,
+        line_gap: 50,
+        cap_height: Some(700),
+        x_height: Some(500),
+    }));
+
+    // USE_TYPO_METRICS switches to the metrics of the OS/2 table
+    write_i16(&mut os2, 62, 1 << 7);
+    let font_bytes = font_with_tables(&[(b"head", head.clone()), (b"hhea", hhea.clone()), (b"OS/2", os2)]);
+    let font_metrics = parse_font_metrics(&font_bytes, 0).unwrap();
+    assert_eq!((font_metrics.ascent, font_metrics.descent, font_metrics.line_gap), (800, -200, 100));
+
+    // Without an OS/2 table, there is no cap height / x-height
+    let font_bytes = font_with_tables(&[(b"head", head), (b"hhea", hhea)]);
+    let font_metrics = parse_font_metrics(&font_bytes, 0).unwrap();
+    assert_eq!((font_metrics.cap_height, font_metrics.x_height), (None, None));
+
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let font_id = app_resources.new_font_id();
+    assert_eq!(app_resources.get_font_metrics(&font_id), None);
+    app_resources.add_font(font_id, FontSource::Bytes(font_bytes, 0));
+    assert_eq!(app_resources.get_font_metrics(&font_id), Some(font_metrics));
+    app_resources.delete_font(&font_id);
+    assert_eq!(app_resources.get_font_metrics(&font_id), None);
+}
+
+#[test]
+fn test_parse_truetype_glyph_outline() {
+
+    use self::GlyphOutlineOperation::{MoveTo, LineTo, QuadraticCurveTo};
+
+    // Two contours: a square with two control points and a contour that starts with two
+    // consecutive control points. Uses short vectors, repeated flags and repeated coordinates.
+    let simple_glyph = vec![
+        0, 2,   0, 0, 0, 0, 0, 0, 0, 0,
+        // End points of the contours, no instructions
+        0, 3,   0, 6,   0, 0,
+        // Flags: on, off + short positive X + same Y, on, off + short negative X + same Y, 2x off, on
+        0x01, 0x32, 0x01, 0x22, 0x08, 1, 0x01,
+        // X deltas
+        0, 0,   100,    0, 0,   100,    0, 10,  0, 10,  0, 0,
+        // Y deltas
+        0, 0,   0, 100,     0xff, 0xa6,     0, 0,   0, 10,
+    ];
+
+    // The simple glyph, scaled by 0.5 and moved by (5, -5)
+    let composite_glyph = vec![0xff, 0xff,  0, 0, 0, 0, 0, 0, 0, 0,     0, 0x0a,    0, 1,   5, 0xfb,    0x20, 0x00];
+    // Composite glyph that references itself
+    let cyclic_glyph = vec![0xff, 0xff,     0, 0, 0, 0, 0, 0, 0, 0,     0, 0x02,    0, 3,   0, 0];
+
+    let mut glyf = Vec::new();
+    let mut loca = vec![0, 0, 0, 0,     0, 0, 0, 0];
+    for glyph in [&simple_glyph, &composite_glyph, &cyclic_glyph].iter() {
+        glyf.extend_from_slice(glyph);
+        loca.extend_from_slice(&[0, 0, 0, glyf.len() as u8]);
+    }
+    let mut head = vec![0; 54];
+    head[51] = 1;
+
+    let font_bytes = font_with_tables(&[(b"glyf", glyf), (b"head", head), (b"loca", loca)]);
+
+    let simple_outline = vec![
+        MoveTo((0.0, 0.0)),
+        QuadraticCurveTo((100.0, 0.0), (100.0, 100.0)),
+        QuadraticCurveTo((0.0, 100.0), (0.0, 0.0)),
+        MoveTo((20.0, 20.0)),
+        QuadraticCurveTo((10.0, 10.0), (15.0, 10.0)),
+        QuadraticCurveTo((20.0, 10.0), (20.0, 20.0)),
+    ];
+
+    assert_eq!(parse_glyph_outline(&font_bytes, 0, 0), Some(GlyphOutline { operations: Vec::new() }));
+    assert_eq!(parse_glyph_outline(&font_bytes, 0, 1), Some(GlyphOutline { operations: simple_outline.clone() }));
+    assert_eq!(parse_glyph_outline(&font_bytes, 0, 2), Some(GlyphOutline {
+        operations: simple_outline.into_iter().map(|operation| operation.map_points(|(x, y)| (x / 2.0 + 5.0, y / 2.0 - 5.0))).collect(),
+    }));
+    assert_eq!(parse_glyph_outline(&font_bytes, 0, 3), None);
+    assert_eq!(parse_glyph_outline(&font_bytes, 0, 4), None);
+    assert_eq!(parse_glyph_outline(&[], 0, 0), None);
+
+    // Contours of on-curve points are closed with a line, the offsets of the `loca` table are
+    // stored as 16-bit values (divided by 2). Flags: 2x on + short positive X and Y, on + short positive X + same Y
+    let triangle = vec![0, 1,   0, 0, 0, 0, 0, 0, 0, 0,     0, 2,   0, 0,   0x37, 0x37, 0x33,   10, 10, 10,     10, 10];
+    let font_bytes = font_with_tables(&[(b"glyf", triangle.clone()), (b"head", vec![0; 54]), (b"loca", vec![0, 0, 0, triangle.len() as u8 / 2])]);
+    assert_eq!(parse_glyph_outline(&font_bytes, 0, 0), Some(GlyphOutline {
+        operations: vec![MoveTo((10.0, 10.0)), LineTo((20.0, 20.0)), LineTo((30.0, 20.0)), LineTo((10.0, 10.0))],
+    }));
+}
+
+#[test]
+fn test_parse_cff_glyph_outline() {
+
+    use self::GlyphOutlineOperation::{MoveTo, LineTo, CubicCurveTo};
+
+    fn cff_index(items: &[&[u8]]) -> Vec<u8> {
+        if items.is_empty() {
+            return vec![0, 0];
+        }
+        let mut index = vec![0, items.len() as u8, 1, 1];
+        let mut offset = 1;
+        for item in items.iter() {
+            offset += item.len();
+            index.push(offset as u8);
+        }
+        for item in items.iter() {
+            index.extend_from_slice(item);
+        }
+        index
+    }
+
+    // 5-byte integer operand of a DICT
+    fn dict_integer(value: usize) -> Vec<u8> {
+        vec![29, (value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+    }
+
+    // Advance width 50, `10 20 rmoveto`, `100 0 rlineto`, the global and the local subroutine,
+    // `10 20 30 40 hvcurveto`, `endchar`
+    let glyph = [189, 149, 159, 21,     239, 139, 5,    32, 29,     32, 10,     149, 159, 169, 179, 31,     14];
+    // `0 -100 rlineto`
+    let global_subr = [139, 39, 5, 11];
+    // `-100 0 rlineto`
+    let local_subr = [39, 139, 5, 11];
+
+    let header = vec![1, 0, 4, 1];
+    let name_index = cff_index(&[b"A"]);
+    let string_index = cff_index(&[]);
+    let global_subr_index = cff_index(&[&global_subr]);
+    let char_strings_index = cff_index(&[&[14], &glyph]);
+    let mut private_dict = dict_integer(6);
+    private_dict.push(19);
+    let local_subr_index = cff_index(&[&local_subr]);
+
+    // The Top DICT has a fixed size of 17 bytes, so its INDEX has 22 bytes
+    let char_strings_offset = header.len() + name_index.len() + 22 + string_index.len() + global_subr_index.len();
+    let private_dict_offset = char_strings_offset + char_strings_index.len();
+    let mut top_dict = dict_integer(char_strings_offset);
+    top_dict.push(17);
+    top_dict.extend(dict_integer(private_dict.len()));
+    top_dict.extend(dict_integer(private_dict_offset));
+    top_dict.push(18);
+
+    let mut cff = header;
+    for table in [name_index, cff_index(&[&top_dict]), string_index, global_subr_index, char_strings_index, private_dict, local_subr_index].iter() {
+        cff.extend_from_slice(table);
+    }
+
+    let font_bytes = font_with_tables(&[(b"CFF ", cff)]);
+
+    assert_eq!(parse_glyph_outline(&font_bytes, 0, 0), Some(GlyphOutline { operations: Vec::new() }));
+    assert_eq!(parse_glyph_outline(&font_bytes, 0, 1), Some(GlyphOutline {
+        operations: vec![
+            MoveTo((10.0, 20.0)),
+            LineTo((110.0, 20.0)),
+            LineTo((110.0, -80.0)),
+            LineTo((10.0, -80.0)),
+            CubicCurveTo((20.0, -80.0), (40.0, -50.0), (40.0, -10.0)),
+            LineTo((10.0, 20.0)),
+        ],
+    }));
+    assert_eq!(parse_glyph_outline(&font_bytes, 0, 2), None);
+}
+
+lazy_static! {
+    /// Caches the result of `query_system_font` for the lifetime of the process (including
+    /// fonts that weren't found), so that every family is only resolved by the OS once
+    static ref SYSTEM_FONT_CACHE: ::std::sync::Mutex<FastHashMap<String, Option<(Vec<u8>, i32)>>> = ::std::sync::Mutex::new(FastHashMap::default());
+}
+
+/// Returns the font + the index of the font (in case the font is a collection)
+fn load_system_font(id: &str) -> Option<(Vec<u8>, i32)> {
+
+    if let Some(font) = SYSTEM_FONT_CACHE.lock().ok().and_then(|cache| cache.get(id).cloned()) {
+        return font;
+    }
+
+    let font = query_system_font(id);
+
+    if let Ok(mut cache) = SYSTEM_FONT_CACHE.lock() {
+        cache.insert(id.to_string(), font.clone());
+    }
+
+    font
+}
+
+/// Queries the OS for the font, see `load_system_font`. On Linux, the font is resolved
+/// via fontconfig first (which respects the configuration of every desktop environment),
+/// gsettings is only queried for the generic families if fontconfig isn't installed.
+fn query_system_font(id: &str) -> Option<(Vec<u8>, i32)> {
+    use font_loader::system_fonts::{self, FontPropertyBuilder};
+
+    #[cfg(target_os = "linux")] {
+        if let Some(font) = load_fontconfig_family(id) {
+            return Some(font);
+        }
+    }
+
+    let font_builder = match id {
+        "monospace" => {
+            #[cfg(target_os = "linux")] {
+                let native_monospace_font = linux_get_native_font(LinuxNativeFontType::Monospace);
+                FontPropertyBuilder::new().family(&native_monospace_font)
+            }
+            #[cfg(not(target_os = "linux"))] {
+                FontPropertyBuilder::new().monospace()
+            }
+        },
+        "fantasy" => {
+            #[cfg(target_os = "macos")] {
+                FontPropertyBuilder::new().family("Papyrus")
+            }
+            #[cfg(not(target_os = "macos"))] {
+                FontPropertyBuilder::new().family("Impact")
+            }
+        },
+        "cursive" => {
+            #[cfg(target_os = "macos")] {
+                FontPropertyBuilder::new().family("Apple Chancery")
+            }
+            #[cfg(not(target_os = "macos"))] {
+                FontPropertyBuilder::new().family("Comic Sans MS")
+            }
+        },
+        "sans-serif" => FontPropertyBuilder::new().family(&native_sans_serif_family()),
+        "serif" => {
+            FontPropertyBuilder::new().family("Times New Roman")
+        },
+        other => FontPropertyBuilder::new().family(other)
+    };
+
+    system_fonts::get(&font_builder.build())
+}
+
+/// Returns the concrete family of the generic "sans-serif" family on the current platform
+fn native_sans_serif_family() -> String {
+    #[cfg(target_os = "macos")] {
+        String::from("Helvetica")
+    }
+    #[cfg(target_os = "linux")] {
+        linux_get_native_font(LinuxNativeFontType::SansSerif)
+    }
+    #[cfg(all(not(target_os = "linux"), not(target_os = "macos")))] {
+        String::from("Segoe UI")
+    }
+}
+
+// Guards against misspelled `target_os` values (i.e. "mac_os"), which silently never match
+#[test]
+fn test_native_sans_serif_family() {
+    let family = native_sans_serif_family();
+    #[cfg(target_os = "macos")] {
+        assert_eq!(family, "Helvetica");
+    }
+    #[cfg(target_os = "windows")] {
+        assert_eq!(family, "Segoe UI");
+    }
+    assert!(!family.is_empty());
+}
+
+/// Resolves a fontconfig pattern to a font file + index via `fc-match`. If fontconfig
+/// isn't installed, the family of the pattern is loaded as a regular system font instead.
+#[cfg(all(target_os = "linux", feature = "fontconfig"))]
+fn load_fontconfig_font(pattern: &str) -> Option<(Vec<u8>, i32)> {
+
+    use std::fs;
+
+    match fc_match(pattern).as_ref().and_then(|s| parse_fc_match_output(s)) {
+        Some((file_path, font_index)) => fs::read(file_path).ok().map(|bytes| (bytes, font_index)),
+        None => {
+            // "Noto Sans:weight=bold" => "Noto Sans"
+            let family = pattern.split(':').next().unwrap_or(pattern).trim();
+            load_system_font(family)
+        }
+    }
+}
+
+/// Resolves a system font family (or a generic family, i.e. "sans-serif") via `fc-match`.
+/// Returns `None` if fontconfig isn't installed or the family isn't installed.
+#[cfg(target_os = "linux")]
+fn load_fontconfig_family(family: &str) -> Option<(Vec<u8>, i32)> {
+
+    use std::fs;
+
+    // Escape the characters that have a special meaning in fontconfig patterns
+    let mut pattern = String::with_capacity(family.len());
+    for c in family.chars() {
+        if c == '-' || c == ':' || c == ',' || c == '\\' {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+
+    let output = fc_match(&pattern)?;
+    let (file_path, font_index) = parse_fc_match_output(&output)?;
+    let matched_families = output.lines().nth(2).unwrap_or("");
+
+    if !fc_family_matches(family, matched_families) {
+        return None;
+    }
+
+    fs::read(file_path).ok().map(|bytes| (bytes, font_index))
+}
+
+/// Runs `fc-match`, returns the file, index and families of the best match, one per line
+#[cfg(target_os = "linux")]
+fn fc_match(pattern: &str) -> Option<String> {
+
+    use std::process::Command;
+
+    Command::new("fc-match")
+        .arg("--format=%{file}\n%{index}\n%{family}")
+        .arg(pattern)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+}
+
+/// `fc-match` substitutes a fallback font for families that aren't installed - this
+/// shouldn't shadow the next font of a `FontSource::SystemStack`. Generic families always match.
+#[cfg(target_os = "linux")]
+fn fc_family_matches(family: &str, matched_families: &str) -> bool {
+    const GENERIC_FAMILIES: &[&str] = &["sans-serif", "serif", "monospace", "cursive", "fantasy"];
+    GENERIC_FAMILIES.contains(&family) ||
+    matched_families.split(',').any(|matched| matched.trim().eq_ignore_ascii_case(family))
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_fc_family_matches() {
+    assert!(fc_family_matches("DejaVu Sans", "DejaVu Sans"));
+    assert!(fc_family_matches("dejavu sans", "DejaVu Sans,DejaVu Sans Condensed"));
+    assert!(fc_family_matches("monospace", "DejaVu Sans Mono"));
+    assert!(!fc_family_matches("Helvetica", "DejaVu Sans"));
+}
+
+// "/usr/share/fonts/NotoSans-Bold.ttf\n0" => ("/usr/share/fonts/NotoSans-Bold.ttf", 0)
+#[cfg(target_os = "linux")]
+fn parse_fc_match_output(input: &str) -> Option<(PathBuf, i32)> {
+    let mut lines = input.lines();
+    let file_path = lines.next()?.trim();
+    if file_path.is_empty() {
+        return None;
+    }
+    let font_index = lines.next().and_then(|index| index.trim().parse().ok()).unwrap_or(0);
+    Some((PathBuf::from(file_path), font_index))
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_parse_fc_match_output() {
+    assert_eq!(parse_fc_match_output("/usr/share/fonts/NotoSans-Bold.ttf\n0"), Some((PathBuf::from("/usr/share/fonts/NotoSans-Bold.ttf"), 0)));
+    assert_eq!(parse_fc_match_output("/usr/share/fonts/NotoSansCJK.ttc\n2"), Some((PathBuf::from("/usr/share/fonts/NotoSansCJK.ttc"), 2)));
+    assert_eq!(parse_fc_match_output(""), None);
+}
+
+/// Return the native fonts
+#[cfg(target_os = "linux")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum LinuxNativeFontType { SansSerif, Monospace }
+
+#[cfg(target_os = "linux")]
+lazy_static! {
+    /// Caches the result of `linux_get_native_font`, since querying gsettings
+    /// spawns a subprocess, which stalls the first frame that renders text
+    static ref LINUX_NATIVE_FONT_CACHE: ::std::sync::Mutex<FastHashMap<LinuxNativeFontType, String>> = ::std::sync::Mutex::new(FastHashMap::default());
+}
+
+#[cfg(target_os = "linux")]
+fn linux_get_native_font(font_type: LinuxNativeFontType) -> String {
+
+    if let Some(font_name) = LINUX_NATIVE_FONT_CACHE.lock().ok().and_then(|cache| cache.get(&font_type).cloned()) {
+        return font_name;
+    }
+
+    let font_name = linux_query_native_font(font_type);
+
+    if let Ok(mut cache) = LINUX_NATIVE_FONT_CACHE.lock() {
+        cache.insert(font_type, font_name.clone());
+    }
+
+    font_name
+}
+
+#[cfg(target_os = "linux")]
+fn linux_query_native_font(font_type: LinuxNativeFontType) -> String {
+
+    use std::process::Command;
+    use self::LinuxNativeFontType::*;
+
+    let font_name = match font_type {
+        SansSerif => "font-name",
+        Monospace => "monospace-font-name",
+    };
+
+    let fallback_font_name = match font_type {
+        SansSerif => "Ubuntu",
+        Monospace => "Ubuntu Mono",
+    };
+
+    // Execute "gsettings get org.gnome.desktop.interface font-name" and parse the output
+    let gsetting_cmd_result =
+        Command::new("gsettings")
+            .arg("get")
+            .arg("org.gnome.desktop.interface")
+            .arg(font_name)
+            .output()
+            .ok().map(|output| output.stdout)
+            .and_then(|stdout_bytes| String::from_utf8(stdout_bytes).ok())
+            .map(|stdout_string| stdout_string.lines().collect::<String>());
+
+    match &gsetting_cmd_result {
+        Some(s) => parse_gsettings_font(s).to_string(),
+        None => fallback_font_name.to_string(),
+    }
+}
+
+// 'Ubuntu Mono 13' => Ubuntu Mono
+#[cfg(target_os = "linux")]
+fn parse_gsettings_font(input: &str) -> &str {
+    use std::char;
+    let input = input.trim();
+    let input = input.trim_matches('\'');
+    let input = input.trim_end_matches(char::is_numeric);
+    let input = input.trim();
+    input
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_parse_gsettings_font() {
+    assert_eq!(parse_gsettings_font("'Ubuntu 11'"), "Ubuntu");
+    assert_eq!(parse_gsettings_font("'Ubuntu Mono 13'"), "Ubuntu Mono");
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ImageInfo {
+    pub(crate) key: ImageKey,
+    pub descriptor: ImageDescriptor,
+    /// Whether the image data is premultiplied (decoded images) or straight alpha (raw images)
+    pub(crate) alpha_type: AlphaType,
+    /// Border insets `(top, right, bottom, left)` if the image is drawn as a nine-patch
+    pub nine_patch_insets: Option<(u32, u32, u32, u32)>,
+}
+
+impl ImageInfo {
+    /// Returns the (width, height) of this image.
+    pub fn get_dimensions(&self) -> (usize, usize) {
+        let width = self.descriptor.size.width;
+        let height = self.descriptor.size.height;
+        (width as usize, height as usize)
+    }
+
+    /// Returns whether every pixel of this image is fully opaque, i.e. whether it
+    /// can be drawn without alpha blending.
+    pub fn is_opaque(&self) -> bool {
+        self.descriptor.is_opaque
+    }
+
+    /// Returns the pixel format the image was uploaded in. Note that this can differ from the
+    /// format of the `RawImage` it was created from, i.e. `R16` images are uploaded as `R8`.
+    pub fn format(&self) -> RawImageFormat {
+        self.descriptor.format
+    }
+
+    /// Computes which part of the image is visible (in image pixels) and where it is drawn
+    /// (relative to the top left corner of the container) if the image is fitted into a
+    /// container of the size `(width, height)` according to the CSS `object-fit` rules.
+    /// The image is always centered in the container.
+    pub fn fit_rect(&self, container: (f32, f32), fit: ObjectFit) -> (SourceRect, DestRect) {
+
+        let (image_width, image_height) = self.get_dimensions();
+        let (image_width, image_height) = (image_width as f32, image_height as f32);
+        let (container_width, container_height) = container;
+
+        if image_width <= 0.0 || image_height <= 0.0 || container_width <= 0.0 || container_height <= 0.0 {
+            return (SourceRect::zero(), DestRect::zero());
+        }
+
+        let fits_into_container = image_width <= container_width && image_height <= container_height;
+        let contain_scale = (container_width / image_width).min(container_height / image_height);
+        let cover_scale = (container_width / image_width).max(container_height / image_height);
+
+        let (scale_x, scale_y) = match fit {
+            ObjectFit::Fill => (container_width / image_width, container_height / image_height),
+            ObjectFit::Contain => (contain_scale, contain_scale),
+            ObjectFit::Cover => (cover_scale, cover_scale),
+            ObjectFit::None => (1.0, 1.0),
+            ObjectFit::ScaleDown => if fits_into_container { (1.0, 1.0) } else { (contain_scale, contain_scale) },
+        };
+
+        let (source_x, source_width, dest_x, dest_width) = fit_axis(image_width, container_width, scale_x);
+        let (source_y, source_height, dest_y, dest_height) = fit_axis(image_height, container_height, scale_y);
+
+        (
+            SourceRect::new(LayoutPoint::new(source_x, source_y), LayoutSize::new(source_width, source_height)),
+            DestRect::new(LayoutPoint::new(dest_x, dest_y), LayoutSize::new(dest_width, dest_height)),
+        )
+    }
+}
+
+/// How an image is fitted into a container of a different size, see `ImageInfo::fit_rect`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ObjectFit {
+    /// The image is stretched to the size of the container, ignoring the aspect ratio
+    Fill,
+    /// The image is scaled to fit into the container, leaving empty space on two sides
+    Contain,
+    /// The image is scaled to cover the whole container, cropping it on two sides
+    Cover,
+    /// The image is drawn at its intrinsic size, cropping it if it's larger than the container
+    None,
+    /// Same as `None` if the image fits into the container, otherwise same as `Contain`
+    ScaleDown,
+}
+
+/// Visible part of an image, in image pixels, see `ImageInfo::fit_rect`
+pub type SourceRect = LayoutRect;
+/// Area that an image is drawn into, relative to its container, see `ImageInfo::fit_rect`
+pub type DestRect = LayoutRect;
+
+/// Centers the scaled image on one axis of the container and clips it to the container,
+/// returns `(source_offset, source_length, dest_offset, dest_length)`
+fn fit_axis(image_length: f32, container_length: f32, scale: f32) -> (f32, f32, f32, f32) {
+    let scaled_length = image_length * scale;
+    let offset = (container_length - scaled_length) / 2.0;
+    let dest_start = offset.max(0.0);
+    let dest_end = (offset + scaled_length).min(container_length);
+    ((dest_start - offset) / scale, (dest_end - dest_start) / scale, dest_start, dest_end - dest_start)
+}
+
+// The next three functions are taken from:
+// https://github.com/christolliday/limn/blob/master/core/src/resources/image.rs
+
+#[cfg(feature = "image_loading")]
+fn prepare_image(image_decoded: DynamicImage, options: ImageDecodeOptions)
+    -> Result<(ImageData, ImageDescriptor), ImageError>
+{
+    use image;
+    let image_dims = image_decoded.dimensions();
+
+    // see: https://github.com/servo/webrender/blob/80c614ab660bf6cca52594d0e33a0be262a7ac12/wrench/src/yaml_frame_reader.rs#L401-L427
+    //
+    // NOTE: The `DynamicImage` of the image crate only has 8-bit variants, the decoders
+    // reject 16-bit sources with `ImageError::UnsupportedColor` - 16-bit greyscale data
+    // has to be loaded via `RawImage::from_luma16` instead.
+    let (format, bytes) = match image_decoded {
+        image::ImageLuma8(bytes) => {
+            let pixels = bytes.into_raw();
+            (RawImageFormat::R8, pixels)
+        },
+        image::ImageLumaA8(bytes) => {
+            let mut pixels = Vec::with_capacity(image_dims.0 as usize * image_dims.1 as usize * 4);
+            for greyscale_alpha in bytes.chunks(2) {
+                let grey = greyscale_alpha[0];
+                let alpha = greyscale_alpha[1];
+                pixels.extend_from_slice(&[
+                    grey,
+                    grey,
+                    grey,
+                    alpha,
+                ]);
+            }
+            // TODO: necessary for greyscale?
+            premultiply_decoded(pixels.as_mut_slice(), options);
+            (RawImageFormat::BGRA8, pixels)
+        },
+        image::ImageRgba8(bytes) if options.keep_rgba8_images => {
+            // no swizzling necessary, the renderer accepts RGBA8
+            let mut pixels = bytes.into_raw();
+            premultiply_decoded(pixels.as_mut_slice(), options);
+            (RawImageFormat::RGBA8, pixels)
+        },
+        image::ImageRgba8(bytes) => {
+            let mut pixels = bytes.into_raw();
+            // no extra allocation necessary, but swizzling
+            for rgba in pixels.chunks_mut(4) {
+                let r = rgba[0];
+                let g = rgba[1];
+                let b = rgba[2];
+                let a = rgba[3];
+                rgba[0] = b;
+                rgba[1] = g;
+                rgba[2] = r;
+                rgba[3] = a;
+            }
+            premultiply_decoded(pixels.as_mut_slice(), options);
+            (RawImageFormat::BGRA8, pixels)
+        },
+        image::ImageRgb8(bytes) => {
+            let mut pixels = Vec::with_capacity(image_dims.0 as usize * image_dims.1 as usize * 4);
+            for rgb in bytes.chunks(3) {
+                pixels.extend_from_slice(&[
+                    rgb[2], // b
+                    rgb[1], // g
+                    rgb[0], // r
+                    0xff    // a
+                ]);
+            }
+            (RawImageFormat::BGRA8, pixels)
+        },
+        image::ImageBgr8(bytes) => {
+            let mut pixels = Vec::with_capacity(image_dims.0 as usize * image_dims.1 as usize * 4);
+            for bgr in bytes.chunks(3) {
+                pixels.extend_from_slice(&[
+                    bgr[0], // b
+                    bgr[1], // g
+                    bgr[2], // r
+                    0xff    // a
+                ]);
+            }
+            (RawImageFormat::BGRA8, pixels)
+        },
+        image::ImageBgra8(bytes) => {
+            // Already in the correct format
+            let mut pixels = bytes.into_raw();
+            premultiply_decoded(pixels.as_mut_slice(), options);
+            (RawImageFormat::BGRA8, pixels)
+        },
+    };
+
+    let opaque = is_image_opaque(format, &bytes[..]);
+    // Disabled per image when the image is uploaded, see `AppResources::set_image_mipmaps`
+    let allow_mipmaps = true;
+    let descriptor = ImageDescriptor::new(image_dims.0 as i32, image_dims.1 as i32, format, opaque, allow_mipmaps);
+    let data = ImageData::new(bytes);
+
+    Ok((data, descriptor))
+}
+
+/// Rounds a 16-bit channel value to 8 bits
+fn u16_to_u8(value: u16) -> u8 {
+    ((u32::from(value) + 128) / 257) as u8
+}
+
+/// Converts little-endian R16 pixels to R8, see `RawImage::from_luma16`
+fn r16_to_r8(pixels: &[u8]) -> Vec<u8> {
+    pixels.chunks_exact(2).map(|value| u16_to_u8(u16::from(value[0]) | (u16::from(value[1]) << 8))).collect()
+}
+
+fn is_image_opaque(format: RawImageFormat, bytes: &[u8]) -> bool {
+    match format {
+        RawImageFormat::BGRA8 | RawImageFormat::RGBA8 => {
+            let mut is_opaque = true;
+            for i in 0..(bytes.len() / 4) {
+                if bytes[i * 4 + 3] != 255 {
+                    is_opaque = false;
+                    break;
+                }
+            }
+            is_opaque
+        }
+        // No alpha channel
+        RawImageFormat::R8 | RawImageFormat::RG8 | RawImageFormat::R16 => true,
+        // Float / integer formats are rare, assume that they're transparent
+        _ => false,
+    }
+}
+
+/// Premultiplies decoded images, see `AppConfig::premultiply_in_linear_light`
+fn premultiply_decoded(data: &mut [u8], options: ImageDecodeOptions) {
+    if options.premultiply_in_linear_light {
+        premultiply_linear(data);
+    } else {
+        premultiply(data);
+    }
+}
+
+/// Premultiplies BGRA8 / RGBA8 pixels, using SSE2 on x86 (four pixels at a time) and
+/// `premultiply_scalar` for the remaining pixels and on other architectures
+fn premultiply(data: &mut [u8]) {
+    #[cfg(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse2")))] {
+        let simd_len = data.len() - data.len() % 16;
+        let (simd, rest) = data.split_at_mut(simd_len);
+        premultiply_sse2(simd);
+        premultiply_scalar(rest);
+    }
+    #[cfg(not(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse2"))))] {
+        premultiply_scalar(data);
+    }
+}
+
+// From webrender/wrench
+fn premultiply_scalar(data: &mut [u8]) {
+    for pixel in data.chunks_mut(4) {
+        let a = u32::from(pixel[3]);
+        pixel[0] = (((pixel[0] as u32 * a) + 128) / 255) as u8;
+        pixel[1] = (((pixel[1] as u32 * a) + 128) / 255) as u8;
+        pixel[2] = (((pixel[2] as u32 * a) + 128) / 255) as u8;
+    }
+}
+
+/// Premultiplies four pixels per iteration, `data.len()` has to be a multiple of 16.
+/// Produces the same output as `premultiply_scalar`, since `x / 255 == (x + (x >> 8) + 1) >> 8`
+/// for all `x = color * alpha + 128` (at most 65153, so the sum can't overflow 16 bits).
+#[cfg(any(target_arch = "x86_64", all(target_arch = "x86", target_feature = "sse2")))]
+fn premultiply_sse2(data: &mut [u8]) {
+
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    // SSE2 is always available on x86_64 (and statically enabled on x86, see the cfg above)
+    unsafe {
+        let zero = _mm_setzero_si128();
+        let rounding = _mm_set1_epi16(128);
+        let one = _mm_set1_epi16(1);
+        // Selects the alpha channel of the two pixels in each 16-bit half
+        let alpha_mask = _mm_set_epi16(-1, 0, 0, 0, -1, 0, 0, 0);
+
+        for chunk in data.chunks_exact_mut(16) {
+            let pixels = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+            let mut halves = [_mm_unpacklo_epi8(pixels, zero), _mm_unpackhi_epi8(pixels, zero)];
+            for half in halves.iter_mut() {
+                let alpha = _mm_shufflehi_epi16(_mm_shufflelo_epi16(*half, 0xff), 0xff);
+                let product = _mm_add_epi16(_mm_mullo_epi16(*half, alpha), rounding);
+                let divided = _mm_srli_epi16(_mm_add_epi16(_mm_add_epi16(product, _mm_srli_epi16(product, 8)), one), 8);
+                *half = _mm_or_si128(_mm_andnot_si128(alpha_mask, divided), _mm_and_si128(alpha_mask, *half));
+            }
+            _mm_storeu_si128(chunk.as_mut_ptr() as *mut __m128i, _mm_packus_epi16(halves[0], halves[1]));
+        }
+    }
+}
+
+/// Same as `premultiply`, but multiplies the color channels with the alpha in linear light
+/// instead of in sRGB space, which results in the same edges as in a browser (less dark fringes
+/// at semi-transparent edges). The alpha channel is not gamma-encoded and left as-is.
+fn premultiply_linear(data: &mut [u8]) {
+
+    let srgb_to_linear = (0..256).map(|c| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    }).collect::<Vec<f32>>();
+
+    let linear_to_srgb = |c: f32| {
+        let c = if c <= 0.003_130_8 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+        (c * 255.0).round().max(0.0).min(255.0) as u8
+    };
+
+    for pixel in data.chunks_mut(4) {
+        let a = f32::from(pixel[3]) / 255.0;
+        pixel[0] = linear_to_srgb(srgb_to_linear[pixel[0] as usize] * a);
+        pixel[1] = linear_to_srgb(srgb_to_linear[pixel[1] as usize] * a);
+        pixel[2] = linear_to_srgb(srgb_to_linear[pixel[2] as usize] * a);
+    }
+}
+
+/// Averages BGRA8, RGBA8 or R8 pixels, see `RawImage::average_color`
+fn average_color(pixels: &[u8], format: RawImageFormat, premultiplied: bool) -> (u8, u8, u8, u8) {
+    match format {
+        RawImageFormat::BGRA8 | RawImageFormat::RGBA8 => {
+            let (r_idx, b_idx) = if format == RawImageFormat::RGBA8 { (0, 2) } else { (2, 0) };
+            let mut sum_r = 0_u64;
+            let mut sum_g = 0_u64;
+            let mut sum_b = 0_u64;
+            let mut sum_a = 0_u64;
+            let mut pixel_count = 0_u64;
+
+            for pixel in pixels.chunks_exact(4) {
+                let a = u64::from(pixel[3]);
+                // Weight each color by its alpha - premultiplied colors already are
+                let weight = if premultiplied { 255 } else { a };
+                sum_b += u64::from(pixel[b_idx]) * weight;
+                sum_g += u64::from(pixel[1]) * weight;
+                sum_r += u64::from(pixel[r_idx]) * weight;
+                sum_a += a;
+                pixel_count += 1;
+            }
+
+            if sum_a == 0 {
+                return (0, 0, 0, 0);
+            }
+
+            (
+                (sum_r / sum_a).min(255) as u8,
+                (sum_g / sum_a).min(255) as u8,
+                (sum_b / sum_a).min(255) as u8,
+                (sum_a / pixel_count) as u8,
+            )
+        },
+        RawImageFormat::R8 => {
+            if pixels.is_empty() {
+                return (0, 0, 0, 0);
+            }
+            let sum: u64 = pixels.iter().map(|p| u64::from(*p)).sum();
+            let grey = (sum / pixels.len() as u64) as u8;
+            (grey, grey, grey, 255)
+        },
+        _ => (0, 0, 0, 0),
+    }
+}
+
+/// Returns the Rec. 709 luminance of a color, using integer weights that sum up to 256
+fn luminance(r: u8, g: u8, b: u8) -> u8 {
+    ((u32::from(r) * 54 + u32::from(g) * 183 + u32::from(b) * 19) >> 8) as u8
+}
+
+/// Finds the most common color of BGRA8, RGBA8 or R8 pixels, see `RawImage::dominant_color`
+fn dominant_color(pixels: &[u8], format: RawImageFormat) -> (u8, u8, u8) {
+
+    // (pixel count, sum of red, sum of green, sum of blue) for each bucket
+    let mut buckets = vec![(0_u64, 0_u64, 0_u64, 0_u64); 16 * 16 * 16];
+
+    let mut insert_pixel = |r: u8, g: u8, b: u8| {
+        let bucket_idx = (usize::from(r >> 4) << 8) | (usize::from(g >> 4) << 4) | usize::from(b >> 4);
+        let bucket = &mut buckets[bucket_idx];
+        bucket.0 += 1;
+        bucket.1 += u64::from(r);
+        bucket.2 += u64::from(g);
+        bucket.3 += u64::from(b);
+    };
+
+    match format {
+        RawImageFormat::BGRA8 => {
+            for bgra in pixels.chunks_exact(4).filter(|bgra| bgra[3] != 0) {
+                insert_pixel(bgra[2], bgra[1], bgra[0]);
+            }
+        },
+        RawImageFormat::RGBA8 => {
+            for rgba in pixels.chunks_exact(4).filter(|rgba| rgba[3] != 0) {
+                insert_pixel(rgba[0], rgba[1], rgba[2]);
+            }
+        },
+        RawImageFormat::R8 => {
+            for grey in pixels {
+                insert_pixel(*grey, *grey, *grey);
+            }
+        },
+        _ => { },
+    }
+
+    match buckets.iter().filter(|bucket| bucket.0 != 0).max_by_key(|bucket| bucket.0) {
+        Some((count, r, g, b)) => ((r / count) as u8, (g / count) as u8, (b / count) as u8),
+        None => (0, 0, 0),
+    }
+}
+
+#[test]
+fn test_average_and_dominant_color() {
+    let image = RawImage {
+        pixels: vec![
+            0, 0, 255, 255, // red, opaque
+            0, 0, 255, 255, // red, opaque
+            255, 0, 0, 0,   // blue, fully transparent
+        ],
+        image_dimensions: (3, 1),
+        data_format: RawImageFormat::BGRA8,
+        alpha_premultiplied: false,
+        allow_mipmaps: true,
+    };
+    assert_eq!(image.average_color(), (255, 0, 0, 170));
+    assert_eq!(image.dominant_color(), (255, 0, 0));
+}
+
+#[test]
+fn test_rgba8_matches_bgra8() {
+    let bgra = RawImage {
+        pixels: vec![
+            10, 20, 30, 255,
+            0, 40, 80, 128,
+        ],
+        image_dimensions: (2, 1),
+        data_format: RawImageFormat::BGRA8,
+        alpha_premultiplied: false,
+        allow_mipmaps: true,
+    };
+    let rgba = RawImage {
+        pixels: vec![
+            30, 20, 10, 255,
+            80, 40, 0, 128,
+        ],
+        image_dimensions: (2, 1),
+        data_format: RawImageFormat::RGBA8,
+        alpha_premultiplied: false,
+        allow_mipmaps: true,
+    };
+
+    assert_eq!(rgba.to_straight_alpha_rgba8(), bgra.to_straight_alpha_rgba8());
+    assert_eq!(rgba.average_color(), bgra.average_color());
+    assert_eq!(rgba.dominant_color(), bgra.dominant_color());
+    assert_eq!(rgba.luminance_histogram()[..], bgra.luminance_histogram()[..]);
+    assert_eq!(rgba.alpha_mask(200), vec![true, false]);
+}
+
+#[test]
+fn test_raw_image_opaque_detection() {
+    let rgba = RawImage::new(2, 1, RawImageFormat::RGBA8, vec![0, 0, 0, 255, 0, 0, 0, 255]).unwrap();
+    let (_, descriptor) = ImageSource::Raw(rgba).get_bytes().unwrap();
+    assert!(descriptor.is_opaque);
+
+    let rgba = RawImage::new(2, 1, RawImageFormat::RGBA8, vec![0, 0, 0, 255, 0, 0, 0, 0]).unwrap();
+    let (_, descriptor) = ImageSource::Raw(rgba).get_bytes().unwrap();
+    assert!(!descriptor.is_opaque);
+
+    let rg = RawImage::new(1, 1, RawImageFormat::RG8, vec![0, 0]).unwrap();
+    let (_, descriptor) = ImageSource::Raw(rg).get_bytes().unwrap();
+    assert!(descriptor.is_opaque);
+
+    let float = RawImage::new(1, 1, RawImageFormat::RGBAF32, vec![0; 16]).unwrap();
+    let (_, descriptor) = ImageSource::Raw(float).get_bytes().unwrap();
+    assert!(!descriptor.is_opaque);
+}
+
+#[test]
+fn test_add_image_raw() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let image_id = app_resources.new_image_id();
+
+    let invalid = RawImage { pixels: vec![0; 7], image_dimensions: (2, 1), data_format: RawImageFormat::BGRA8, alpha_premultiplied: false, allow_mipmaps: true };
+    assert_eq!(app_resources.add_image_raw(image_id, invalid), Err(RawImageError::InvalidPixelBufferLength { expected: 8, actual: 7 }));
+    assert!(!app_resources.has_image(&image_id));
+
+    let valid = RawImage { pixels: vec![0; 8], image_dimensions: (2, 1), data_format: RawImageFormat::BGRA8, alpha_premultiplied: false, allow_mipmaps: true };
+    assert_eq!(app_resources.add_image_raw(image_id, valid), Ok(()));
+    assert!(app_resources.has_image(&image_id));
+}
+
+#[test]
+fn test_update_image_region() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let image_id = app_resources.new_image_id();
+    app_resources.add_image_raw(image_id, RawImage::new(2, 2, RawImageFormat::R8, vec![0; 4]).unwrap()).unwrap();
+
+    assert_eq!(app_resources.update_image_region(image_id, (1, 0, 1, 2), &[7, 8]), Ok(()));
+    match app_resources.image_sources.get(&image_id) {
+        Some(ImageSource::Raw(raw_image)) => assert_eq!(raw_image.pixels, vec![0, 7, 0, 8]),
+        _ => panic!("expected a raw image"),
+    }
+
+    assert_eq!(
+        app_resources.update_image_region(image_id, (1, 0, 1, 2), &[7]),
+        Err(ImageUpdateError::InvalidPixelBufferLength { expected: 2, actual: 1 })
+    );
+
+    // The pixels of the image don't match its dimensions anymore
+    if let Some(ImageSource::Raw(raw_image)) = app_resources.image_sources.get_mut(&image_id) {
+        raw_image.pixels.truncate(3);
+    }
+    assert_eq!(
+        app_resources.update_image_region(image_id, (1, 0, 1, 2), &[7, 8]),
+        Err(ImageUpdateError::InvalidImage(image_id, RawImageError::InvalidPixelBufferLength { expected: 4, actual: 3 }))
+    );
+}
+
+#[test]
+fn test_bitmap_font_atlas() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+
+    let mut glyphs = BTreeMap::new();
+    glyphs.insert('a', RawImage::new(3, 2, RawImageFormat::R8, vec![200; 6]).unwrap());
+    glyphs.insert('b', RawImage::new(1, 3, RawImageFormat::RGBA8, vec![10, 20, 30, 255, 10, 20, 30, 255, 10, 20, 30, 255]).unwrap());
+    // Wider than an atlas, gets an atlas of its own
+    glyphs.insert('w', RawImage::new(1100, 1, RawImageFormat::R8, vec![0; 1100]).unwrap());
+
+    let bitmap_font = app_resources.add_bitmap_font(glyphs);
+    let a = *bitmap_font.get_glyph('a').unwrap();
+    let b = *bitmap_font.get_glyph('b').unwrap();
+    let w = *bitmap_font.get_glyph('w').unwrap();
+
+    assert_eq!(a.atlas_id, b.atlas_id);
+    assert!(w.atlas_id != a.atlas_id);
+    assert_eq!(app_resources.get_atlas_glyph(&a.image_id), Some(&AtlasGlyph { atlas_id: a.atlas_id, rect: (a.atlas_offset.0, a.atlas_offset.1, 3, 2) }));
+    assert_eq!(app_resources.get_image_dimensions(&a.image_id), Some((3, 2)));
+    assert_eq!(app_resources.get_image_dimensions(&b.image_id), Some((1, 3)));
+
+    // Both glyphs fit on the first shelf, separated by the padding
+    assert_eq!(b.atlas_offset, (0, 0));
+    assert_eq!(a.atlas_offset, (2, 0));
+
+    let pixel_at = |atlas: &RawImage, (x, y): (u32, u32)| {
+        let start = ((y * atlas.image_dimensions.0 + x) * 4) as usize;
+        atlas.pixels[start..start + 4].to_vec()
+    };
+
+    match app_resources.image_sources.get(&a.atlas_id) {
+        Some(ImageSource::Raw(atlas)) => {
+            assert_eq!(atlas.image_dimensions, (1024, 4));
+            assert_eq!(atlas.data_format, RawImageFormat::BGRA8);
+            assert_eq!(pixel_at(atlas, (0, 2)), vec![30, 20, 10, 255]);
+            assert_eq!(pixel_at(atlas, (4, 1)), vec![200, 200, 200, 255]);
+            assert_eq!(pixel_at(atlas, (1, 0)), vec![0, 0, 0, 0]);
+        },
+        _ => panic!("the atlas of the bitmap font is not a raw image"),
+    }
+
+    match app_resources.image_sources.get(&w.atlas_id) {
+        Some(ImageSource::Raw(atlas)) => assert_eq!(atlas.image_dimensions, (1101, 2)),
+        _ => panic!("the atlas of the bitmap font is not a raw image"),
+    }
+
+    app_resources.delete_bitmap_font(&bitmap_font);
+    assert!(app_resources.get_atlas_glyph(&a.image_id).is_none());
+    assert!(!app_resources.image_sources.contains_key(&a.atlas_id));
+    assert!(!app_resources.image_sources.contains_key(&w.atlas_id));
+}
+
+#[test]
+fn test_image_load_errors() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let (broken, valid) = (app_resources.new_image_id(), app_resources.new_image_id());
+    app_resources.image_sources.insert(broken, ImageSource::Callback(ImageCallback(Arc::new(|| None))));
+    app_resources.add_image_raw(valid, RawImage::new(1, 1, RawImageFormat::R8, vec![0]).unwrap()).unwrap();
+
+    let mut images = FastHashSet::default();
+    images.insert(broken);
+    images.insert(valid);
+
+    let mut load_errors = Vec::new();
+    let added = build_add_image_resource_updates(&mut app_resources, &images, &mut load_errors);
+    assert_eq!(added.len(), 1);
+    assert_eq!(added[0].0, valid);
+
+    match load_errors.as_slice() {
+        [(ResourceWarningId::Image(id), ResourceLoadError::Image(ImageReloadError::NoCallbackImage))] => assert_eq!(*id, broken),
+        other => panic!("unexpected load errors: {:?}", other),
+    }
+}
+
+#[test]
+fn test_callback_image_alpha_type() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let image_id = app_resources.new_image_id();
+    let premultiplied = Arc::new(AtomicBool::new(true));
+    let callback_premultiplied = premultiplied.clone();
+    app_resources.image_sources.insert(image_id, ImageSource::Callback(ImageCallback(Arc::new(move || {
+        let mut raw_image = RawImage::new(1, 1, RawImageFormat::BGRA8, vec![64, 64, 64, 128]).unwrap();
+        raw_image.alpha_premultiplied = callback_premultiplied.load(Ordering::SeqCst);
+        Some(raw_image)
+    }))));
+
+    let mut images = FastHashSet::default();
+    images.insert(image_id);
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+    assert_eq!(app_resources.get_image_info(&image_id).unwrap().alpha_type, AlphaType::PremultipliedAlpha);
+
+    // The next image of the callback is straight alpha
+    premultiplied.store(false, Ordering::SeqCst);
+    app_resources.begin_frame();
+    app_resources.update_callback_images(&images);
+    assert_eq!(app_resources.get_image_info(&image_id).unwrap().alpha_type, AlphaType::Alpha);
+}
+
+#[test]
+fn test_shared_image_keys() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let (first, duplicate, other) = (app_resources.new_image_id(), app_resources.new_image_id(), app_resources.new_image_id());
+
+    app_resources.add_image_raw(first, RawImage::new(1, 1, RawImageFormat::R8, vec![7]).unwrap()).unwrap();
+    app_resources.add_image_raw(duplicate, RawImage::new(1, 1, RawImageFormat::R8, vec![7]).unwrap()).unwrap();
+    app_resources.add_image_raw(other, RawImage::new(1, 1, RawImageFormat::R8, vec![8]).unwrap()).unwrap();
+
+    let mut images = FastHashSet::default();
+    images.insert(first);
+    images.insert(duplicate);
+    images.insert(other);
+
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+
+    // The duplicate image is only uploaded once
+    let uploads = app_resources.take_resource_updates();
+    let uploaded_keys = uploads.iter().filter_map(|u| match u { FakeResourceUpdate::AddImage(key) => Some(*key), _ => None }).collect::<Vec<_>>();
+    assert_eq!(uploaded_keys.len(), 2);
+    let first_key = app_resources.get_image_info(&first).unwrap().key;
+    assert_eq!(app_resources.get_image_info(&duplicate).unwrap().key, first_key);
+    assert!(app_resources.get_image_info(&other).unwrap().key != first_key);
+
+    // Deleting one of the duplicates keeps the shared key alive
+    let delete_first = vec![(first, DeleteImageMsg(first_key, *app_resources.get_image_info(&first).unwrap()))];
+    delete_resources(&mut app_resources, Vec::new(), delete_first);
+    assert_eq!(app_resources.take_resource_updates(), Vec::new());
+
+    let delete_duplicate = vec![(duplicate, DeleteImageMsg(first_key, *app_resources.get_image_info(&duplicate).unwrap()))];
+    delete_resources(&mut app_resources, Vec::new(), delete_duplicate);
+    assert_eq!(app_resources.take_resource_updates(), vec![FakeResourceUpdate::DeleteImage(first_key)]);
+}
+
+#[test]
+fn test_shared_image_key_hash_collision() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let (first, second) = (app_resources.new_image_id(), app_resources.new_image_id());
+
+    app_resources.add_image_raw(first, RawImage::new(2, 1, RawImageFormat::R8, vec![7, 7]).unwrap()).unwrap();
+    app_resources.add_image_raw(second, RawImage::new(1, 1, RawImageFormat::R8, vec![8]).unwrap()).unwrap();
+
+    let mut images = FastHashSet::default();
+    images.insert(first);
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+    let first_info = *app_resources.get_image_info(&first).unwrap();
+
+    // Pretend that the second image has the same content hash as the first image
+    let first_hash = app_resources.image_content_hashes[&first];
+    let second_descriptor = ImageDescriptor { size: DeviceUintSize::new(1, 1), .. first_info.descriptor };
+    let second_hash = image_content_hash(&ImageData::new(vec![8]), &second_descriptor).unwrap();
+    let shared_key = app_resources.shared_image_keys.remove(&first_hash).unwrap();
+    app_resources.shared_image_keys.insert(second_hash, shared_key);
+    app_resources.image_content_hashes.insert(first, second_hash);
+    app_resources.take_resource_updates();
+
+    let mut images = FastHashSet::default();
+    images.insert(second);
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+
+    // The size differs, so the second image is uploaded under its own key
+    let second_key = app_resources.get_image_info(&second).unwrap().key;
+    assert!(second_key != first_info.key);
+    assert_eq!(app_resources.take_resource_updates(), vec![FakeResourceUpdate::AddImage(second_key)]);
+
+    let delete_second = vec![(second, DeleteImageMsg(second_key, *app_resources.get_image_info(&second).unwrap()))];
+    delete_resources(&mut app_resources, Vec::new(), delete_second);
+    assert_eq!(app_resources.take_resource_updates(), vec![FakeResourceUpdate::DeleteImage(second_key)]);
+    assert_eq!(app_resources.shared_image_keys[&second_hash].image_count, 1);
+}
 
-/// Returns the font + the index of the font (in case the font is a collection)
-fn load_system_font(id: &str) -> Option<(Vec<u8>, i32)> {
-    use font_loader::system_fonts::{self, FontPropertyBuilder};
+#[test]
+fn test_pinned_images_survive_gc() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let (pinned, unpinned) = (app_resources.new_image_id(), app_resources.new_image_id());
 
-    let font_builder = match id {
-        "monospace" => {
-            #[cfg(target_os = "linux")] {
-                let native_monospace_font = linux_get_native_font(LinuxNativeFontType::Monospace);
-                FontPropertyBuilder::new().family(&native_monospace_font)
-            }
-            #[cfg(not(target_os = "linux"))] {
-                FontPropertyBuilder::new().monospace()
-            }
-        },
-        "fantasy" => FontPropertyBuilder::new().oblique(),
-        "sans-serif" => {
-            #[cfg(target_os = "mac_os")] {
-                FontPropertyBuilder::new().family("Helvetica")
-            }
-            #[cfg(target_os = "linux")] {
-                let native_sans_serif_font = linux_get_native_font(LinuxNativeFontType::SansSerif);
-                FontPropertyBuilder::new().family(&native_sans_serif_font)
-            }
-            #[cfg(all(not(target_os = "linux"), not(target_os = "mac_os")))] {
-                FontPropertyBuilder::new().family("Segoe UI")
-            }
-        },
-        "serif" => {
-            FontPropertyBuilder::new().family("Times New Roman")
-        },
-        other => FontPropertyBuilder::new().family(other)
+    app_resources.add_image_raw(pinned, RawImage::new(1, 1, RawImageFormat::R8, vec![1]).unwrap()).unwrap();
+    app_resources.add_image_raw(unpinned, RawImage::new(1, 1, RawImageFormat::R8, vec![2]).unwrap()).unwrap();
+
+    let mut images = FastHashSet::default();
+    images.insert(pinned);
+    images.insert(unpinned);
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+
+    // Pinned twice, so one unpin isn't enough
+    app_resources.pin_image(pinned);
+    app_resources.pin_image(pinned);
+    app_resources.unpin_image(pinned);
+    app_resources.unpin_image(unpinned);
+
+    // Neither image is used in the next frame
+    app_resources.garbage_collect_fonts_and_images();
+    assert!(app_resources.get_image_info(&pinned).is_some());
+    assert!(app_resources.get_image_info(&unpinned).is_none());
+
+    app_resources.unpin_image(pinned);
+    assert!(!app_resources.is_image_pinned(&pinned));
+    app_resources.garbage_collect_fonts_and_images();
+    assert!(app_resources.get_image_info(&pinned).is_none());
+}
+
+#[test]
+fn test_gc_policy_frame_ttl() {
+    let app_config = AppConfig { gc_policy: GcPolicy::FrameTtl(2), .. AppConfig::default() };
+    let mut app_resources = AppResources::new(&app_config).unwrap();
+    let image_id = app_resources.new_image_id();
+
+    app_resources.add_image_raw(image_id, RawImage::new(1, 1, RawImageFormat::R8, vec![1]).unwrap()).unwrap();
+    let mut images = FastHashSet::default();
+    images.insert(image_id);
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+
+    // Used in frame 0, not used in frames 1 and 2
+    app_resources.last_frame_image_keys.insert(image_id);
+    app_resources.image_last_used_frame.insert(image_id, app_resources.get_current_frame());
+    app_resources.garbage_collect_fonts_and_images();
+    app_resources.begin_frame();
+    app_resources.garbage_collect_fonts_and_images();
+    assert!(app_resources.get_image_info(&image_id).is_some());
+    app_resources.begin_frame();
+    app_resources.garbage_collect_fonts_and_images();
+    assert!(app_resources.get_image_info(&image_id).is_none());
+}
+
+#[test]
+fn test_gc_deletes_font_missing_from_last_frame() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let font_id = ImmediateFontId::Resolved(app_resources.new_font_id());
+    let font_key = FontKey::new(IdNamespace(0), 0);
+    let font_instance_key = FontInstanceKey::new(IdNamespace(0), 1);
+    let instance_spec = FontInstanceSpec::new(Au::from_px(12));
+
+    let mut loaded_font = LoadedFont::new(font_key, Vec::new(), 0);
+    loaded_font.font_instances.insert(instance_spec.clone(), font_instance_key);
+    app_resources.currently_registered_fonts.insert(font_id.clone(), loaded_font);
+
+    // Frame N: the font is used
+    let mut used_instances = FastHashSet::default();
+    used_instances.insert(instance_spec.clone());
+    app_resources.last_frame_font_keys.insert(font_id.clone(), used_instances);
+    app_resources.font_last_used_frame.insert(font_id.clone(), app_resources.get_current_frame());
+    app_resources.garbage_collect_fonts_and_images();
+    assert!(app_resources.currently_registered_fonts.contains_key(&font_id));
+    app_resources.begin_frame();
+
+    // Frame N + 1: the font has no entry in last_frame_font_keys at all
+    app_resources.garbage_collect_fonts_and_images();
+    assert!(!app_resources.currently_registered_fonts.contains_key(&font_id));
+    assert_eq!(app_resources.take_resource_updates(), vec![
+        FakeResourceUpdate::DeleteFontInstance(font_instance_key),
+        FakeResourceUpdate::DeleteFont(font_key),
+    ]);
+}
+
+#[test]
+fn test_decoded_image_cache() {
+    let app_config = AppConfig { decoded_image_cache_bytes: Some(2), .. AppConfig::default() };
+    let mut app_resources = AppResources::new(&app_config).unwrap();
+    let (first, second, third) = (app_resources.new_image_id(), app_resources.new_image_id(), app_resources.new_image_id());
+
+    for (image_id, pixel) in [(first, 1), (second, 2), (third, 3)].iter() {
+        app_resources.add_image_raw(*image_id, RawImage::new(1, 1, RawImageFormat::R8, vec![*pixel]).unwrap()).unwrap();
+    }
+
+    assert!(app_resources.get_cached_image_bytes(&first).unwrap().is_ok());
+    assert!(app_resources.get_cached_image_bytes(&second).unwrap().is_ok());
+    assert!(app_resources.get_cached_image_bytes(&first).unwrap().is_ok());
+    assert_eq!(app_resources.cache_stats().decoded_image_hits, 1);
+    assert_eq!(app_resources.cache_stats().decoded_image_misses, 2);
+
+    // The budget only fits two images, the least recently used one is evicted
+    assert!(app_resources.get_cached_image_bytes(&third).unwrap().is_ok());
+    let cache = app_resources.decoded_image_cache.as_ref().unwrap();
+    assert!(cache.images.contains_key(&first));
+    assert!(!cache.images.contains_key(&second));
+    assert_eq!(cache.current_bytes, 2);
+
+    app_resources.delete_image(&first);
+    assert!(!app_resources.decoded_image_cache.as_ref().unwrap().images.contains_key(&first));
+}
+
+#[test]
+fn test_registered_font_sizes() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let font_id = app_resources.new_font_id();
+    app_resources.css_ids_to_font_ids.insert(String::from("Roboto"), font_id);
+
+    let mut resolved = LoadedFont::new(FontKey::new(IdNamespace(0), 0), Vec::new(), 0);
+    resolved.font_instances.insert(FontInstanceSpec::new(Au::from_px(16)), FontInstanceKey::new(IdNamespace(0), 1));
+    resolved.font_instances.insert(FontInstanceSpec { synthetic_bold: true, .. FontInstanceSpec::new(Au::from_px(12)) }, FontInstanceKey::new(IdNamespace(0), 2));
+    let mut unresolved = LoadedFont::new(FontKey::new(IdNamespace(0), 3), Vec::new(), 0);
+    unresolved.font_instances.insert(FontInstanceSpec::new(Au::from_px(12)), FontInstanceKey::new(IdNamespace(0), 4));
+
+    assert_eq!(app_resources.get_registered_font_sizes(&font_id), None);
+    app_resources.currently_registered_fonts.insert(ImmediateFontId::Resolved(font_id), resolved);
+    app_resources.currently_registered_fonts.insert(ImmediateFontId::Unresolved(String::from("Roboto")), unresolved);
+    assert_eq!(app_resources.get_registered_font_sizes(&font_id), Some(vec![Au::from_px(12), Au::from_px(16)]));
+}
+
+#[test]
+fn test_font_content_id() {
+    let font = LoadedFont::new(FontKey::new(IdNamespace(0), 0), b"a".to_vec(), 0);
+    let other_key = LoadedFont::new(FontKey::new(IdNamespace(0), 1), b"a".to_vec(), 0);
+    let other_index = LoadedFont::new(FontKey::new(IdNamespace(0), 0), b"a".to_vec(), 1);
+    // FNV-1a of "a\0\0\0\0"
+    assert_eq!(font.content_id(&[]), 0x6de9_d1fe_51c9_cb4c);
+    assert_eq!(font.content_id(&[]), other_key.content_id(&[]));
+    assert!(font.content_id(&[]) != other_index.content_id(&[]));
+
+    // The variation coordinates are part of the id, their order isn't
+    let wght = 0x7767_6874; // "wght"
+    let wdth = 0x7764_7468; // "wdth"
+    let bold = font.content_id(&[(wght, 700.0)]);
+    assert!(bold != font.content_id(&[]));
+    assert!(bold != font.content_id(&[(wght, 400.0)]));
+    assert_eq!(font.content_id(&[(wght, 700.0), (wdth, 75.0)]), font.content_id(&[(wdth, 75.0), (wght, 700.0)]));
+
+    // The id survives dropping the bytes after the upload
+    let mut dropped = font.clone();
+    dropped.font_bytes = Vec::new();
+    assert_eq!(dropped.content_id(&[(wght, 700.0)]), bold);
+}
+
+#[test]
+fn test_image_fit_rect() {
+    let image_info = ImageInfo {
+        key: ImageKey::DUMMY,
+        descriptor: ImageDescriptor::new(200, 100, RawImageFormat::BGRA8, true, false),
+        alpha_type: AlphaType::PremultipliedAlpha,
+        nine_patch_insets: None,
     };
+    let rect = |x, y, w, h| LayoutRect::new(LayoutPoint::new(x, y), LayoutSize::new(w, h));
+    let container = (100.0, 100.0);
+
+    assert_eq!(image_info.fit_rect(container, ObjectFit::Fill), (rect(0.0, 0.0, 200.0, 100.0), rect(0.0, 0.0, 100.0, 100.0)));
+    assert_eq!(image_info.fit_rect(container, ObjectFit::Contain), (rect(0.0, 0.0, 200.0, 100.0), rect(0.0, 25.0, 100.0, 50.0)));
+    assert_eq!(image_info.fit_rect(container, ObjectFit::Cover), (rect(50.0, 0.0, 100.0, 100.0), rect(0.0, 0.0, 100.0, 100.0)));
+    assert_eq!(image_info.fit_rect(container, ObjectFit::None), (rect(50.0, 0.0, 100.0, 100.0), rect(0.0, 0.0, 100.0, 100.0)));
+    assert_eq!(image_info.fit_rect(container, ObjectFit::ScaleDown), image_info.fit_rect(container, ObjectFit::Contain));
+    assert_eq!(image_info.fit_rect((400.0, 400.0), ObjectFit::ScaleDown), (rect(0.0, 0.0, 200.0, 100.0), rect(100.0, 150.0, 200.0, 100.0)));
+}
 
-    system_fonts::get(&font_builder.build())
+#[test]
+fn test_raw_image_new() {
+    assert!(RawImage::new(2, 2, RawImageFormat::BGRA8, vec![0; 16]).is_ok());
+    assert!(RawImage::new(2, 2, RawImageFormat::R8, vec![0; 4]).is_ok());
+    assert_eq!(
+        RawImage::new(2, 2, RawImageFormat::BGRA8, vec![0; 15]),
+        Err(RawImageError::InvalidPixelBufferLength { expected: 16, actual: 15 })
+    );
 }
 
-/// Return the native fonts
-#[cfg(target_os = "linux")]
-enum LinuxNativeFontType { SansSerif, Monospace }
+#[cfg(feature = "icc")]
+#[test]
+fn test_icc_profile() {
 
-#[cfg(target_os = "linux")]
-fn linux_get_native_font(font_type: LinuxNativeFontType) -> String {
+    fn s15_fixed16(value: f32) -> [u8;4] {
+        let bits = (value * 65536.0).round() as i32 as u32;
+        [(bits >> 24) as u8, (bits >> 16) as u8, (bits >> 8) as u8, bits as u8]
+    }
 
-    use std::process::Command;
-    use self::LinuxNativeFontType::*;
+    fn be_u32(value: u32) -> [u8;4] {
+        [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+    }
 
-    let font_name = match font_type {
-        SansSerif => "font-name",
-        Monospace => "monospace-font-name",
+    // Minimal sRGB matrix / TRC profile (D50-adapted primaries, parametric sRGB curve)
+    let primaries: [(&[u8], [f32;3]);3] = [
+        (b"rXYZ", [0.436_066, 0.222_488, 0.013_916]),
+        (b"gXYZ", [0.385_147, 0.716_873, 0.097_088]),
+        (b"bXYZ", [0.143_066, 0.060_608, 0.714_096]),
+    ];
+
+    let mut tag_data = Vec::new();
+    let mut tag_table = Vec::new();
+    let data_start = 132 + 6 * 12;
+
+    for (signature, xyz) in primaries.iter() {
+        tag_table.push((*signature, data_start + tag_data.len(), 20));
+        tag_data.extend_from_slice(b"XYZ \0\0\0\0");
+        for v in xyz.iter() {
+            tag_data.extend_from_slice(&s15_fixed16(*v));
+        }
+    }
+
+    let curve_offset = data_start + tag_data.len();
+    tag_data.extend_from_slice(b"para\0\0\0\0\0\x03\0\0");
+    for v in [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.040_45].iter() {
+        tag_data.extend_from_slice(&s15_fixed16(*v));
+    }
+    for signature in [b"rTRC", b"gTRC", b"bTRC"].iter() {
+        tag_table.push((&signature[..], curve_offset, 32));
+    }
+
+    let mut profile = vec![0; 128];
+    profile[16..20].copy_from_slice(b"RGB ");
+    profile.extend_from_slice(&be_u32(tag_table.len() as u32));
+    for (signature, offset, size) in tag_table {
+        profile.extend_from_slice(signature);
+        profile.extend_from_slice(&be_u32(offset as u32));
+        profile.extend_from_slice(&be_u32(size));
+    }
+    profile.extend_from_slice(&tag_data);
+
+    // Embed the profile in two APP2 chunks, in reverse order
+    let (first, second) = profile.split_at(100);
+    let mut jpeg = vec![0xFF, 0xD8];
+    for (sequence_number, chunk) in [(2, second), (1, first)].iter() {
+        let length = 2 + 14 + chunk.len();
+        jpeg.extend_from_slice(&[0xFF, 0xE2, (length >> 8) as u8, length as u8]);
+        jpeg.extend_from_slice(b"ICC_PROFILE\0");
+        jpeg.extend_from_slice(&[*sequence_number, 2]);
+        jpeg.extend_from_slice(chunk);
+    }
+    jpeg.extend_from_slice(&[0xFF, 0xDA]);
+
+    let extracted = extract_jpeg_icc_profile(&jpeg).unwrap();
+    assert_eq!(extracted, profile);
+    assert_eq!(extract_jpeg_icc_profile(&[0xFF, 0xD8, 0xFF, 0xDA]), None);
+
+    // Converting from sRGB to sRGB should not change the pixels (except for rounding)
+    let icc_profile = IccRgbProfile::parse(&extracted).unwrap();
+    let original = vec![0, 0, 0, 255, 128, 64, 200, 7, 255, 255, 255, 0];
+    let mut converted = original.clone();
+    icc_profile.convert_to_srgb(&mut converted, 4);
+    for (a, b) in original.iter().zip(converted.iter()) {
+        assert!((i32::from(*a) - i32::from(*b)).abs() <= 1, "{:?} != {:?}", original, converted);
+    }
+}
+
+#[test]
+fn test_get_loaded_ids_sorted() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let raw_image = RawImage::new(1, 1, RawImageFormat::R8, vec![0]).unwrap();
+    let image_ids = (0..16).map(|_| app_resources.new_image_id()).collect::<Vec<_>>();
+    for image_id in image_ids.iter().rev() {
+        app_resources.add_image_raw(*image_id, raw_image.clone()).unwrap();
+    }
+    assert_eq!(app_resources.get_loaded_image_ids(), image_ids);
+}
+
+#[test]
+fn test_tiled_image_tiles_in_rect() {
+    let tiled_image = TiledImage {
+        tile_provider: Box::new(|_| RawImage::new(0, 0, RawImageFormat::R8, Vec::new()).unwrap()),
+        full_size: (1000, 500),
+        tile_size: (256, 256),
+        resident_tiles: FastHashMap::default(),
     };
 
-    let fallback_font_name = match font_type {
-        SansSerif => "Ubuntu",
-        Monospace => "Ubuntu Mono",
+    let tile = |x, y| TileCoord { x, y };
+    assert_eq!(tiled_image.get_tiles_in_rect((0.0, 0.0), (256.0, 256.0)), vec![tile(0, 0)]);
+    assert_eq!(tiled_image.get_tiles_in_rect((300.0, 200.0), (600.0, 300.0)), vec![tile(1, 0), tile(2, 0), tile(1, 1), tile(2, 1)]);
+    // Clamped to the size of the image
+    assert_eq!(tiled_image.get_tiles_in_rect((900.0, 400.0), (2000.0, 2000.0)), vec![tile(3, 1)]);
+    assert_eq!(tiled_image.get_tile_rect(tile(3, 1)), (768, 256, 232, 244));
+}
+
+#[test]
+fn test_derive_synthetic_font() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let base = app_resources.new_font_id();
+    let size = Au::from_px(12);
+
+    assert_eq!(app_resources.derive_synthetic_font(base, true, false), None);
+
+    app_resources.add_font(base, FontSource::Embedded(b"font"));
+    let bold = app_resources.derive_synthetic_font(base, true, false).unwrap();
+    let bold_italic = app_resources.derive_synthetic_font(bold, false, true).unwrap();
+
+    let (font_id, instance_spec) = app_resources.resolve_font_instance(ImmediateFontId::Resolved(bold_italic), size, Vec::new());
+    assert_eq!(font_id, ImmediateFontId::Resolved(base));
+    assert_eq!(instance_spec, FontInstanceSpec { synthetic_bold: true, synthetic_italic: true, .. FontInstanceSpec::new(size) });
+
+    let (font_id, instance_spec) = app_resources.resolve_font_instance(ImmediateFontId::Resolved(base), size, Vec::new());
+    assert_eq!(font_id, ImmediateFontId::Resolved(base));
+    assert_eq!(instance_spec, FontInstanceSpec::new(size));
+
+    // Different variation axis values need a separate font instance
+    let wght = 0x7767_6874; // "wght"
+    let (_, light) = app_resources.resolve_font_instance(ImmediateFontId::Resolved(base), size, vec![(wght, 300.0)]);
+    let (_, heavy) = app_resources.resolve_font_instance(ImmediateFontId::Resolved(base), size, vec![(wght, 900.0)]);
+    assert!(light != heavy && light != FontInstanceSpec::new(size));
+
+    // Variation values are compared by their bits, like in the `Hash` implementation
+    let (_, nan) = app_resources.resolve_font_instance(ImmediateFontId::Resolved(base), size, vec![(wght, ::std::f32::NAN)]);
+    assert_eq!(nan, nan.clone());
+}
+
+#[cfg(feature = "image_loading")]
+#[test]
+fn test_read_image_header_dimensions() {
+    use image::ImageFormat;
+
+    let png = [
+        0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A,
+        0, 0, 0, 13, b'I', b'H', b'D', b'R',
+        0, 1, 0, 0, 0, 0, 0x80, 0,
+    ];
+    assert_eq!(read_image_header_dimensions(&png, ImageFormat::PNG), Some((65536, 32768)));
+    assert_eq!(read_image_header_dimensions(&png[..20], ImageFormat::PNG), None);
+
+    // SOI, APP0 segment with 2 bytes of payload, SOF0 (8 bit precision, 480x640)
+    let jpeg = [
+        0xFF, 0xD8,
+        0xFF, 0xE0, 0, 4, 0, 0,
+        0xFF, 0xC0, 0, 17, 8, 0x01, 0xE0, 0x02, 0x80,
+    ];
+    assert_eq!(read_image_header_dimensions(&jpeg, ImageFormat::JPEG), Some((640, 480)));
+
+    let gif = [b'G', b'I', b'F', b'8', b'9', b'a', 0x10, 0x27, 0x01, 0x00];
+    assert_eq!(read_image_header_dimensions(&gif, ImageFormat::GIF), Some((10000, 1)));
+
+    // Little-endian, one IFD with ImageWidth (SHORT) = 800 and ImageLength (LONG) = 600
+    let tiff = [
+        b'I', b'I', 42, 0, 8, 0, 0, 0,
+        2, 0,
+        0x00, 0x01, 3, 0, 1, 0, 0, 0, 0x20, 0x03, 0, 0,
+        0x01, 0x01, 4, 0, 1, 0, 0, 0, 0x58, 0x02, 0, 0,
+        0, 0, 0, 0,
+    ];
+    assert_eq!(read_image_header_dimensions(&tiff, ImageFormat::TIFF), Some((800, 600)));
+
+    // Lossless, 100x50 (both stored minus one)
+    let webp_lossless = [
+        b'R', b'I', b'F', b'F', 0, 0, 0, 0, b'W', b'E', b'B', b'P',
+        b'V', b'P', b'8', b'L', 0, 0, 0, 0, 0x2F, 0x63, 0x40, 0x0C, 0x00,
+    ];
+    assert_eq!(read_image_header_dimensions(&webp_lossless, ImageFormat::WEBP), Some((100, 50)));
+
+    // Extended, 1000x2 canvas (both stored minus one)
+    let webp_extended = [
+        b'R', b'I', b'F', b'F', 0, 0, 0, 0, b'W', b'E', b'B', b'P',
+        b'V', b'P', b'8', b'X', 10, 0, 0, 0, 0, 0, 0, 0, 0xE7, 0x03, 0x00, 0x01, 0x00, 0x00,
+    ];
+    assert_eq!(read_image_header_dimensions(&webp_extended, ImageFormat::WEBP), Some((1000, 2)));
+
+    // The directory claims 256x16, the embedded bitmap is 48x48 (the height includes the AND mask)
+    let ico = [
+        0, 0, 1, 0, 1, 0,
+        0, 16, 0, 0, 1, 0, 32, 0, 0, 0, 0, 0, 22, 0, 0, 0,
+        40, 0, 0, 0, 48, 0, 0, 0, 96, 0, 0, 0,
+    ];
+    assert_eq!(read_image_header_dimensions(&ico, ImageFormat::ICO), Some((48, 48)));
+
+    let hdr = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 512 +X 768\n";
+    assert_eq!(read_image_header_dimensions(hdr, ImageFormat::HDR), Some((768, 512)));
+
+    let pnm = b"P6\n# created by hand\n640 480\n255\n";
+    assert_eq!(read_image_header_dimensions(pnm, ImageFormat::PNM), Some((640, 480)));
+    assert_eq!(read_image_header_dimensions(b"P7\nWIDTH 640\n", ImageFormat::PNM), None);
+}
+
+#[test]
+fn test_check_image_size() {
+    let limited = ImageDecodeOptions { max_image_pixels: Some(10_000), .. Default::default() };
+    assert!(check_image_size(100, 100, limited).is_ok());
+    match check_image_size(100, 101, limited) {
+        Err(ImageReloadError::TooLarge { width: 100, height: 101, limit: 10_000 }) => { },
+        other => panic!("expected ImageReloadError::TooLarge, got {:?}", other),
+    }
+    assert!(check_image_size(::std::u32::MAX, ::std::u32::MAX, ImageDecodeOptions::default()).is_ok());
+}
+
+#[cfg(feature = "image_loading")]
+#[test]
+fn test_read_jpeg_exif_orientation() {
+
+    // SOI, APP1 segment with an IFD0 containing one entry (tag, SHORT, count 1, value)
+    let jpeg = |tiff: &[u8]| {
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1, 0, (2 + 6 + tiff.len()) as u8];
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(tiff);
+        jpeg.extend_from_slice(&[0xFF, 0xDA]);
+        jpeg
     };
 
-    // Execute "gsettings get org.gnome.desktop.interface font-name" and parse the output
-    let gsetting_cmd_result =
-        Command::new("gsettings")
-            .arg("get")
-            .arg("org.gnome.desktop.interface")
-            .arg(font_name)
-            .output()
-            .ok().map(|output| output.stdout)
-            .and_then(|stdout_bytes| String::from_utf8(stdout_bytes).ok())
-            .map(|stdout_string| stdout_string.lines().collect::<String>());
+    let little_endian = [b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0, 0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0];
+    assert_eq!(read_jpeg_exif_orientation(&jpeg(&little_endian)), Some(6));
 
-    match &gsetting_cmd_result {
-        Some(s) => parse_gsettings_font(s).to_string(),
-        None => fallback_font_name.to_string(),
+    let big_endian = [b'M', b'M', 0, 42, 0, 0, 0, 8, 0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 8, 0, 0];
+    assert_eq!(read_jpeg_exif_orientation(&jpeg(&big_endian)), Some(8));
+
+    // Other tag (ImageWidth) instead of the orientation
+    let no_orientation = [b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0, 0x00, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0];
+    assert_eq!(read_jpeg_exif_orientation(&jpeg(&no_orientation)), None);
+    assert_eq!(read_jpeg_exif_orientation(&[0xFF, 0xD8, 0xFF, 0xDA]), None);
+}
+
+#[test]
+fn test_image_ttl() {
+    use std::time::{Duration, Instant};
+
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let raw_image = RawImage::new(1, 1, RawImageFormat::R8, vec![0]).unwrap();
+    let (short_lived, long_lived, permanent) = (app_resources.new_image_id(), app_resources.new_image_id(), app_resources.new_image_id());
+
+    for image_id in [short_lived, long_lived, permanent].iter() {
+        app_resources.add_image_raw(*image_id, raw_image.clone()).unwrap();
     }
+
+    app_resources.set_image_ttl(short_lived, Duration::from_secs(1));
+    app_resources.set_image_ttl(long_lived, Duration::from_secs(60));
+
+    let now = Instant::now();
+    assert_eq!(app_resources.expire_resources(now), Vec::new());
+    assert_eq!(app_resources.expire_resources(now + Duration::from_secs(2)), vec![short_lived]);
+    assert!(!app_resources.has_image(&short_lived));
+    assert!(app_resources.has_image(&long_lived));
+    assert_eq!(app_resources.expire_resources(now + Duration::from_secs(3600)), vec![long_lived]);
+    assert!(app_resources.has_image(&permanent));
 }
 
-// 'Ubuntu Mono 13' => Ubuntu Mono
-#[cfg(target_os = "linux")]
-fn parse_gsettings_font(input: &str) -> &str {
-    use std::char;
-    let input = input.trim();
-    let input = input.trim_matches('\'');
-    let input = input.trim_end_matches(char::is_numeric);
-    let input = input.trim();
-    input
+#[test]
+fn test_text_meta() {
+    let mut text_cache = TextCache::default();
+    let plain = text_cache.add_text("Hello");
+    let meta = TextMeta { lang: Some("sr".into()), script: Some(Script(*b"Cyrl")) };
+    let with_meta = text_cache.add_text_with_meta("Здраво", meta.clone());
+
+    assert_eq!(text_cache.get_text_meta(&plain), None);
+    assert_eq!(text_cache.get_text_meta(&with_meta), Some(&meta));
+
+    text_cache.delete_text(with_meta);
+    assert_eq!(text_cache.get_text_meta(&with_meta), None);
 }
 
 #[test]
-#[cfg(target_os = "linux")]
-fn test_parse_gsettings_font() {
-    assert_eq!(parse_gsettings_font("'Ubuntu 11'"), "Ubuntu");
-    assert_eq!(parse_gsettings_font("'Ubuntu Mono 13'"), "Ubuntu Mono");
+fn test_text_cache_layouted_strings() {
+    use std::cell::Cell;
+
+    let mut text_cache = TextCache::default();
+    let text_id = text_cache.add_text("Hello World");
+    let (small, large) = (FontInstanceKey::new(IdNamespace(0), 1), FontInstanceKey::new(IdNamespace(0), 2));
+
+    let layout_calls = Cell::new(0);
+    let layout = |font_size_px: f32| {
+        let layout_calls = &layout_calls;
+        move |_: &Words| {
+            layout_calls.set(layout_calls.get() + 1);
+            Some(ScaledWords { font_size_px, items: Vec::new(), longest_word_width: 0.0, space_advance_px: 0.0, space_codepoint: 0 })
+        }
+    };
+
+    assert!(text_cache.get_or_layout(&text_id, small, layout(10.0)).is_some());
+    assert!(text_cache.get_or_layout(&text_id, small, layout(10.0)).is_some());
+    assert!(text_cache.get_or_layout(&text_id, large, layout(20.0)).is_some());
+    assert_eq!(layout_calls.get(), 2);
+
+    // Deleting a font instance only evicts the layouts of that instance
+    text_cache.evict_font_instance(&small);
+    assert!(text_cache.get_or_layout(&text_id, large, layout(20.0)).is_some());
+    assert!(text_cache.get_or_layout(&text_id, small, layout(10.0)).is_some());
+    assert_eq!(layout_calls.get(), 3);
+
+    // Changing the text keeps the TextId, but the text has to be laid out again
+    assert!(text_cache.update_text(text_id, "Hello"));
+    assert!(text_cache.get_or_layout(&text_id, small, layout(10.0)).is_some());
+    assert_eq!(layout_calls.get(), 4);
+
+    text_cache.delete_text(text_id);
+    assert!(!text_cache.update_text(text_id, "Hello"));
+    assert!(text_cache.get_or_layout(&text_id, small, layout(10.0)).is_none());
+    assert!(text_cache.layouted_strings_cache.borrow().is_empty());
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct ImageInfo {
-    pub(crate) key: ImageKey,
-    pub descriptor: ImageDescriptor,
+#[test]
+fn test_nine_patch_insets() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let raw_image = RawImage::new(4, 4, RawImageFormat::BGRA8, vec![0; 64]).unwrap();
+    let image_id = app_resources.new_image_id();
+
+    app_resources.add_nine_patch(image_id, ImageSource::Raw(raw_image.clone()), (1, 2, 1, 2));
+    assert_eq!(app_resources.get_nine_patch_insets(&image_id), Some((1, 2, 1, 2)));
+
+    let duplicated_id = app_resources.duplicate_image(&image_id).unwrap();
+    assert_eq!(app_resources.get_nine_patch_insets(&duplicated_id), Some((1, 2, 1, 2)));
+
+    app_resources.add_image_raw(image_id, raw_image).unwrap();
+    assert_eq!(app_resources.get_nine_patch_insets(&image_id), None);
 }
 
-impl ImageInfo {
-    /// Returns the (width, height) of this image.
-    pub fn get_dimensions(&self) -> (usize, usize) {
-        let width = self.descriptor.size.width;
-        let height = self.descriptor.size.height;
-        (width as usize, height as usize)
-    }
+#[cfg(feature = "svg")]
+#[test]
+fn test_rasterize_polygon_coverage() {
+    // Rectangle from (0.5, 1.0) to (3.0, 3.0)
+    let edges = [
+        ((0.5, 1.0), (3.0, 1.0)),
+        ((3.0, 1.0), (3.0, 3.0)),
+        ((3.0, 3.0), (0.5, 3.0)),
+        ((0.5, 3.0), (0.5, 1.0)),
+    ];
+    let coverage = rasterize_polygon_coverage(&edges, 4, 4);
+    assert_eq!(coverage, vec![
+        0.0, 0.0, 0.0, 0.0,
+        0.5, 1.0, 1.0, 0.0,
+        0.5, 1.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 0.0,
+    ]);
 }
 
-// The next three functions are taken from:
-// https://github.com/christolliday/limn/blob/master/core/src/resources/image.rs
+#[cfg(feature = "image_loading")]
+#[test]
+fn test_base64() {
+    for (decoded, encoded) in [(&b""[..], ""), (b"f", "Zg=="), (b"fo", "Zm8="), (b"foo", "Zm9v"), (b"foobar", "Zm9vYmFy")].iter() {
+        assert_eq!(base64_encode(decoded), *encoded);
+        assert_eq!(base64_decode(encoded).unwrap(), decoded.to_vec());
+    }
+    assert_eq!(base64_decode("Zm9v\r\nYmFy"), Some(b"foobar".to_vec()));
+    assert_eq!(base64_decode("Zm9v!"), None);
+}
 
 #[cfg(feature = "image_loading")]
-fn prepare_image(image_decoded: DynamicImage)
-    -> Result<(ImageData, ImageDescriptor), ImageError>
-{
-    use image;
-    let image_dims = image_decoded.dimensions();
+#[test]
+fn test_clipboard_image() {
+    let image = RawImage::new(2, 1, RawImageFormat::BGRA8, vec![255, 0, 0, 255, 0, 0, 255, 128]).unwrap();
+    let png = encode_png(&image.to_straight_alpha_rgba8(), 2, 1).unwrap();
+    let data_uri = format!("{}{}", CLIPBOARD_PNG_DATA_URI_PREFIX, base64_encode(&png));
+    assert_eq!(parse_clipboard_image(&data_uri), Some(image));
+    assert_eq!(parse_clipboard_image("Hello"), None);
+}
 
-    // see: https://github.com/servo/webrender/blob/80c614ab660bf6cca52594d0e33a0be262a7ac12/wrench/src/yaml_frame_reader.rs#L401-L427
-    let (format, bytes) = match image_decoded {
-        image::ImageLuma8(bytes) => {
-            let pixels = bytes.into_raw();
-            (RawImageFormat::R8, pixels)
-        },
-        image::ImageLumaA8(bytes) => {
-            let mut pixels = Vec::with_capacity(image_dims.0 as usize * image_dims.1 as usize * 4);
-            for greyscale_alpha in bytes.chunks(2) {
-                let grey = greyscale_alpha[0];
-                let alpha = greyscale_alpha[1];
-                pixels.extend_from_slice(&[
-                    grey,
-                    grey,
-                    grey,
-                    alpha,
-                ]);
-            }
-            // TODO: necessary for greyscale?
-            premultiply(pixels.as_mut_slice());
-            (RawImageFormat::BGRA8, pixels)
-        },
-        image::ImageRgba8(mut bytes) => {
-            let mut pixels = bytes.into_raw();
-            // no extra allocation necessary, but swizzling
-            for rgba in pixels.chunks_mut(4) {
-                let r = rgba[0];
-                let g = rgba[1];
-                let b = rgba[2];
-                let a = rgba[3];
-                rgba[0] = b;
-                rgba[1] = r;
-                rgba[2] = g;
-                rgba[3] = a;
-            }
-            premultiply(pixels.as_mut_slice());
-            (RawImageFormat::BGRA8, pixels)
-        },
-        image::ImageRgb8(bytes) => {
-            let mut pixels = Vec::with_capacity(image_dims.0 as usize * image_dims.1 as usize * 4);
-            for rgb in bytes.chunks(3) {
-                pixels.extend_from_slice(&[
-                    rgb[2], // b
-                    rgb[1], // g
-                    rgb[0], // r
-                    0xff    // a
-                ]);
-            }
-            (RawImageFormat::BGRA8, pixels)
-        },
-        image::ImageBgr8(bytes) => {
-            let mut pixels = Vec::with_capacity(image_dims.0 as usize * image_dims.1 as usize * 4);
-            for bgr in bytes.chunks(3) {
-                pixels.extend_from_slice(&[
-                    bgr[0], // b
-                    bgr[1], // g
-                    bgr[2], // r
-                    0xff    // a
-                ]);
-            }
-            (RawImageFormat::BGRA8, pixels)
-        },
-        image::ImageBgra8(bytes) => {
-            // Already in the correct format
-            let mut pixels = bytes.into_raw();
-            premultiply(pixels.as_mut_slice());
-            (RawImageFormat::BGRA8, pixels)
-        },
+#[cfg(feature = "svg_parsing")]
+#[test]
+fn test_rasterize_svg() {
+    let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="4" height="2" viewBox="0 0 8 4">
+        <rect x="0" y="0" width="4" height="4" fill="#ff0000"/>
+    </svg>"##;
+
+    let intrinsic = rasterize_svg(svg, None, ImageDecodeOptions::default()).unwrap();
+    assert_eq!(intrinsic.image_dimensions, (4, 2));
+    // Left half red, right half transparent
+    assert_eq!(&intrinsic.pixels[0..4], &[0, 0, 255, 255]);
+    assert_eq!(&intrinsic.pixels[12..16], &[0, 0, 0, 0]);
+
+    let scaled = rasterize_svg(svg, Some((16, 8)), ImageDecodeOptions::default()).unwrap();
+    assert_eq!(scaled.image_dimensions, (16, 8));
+    assert_eq!(&scaled.pixels[7 * 4..8 * 4], &[0, 0, 255, 255]);
+    assert_eq!(&scaled.pixels[8 * 4..9 * 4], &[0, 0, 0, 0]);
+
+    assert!(rasterize_svg("not an svg", None, ImageDecodeOptions::default()).is_err());
+
+    // The transforms of groups are applied
+    let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="4" height="4">
+        <g transform="translate(2 0)"><rect x="0" y="0" width="2" height="4" fill="#0000ff"/></g>
+    </svg>"##;
+    let translated = rasterize_svg(svg, None, ImageDecodeOptions::default()).unwrap();
+    assert_eq!(&translated.pixels[0..4], &[0, 0, 0, 0]);
+    assert_eq!(&translated.pixels[12..16], &[255, 0, 0, 255]);
+
+    // A horizontal line with a stroke width of 2 covers the rows 1 and 2
+    let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="4" height="4">
+        <path d="M 0 2 L 4 2" fill="none" stroke="#00ff00" stroke-width="2"/>
+    </svg>"##;
+    let stroked = rasterize_svg(svg, None, ImageDecodeOptions::default()).unwrap();
+    assert_eq!(&stroked.pixels[0..4], &[0, 0, 0, 0]);
+    assert_eq!(&stroked.pixels[16..20], &[0, 255, 0, 255]);
+    assert_eq!(&stroked.pixels[32..36], &[0, 255, 0, 255]);
+    assert_eq!(&stroked.pixels[48..52], &[0, 0, 0, 0]);
+}
+
+#[cfg(feature = "svg_parsing")]
+#[test]
+fn test_unsupported_svg_features() {
+    let solid = r##"<svg xmlns="http://www.w3.org/2000/svg" width="4" height="4">
+        <rect x="0" y="0" width="4" height="4" fill="#ff0000" stroke="#000000"/>
+    </svg>"##;
+    assert!(unsupported_svg_features(solid).is_empty());
+
+    let gradient = r##"<svg xmlns="http://www.w3.org/2000/svg" width="4" height="4">
+        <defs>
+            <linearGradient id="gradient"><stop offset="0" stop-color="#000000"/><stop offset="1" stop-color="#ffffff"/></linearGradient>
+        </defs>
+        <rect x="0" y="0" width="4" height="4" fill="url(#gradient)"/>
+    </svg>"##;
+    assert_eq!(unsupported_svg_features(gradient), vec!["gradients and patterns"]);
+}
+
+#[test]
+fn test_clipboard_uri_list() {
+    let uri_list = "copy\nfile:///home/user/My%20File.txt\n# comment\nhttp://example.com\nfile://localhost/tmp/a%25b\n";
+    assert_eq!(parse_clipboard_uri_list(uri_list), vec![
+        PathBuf::from("/home/user/My File.txt"),
+        PathBuf::from("/tmp/a%b"),
+    ]);
+
+    let paths = vec![PathBuf::from("/home/user/My File.txt"), PathBuf::from("/tmp/ü")];
+    assert_eq!(format_clipboard_uri_list(&paths), "file:///home/user/My%20File.txt\r\nfile:///tmp/%C3%BC");
+    assert_eq!(parse_clipboard_uri_list(&format_clipboard_uri_list(&paths)), paths);
+}
+
+#[test]
+fn test_alpha_mask() {
+    let image = RawImage {
+        pixels: vec![
+            0, 0, 0, 255,
+            0, 0, 0, 128,
+            0, 0, 0, 127,
+            0, 0, 0, 0,
+        ],
+        image_dimensions: (2, 2),
+        data_format: RawImageFormat::BGRA8,
+        alpha_premultiplied: false,
+        allow_mipmaps: true,
     };
+    assert_eq!(image.alpha_mask(128), vec![true, true, false, false]);
+    assert_eq!(image.alpha_mask(0), vec![true; 4]);
+}
 
-    let opaque = is_image_opaque(format, &bytes[..]);
-    let allow_mipmaps = true;
-    let descriptor = ImageDescriptor::new(image_dims.0 as i32, image_dims.1 as i32, format, opaque, allow_mipmaps);
-    let data = ImageData::new(bytes);
+/// Converts the output of the decoding step (premultiplied with the same `options`)
+/// back into a straight-alpha `RawImage`
+fn decoded_image_to_raw_image(data: ImageData, descriptor: ImageDescriptor, options: ImageDecodeOptions) -> Option<RawImage> {
 
-    Ok((data, descriptor))
+    let mut pixels = match data {
+        ImageData::Raw(pixels) => (*pixels).clone(),
+        _ => return None,
+    };
+
+    // Decoded color images are premultiplied, see `prepare_image`
+    if descriptor.format == RawImageFormat::BGRA8 || descriptor.format == RawImageFormat::RGBA8 {
+        unpremultiply_decoded(&mut pixels, options);
+    }
+
+    Some(RawImage {
+        pixels,
+        image_dimensions: (descriptor.size.width as u32, descriptor.size.height as u32),
+        data_format: descriptor.format,
+        alpha_premultiplied: false,
+        allow_mipmaps: descriptor.allow_mipmaps,
+    })
 }
 
-fn is_image_opaque(format: RawImageFormat, bytes: &[u8]) -> bool {
-    match format {
-        RawImageFormat::BGRA8 => {
-            let mut is_opaque = true;
-            for i in 0..(bytes.len() / 4) {
-                if bytes[i * 4 + 3] != 255 {
-                    is_opaque = false;
-                    break;
-                }
-            }
-            is_opaque
+/// Inverse of `premultiply_decoded`
+fn unpremultiply_decoded(data: &mut [u8], options: ImageDecodeOptions) {
+    if options.premultiply_in_linear_light {
+        unpremultiply_linear(data);
+    } else {
+        unpremultiply(data);
+    }
+}
+
+/// Inverse of `premultiply_linear`, fully transparent pixels are left as black
+fn unpremultiply_linear(data: &mut [u8]) {
+
+    let srgb_to_linear = (0..256).map(|c| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    }).collect::<Vec<f32>>();
+
+    let linear_to_srgb = |c: f32| {
+        let c = c.min(1.0);
+        let c = if c <= 0.003_130_8 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+        (c * 255.0).round().max(0.0).min(255.0) as u8
+    };
+
+    for pixel in data.chunks_mut(4) {
+        if pixel[3] == 0 {
+            continue;
         }
-        RawImageFormat::R8 => true,
-        _ => unreachable!(),
+        let a = f32::from(pixel[3]) / 255.0;
+        pixel[0] = linear_to_srgb(srgb_to_linear[pixel[0] as usize] / a);
+        pixel[1] = linear_to_srgb(srgb_to_linear[pixel[1] as usize] / a);
+        pixel[2] = linear_to_srgb(srgb_to_linear[pixel[2] as usize] / a);
     }
 }
 
-// From webrender/wrench
-// These are slow. Gecko's gfx/2d/Swizzle.cpp has better versions
-fn premultiply(data: &mut [u8]) {
+/// Inverse of `premultiply`, fully transparent pixels are left as black
+fn unpremultiply(data: &mut [u8]) {
     for pixel in data.chunks_mut(4) {
         let a = u32::from(pixel[3]);
-        pixel[0] = (((pixel[0] as u32 * a) + 128) / 255) as u8;
-        pixel[1] = (((pixel[1] as u32 * a) + 128) / 255) as u8;
-        pixel[2] = (((pixel[2] as u32 * a) + 128) / 255) as u8;
+        if a == 0 {
+            continue;
+        }
+        pixel[0] = ((pixel[0] as u32 * 255 + a / 2) / a).min(255) as u8;
+        pixel[1] = ((pixel[1] as u32 * 255 + a / 2) / a).min(255) as u8;
+        pixel[2] = ((pixel[2] as u32 * 255 + a / 2) / a).min(255) as u8;
     }
 }
 
+#[test]
+fn test_linear_gradient_image() {
+    let black = ColorU { r: 0, g: 0, b: 0, a: 255 };
+    let white = ColorU { r: 255, g: 255, b: 255, a: 255 };
+    let stops = vec![
+        GradientImageStop { offset: 0.0, color: black },
+        GradientImageStop { offset: 1.0, color: white },
+    ];
+    // Left-to-right gradient, 4 pixels wide
+    let image = RawImage::linear_gradient((4, 1), stops, 90.0);
+    let reds = image.pixels.chunks(4).map(|bgra| bgra[2]).collect::<Vec<u8>>();
+    assert_eq!(reds, vec![32, 96, 159, 223]);
+}
+
+#[test]
+fn test_gradient_image_cache() {
+    let black = ColorU { r: 0, g: 0, b: 0, a: 255 };
+    let gradient = |angle| GradientImage::Linear { size: (2, 2), stops: vec![GradientImageStop { offset: 0.0, color: black }], angle };
+
+    // Equality and hashing both compare the bits of the floats
+    assert!(gradient(0.0) != gradient(-0.0));
+
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let image_id = app_resources.add_gradient_image(gradient(0.0));
+    assert_eq!(app_resources.add_gradient_image(gradient(0.0)), image_id);
+    assert!(app_resources.add_gradient_image(gradient(-0.0)) != image_id);
+
+    app_resources.delete_image(&image_id);
+    assert!(app_resources.gradient_images.values().all(|gradient_image_id| *gradient_image_id != image_id));
+}
+
 #[test]
 fn test_premultiply() {
     let mut color = [255, 0, 0, 127];
@@ -1172,6 +9520,186 @@ fn test_premultiply() {
     assert_eq!(color, [127, 0, 0, 127]);
 }
 
+#[test]
+fn test_premultiply_simd_matches_scalar() {
+
+    // Every color / alpha combination
+    let mut all = Vec::with_capacity(256 * 256 * 4);
+    for alpha in 0..=255u8 {
+        for color in 0..=255u8 {
+            all.extend_from_slice(&[color, 255 - color, color / 2, alpha]);
+        }
+    }
+    let mut expected = all.clone();
+    premultiply_scalar(&mut expected);
+    premultiply(&mut all);
+    assert!(all == expected);
+
+    // Random buffers with lengths that aren't a multiple of the SIMD width
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    for pixel_count in 0..64 {
+        let mut pixels = (0..pixel_count * 4).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 32) as u8
+        }).collect::<Vec<u8>>();
+        let mut expected = pixels.clone();
+        premultiply_scalar(&mut expected);
+        premultiply(&mut pixels);
+        assert_eq!(pixels, expected);
+    }
+}
+
+#[cfg(feature = "image_loading")]
+#[test]
+fn test_prepare_image_keep_rgba8() {
+    use image::RgbaImage;
+    let rgba = || DynamicImage::ImageRgba8(RgbaImage::from_raw(1, 1, vec![10, 20, 30, 255]).unwrap());
+
+    let (data, descriptor) = prepare_image(rgba(), ImageDecodeOptions::default()).unwrap();
+    assert_eq!(descriptor.format, RawImageFormat::BGRA8);
+    match data {
+        ImageData::Raw(pixels) => assert_eq!(&pixels[..], &[30, 20, 10, 255]),
+        _ => panic!("expected raw image data"),
+    }
+
+    let (data, descriptor) = prepare_image(rgba(), ImageDecodeOptions { keep_rgba8_images: true, .. Default::default() }).unwrap();
+    assert_eq!(descriptor.format, RawImageFormat::RGBA8);
+    match data {
+        ImageData::Raw(pixels) => assert_eq!(&pixels[..], &[10, 20, 30, 255]),
+        _ => panic!("expected raw image data"),
+    }
+
+    // Copies of decoded RGBA8 images are un-premultiplied, like BGRA8 images
+    let translucent = DynamicImage::ImageRgba8(RgbaImage::from_raw(1, 1, vec![200, 100, 50, 128]).unwrap());
+    let (data, descriptor) = prepare_image(translucent, ImageDecodeOptions { keep_rgba8_images: true, .. Default::default() }).unwrap();
+    let raw_image = decoded_image_to_raw_image(data, descriptor, ImageDecodeOptions::default()).unwrap();
+    assert!(!raw_image.alpha_premultiplied);
+    assert_eq!(raw_image.pixels, vec![199, 100, 50, 128]);
+}
+
+#[cfg(feature = "image_loading")]
+#[test]
+fn test_dynamic_image_round_trip() {
+    use image::RgbaImage;
+    let rgba = vec![10, 20, 30, 255, 200, 100, 50, 255];
+
+    let raw_image = RawImage::from_dynamic_image(DynamicImage::ImageRgba8(RgbaImage::from_raw(2, 1, rgba.clone()).unwrap())).unwrap();
+    assert_eq!(raw_image.data_format, RawImageFormat::BGRA8);
+    assert_eq!(raw_image.pixels, vec![30, 20, 10, 255, 50, 100, 200, 255]);
+
+    match raw_image.to_dynamic_image() {
+        Some(DynamicImage::ImageRgba8(image)) => assert_eq!(image.into_raw(), rgba),
+        _ => panic!("expected an RGBA8 image"),
+    }
+}
+
+#[cfg(feature = "image_loading")]
+#[test]
+fn test_prepare_image_premultiply_in_linear_light() {
+    use image::RgbaImage;
+    let grey = || DynamicImage::ImageRgba8(RgbaImage::from_raw(1, 1, vec![128, 128, 128, 128]).unwrap());
+
+    let (data, _) = prepare_image(grey(), ImageDecodeOptions::default()).unwrap();
+    match data {
+        ImageData::Raw(pixels) => assert_eq!(&pixels[..], &[64, 64, 64, 128]),
+        _ => panic!("expected raw image data"),
+    }
+
+    let (data, _) = prepare_image(grey(), ImageDecodeOptions { premultiply_in_linear_light: true, .. Default::default() }).unwrap();
+    match data {
+        ImageData::Raw(pixels) => assert_eq!(&pixels[..], &[93, 93, 93, 128]),
+        _ => panic!("expected raw image data"),
+    }
+}
+
+#[test]
+fn test_premultiply_linear() {
+    // 50% grey at the semi-transparent edge of an image
+    let mut byte_space = [128, 128, 128, 128];
+    let mut linear_light = byte_space;
+    premultiply(&mut byte_space);
+    premultiply_linear(&mut linear_light);
+    assert_eq!(byte_space, [64, 64, 64, 128]);
+    assert_eq!(linear_light, [93, 93, 93, 128]);
+
+    // Fully opaque and fully transparent pixels are the same in both modes
+    let mut opaque = [10, 128, 250, 255];
+    premultiply_linear(&mut opaque);
+    assert_eq!(opaque, [10, 128, 250, 255]);
+    let mut transparent = [10, 128, 250, 0];
+    premultiply_linear(&mut transparent);
+    assert_eq!(transparent, [0, 0, 0, 0]);
+}
+
+#[test]
+fn test_unpremultiply_decoded() {
+    let linear_light = ImageDecodeOptions { premultiply_in_linear_light: true, .. ImageDecodeOptions::default() };
+
+    // Pixels that were premultiplied in linear light are un-premultiplied in linear light
+    let mut grey = [128, 128, 128, 128];
+    premultiply_decoded(&mut grey, linear_light);
+    unpremultiply_decoded(&mut grey, linear_light);
+    assert_eq!(grey, [129, 129, 129, 128]);
+
+    let mut grey = [128, 128, 128, 128];
+    premultiply_decoded(&mut grey, ImageDecodeOptions::default());
+    unpremultiply_decoded(&mut grey, ImageDecodeOptions::default());
+    assert_eq!(grey, [128, 128, 128, 128]);
+}
+
+#[test]
+fn test_premultiply_transparent() {
+    let mut color = [255, 127, 3, 0];
+    premultiply(&mut color);
+    assert_eq!(color, [0, 0, 0, 0]);
+}
+
+#[test]
+fn test_premultiply_opaque() {
+    let mut color = [255, 127, 3, 255];
+    premultiply(&mut color);
+    assert_eq!(color, [255, 127, 3, 255]);
+}
+
+#[test]
+fn test_premultiply_roundtrip() {
+    // Un-premultiplying can't recover the exact color for low alpha values,
+    // since the premultiplied color has less precision - only test mid-range alphas
+    for alpha in (96..=224).step_by(16) {
+        for value in (0..=255).step_by(5) {
+            let original = [value as u8, (255 - value) as u8, (value / 2) as u8, alpha as u8];
+            let mut color = original;
+            premultiply(&mut color);
+            unpremultiply(&mut color);
+            for channel in 0..3 {
+                let difference = (i32::from(color[channel]) - i32::from(original[channel])).abs();
+                assert!(difference <= 1, "{:?} => {:?}", original, color);
+            }
+            assert_eq!(color[3], original[3]);
+        }
+    }
+}
+
+#[test]
+fn test_to_straight_alpha_rgba8() {
+    let image = RawImage {
+        pixels: vec![
+            0, 0, 127, 127, // premultiplied red, half-transparent
+            0, 0, 0, 0,     // fully transparent
+        ],
+        image_dimensions: (2, 1),
+        data_format: RawImageFormat::BGRA8,
+        alpha_premultiplied: true,
+        allow_mipmaps: true,
+    };
+    assert_eq!(image.to_straight_alpha_rgba8(), vec![
+        255, 0, 0, 127,
+        0, 0, 0, 0,
+    ]);
+}
+
 #[test]
 fn test_font_gc() {
 
@@ -1223,9 +9751,9 @@ fn test_font_gc() {
 
     // Assert that all fonts got added and detected correctly
     let mut expected_fonts = FastHashMap::new();
-    expected_fonts.insert(FontId::new(), FontSource::System(String::from("Helvetica")));
-    expected_fonts.insert(FontId::new(), FontSource::System(String::from("Arial")));
-    expected_fonts.insert(FontId::new(), FontSource::System(String::from("Times New Roman")));
+    expected_fonts.insert(app_resources.new_font_id(), FontSource::System(String::from("Helvetica")));
+    expected_fonts.insert(app_resources.new_font_id(), FontSource::System(String::from("Arial")));
+    expected_fonts.insert(app_resources.new_font_id(), FontSource::System(String::from("Times New Roman")));
 
     fn build_map<T: Hash + Eq, U>(i: Vec<(T, U)>) -> FastHashMap<T, U> {
         let mut map = FastHashMap::default();
@@ -1239,26 +9767,40 @@ fn test_font_gc() {
         set
     }
 
-    assert_eq!(scan_ui_description_for_image_keys(&app_resources, &display_list_frame_1), FastHashSet::default());
-    assert_eq!(scan_ui_description_for_image_keys(&app_resources, &display_list_frame_2), FastHashSet::default());
-    assert_eq!(scan_ui_description_for_image_keys(&app_resources, &display_list_frame_3), FastHashSet::default());
+    assert_eq!(scan_ui_description_for_image_keys(&app_resources, &display_list_frame_1, ImageScanFilter::All), FastHashSet::default());
+    assert_eq!(scan_ui_description_for_image_keys(&app_resources, &display_list_frame_2, ImageScanFilter::All), FastHashSet::default());
+    assert_eq!(scan_ui_description_for_image_keys(&app_resources, &display_list_frame_3, ImageScanFilter::All), FastHashSet::default());
 
     assert_eq!(scan_ui_description_for_font_keys(&app_resources, &display_list_frame_1), build_map(vec![
-        (ImmediateFontId::Unresolved("Arial".to_string()), build_set(vec![px_to_au(10.0)])),
-        (ImmediateFontId::Unresolved("Helvetica".to_string()), build_set(vec![px_to_au(10.0)])),
-        (ImmediateFontId::Unresolved("Times New Roman".to_string()), build_set(vec![px_to_au(10.0)])),
+        (ImmediateFontId::Unresolved("Arial".to_string()), build_set(vec![FontInstanceSpec::new(px_to_au(10.0))])),
+        (ImmediateFontId::Unresolved("Helvetica".to_string()), build_set(vec![FontInstanceSpec::new(px_to_au(10.0))])),
+        (ImmediateFontId::Unresolved("Times New Roman".to_string()), build_set(vec![FontInstanceSpec::new(px_to_au(10.0))])),
     ]));
     assert_eq!(scan_ui_description_for_font_keys(&app_resources, &display_list_frame_2), build_map(vec![
-        (ImmediateFontId::Unresolved("sans-serif".to_string()), build_set(vec![px_to_au(10.0)])),
+        (ImmediateFontId::Unresolved("sans-serif".to_string()), build_set(vec![FontInstanceSpec::new(px_to_au(10.0))])),
     ]));
     assert_eq!(scan_ui_description_for_font_keys(&app_resources, &display_list_frame_3), build_map(vec![
-        (ImmediateFontId::Unresolved("Arial".to_string()), build_set(vec![px_to_au(10.0)])),
-        (ImmediateFontId::Unresolved("Helvetica".to_string()), build_set(vec![px_to_au(10.0)])),
-        (ImmediateFontId::Unresolved("Times New Roman".to_string()), build_set(vec![px_to_au(10.0)])),
+        (ImmediateFontId::Unresolved("Arial".to_string()), build_set(vec![FontInstanceSpec::new(px_to_au(10.0))])),
+        (ImmediateFontId::Unresolved("Helvetica".to_string()), build_set(vec![FontInstanceSpec::new(px_to_au(10.0))])),
+        (ImmediateFontId::Unresolved("Times New Roman".to_string()), build_set(vec![FontInstanceSpec::new(px_to_au(10.0))])),
     ]));
 
 
 
+    // Returns the (added, deleted) font keys since the last call
+    fn take_font_updates(app_resources: &mut AppResources) -> (FastHashSet<FontKey>, FastHashSet<FontKey>) {
+        let mut added = FastHashSet::default();
+        let mut deleted = FastHashSet::default();
+        for update in app_resources.take_resource_updates() {
+            match update {
+                FakeResourceUpdate::AddFont(key) => { added.insert(key); },
+                FakeResourceUpdate::DeleteFont(key) => { deleted.insert(key); },
+                _ => { },
+            }
+        }
+        (added, deleted)
+    }
+
     app_resources.add_fonts_and_images(&display_list_frame_1);
     assert_eq!(app_resources.currently_registered_fonts.len(), 3);
     assert_eq!(app_resources.last_frame_font_keys.len(), 3);
@@ -1267,6 +9809,11 @@ fn test_font_gc() {
     app_resources.garbage_collect_fonts_and_images();
     assert_eq!(app_resources.currently_registered_fonts.len(), 3); // fails
 
+    // Every font got its own key
+    let (added_frame_1, deleted_frame_1) = take_font_updates(&mut app_resources);
+    assert_eq!(added_frame_1.len(), 3);
+    assert!(deleted_frame_1.is_empty());
+
     // Assert that fonts don't get double-inserted, still the same font sources as previously
     app_resources.add_fonts_and_images(&display_list_frame_3);
     app_resources.garbage_collect_fonts_and_images();
@@ -1280,13 +9827,605 @@ fn test_font_gc() {
     app_resources.add_fonts_and_images(&display_list_frame_3);
     app_resources.garbage_collect_fonts_and_images();
     assert_eq!(app_resources.currently_registered_fonts.len(), 3);
+    assert_eq!(take_font_updates(&mut app_resources), (FastHashSet::default(), FastHashSet::default()));
 
     // If the DOM changes, the fonts should get deleted, the only font still present is "sans-serif"
     app_resources.add_fonts_and_images(&display_list_frame_2);
     app_resources.garbage_collect_fonts_and_images();
     assert_eq!(app_resources.currently_registered_fonts.len(), 1);
+    let (added_frame_2, deleted_frame_2) = take_font_updates(&mut app_resources);
+    assert_eq!(added_frame_2.len(), 1);
+    assert_eq!(deleted_frame_2, added_frame_1);
 
     app_resources.add_fonts_and_images(&display_list_frame_1);
     app_resources.garbage_collect_fonts_and_images();
     assert_eq!(app_resources.currently_registered_fonts.len(), 3);
+    let (added_frame_1_again, deleted_frame_1_again) = take_font_updates(&mut app_resources);
+    assert_eq!(added_frame_1_again.len(), 3);
+    assert!(added_frame_1_again.is_disjoint(&added_frame_1));
+    assert_eq!(deleted_frame_1_again, added_frame_2);
+}
+
+#[test]
+fn test_gc_frame_counter() {
+
+    use std::collections::BTreeMap;
+    use prelude::*;
+    use ui_description::UiDescription;
+    use ui_state::UiState;
+
+    struct Mock { }
+
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let mut focused_node = None;
+    let mut pending_focus_target = None;
+    let hovered_nodes = BTreeMap::new();
+    let css = css::from_str(r#"
+        #one { font-family: Helvetica; }
+    "#).unwrap();
+
+    let mut ui_state: UiState<Mock> = Dom::mock_from_xml(r#"
+        <p id="one">Hello</p>
+    "#).into_ui_state();
+    let ui_description = UiDescription::match_css_to_dom(&mut ui_state, &css, &mut focused_node, &mut pending_focus_target, &hovered_nodes, false);
+    let display_list = DisplayList::new_from_ui_description(&ui_description, &ui_state);
+
+    let font_id = ImmediateFontId::Unresolved("Helvetica".to_string());
+    assert_eq!(app_resources.frames_since_font_use(&font_id), None);
+
+    app_resources.add_fonts_and_images(&display_list);
+
+    // Once loaded, the system font is tracked under a resolved FontId
+    assert_eq!(app_resources.frames_since_font_use(&font_id), None);
+    let font_id = ImmediateFontId::Resolved(*app_resources.get_css_font_id("Helvetica").unwrap());
+    assert_eq!(app_resources.frames_since_font_use(&font_id), Some(0));
+
+    for _ in 0..10 {
+        app_resources.begin_frame();
+    }
+
+    assert_eq!(app_resources.get_current_frame(), 10);
+    assert_eq!(app_resources.frames_since_font_use(&font_id), Some(10));
+
+    app_resources.add_fonts_and_images(&display_list);
+    assert_eq!(app_resources.frames_since_font_use(&font_id), Some(0));
+}
+
+#[test]
+fn test_animated_image_swaps_frame_keys() {
+    use std::time::Duration;
+
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let image_id = app_resources.new_image_id();
+    let frame = |value: u8| (RawImage::new(1, 1, RawImageFormat::R8, vec![value]).unwrap(), Duration::from_millis(100));
+
+    assert_eq!(app_resources.add_animated_image_raw(image_id, Vec::new()), Err(RawImageError::NoFrames));
+    app_resources.add_animated_image_raw(image_id, vec![frame(1), frame(2), frame(3)]).unwrap();
+
+    let mut images = FastHashSet::default();
+    images.insert(image_id);
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+    let first_key = app_resources.get_image_info(&image_id).unwrap().key;
+    app_resources.take_resource_updates();
+
+    // Less than the duration of the first frame
+    assert!(!app_resources.advance_animated_image(&image_id, Duration::from_millis(50)));
+    // The second frame is uploaded under a new key, the ImageId stays the same
+    assert!(app_resources.advance_animated_image(&image_id, Duration::from_millis(50)));
+    let second_key = app_resources.get_image_info(&image_id).unwrap().key;
+    assert!(first_key != second_key);
+    assert_eq!(app_resources.take_resource_updates(), vec![FakeResourceUpdate::AddImage(second_key)]);
+
+    // Two complete loops and two more frames later, the first frame is displayed again (still uploaded)
+    assert!(app_resources.advance_animated_image(&image_id, Duration::from_millis(850)));
+    assert_eq!(app_resources.get_animated_image_frame(&image_id), Some((3, 0)));
+    assert_eq!(app_resources.get_image_info(&image_id).unwrap().key, first_key);
+    assert_eq!(app_resources.take_resource_updates(), Vec::new());
+
+    // Deleting the image deletes all uploaded frames
+    app_resources.delete_image(&image_id);
+    app_resources.garbage_collect_fonts_and_images();
+    assert_eq!(app_resources.take_resource_updates(), vec![
+        FakeResourceUpdate::DeleteImage(second_key),
+        FakeResourceUpdate::DeleteImage(first_key),
+    ]);
+}
+
+#[cfg(feature = "image_loading")]
+#[test]
+fn test_background_image_decoding() {
+    let app_config = AppConfig { image_decode_threads: Some(1), .. AppConfig::default() };
+    let mut app_resources = AppResources::new(&app_config).unwrap();
+    let (invalid, raw) = (app_resources.new_image_id(), app_resources.new_image_id());
+
+    app_resources.add_image(invalid, ImageSource::Bytes(vec![1, 2, 3]));
+    app_resources.add_image_raw(raw, RawImage::new(1, 1, RawImageFormat::R8, vec![1]).unwrap()).unwrap();
+
+    let mut images = FastHashSet::default();
+    images.insert(invalid);
+    images.insert(raw);
+
+    // Raw images don't need to be decoded, the encoded image is decoded in the background
+    let mut load_errors = Vec::new();
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut load_errors);
+    assert_eq!(add_image_resource_updates.iter().map(|(image_id, _)| *image_id).collect::<Vec<_>>(), vec![raw]);
+    assert_eq!(app_resources.pending_image_decodes(), 1);
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+
+    let started = Instant::now();
+    while !app_resources.poll_pending_images() {
+        assert!(started.elapsed() < Duration::from_secs(10), "image wasn't decoded in the background");
+        ::std::thread::sleep(Duration::from_millis(1));
+    }
+
+    assert_eq!(app_resources.pending_image_decodes(), 0);
+    assert!(build_add_image_resource_updates(&mut app_resources, &images, &mut load_errors).is_empty());
+    assert_eq!(load_errors.len(), 1);
+    assert_eq!(load_errors[0].0, ResourceWarningId::Image(invalid));
+}
+
+#[test]
+fn test_file_watcher_debounces_changes() {
+    use std::path::Path;
+
+    let mut file_watcher = FileWatcher::default();
+    let file = Path::new("icon.png").to_path_buf();
+    let time = |secs: u64| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+
+    // Seen for the first time: the file was just loaded
+    assert!(file_watcher.update(vec![(file.clone(), time(1))]).is_empty());
+    // Changed twice in a row, the file is still being written
+    assert!(file_watcher.update(vec![(file.clone(), time(2))]).is_empty());
+    assert!(file_watcher.update(vec![(file.clone(), time(3))]).is_empty());
+    // Temporarily missing while the editor replaces it
+    assert!(file_watcher.update(vec![(file.clone(), None)]).is_empty());
+    // Unchanged since the last check: reload once
+    assert_eq!(file_watcher.update(vec![(file.clone(), time(3))]).into_iter().collect::<Vec<_>>(), vec![file.clone()]);
+    assert!(file_watcher.update(vec![(file.clone(), time(3))]).is_empty());
+}
+
+#[test]
+fn test_reload_changed_font_invalidates_font_metrics() {
+    use std::fs;
+
+    let font_path = ::std::env::temp_dir().join("azul_test_reload_changed_font.ttf");
+    fs::write(&font_path, b"font").unwrap();
+
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let font_id = app_resources.new_font_id();
+    app_resources.add_font(font_id, FontSource::File(font_path.clone()));
+    let font_metrics = FontMetrics { units_per_em: 1000, ascent: 800, descent: -200, line_gap: 0, cap_height: None, x_height: None };
+    app_resources.font_metrics_cache.get_mut().insert(font_id, font_metrics);
+    assert_eq!(app_resources.get_font_metrics(&font_id), Some(font_metrics));
+
+    // The file changed at the last check and stayed the same since then
+    app_resources.enable_file_watching();
+    let modification_time = fs::metadata(&font_path).and_then(|metadata| metadata.modified()).unwrap();
+    if let Some(file_watcher) = app_resources.file_watcher.as_mut() {
+        file_watcher.modification_times.insert(font_path.clone(), modification_time);
+        file_watcher.changed_files.insert(font_path.clone());
+    }
+
+    app_resources.reload_changed_files(Instant::now());
+    assert!(!app_resources.font_metrics_cache.borrow().contains_key(&font_id));
+
+    fs::remove_file(&font_path).unwrap();
+}
+
+#[test]
+fn test_drop_font_bytes_after_upload() {
+    let app_config = AppConfig { drop_font_bytes_after_upload: true, .. AppConfig::default() };
+    let mut app_resources = AppResources::new(&app_config).unwrap();
+    let id = app_resources.new_font_id();
+    app_resources.add_font(id, FontSource::Bytes(vec![1, 2, 3], 0));
+    let font_id = ImmediateFontId::Resolved(id);
+
+    let mut fonts = FastHashMap::default();
+    fonts.insert(font_id.clone(), vec![FontInstanceSpec::new(Au::from_px(12))].into_iter().collect());
+    let add_font_resource_updates = build_add_font_resource_updates(&mut app_resources, &fonts, &mut Vec::new());
+    add_resources(&mut app_resources, add_font_resource_updates, Vec::new());
+    app_resources.take_resource_updates();
+
+    // Kept for the layout of the frame that uploads the font, dropped at the end of the frame
+    assert_eq!(app_resources.get_loaded_font(&font_id).unwrap().font_bytes, vec![1, 2, 3]);
+    app_resources.last_frame_font_keys = fonts.clone();
+    app_resources.font_last_used_frame.insert(font_id.clone(), app_resources.get_current_frame());
+    app_resources.garbage_collect_fonts_and_images();
+    assert!(app_resources.get_loaded_font(&font_id).unwrap().font_bytes.is_empty());
+
+    // Read once when the font is used in the next frame
+    app_resources.begin_frame();
+    app_resources.reload_dropped_font_bytes(fonts.keys());
+    assert_eq!(app_resources.get_loaded_font(&font_id).unwrap().font_bytes, vec![1, 2, 3]);
+}
+
+#[test]
+fn test_identical_fonts_share_font_key() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let (first, second) = (app_resources.new_font_id(), app_resources.new_font_id());
+    app_resources.add_font(first, FontSource::Bytes(vec![1, 2, 3], 0));
+    app_resources.add_font(second, FontSource::Bytes(vec![1, 2, 3], 0));
+
+    let add_font = |app_resources: &mut AppResources, font_id: FontId| {
+        let mut fonts = FastHashMap::default();
+        fonts.insert(ImmediateFontId::Resolved(font_id), vec![FontInstanceSpec::new(Au::from_px(12))].into_iter().collect());
+        let add_font_resource_updates = build_add_font_resource_updates(app_resources, &fonts, &mut Vec::new());
+        add_resources(app_resources, add_font_resource_updates, Vec::new());
+        app_resources.get_loaded_font(&ImmediateFontId::Resolved(font_id)).unwrap().font_key
+    };
+
+    let first_key = add_font(&mut app_resources, first);
+    assert_eq!(add_font(&mut app_resources, second), first_key);
+    // The font is only uploaded once, each font gets its own font instance
+    let uploads = app_resources.take_resource_updates();
+    assert_eq!(uploads.iter().filter(|update| **update == FakeResourceUpdate::AddFont(first_key)).count(), 1);
+    assert_eq!(uploads.len(), 3);
+
+    // The FontKey is only deleted together with the last font using it
+    app_resources.garbage_collect_fonts_and_images();
+    let deleted = app_resources.take_resource_updates();
+    assert_eq!(deleted.iter().filter(|update| **update == FakeResourceUpdate::DeleteFont(first_key)).count(), 1);
+    assert!(app_resources.shared_font_keys.is_empty());
+}
+
+#[test]
+fn test_shared_font_key_hash_collision() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let (first, second) = (app_resources.new_font_id(), app_resources.new_font_id());
+    app_resources.add_font(first, FontSource::Bytes(vec![1, 2, 3], 0));
+    app_resources.add_font(second, FontSource::Bytes(vec![4, 5, 6], 0));
+
+    let add_font = |app_resources: &mut AppResources, font_id: FontId| {
+        let mut fonts = FastHashMap::default();
+        fonts.insert(ImmediateFontId::Resolved(font_id), vec![FontInstanceSpec::new(Au::from_px(12))].into_iter().collect());
+        let add_font_resource_updates = build_add_font_resource_updates(app_resources, &fonts, &mut Vec::new());
+        add_resources(app_resources, add_font_resource_updates, Vec::new());
+        app_resources.get_loaded_font(&ImmediateFontId::Resolved(font_id)).unwrap().font_key
+    };
+
+    let first_key = add_font(&mut app_resources, first);
+
+    // Pretend that the bytes of the second font have the same content hash as the first font
+    let first_hash = font_content_hash(&[1, 2, 3], 0);
+    let second_hash = font_content_hash(&[4, 5, 6], 0);
+    let shared_key = app_resources.shared_font_keys.remove(&first_hash).unwrap();
+    app_resources.shared_font_keys.insert(second_hash, shared_key);
+
+    // The bytes differ, so the second font is uploaded under its own key
+    let second_key = add_font(&mut app_resources, second);
+    assert!(second_key != first_key);
+    let uploads = app_resources.take_resource_updates();
+    assert_eq!(uploads.iter().filter(|update| **update == FakeResourceUpdate::AddFont(second_key)).count(), 1);
+    assert!(app_resources.font_content_hashes.get(&ImmediateFontId::Resolved(second)).is_none());
+}
+
+#[test]
+fn test_resource_warnings_are_bounded() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let image_id = app_resources.new_image_id();
+    let warning = |message: String| ResourceWarning { id: ResourceWarningId::Image(image_id), message };
+
+    // A warning that is repeated every frame is only stored once
+    app_resources.push_resource_warning(warning("broken".to_string()));
+    app_resources.push_resource_warning(warning("broken".to_string()));
+    assert_eq!(app_resources.take_resource_warnings(), vec![warning("broken".to_string())]);
+    assert!(app_resources.take_resource_warnings().is_empty());
+
+    // Only the newest warnings are kept
+    for i in 0..MAX_RESOURCE_WARNINGS + 10 {
+        app_resources.push_resource_warning(warning(i.to_string()));
+    }
+    let warnings = app_resources.take_resource_warnings();
+    assert_eq!(warnings.len(), MAX_RESOURCE_WARNINGS);
+    assert_eq!(warnings[0], warning(10.to_string()));
+}
+
+#[test]
+fn test_memory_report() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let (first, duplicate, color) = (app_resources.new_image_id(), app_resources.new_image_id(), app_resources.new_image_id());
+
+    app_resources.add_image_raw(first, RawImage::new(2, 2, RawImageFormat::R8, vec![1; 4]).unwrap()).unwrap();
+    app_resources.add_image_raw(duplicate, RawImage::new(2, 2, RawImageFormat::R8, vec![1; 4]).unwrap()).unwrap();
+    app_resources.add_image_raw(color, RawImage::new(1, 1, RawImageFormat::BGRA8, vec![1; 4]).unwrap()).unwrap();
+
+    let images = vec![first, duplicate, color].into_iter().collect();
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+
+    // The duplicate image shares the ImageKey of the first image
+    let report = app_resources.memory_report();
+    assert_eq!(report.image_keys, 2);
+    assert_eq!(report.image_bytes, 8);
+    assert_eq!(report.font_keys, 0);
+    assert!(report.fonts.is_empty());
+}
+
+#[test]
+fn test_force_evict() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let (used, unused) = (app_resources.new_image_id(), app_resources.new_image_id());
+
+    app_resources.add_image_raw(used, RawImage::new(1, 1, RawImageFormat::R8, vec![1]).unwrap()).unwrap();
+    app_resources.add_image_raw(unused, RawImage::new(1, 1, RawImageFormat::R8, vec![2]).unwrap()).unwrap();
+
+    let images = vec![used, unused].into_iter().collect();
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+    app_resources.take_resource_updates();
+
+    // Only the image that is used in the current frame is kept
+    app_resources.last_frame_image_keys.remove(&unused);
+    app_resources.force_evict_unused();
+    assert!(app_resources.get_image_info(&used).is_some());
+    assert!(app_resources.get_image_info(&unused).is_none());
+    assert!(app_resources.has_image(&unused));
+
+    let used_key = app_resources.get_image_info(&used).unwrap().key;
+    app_resources.take_resource_updates();
+    app_resources.force_evict_all();
+    assert!(app_resources.get_image_info(&used).is_none());
+    assert!(app_resources.has_image(&used));
+    assert_eq!(app_resources.take_resource_updates(), vec![FakeResourceUpdate::DeleteImage(used_key)]);
+}
+
+#[test]
+fn test_premultiplied_raw_image() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let (straight, premultiplied) = (app_resources.new_image_id(), app_resources.new_image_id());
+
+    let pixels = vec![0, 0, 127, 127];
+    app_resources.add_image_raw(straight, RawImage::new(1, 1, RawImageFormat::BGRA8, pixels.clone()).unwrap()).unwrap();
+    app_resources.add_image_raw(premultiplied, RawImage::new(1, 1, RawImageFormat::BGRA8, pixels.clone()).unwrap().with_premultiplied_alpha()).unwrap();
+
+    let images = vec![straight, premultiplied].into_iter().collect();
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+
+    // Premultiplied pixels must not be premultiplied again when rendering
+    assert_eq!(app_resources.get_image_info(&straight).unwrap().alpha_type, AlphaType::Alpha);
+    assert_eq!(app_resources.get_image_info(&premultiplied).unwrap().alpha_type, AlphaType::PremultipliedAlpha);
+
+    let straight_image = RawImage::new(1, 1, RawImageFormat::BGRA8, pixels.clone()).unwrap();
+    assert_eq!(straight_image.to_straight_alpha_rgba8(), vec![127, 0, 0, 127]);
+    assert_eq!(straight_image.with_premultiplied_alpha().to_straight_alpha_rgba8(), vec![255, 0, 0, 127]);
+}
+
+#[test]
+fn test_16_bit_raw_images() {
+    let luma = RawImage::from_luma16(3, 1, &[0, 0x8080, 0xffff]).unwrap();
+    assert_eq!(luma.data_format, RawImageFormat::R16);
+    assert_eq!(luma.pixels, vec![0, 0, 0x80, 0x80, 0xff, 0xff]);
+    assert_eq!(RawImage::from_luma16(1, 1, &[0x1234]).unwrap().pixels, vec![0x34, 0x12]);
+
+    // Uploaded as R8, since not all devices support 16-bit textures
+    let (data, descriptor) = ImageSource::Raw(luma.clone()).get_bytes().unwrap();
+    assert_eq!(descriptor.format, RawImageFormat::R8);
+    assert!(descriptor.is_opaque);
+    match data {
+        ImageData::Raw(pixels) => assert_eq!(&pixels[..], &[0, 128, 255]),
+        _ => panic!("expected raw image data"),
+    }
+    assert_eq!(luma.to_straight_alpha_rgba8()[4..8], [128, 128, 128, 255]);
+
+    let rgba = RawImage::from_rgba16(1, 1, &[0xffff, 0x8080, 0, 0x7f7f]).unwrap();
+    assert_eq!(rgba.data_format, RawImageFormat::BGRA8);
+    assert_eq!(rgba.pixels, vec![0, 128, 255, 127]);
+    assert!(RawImage::from_luma16(2, 2, &[0; 3]).is_err());
+}
+
+#[test]
+fn test_ids_are_scoped_to_app_resources() {
+    let mut first = AppResources::new(&AppConfig::default()).unwrap();
+    let mut second = AppResources::new(&AppConfig::default()).unwrap();
+
+    // The same sequence of calls produces the same IDs
+    assert_eq!(first.add_css_image_id("logo"), second.add_css_image_id("logo"));
+    assert_eq!(first.add_css_font_id("Roboto"), second.add_css_font_id("Roboto"));
+    assert_eq!(first.add_text("Hello"), second.add_text("Hello"));
+
+    // Registering an ID from outside (i.e. restored from a save file) reserves it
+    let restored = unsafe { ImageId::from_usize(10) };
+    first.add_image_raw(restored, RawImage::new(1, 1, RawImageFormat::R8, vec![0]).unwrap()).unwrap();
+    assert_eq!(first.new_image_id().as_usize(), 11);
+}
+
+#[test]
+fn test_resource_updates_are_batched() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let (used, unused, added) = (app_resources.new_image_id(), app_resources.new_image_id(), app_resources.new_image_id());
+
+    for (image_id, pixel) in vec![(used, 1), (unused, 2), (added, 3)] {
+        app_resources.add_image_raw(image_id, RawImage::new(1, 1, RawImageFormat::R8, vec![pixel]).unwrap()).unwrap();
+    }
+
+    let images = vec![used, unused].into_iter().collect();
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+    app_resources.take_resource_updates();
+    let unused_key = app_resources.get_image_info(&unused).unwrap().key;
+
+    // The deletions of the GC are submitted together with the additions of the next frame
+    app_resources.last_frame_image_keys.remove(&unused);
+    app_resources.garbage_collect_fonts_and_images();
+    app_resources.begin_frame();
+    assert_eq!(app_resources.fake_render_api.take_resource_updates(), Vec::new());
+
+    let images = vec![used, added].into_iter().collect();
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+    let added_key = app_resources.get_image_info(&added).unwrap().key;
+
+    assert_eq!(app_resources.take_resource_updates(), vec![
+        FakeResourceUpdate::DeleteImage(unused_key),
+        FakeResourceUpdate::AddImage(added_key),
+    ]);
+    assert_eq!(app_resources.current_frame_stats.resource_update_submissions, 1);
+    assert_eq!(app_resources.current_frame_stats.scene_builder_flushes, 1);
+}
+
+#[test]
+fn test_font_instances_without_font() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let font_id = app_resources.new_font_id();
+    app_resources.add_font(font_id, FontSource::Bytes(vec![1, 2, 3], 0));
+
+    let mut fonts = FastHashMap::default();
+    fonts.insert(ImmediateFontId::Resolved(font_id), vec![FontInstanceSpec::new(Au::from_px(12))].into_iter().collect());
+    let add_font_resource_updates = build_add_font_resource_updates(&app_resources, &fonts, &mut Vec::new());
+
+    // Drop the font itself, i.e. as if its bytes failed to load: the instance is skipped instead of panicking
+    let instances_only = add_font_resource_updates.iter().cloned().filter(|(_, add_font_msg)| match add_font_msg {
+        AddFontMsg::Font(..) => false,
+        AddFontMsg::Instance(..) => true,
+    }).collect::<Vec<_>>();
+    assert_eq!(instances_only.len(), 1);
+    add_resources(&mut app_resources, instances_only, Vec::new());
+    assert_eq!(app_resources.take_resource_updates(), Vec::new());
+    assert!(app_resources.get_loaded_font(&ImmediateFontId::Resolved(font_id)).is_none());
+
+    // Deleting the font before its instances doesn't panic either
+    add_resources(&mut app_resources, add_font_resource_updates, Vec::new());
+    let loaded_font = app_resources.get_loaded_font(&ImmediateFontId::Resolved(font_id)).unwrap();
+    let (instance_spec, font_instance_key) = loaded_font.font_instances.iter().next().map(|(spec, key)| (spec.clone(), *key)).unwrap();
+    let font_key = loaded_font.font_key;
+    delete_resources(&mut app_resources, vec![
+        (ImmediateFontId::Resolved(font_id), DeleteFontMsg::Font(font_key)),
+        (ImmediateFontId::Resolved(font_id), DeleteFontMsg::Instance(font_instance_key, instance_spec)),
+    ], Vec::new());
+    assert!(app_resources.get_loaded_font(&ImmediateFontId::Resolved(font_id)).is_none());
+}
+
+#[test]
+fn test_get_image_and_font_source() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let image_id = app_resources.new_image_id();
+    let font_id = app_resources.new_font_id();
+    assert!(app_resources.get_image_source(&image_id).is_none());
+    assert!(app_resources.get_font_source(&font_id).is_none());
+
+    let raw = RawImage::new(1, 1, RawImageFormat::R8, vec![1]).unwrap();
+    app_resources.add_image_raw(image_id, raw.clone()).unwrap();
+    app_resources.add_font(font_id, FontSource::System("Arial".into()));
+    assert_eq!(app_resources.get_image_source(&image_id), Some(&ImageSource::Raw(raw)));
+    assert_eq!(app_resources.get_font_source(&font_id), Some(&FontSource::System("Arial".into())));
+}
+
+#[cfg(feature = "image_loading")]
+#[test]
+fn test_add_images_prewarm() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let icons = (0..3).map(|i| {
+        let image_id = app_resources.new_image_id();
+        (image_id, ImageSource::Raw(RawImage::new(1, 1, RawImageFormat::R8, vec![i]).unwrap()))
+    }).collect::<Vec<_>>();
+    let icon_ids = icons.iter().map(|(image_id, _)| *image_id).collect::<Vec<_>>();
+
+    // Uploaded and submitted right away, without waiting for a frame
+    app_resources.add_images_prewarm(icons);
+    assert!(icon_ids.iter().all(|image_id| app_resources.has_image(image_id)));
+    assert!(icon_ids.iter().all(|image_id| app_resources.currently_registered_images.contains_key(image_id)));
+    assert_eq!(app_resources.fake_render_api.take_resource_updates().len(), 3);
+    assert_eq!(app_resources.current_frame_stats.resource_update_submissions, 1);
+
+    let font_ids = (0..2).map(|_| app_resources.new_font_id()).collect::<Vec<_>>();
+    app_resources.add_fonts(font_ids.iter().map(|font_id| (*font_id, FontSource::System("Arial".into()))));
+    assert!(font_ids.iter().all(|font_id| app_resources.has_font(font_id)));
+}
+
+#[test]
+fn test_image_info_opaque_and_format() {
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let opaque = app_resources.new_image_id();
+    let transparent = app_resources.new_image_id();
+    app_resources.add_image_raw(opaque, RawImage::new(1, 1, RawImageFormat::RGBA8, vec![0, 0, 0, 255]).unwrap()).unwrap();
+    app_resources.add_image_raw(transparent, RawImage::new(1, 1, RawImageFormat::BGRA8, vec![0, 0, 0, 0]).unwrap()).unwrap();
+
+    let mut images = FastHashSet::default();
+    images.insert(opaque);
+    images.insert(transparent);
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+
+    let opaque_info = app_resources.get_image_info(&opaque).unwrap();
+    let transparent_info = app_resources.get_image_info(&transparent).unwrap();
+    assert!(opaque_info.is_opaque());
+    assert!(!transparent_info.is_opaque());
+    assert_eq!(transparent_info.format(), RawImageFormat::BGRA8);
+}
+
+#[test]
+fn test_get_text_string() {
+    let mut text_cache = TextCache::default();
+    // "e" + combining acute accent is NFC-normalized to "\u{e9}" in the words
+    let original = "Cafe\u{301}\r\n  two   spaces";
+    let text_id = text_cache.add_text(original);
+    assert_eq!(text_cache.get_string(&text_id), Some(original));
+    assert_ne!(text_cache.get_text(&text_id).unwrap().get_str(), original);
+
+    assert!(text_cache.update_text(text_id, "Hello"));
+    assert_eq!(text_cache.get_string(&text_id), Some("Hello"));
+
+    text_cache.delete_text(text_id);
+    assert_eq!(text_cache.get_string(&text_id), None);
+}
+
+#[cfg(feature = "image_loading")]
+#[test]
+fn test_image_max_dimensions() {
+    use image::{ColorType, png::PNGEncoder};
+
+    let mut png = Vec::new();
+    PNGEncoder::new(&mut png).encode(&vec![255; 40 * 20 * 3], 40, 20, ColorType::RGB(8)).unwrap();
+
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let photo = app_resources.new_image_id();
+    let raw = app_resources.new_image_id();
+    app_resources.add_image(photo, ImageSource::Bytes(png));
+    app_resources.add_image(raw, ImageSource::Raw(RawImage::new(40, 20, RawImageFormat::R8, vec![0; 40 * 20]).unwrap()));
+
+    let size = |app_resources: &AppResources, image_id| {
+        let (_, descriptor) = app_resources.get_image_bytes(&image_id).unwrap().unwrap();
+        (descriptor.size.width, descriptor.size.height)
+    };
+
+    assert_eq!(size(&app_resources, photo), (40, 20));
+
+    // Scaled down preserving the aspect ratio, images that already fit are not scaled up
+    app_resources.set_image_max_dimensions(photo, Some((10, 10)));
+    app_resources.set_image_max_dimensions(raw, Some((10, 10)));
+    assert_eq!(size(&app_resources, photo), (10, 5));
+    assert_eq!(size(&app_resources, raw), (40, 20));
+    app_resources.set_image_max_dimensions(photo, Some((100, 100)));
+    assert_eq!(size(&app_resources, photo), (40, 20));
+
+    app_resources.set_image_max_dimensions(photo, None);
+    assert_eq!(app_resources.get_image_max_dimensions(&photo), None);
+    assert_eq!(size(&app_resources, photo), (40, 20));
+}
+
+#[test]
+fn test_image_mipmaps() {
+    let (_, descriptor) = ImageSource::Raw(RawImage::new(1, 1, RawImageFormat::R8, vec![0]).unwrap()).get_bytes().unwrap();
+    assert!(descriptor.allow_mipmaps);
+    let (_, descriptor) = ImageSource::Raw(RawImage::new(1, 1, RawImageFormat::R8, vec![0]).unwrap().without_mipmaps()).get_bytes().unwrap();
+    assert!(!descriptor.allow_mipmaps);
+
+    let mut app_resources = AppResources::new(&AppConfig::default()).unwrap();
+    let mipmapped = app_resources.new_image_id();
+    let pixel_art = app_resources.new_image_id();
+    app_resources.add_image_raw(mipmapped, RawImage::new(1, 1, RawImageFormat::R8, vec![1]).unwrap()).unwrap();
+    app_resources.add_image_raw(pixel_art, RawImage::new(1, 1, RawImageFormat::R8, vec![2]).unwrap()).unwrap();
+    app_resources.set_image_mipmaps(pixel_art, false);
+    assert!(!app_resources.get_image_mipmaps(&pixel_art));
+
+    let mut images = FastHashSet::default();
+    images.insert(mipmapped);
+    images.insert(pixel_art);
+    let add_image_resource_updates = build_add_image_resource_updates(&mut app_resources, &images, &mut Vec::new());
+    add_resources(&mut app_resources, Vec::new(), add_image_resource_updates);
+
+    assert!(app_resources.get_image_info(&mipmapped).unwrap().descriptor.allow_mipmaps);
+    assert!(!app_resources.get_image_info(&pixel_art).unwrap().descriptor.allow_mipmaps);
 }
@@ -0,0 +1,402 @@
+//! Copying images to / from the system clipboard via the native image formats
+//! (`CF_DIB` / `"PNG"` on Windows, `image/png` on X11, `public.png` on macOS),
+//! which the `clipboard2` backend doesn't support - it only handles text.
+
+use std::io::{Error as IoError, ErrorKind};
+use clipboard2::ClipboardError;
+use image::{self, ImageFormat, ColorType, png::PNGEncoder};
+use app_resources::{RawImage, RawImageFormat};
+
+/// Handle to the native image formats of the system clipboard. On X11, the copied image is
+/// only available as long as the handle is alive (the handle owns the clipboard selection).
+pub(crate) struct ImageClipboard {
+    native: platform::NativeClipboard,
+}
+
+impl ImageClipboard {
+
+    /// Returns `ClipboardError::Unimplemented` on platforms without a native image clipboard
+    pub(crate) fn new() -> Result<Self, ClipboardError> {
+        Ok(Self { native: platform::NativeClipboard::new()? })
+    }
+
+    /// Returns the image on the clipboard as a BGRA8 image (straight alpha),
+    /// or `None` if the clipboard doesn't contain an image in a supported format
+    pub(crate) fn get_image(&self) -> Result<Option<RawImage>, ClipboardError> {
+        self.native.load()
+    }
+
+    /// Puts the image on the clipboard. Only BGRA8, RGBA8 and R8 images can be copied, other
+    /// formats (or pixels that don't match the dimensions) return `ClipboardError::Unimplemented`.
+    /// Premultiplied images are un-premultiplied, since the clipboard formats use straight alpha.
+    pub(crate) fn set_image(&mut self, image: &RawImage) -> Result<(), ClipboardError> {
+
+        if image.validate().is_err() {
+            return Err(ClipboardError::Unimplemented);
+        }
+
+        match image.data_format {
+            RawImageFormat::BGRA8 | RawImageFormat::RGBA8 | RawImageFormat::R8 => { },
+            _ => return Err(ClipboardError::Unimplemented),
+        }
+
+        let (width, height) = image.image_dimensions;
+        self.native.store(&image.to_straight_alpha_rgba8(), width, height)
+    }
+}
+
+fn other_error(message: &str) -> ClipboardError {
+    ClipboardError::IoError(IoError::new(ErrorKind::Other, message))
+}
+
+/// Encodes straight-alpha RGBA8 pixels as a PNG file
+pub(crate) fn encode_png(rgba: &[u8], width: u32, height: u32) -> Result<Vec<u8>, ClipboardError> {
+    let mut png = Vec::new();
+    PNGEncoder::new(&mut png).encode(rgba, width, height, ColorType::RGBA(8)).map_err(ClipboardError::IoError)?;
+    Ok(png)
+}
+
+/// Decodes a PNG file into a straight-alpha BGRA8 image, returns `None` if the PNG is invalid
+pub(crate) fn decode_png(png: &[u8]) -> Option<RawImage> {
+
+    let decoded = image::load_from_memory_with_format(png, ImageFormat::PNG).ok()?.to_rgba();
+    let image_dimensions = decoded.dimensions();
+    let mut pixels = decoded.into_raw();
+    for pixel in pixels.chunks_mut(4) {
+        pixel.swap(0, 2);
+    }
+
+    Some(RawImage { pixels, image_dimensions, data_format: RawImageFormat::BGRA8, alpha_premultiplied: false, allow_mipmaps: true })
+}
+
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BITMAPINFOHEADER_SIZE: usize = 40;
+
+fn push_u16_le(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&[value as u8, (value >> 8) as u8]);
+}
+
+fn push_u32_le(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]);
+}
+
+/// Encodes straight-alpha RGBA8 pixels as a device-independent bitmap (a `BITMAPINFOHEADER`
+/// followed by bottom-up 32-bit BGRA rows), the `CF_DIB` clipboard format of Windows
+pub(crate) fn encode_dib(rgba: &[u8], width: u32, height: u32) -> Vec<u8> {
+
+    let mut dib = Vec::with_capacity(BITMAPINFOHEADER_SIZE + rgba.len());
+
+    push_u32_le(&mut dib, BITMAPINFOHEADER_SIZE as u32);
+    push_u32_le(&mut dib, width);
+    push_u32_le(&mut dib, height); // positive height: bottom-up rows
+    push_u16_le(&mut dib, 1); // planes
+    push_u16_le(&mut dib, 32); // bits per pixel
+    push_u32_le(&mut dib, BI_RGB);
+    push_u32_le(&mut dib, rgba.len() as u32);
+    push_u32_le(&mut dib, 0); // horizontal resolution
+    push_u32_le(&mut dib, 0); // vertical resolution
+    push_u32_le(&mut dib, 0); // colors used
+    push_u32_le(&mut dib, 0); // important colors
+
+    let row_len = width as usize * 4;
+    if row_len != 0 {
+        for row in rgba.chunks(row_len).rev() {
+            for pixel in row.chunks(4) {
+                dib.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+            }
+        }
+    }
+
+    dib
+}
+
+/// Decodes an uncompressed 24-bit or 32-bit device-independent bitmap (`CF_DIB`) into a
+/// straight-alpha BGRA8 image. Returns `None` for other bit depths, compressed or palette
+/// bitmaps. 32-bit bitmaps whose alpha channel is zero everywhere are treated as opaque,
+/// since most applications don't fill the (officially unused) alpha channel.
+pub(crate) fn decode_dib(dib: &[u8]) -> Option<RawImage> {
+
+    let u16_at = |pos: usize| dib.get(pos..pos + 2).map(|b| u16::from(b[0]) | u16::from(b[1]) << 8);
+    let u32_at = |pos: usize| dib.get(pos..pos + 4).map(|b| {
+        u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 | u32::from(b[3]) << 24
+    });
+
+    let header_size = u32_at(0)? as usize;
+    let width = u32_at(4)? as i32;
+    let height = u32_at(8)? as i32;
+    let bits_per_pixel = u16_at(14)?;
+    let compression = u32_at(16)?;
+
+    if header_size < BITMAPINFOHEADER_SIZE || width <= 0 || height == 0 {
+        return None;
+    }
+
+    let bytes_per_pixel = match (bits_per_pixel, compression) {
+        (24, BI_RGB) => 3,
+        (32, BI_RGB) | (32, BI_BITFIELDS) => 4,
+        _ => return None,
+    };
+
+    // The color masks directly follow a BITMAPINFOHEADER (newer headers contain them),
+    // only the default BGRA layout is supported
+    let mut pixel_offset = header_size;
+    if compression == BI_BITFIELDS {
+        if (u32_at(40)?, u32_at(44)?, u32_at(48)?) != (0x00ff_0000, 0x0000_ff00, 0x0000_00ff) {
+            return None;
+        }
+        if header_size == BITMAPINFOHEADER_SIZE {
+            pixel_offset += 12;
+        }
+    }
+
+    // Negative height: top-down rows
+    let top_down = height < 0;
+    let width = width as usize;
+    let height = height.checked_abs()? as usize;
+    let stride = (width.checked_mul(bytes_per_pixel)? + 3) & !3;
+    let pixel_data = dib.get(pixel_offset..pixel_offset.checked_add(stride.checked_mul(height)?)?)?;
+
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        let row_idx = if top_down { y } else { height - 1 - y };
+        let row = &pixel_data[row_idx * stride..row_idx * stride + width * bytes_per_pixel];
+        for pixel in row.chunks(bytes_per_pixel) {
+            let alpha = if bytes_per_pixel == 4 { pixel[3] } else { 0xff };
+            pixels.extend_from_slice(&[pixel[0], pixel[1], pixel[2], alpha]);
+        }
+    }
+
+    if bytes_per_pixel == 4 && pixels.chunks(4).all(|bgra| bgra[3] == 0) {
+        for bgra in pixels.chunks_mut(4) {
+            bgra[3] = 0xff;
+        }
+    }
+
+    Some(RawImage {
+        pixels,
+        image_dimensions: (width as u32, height as u32),
+        data_format: RawImageFormat::BGRA8,
+        alpha_premultiplied: false,
+        allow_mipmaps: true,
+    })
+}
+
+/// Puts PNG and DIB data on the clipboard (newer applications prefer the PNG, since it keeps
+/// the alpha channel), reads the PNG or - if no application put a PNG on the clipboard - the DIB
+#[cfg(target_os = "windows")]
+mod platform {
+
+    use clipboard_win::{Clipboard, formats::CF_DIB, raw};
+    use clipboard2::ClipboardError;
+    use app_resources::RawImage;
+    use super::{encode_png, decode_png, encode_dib, decode_dib};
+
+    /// Name of the registered clipboard format that browsers and image editors use for PNG files
+    const PNG_FORMAT_NAME: &str = "PNG";
+
+    pub(super) struct NativeClipboard;
+
+    impl NativeClipboard {
+
+        pub(super) fn new() -> Result<Self, ClipboardError> {
+            Ok(NativeClipboard)
+        }
+
+        pub(super) fn load(&self) -> Result<Option<RawImage>, ClipboardError> {
+            let clipboard = Clipboard::new().map_err(ClipboardError::IoError)?;
+            if let Some(png_format) = raw::register_format(PNG_FORMAT_NAME) {
+                if let Some(image) = get_format(&clipboard, png_format)?.and_then(|png| decode_png(&png)) {
+                    return Ok(Some(image));
+                }
+            }
+            Ok(get_format(&clipboard, CF_DIB)?.and_then(|dib| decode_dib(&dib)))
+        }
+
+        pub(super) fn store(&mut self, rgba: &[u8], width: u32, height: u32) -> Result<(), ClipboardError> {
+            let png = encode_png(rgba, width, height)?;
+            let clipboard = Clipboard::new().map_err(ClipboardError::IoError)?;
+            clipboard.empty().map_err(ClipboardError::IoError)?;
+            if let Some(png_format) = raw::register_format(PNG_FORMAT_NAME) {
+                clipboard.set(png_format, &png).map_err(ClipboardError::IoError)?;
+            }
+            clipboard.set(CF_DIB, &encode_dib(rgba, width, height)).map_err(ClipboardError::IoError)
+        }
+    }
+
+    fn get_format(clipboard: &Clipboard, format: u32) -> Result<Option<Vec<u8>>, ClipboardError> {
+        let size = match raw::size(format) {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+        let mut data = vec![0; size];
+        let len = clipboard.get(format, &mut data).map_err(ClipboardError::IoError)?;
+        data.truncate(len);
+        Ok(Some(data))
+    }
+}
+
+/// Offers the image as `image/png` target of the CLIPBOARD selection
+#[cfg(target_os = "linux")]
+mod platform {
+
+    use std::time::Duration;
+    use x11_clipboard::Clipboard;
+    use clipboard2::ClipboardError;
+    use app_resources::RawImage;
+    use super::{encode_png, decode_png, other_error};
+
+    const PNG_TARGET: &str = "image/png";
+    /// How long to wait for the owner of the selection to send the image
+    const LOAD_TIMEOUT_MS: u64 = 500;
+
+    pub(super) struct NativeClipboard {
+        clipboard: Clipboard,
+    }
+
+    impl NativeClipboard {
+
+        pub(super) fn new() -> Result<Self, ClipboardError> {
+            let clipboard = Clipboard::new().map_err(|_| other_error("could not connect to the X server"))?;
+            Ok(Self { clipboard })
+        }
+
+        pub(super) fn load(&self) -> Result<Option<RawImage>, ClipboardError> {
+            let getter = &self.clipboard.getter;
+            let png_target = getter.get_atom(PNG_TARGET).map_err(|_| other_error("could not intern the image/png atom"))?;
+            // Fails if the clipboard is empty or its owner doesn't offer a PNG
+            match self.clipboard.load(getter.atoms.clipboard, png_target, getter.atoms.property, Duration::from_millis(LOAD_TIMEOUT_MS)) {
+                Ok(png) => Ok(decode_png(&png)),
+                Err(_) => Ok(None),
+            }
+        }
+
+        pub(super) fn store(&mut self, rgba: &[u8], width: u32, height: u32) -> Result<(), ClipboardError> {
+            let png = encode_png(rgba, width, height)?;
+            let setter = &self.clipboard.setter;
+            let png_target = setter.get_atom(PNG_TARGET).map_err(|_| other_error("could not intern the image/png atom"))?;
+            self.clipboard.store(setter.atoms.clipboard, png_target, png).map_err(|_| other_error("could not take ownership of the clipboard"))
+        }
+    }
+}
+
+/// Reads and writes the `public.png` type of the general `NSPasteboard`
+#[cfg(target_os = "macos")]
+mod platform {
+
+    use std::{slice, os::raw::{c_char, c_void}};
+    use objc::runtime::{Class, Object, BOOL, NO};
+    use clipboard2::ClipboardError;
+    use app_resources::RawImage;
+    use super::{encode_png, decode_png, other_error};
+
+    const PNG_TYPE: &[u8] = b"public.png\0";
+
+    pub(super) struct NativeClipboard;
+
+    impl NativeClipboard {
+
+        pub(super) fn new() -> Result<Self, ClipboardError> {
+            Ok(NativeClipboard)
+        }
+
+        pub(super) fn load(&self) -> Result<Option<RawImage>, ClipboardError> {
+            let (pasteboard, png_type) = pasteboard_and_png_type()?;
+            unsafe {
+                let data: *mut Object = msg_send![pasteboard, dataForType: png_type];
+                if data.is_null() {
+                    return Ok(None);
+                }
+                let len: usize = msg_send![data, length];
+                let bytes: *const u8 = msg_send![data, bytes];
+                if bytes.is_null() {
+                    return Ok(None);
+                }
+                Ok(decode_png(slice::from_raw_parts(bytes, len)))
+            }
+        }
+
+        pub(super) fn store(&mut self, rgba: &[u8], width: u32, height: u32) -> Result<(), ClipboardError> {
+            let png = encode_png(rgba, width, height)?;
+            let (pasteboard, png_type) = pasteboard_and_png_type()?;
+            let ns_data = Class::get("NSData").ok_or_else(|| other_error("NSData is not available"))?;
+            unsafe {
+                let data: *mut Object = msg_send![ns_data, dataWithBytes: png.as_ptr() as *const c_void length: png.len()];
+                let _: isize = msg_send![pasteboard, clearContents];
+                let success: BOOL = msg_send![pasteboard, setData: data forType: png_type];
+                if success == NO {
+                    return Err(other_error("could not write the image to the pasteboard"));
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn pasteboard_and_png_type() -> Result<(*mut Object, *mut Object), ClipboardError> {
+        let ns_pasteboard = Class::get("NSPasteboard").ok_or_else(|| other_error("NSPasteboard is not available"))?;
+        let ns_string = Class::get("NSString").ok_or_else(|| other_error("NSString is not available"))?;
+        unsafe {
+            let pasteboard: *mut Object = msg_send![ns_pasteboard, generalPasteboard];
+            let png_type: *mut Object = msg_send![ns_string, stringWithUTF8String: PNG_TYPE.as_ptr() as *const c_char];
+            if pasteboard.is_null() || png_type.is_null() {
+                return Err(other_error("could not access the general pasteboard"));
+            }
+            Ok((pasteboard, png_type))
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+mod platform {
+
+    use clipboard2::ClipboardError;
+    use app_resources::RawImage;
+
+    pub(super) struct NativeClipboard;
+
+    impl NativeClipboard {
+
+        pub(super) fn new() -> Result<Self, ClipboardError> {
+            Err(ClipboardError::Unimplemented)
+        }
+
+        pub(super) fn load(&self) -> Result<Option<RawImage>, ClipboardError> {
+            Err(ClipboardError::Unimplemented)
+        }
+
+        pub(super) fn store(&mut self, _rgba: &[u8], _width: u32, _height: u32) -> Result<(), ClipboardError> {
+            Err(ClipboardError::Unimplemented)
+        }
+    }
+}
+
+#[test]
+fn test_dib_round_trip() {
+    // 2x2 image, the second row is half-transparent
+    let rgba = vec![
+        255, 0, 0, 255,     0, 255, 0, 255,
+        0, 0, 255, 128,     10, 20, 30, 128,
+    ];
+    let dib = encode_dib(&rgba, 2, 2);
+    assert_eq!(dib.len(), BITMAPINFOHEADER_SIZE + rgba.len());
+    // Bottom-up: the first row of the DIB is the last row of the image
+    assert_eq!(&dib[BITMAPINFOHEADER_SIZE..BITMAPINFOHEADER_SIZE + 4], &[255, 0, 0, 128]);
+
+    let image = decode_dib(&dib).unwrap();
+    assert_eq!(image.image_dimensions, (2, 2));
+    assert_eq!(image.to_straight_alpha_rgba8(), rgba);
+}
+
+#[test]
+fn test_decode_dib_24_bit() {
+    // 1x2 top-down 24-bit DIB, the rows are padded to 4 bytes
+    let mut dib = encode_dib(&[], 1, 0)[..BITMAPINFOHEADER_SIZE].to_vec();
+    dib[8..12].copy_from_slice(&[0xfe, 0xff, 0xff, 0xff]); // height = -2
+    dib[14] = 24;
+    dib.extend_from_slice(&[1, 2, 3, 0, 4, 5, 6, 0]);
+
+    let image = decode_dib(&dib).unwrap();
+    assert_eq!(image.image_dimensions, (1, 2));
+    assert_eq!(image.pixels, vec![1, 2, 3, 255, 4, 5, 6, 255]);
+    assert_eq!(decode_dib(&dib[..dib.len() - 1]), None);
+}
@@ -24,7 +24,7 @@ use azul_css::{
 };
 use {
     FastHashMap,
-    app_resources::AppResources,
+    app_resources::{AppResources, TiledImage, AtlasGlyph},
     callbacks::{IFrameCallback, GlTextureCallback, HidpiAdjustedBounds, StackCheckedPointer},
     ui_state::UiState,
     ui_description::{UiDescription, StyledNode},
@@ -153,6 +153,16 @@ impl<'a, T: 'a> DisplayList<'a, T> {
             &layout_result.node_depths, window.internal.pipeline_id
         );
 
+        // Now that the positions of all nodes are known, load the background images
+        // that are near the viewport (only if lazy image decoding is enabled)
+        app_resources.add_images_in_viewport(
+            &self,
+            &layout_result.rects,
+            &get_scroll_offsets(&scrollable_nodes, &window.scroll_states),
+            LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(window_size.width as f32, window_size.height as f32)),
+            window.state.size.hidpi_factor as f32,
+        );
+
         // Make sure unused scroll states are garbage collected.
         window.scroll_states.remove_unused_scroll_states();
 
@@ -456,6 +466,14 @@ fn get_nodes_that_need_scroll_clip<'a, T: 'a>(
     ScrolledNodes { overflowing_nodes: nodes, tags_to_node_ids }
 }
 
+/// Returns how far the content of each scroll frame is scrolled, without marking the scroll states as used
+fn get_scroll_offsets(scrolled_nodes: &ScrolledNodes, scroll_states: &ScrollStates) -> BTreeMap<NodeId, (f32, f32)> {
+    scrolled_nodes.overflowing_nodes.iter().filter_map(|(node_id, overflowing_node)| {
+        let scroll_offset = scroll_states.peek_scroll_amount(&overflowing_node.parent_external_scroll_id)?;
+        Some((*node_id, scroll_offset))
+    }).collect()
+}
+
 fn node_needs_to_clip_children(layout: &RectLayout) -> bool {
     let overflow = layout.overflow.unwrap_or_default();
     !overflow.is_horizontal_overflow_visible() ||
@@ -860,6 +878,15 @@ fn push_iframe<'a,'b,'c,'d,'e,'f, T>(
         rect_origin,
     );
 
+    // The scroll states of iframes aren't preserved across frames, so they aren't scrolled yet
+    referenced_mutable_content.app_resources.add_images_in_viewport(
+        &display_list,
+        &layout_result.rects,
+        &BTreeMap::new(),
+        LayoutRect::new(rect_origin, rect_size),
+        rectangle.window_size.hidpi_factor as f32,
+    );
+
     let mut scrollable_nodes = get_nodes_that_need_scroll_clip(
         node_hierarchy, &display_list.rectangles, node_data, &layout_result.rects,
         &layout_result.node_depths, referenced_content.pipeline_id
@@ -1357,8 +1384,8 @@ fn push_background(
             if let Some(image_id) = app_resources.get_css_image_id(&style_image_id.0) {
 
                 let bounds = info.rect;
-                let image_dimensions = app_resources.get_image_info(image_id)
-                    .map(|info| (info.descriptor.size.width, info.descriptor.size.height))
+                let image_dimensions = app_resources.get_image_dimensions(image_id)
+                    .map(|(width, height)| (width as i32, height as i32))
                     .unwrap_or((bounds.size.width as i32, bounds.size.height as i32)); // better than crashing...
 
                 let size = match background_size {
@@ -1443,13 +1470,125 @@ fn push_image(
     image_id: &ImageId,
     size: TypedSize2D<f32, LayoutPixel>
 ) {
-    if let Some(image_info) = app_resources.get_image_info(image_id) {
+    use euclid::SideOffsets2D;
+    use webrender::api::{
+        BorderDetails, NinePatchBorder, NinePatchBorderSource, RepeatMode, LayoutSideOffsets,
+    };
+
+    if let Some(tiled_image) = app_resources.get_tiled_image(image_id) {
+        push_image_tiles(info, builder, tiled_image, size);
+        return;
+    }
+
+    if let Some(atlas_glyph) = app_resources.get_atlas_glyph(image_id) {
+        push_atlas_glyph(info, builder, app_resources, atlas_glyph, size);
+        return;
+    }
+
+    let image_info = match app_resources.get_image_info(image_id) {
+        Some(s) => s,
+        None => return,
+    };
+
+    match image_info.nine_patch_insets {
+        Some((top, right, bottom, left)) => {
+            // Nine-patch images always fill the entire node, the corners are drawn at their
+            // original size, the edges and the center get stretched
+            let (width, height) = image_info.get_dimensions();
+            let widths = LayoutSideOffsets::new(top as f32, right as f32, bottom as f32, left as f32);
+            builder.push_border(info, widths, BorderDetails::NinePatch(NinePatchBorder {
+                source: NinePatchBorderSource::Image(image_info.key),
+                width: width as u32,
+                height: height as u32,
+                slice: SideOffsets2D::new(top, right, bottom, left),
+                fill: true,
+                repeat_horizontal: RepeatMode::Stretch,
+                repeat_vertical: RepeatMode::Stretch,
+                outset: SideOffsets2D::zero(),
+            }));
+        },
+        None => {
+            builder.push_image(
+                info,
+                size,
+                LayoutSize::zero(),
+                ImageRendering::Auto,
+                image_info.alpha_type,
+                image_info.key,
+                ColorF::WHITE,
+            );
+        }
+    }
+}
+
+/// Pushes the currently uploaded tiles of a tiled image, the entire image is scaled to `size`
+/// Draws a glyph of a bitmap font: the atlas image is scaled so that the glyph has the size
+/// `size`, moved so that the glyph is at the origin of the node and clipped to the glyph
+fn push_atlas_glyph(
+    info: &PrimitiveInfo<LayoutPixel>,
+    builder: &mut DisplayListBuilder,
+    app_resources: &AppResources,
+    atlas_glyph: &AtlasGlyph,
+    size: TypedSize2D<f32, LayoutPixel>
+) {
+    let atlas_info = match app_resources.get_image_info(&atlas_glyph.atlas_id) {
+        Some(s) => s,
+        None => return,
+    };
+
+    let (x, y, width, height) = atlas_glyph.rect;
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let scale_x = size.width / width as f32;
+    let scale_y = size.height / height as f32;
+    let (atlas_width, atlas_height) = atlas_info.get_dimensions();
+    let atlas_size = LayoutSize::new(atlas_width as f32 * scale_x, atlas_height as f32 * scale_y);
+    let atlas_rect = LayoutRect::new(
+        LayoutPoint::new(info.rect.origin.x - x as f32 * scale_x, info.rect.origin.y - y as f32 * scale_y),
+        atlas_size,
+    );
+
+    let glyph_rect = LayoutRect::new(info.rect.origin, size);
+    let clip_rect = match glyph_rect.intersection(&info.clip_rect) {
+        Some(s) => s,
+        None => return,
+    };
+
+    builder.push_image(
+        &LayoutPrimitiveInfo::with_clip_rect(atlas_rect, clip_rect),
+        atlas_size,
+        LayoutSize::zero(),
+        ImageRendering::Auto,
+        atlas_info.alpha_type,
+        atlas_info.key,
+        ColorF::WHITE,
+    );
+}
+
+fn push_image_tiles(
+    info: &PrimitiveInfo<LayoutPixel>,
+    builder: &mut DisplayListBuilder,
+    tiled_image: &TiledImage,
+    size: TypedSize2D<f32, LayoutPixel>
+) {
+    let scale_x = size.width / tiled_image.full_size.0 as f32;
+    let scale_y = size.height / tiled_image.full_size.1 as f32;
+
+    for (tile, (image_info, _)) in tiled_image.resident_tiles.iter() {
+        let (x, y, width, height) = tiled_image.get_tile_rect(*tile);
+        let tile_size = LayoutSize::new(width as f32 * scale_x, height as f32 * scale_y);
+        let tile_rect = LayoutRect::new(
+            LayoutPoint::new(info.rect.origin.x + x as f32 * scale_x, info.rect.origin.y + y as f32 * scale_y),
+            tile_size,
+        );
         builder.push_image(
-            info,
-            size,
+            &LayoutPrimitiveInfo::with_clip_rect(tile_rect, info.clip_rect),
+            tile_size,
             LayoutSize::zero(),
             ImageRendering::Auto,
-            AlphaType::PremultipliedAlpha,
+            image_info.alpha_type,
             image_info.key,
             ColorF::WHITE,
         );
@@ -1556,6 +1695,7 @@ fn apply_style_property(rect: &mut DisplayRectangle, property: &CssProperty) {
         Background(b)       => { rect.style.background = Some(b.clone());               },
         FontSize(f)         => { rect.style.font_size = Some(*f);                       },
         FontFamily(f)       => { rect.style.font_family = Some(f.clone());              },
+        FontVariationSettings(v) => { rect.style.font_variation_settings = Some(v.clone()); },
         LetterSpacing(l)    => { rect.style.letter_spacing = Some(*l);                  },
         TextAlign(ta)       => { rect.style.text_align = Some(*ta);                     },
         BoxShadow(b)        => { StyleBoxShadow::merge(&mut rect.style.box_shadow, b);  },
@@ -172,6 +172,17 @@ pub(crate) use azul_dependencies::lyon;
 pub(crate) use azul_dependencies::usvg;
 #[cfg(feature = "faster-hashing")]
 pub(crate) use azul_dependencies::twox_hash;
+#[cfg(feature = "qrcode")]
+extern crate qrcode_generator as qrcode;
+#[cfg(feature = "tiff")]
+extern crate tiff_decoder as tiff;
+#[cfg(target_os = "windows")]
+extern crate clipboard_win;
+#[cfg(target_os = "linux")]
+extern crate x11_clipboard;
+#[cfg(target_os = "macos")]
+#[macro_use]
+extern crate objc;
 
 #[cfg(feature = "css_parser")]
 extern crate azul_css;
@@ -237,6 +248,9 @@ mod stack_checked_pointer;
 mod window_state;
 /// ImageId / FontId handling and caching
 mod app_resources;
+/// Native clipboard formats for images (the clipboard2 backend only supports text)
+#[cfg(feature = "image_loading")]
+mod clipboard_image;
 
 /// Font & image resource handling, lookup and caching
 pub mod resources {
@@ -244,8 +258,17 @@ pub mod resources {
     pub use app_resources::{
         FontId, ImageId, LoadedFont, RawImage, FontReloadError, FontSource, ImageReloadError,
         ImageSource, RawImageFormat, CssFontId, CssImageId,
-        TextCache, TextId,
+        TextCache, TextId, TextMeta, Script, ChannelHistograms, GradientImage, GradientImageStop,
+        ImageIdExists, WordSplitter, ImageUpdateError, FrameStats, ResourceCounts, ResourceMemoryReport, FontMemoryUsage, FontVariant, FontMetrics, CacheStats, ResourceCapacities,
+        BitmapFont, BitmapGlyph, CLIPBOARD_FORMAT_TEXT, CLIPBOARD_FORMAT_FILE_LIST, CLIPBOARD_FORMAT_PNG, ImageInfo, ObjectFit, SourceRect, DestRect,
+        ResourceWarning, ResourceWarningId, ResourceLoadError, RawImageError, FontInstanceSpec, ImageCallback,
+        TileCoord, TileProvider, FontRenderMode, FontLcdFilter, FontHinting, FontPlatformOptions,
+        GcPolicy, GlyphOutline, GlyphOutlineOperation,
     };
+    #[cfg(feature = "svg")]
+    pub use app_resources::TextRasterizationError;
+    #[cfg(feature = "qrcode")]
+    pub use app_resources::{QrEcLevel, QrError};
 }
 
 // Faster implementation of a HashMap (optional, disabled by default, turn on with --feature="faster-hashing")
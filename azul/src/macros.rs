@@ -188,6 +188,22 @@ impl<T> $struct_name<T> {
         self.$struct_field.add_image(image_id, image_source)
     }
 
+    /// See [`AppResources::add_images`]
+    ///
+    /// [`AppResources::add_images`]: ../app_resources/struct.AppResources.html#method.add_images
+    #[cfg(feature = "image_loading")]
+    pub fn add_images<I: IntoIterator<Item=(ImageId, ImageSource)>>(&mut self, images: I) {
+        self.$struct_field.add_images(images)
+    }
+
+    /// See [`AppResources::add_images_prewarm`]
+    ///
+    /// [`AppResources::add_images_prewarm`]: ../app_resources/struct.AppResources.html#method.add_images_prewarm
+    #[cfg(feature = "image_loading")]
+    pub fn add_images_prewarm<I: IntoIterator<Item=(ImageId, ImageSource)>>(&mut self, images: I) {
+        self.$struct_field.add_images_prewarm(images)
+    }
+
     /// See [`AppResources::has_image`]
     ///
     /// [`AppResources::has_image`]: ../app_resources/struct.AppResources.html#method.has_image
@@ -195,6 +211,13 @@ impl<T> $struct_name<T> {
         self.$struct_field.has_image(image_id)
     }
 
+    /// See [`AppResources::get_image_source`]
+    ///
+    /// [`AppResources::get_image_source`]: ../app_resources/struct.AppResources.html#method.get_image_source
+    pub fn get_image_source(&self, image_id: &ImageId) -> Option<&ImageSource> {
+        self.$struct_field.get_image_source(image_id)
+    }
+
     /// Given an `ImageId`, returns the bytes for that image or `None`, if the `ImageId` is invalid.
     ///
     /// See [`AppResources::get_image_bytes`]
@@ -218,6 +241,13 @@ impl<T> $struct_name<T> {
         self.$struct_field.add_css_image_id(css_id)
     }
 
+    /// See [`AppResources::try_add_css_image_id`]
+    ///
+    /// [`AppResources::try_add_css_image_id`]: ../app_resources/struct.AppResources.html#method.try_add_css_image_id
+    pub fn try_add_css_image_id<S: Into<String>>(&mut self, css_id: S) -> Result<ImageId, ImageIdExists> {
+        self.$struct_field.try_add_css_image_id(css_id)
+    }
+
     /// See [`AppResources::has_css_image_id`]
     ///
     /// [`AppResources::has_css_image_id`]: ../app_resources/struct.AppResources.html#method.has_css_image_id
@@ -289,6 +319,13 @@ impl<T> $struct_name<T> {
         self.$struct_field.add_font(font_id, font_source)
     }
 
+    /// See [`AppResources::add_fonts`]
+    ///
+    /// [`AppResources::add_fonts`]: ../app_resources/struct.AppResources.html#method.add_fonts
+    pub fn add_fonts<I: IntoIterator<Item=(FontId, FontSource)>>(&mut self, fonts: I) {
+        self.$struct_field.add_fonts(fonts)
+    }
+
     /// See [`AppResources::has_font`]
     ///
     /// [`AppResources::has_font`]: ../app_resources/struct.AppResources.html#method.has_font
@@ -296,6 +333,13 @@ impl<T> $struct_name<T> {
         self.$struct_field.has_font(font_id)
     }
 
+    /// See [`AppResources::get_font_source`]
+    ///
+    /// [`AppResources::get_font_source`]: ../app_resources/struct.AppResources.html#method.get_font_source
+    pub fn get_font_source(&self, font_id: &FontId) -> Option<&FontSource> {
+        self.$struct_field.get_font_source(font_id)
+    }
+
     /// See [`AppResources::delete_font`]
     ///
     /// [`AppResources::delete_font`]: ../app_resources/struct.AppResources.html#method.delete_font
@@ -320,6 +364,20 @@ impl<T> $struct_name<T> {
         self.$struct_field.add_text(text)
     }
 
+    /// See [`AppResources::add_text_with_meta`].
+    ///
+    /// [`AppResources::add_text_with_meta`]: ../app_resources/struct.AppResources.html#method.add_text_with_meta
+    pub fn add_text_with_meta(&mut self, text: &str, meta: TextMeta) -> TextId {
+        self.$struct_field.add_text_with_meta(text, meta)
+    }
+
+    /// See [`AppResources::get_text_string`].
+    ///
+    /// [`AppResources::get_text_string`]: ../app_resources/struct.AppResources.html#method.get_text_string
+    pub fn get_text_string(&self, id: &TextId) -> Option<&str> {
+        self.$struct_field.get_text_string(id)
+    }
+
     /// Removes a string from both the string cache and the layouted text cache
     ///
     /// See [`AppResources::delete_text`].
@@ -360,6 +418,27 @@ impl<T> $struct_name<T> {
     pub fn set_clipboard_string<I: Into<String>>(&mut self, contents: I) -> Result<(), ClipboardError> {
         self.$struct_field.set_clipboard_string(contents)
     }
+
+    /// See [`AppResources::clipboard_formats`]
+    ///
+    /// [`AppResources::clipboard_formats`]: ../app_resources/struct.AppResources.html#method.clipboard_formats
+    pub fn clipboard_formats(&self) -> Result<Vec<String>, ClipboardError> {
+        self.$struct_field.clipboard_formats()
+    }
+
+    /// See [`AppResources::get_clipboard_files`]
+    ///
+    /// [`AppResources::get_clipboard_files`]: ../app_resources/struct.AppResources.html#method.get_clipboard_files
+    pub fn get_clipboard_files(&self) -> Result<Vec<::std::path::PathBuf>, ClipboardError> {
+        self.$struct_field.get_clipboard_files()
+    }
+
+    /// See [`AppResources::set_clipboard_files`]
+    ///
+    /// [`AppResources::set_clipboard_files`]: ../app_resources/struct.AppResources.html#method.set_clipboard_files
+    pub fn set_clipboard_files(&mut self, paths: &[::std::path::PathBuf]) -> Result<(), ClipboardError> {
+        self.$struct_field.set_clipboard_files(paths)
+    }
 }
 
 )}
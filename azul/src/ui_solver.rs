@@ -1106,7 +1106,7 @@ fn get_content_width<T>(
 ) -> Option<f32> {
     use dom::NodeType::*;
     match node_type {
-        Image(image_id) => app_resources.get_image_info(image_id).map(|info| info.descriptor.size.width as f32),
+        Image(image_id) => app_resources.get_image_dimensions(image_id).map(|(width, _)| width as f32),
         Label(_) | Text(_) => positioned_words.get(node_id).map(|pos| pos.0.content_size.width),
         _ => None,
     }
@@ -1122,11 +1122,11 @@ fn get_content_height<T>(
     use dom::NodeType::*;
     match &node_type {
         Image(i) => {
-            let image_size = &app_resources.get_image_info(i)?.descriptor.size;
-            let aspect_ratio = image_size.width as f32 / image_size.height as f32;
+            let (image_width, image_height) = app_resources.get_image_dimensions(i)?;
+            let aspect_ratio = image_width as f32 / image_height as f32;
             let preferred_height = div_width * aspect_ratio;
             Some(PreferredHeight::Image {
-                original_dimensions: (image_size.width as usize, image_size.height as usize),
+                original_dimensions: (image_width, image_height),
                 aspect_ratio,
                 preferred_height,
             })
@@ -1178,6 +1178,14 @@ pub(crate) fn get_font_size(rect_style: &RectStyle) -> StyleFontSize {
     rect_style.font_size.unwrap_or(DEFAULT_FONT_SIZE)
 }
 
+/// Returns the `font-variation-settings` as `(tag, value)`, empty if the default axis values are used
+pub(crate) fn get_font_variations(rect_style: &RectStyle) -> Vec<(u32, f32)> {
+    match &rect_style.font_variation_settings {
+        Some(settings) => settings.variations.iter().map(|v| (v.tag_u32(), v.value.get())).collect(),
+        None => Vec::new(),
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct PositionedRectangle {
     pub bounds: LayoutRect,
@@ -1236,8 +1244,8 @@ pub(crate) fn do_the_layout<'a,'b, T>(
 
     // Resolve cached text IDs or break new, uncached strings into words / text runs
     let word_cache = create_word_cache(app_resources, node_data);
-    // Scale the words to the correct size - TODO: Caching / GC!
-    let scaled_words = create_scaled_words(app_resources, &word_cache, display_rects);
+    // Scale the words to the correct size, the words of cached texts are only scaled once per font instance
+    let scaled_words = create_scaled_words(app_resources, node_data, &word_cache, display_rects);
     // Layout all words as if there was no max-width constraint
     let word_positions_no_max_width = create_word_positions(
         &word_cache,
@@ -1343,8 +1351,9 @@ fn create_word_cache<T>(
     }).collect()
 }
 
-fn create_scaled_words<'a>(
+fn create_scaled_words<'a, T>(
     app_resources: &AppResources,
+    node_data: &NodeDataContainer<NodeData<T>>,
     words: &BTreeMap<NodeId, Words>,
     display_rects: &NodeDataContainer<DisplayRectangle<'a>>,
 ) -> BTreeMap<NodeId, (ScaledWords, FontInstanceKey)> {
@@ -1362,18 +1371,26 @@ fn create_scaled_words<'a>(
             None => ImmediateFontId::Unresolved(css_font_id.to_string()),
         };
 
+        let (font_id, instance_spec) = app_resources.resolve_font_instance(font_id, font_size_au, get_font_variations(&style));
         let loaded_font = app_resources.get_loaded_font(&font_id)?;
-        let font_instance_key = loaded_font.font_instances.get(&font_size_au)?;
+        let font_instance_key = loaded_font.font_instances.get(&instance_spec)?;
+
+        // The font bytes are only needed if the words aren't cached yet
+        let scale_words = |words: &Words| {
+            let (font_bytes, font_index) = app_resources.get_loaded_font_bytes(&font_id)?;
+            Some(words_to_scaled_words(
+                words,
+                &font_bytes,
+                font_index as u32,
+                font_size.0.to_pixels(),
+            ))
+        };
 
-        let font_bytes = &loaded_font.font_bytes;
-        let font_index = loaded_font.font_index as u32;
+        let scaled_words = match &node_data[*node_id].node_type {
+            NodeType::Text(text_id) => app_resources.get_text_cache().get_or_layout(text_id, *font_instance_key, scale_words)?,
+            _ => scale_words(words)?,
+        };
 
-        let scaled_words = words_to_scaled_words(
-            words,
-            font_bytes,
-            font_index,
-            font_size.0.to_pixels(),
-        );
         Some((*node_id, (scaled_words, *font_instance_key)))
     }).collect()
 }
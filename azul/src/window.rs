@@ -395,6 +395,12 @@ impl ScrollStates {
         Some(entry.get())
     }
 
+    /// Same as `get_scroll_amount`, but doesn't mark the scroll state as used in this frame
+    pub(crate) fn peek_scroll_amount(&self, scroll_id: &ExternalScrollId) -> Option<(f32, f32)> {
+        let entry = self.0.get(scroll_id)?;
+        Some((entry.scroll_amount_x, entry.scroll_amount_y))
+    }
+
     /// Updating the scroll amount does not update the `entry.used_this_frame`,
     /// since that is only relevant when we are actually querying the renderer.
     pub(crate) fn scroll_node(&mut self, scroll_id: &ExternalScrollId, scroll_by_x: f32, scroll_by_y: f32) {